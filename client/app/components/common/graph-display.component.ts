@@ -11,6 +11,7 @@ export type DataNode = {
 };
 export type DataEdge = {
 	id: string,                                //unique edge id (usually formatted as sourceId,targetId)
+	label: string | null,                      //what the pointer points into, if known
 	source: DataNode & d3.SimulationNodeDatum, //reference to source node (can be initialized to an id string)
 	target: DataNode & d3.SimulationNodeDatum  //reference to target node (can be initialized to an id string)
 };
@@ -155,6 +156,10 @@ export class GraphDisplayComponent implements OnInit, AfterViewInit, OnChanges,
 		newEdge.append('line')
 			.attr('marker-end', 'url(#edge)');
 
+		newEdge.append('text')
+			.attr('class', 'edge-text')
+			.attr('text-anchor', 'middle');
+
 		edge.exit().remove();
 
 		let node = this.nodesGroup.selectAll('g')
@@ -185,6 +190,8 @@ export class GraphDisplayComponent implements OnInit, AfterViewInit, OnChanges,
 		node.exit().remove();
 
 		this.allEdges = edge.merge(newEdge);
+		this.allEdges.select('.edge-text')
+			.text((e:DataEdge) => e.label || '');
 		this.allNodes = node.merge(newNode);
 
 		this.allNodes.select('.node-tracked-circle')
@@ -202,6 +209,9 @@ export class GraphDisplayComponent implements OnInit, AfterViewInit, OnChanges,
 			.attr("y1", (d: DataEdge) => d.source.y!)
 			.attr("x2", (d: DataEdge) => d.target.x!)
 			.attr("y2", (d: DataEdge) => d.target.y!)
+		this.allEdges.selectAll('text')
+			.attr("x", (d: DataEdge) => (d.source.x! + d.target.x!) / 2)
+			.attr("y", (d: DataEdge) => (d.source.y! + d.target.y!) / 2);
 
 		this.allNodes
 			.attr('transform', (d:DataNode & d3.SimulationNodeDatum) => `translate(${d.x},${d.y})`);
@@ -1,6 +1,6 @@
 import {Component, Input, OnInit, QueryList, ViewChildren} from "@angular/core";
 import {SourceType, SourceTypeId} from "../../../models/SourceType";
-import {Value} from "../../../models/Value";
+import {TextValue, Value} from "../../../models/Value";
 import {StructTypeDisplay} from "./struct-type-display.component";
 import {PrimitiveTypeDisplay} from "./primitive-type-display.component";
 import {PointerTypeDisplay} from "./pointer-type-display.component";
@@ -17,7 +17,8 @@ import {MatchMaxHeightDirective} from "../../../directives/MatchMaxHeight.direct
 @Component({
     selector: 'spice-array-type-display',
     template: `        
-        <span class="array" *ngIf="types && type && baseType">
+        <span class="array text" *ngIf="text !== undefined">"{{text.text}}"<span *ngIf="text.truncated">...</span></span>
+        <span class="array" *ngIf="types && type && baseType && text === undefined">
             <span *ngIf="!expanded" class="hide-show" (click)="expanded=true" title="show array">(<md-icon>visibility</md-icon>)[...]</span>
             <span *ngIf="expanded" class="hide-show" (click)="expanded=false" title="hide array">(<md-icon>visibility_off</md-icon>)</span>
             <span *ngIf="expanded">
@@ -109,6 +110,7 @@ export class ArrayTypeDisplay implements OnInit{
     public sizeIterator:number[] = [];
     public baseType:SourceType | undefined;
     public baseValues:Value;
+    public text:TextValue | undefined;
 
     public get expanded() {
         return this._expanded;
@@ -138,6 +140,9 @@ export class ArrayTypeDisplay implements OnInit{
         if(this.value) {
             if(Array.isArray(this.value.value)) {
                 this.baseValues = this.value;
+            } else if(Value.isText(this.value.value)) {
+                this.text = this.value.value;
+                this.baseValues = {value: []};
             } else {
                 console.error('Input Value is not a Value[]', this.value);
             }
@@ -152,6 +157,9 @@ export class ArrayTypeDisplay implements OnInit{
     }
 
     public getValue(parameters:{[address: number]: Value}):Value | undefined {
+        if(this.text !== undefined) {
+            return {value: this.text};
+        }
         if(this.type && this.type.data.tType === 'array' && this.baseType) {
             let outVals:Value = {value: []};
             let list: QueryList<StructTypeDisplay> | QueryList<PrimitiveTypeDisplay> | QueryList<ArrayTypeDisplay> | QueryList<PointerTypeDisplay>  | QueryList<FunctionTypeDisplay>;
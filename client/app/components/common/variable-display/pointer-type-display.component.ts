@@ -12,6 +12,7 @@ import { MatchMaxHeightDirective } from "../../../directives/MatchMaxHeight.dire
  * This component is responsible for displaying pointer SourceTypes
  * It is generated through the Variable Display Components chain of generated variable displays.
  * This generates an additional display component for the type/value this pointer points to.
+ * Pointers with array views point to a whole buffer, which is displayed as an array.
  */
 
 @Component({
@@ -26,7 +27,7 @@ import { MatchMaxHeightDirective } from "../../../directives/MatchMaxHeight.dire
         <span *ngIf="types && type && !canExpand()" class="pointer empty">
             NULL
         </span>
-        <span class="pointer-contents" *ngIf="expanded && types && type && canExpand()" [ngSwitch]="types.get(type.data.sType).data.tType">
+        <span class="pointer-contents" *ngIf="expanded && types && type && canExpand()" [ngSwitch]="arrayType ? 'array' : types.get(type.data.sType).data.tType">
             <spice-struct-type-display
                     #struct
                     *ngSwitchCase="'struct'"
@@ -46,7 +47,7 @@ import { MatchMaxHeightDirective } from "../../../directives/MatchMaxHeight.dire
             <spice-array-type-display
                     #array
                     *ngSwitchCase="'array'"
-                    [type]="types.get(type.data.sType)"
+                    [type]="arrayType || types.get(type.data.sType)"
                     [value]="childValue"
                     [valueMap]="valueMap"
                     [editable]="editable"
@@ -107,6 +108,7 @@ export class PointerTypeDisplay implements OnInit {
     private functionDisplay: FunctionTypeDisplay;
 
     public childValue:Value | null = null;
+    public arrayType:SourceType | null = null;
 
     private _expanded:boolean;
     public get expanded() {
@@ -159,7 +161,7 @@ export class PointerTypeDisplay implements OnInit {
                 }
             }
             parameters[i] = targetVal;
-            return {value: i};
+            return {value: i.toString()};
 
         } else {
             return undefined;
@@ -181,6 +183,15 @@ export class PointerTypeDisplay implements OnInit {
                 this.childValue = this.valueMap[n];
             }
         }
+        if(this.childValue && Array.isArray(this.childValue.value) && this.type && this.types && this.type.data.tType === 'pointer') {
+            let targetType = this.types.get(this.type.data.sType);
+            if(targetType && targetType.data.tType !== 'array') {
+                this.arrayType = Object.assign(new SourceType(), {
+                    id: targetType.id,
+                    data: {tType: 'array', sType: targetType.id, count: this.childValue.value.length}
+                });
+            }
+        }
     }
 
     constructor() {}
@@ -64,15 +64,18 @@ export class PrimitiveTypeDisplay {
                     return {value: !!inputVal};
                 case 'int':
                     if(this.isNumeric(inputVal)) {
-                        return {value: parseInt(inputVal)};
+                        return {value: this.type.data.size >= 8 ? inputVal.trim() : parseInt(inputVal)};
                     }
                     break;
                 case 'uint':
                     if(this.isNumeric(inputVal) && parseInt(inputVal) >= 0) {
-                        return {value: parseInt(inputVal)};
+                        return {value: this.type.data.size >= 8 ? inputVal.trim() : parseInt(inputVal)};
                     }
                     break;
                 case 'float':
+                    if(['NaN', 'Infinity', '-Infinity'].indexOf(inputVal.trim()) !== -1) {
+                        return {value: inputVal.trim()};
+                    }
                     if(this.isNumeric(inputVal)) {
                         return {value: parseFloat(inputVal)}
                     }
@@ -160,6 +163,9 @@ export class PrimitiveTypeDisplay {
             if(this.value.value === null) {
                 return 'null';
             }
+            if(Value.isText(this.value.value)) {
+                return `"${this.value.value.text}"` + (this.value.value.truncated ? '...' : '');
+            }
             return this.value.value.toString();
         }
         return '~'
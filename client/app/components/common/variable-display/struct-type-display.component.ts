@@ -21,7 +21,7 @@ import {FunctionTypeDisplay} from "./function-type-display.component";
                 {{type.data.tType === 'struct' ? type.data.name : 'Type Error'}}
             </div>
             <table >
-                <tr *ngFor="let f of getFields()" [ngSwitch]="types.get(f.sType).data.tType">
+                <tr *ngFor="let f of getFields(); let i = index" [ngSwitch]="types.get(f.sType).data.tType">
                     <td>
                         <div class="fieldName">{{f.name}}</div>
                         <div class="fieldType" *ngIf="!compact">{{types.get(f.sType).toString(types)}}</div>
@@ -31,7 +31,7 @@ import {FunctionTypeDisplay} from "./function-type-display.component";
                                 *ngSwitchCase="'struct'"
                                 [type]="types.get(f.sType)"
                                 [types]="types"
-                                [value]="getStructValue(f.offset)"
+                                [value]="getStructValue(i)"
                                 [valueMap]="valueMap"
                                 [editable]="editable"
                                 [lineNum]="lineNum"
@@ -39,13 +39,13 @@ import {FunctionTypeDisplay} from "./function-type-display.component";
                         <spice-primitive-type-display
                                 *ngSwitchCase="'primitive'"
                                 [type]="types.get(f.sType)"
-                                [value]="getStructValue(f.offset)"
+                                [value]="getStructValue(i)"
                                 [editable]="editable"
                                 [compact]="compact && !editable"></spice-primitive-type-display>
                         <spice-array-type-display
                                 *ngSwitchCase="'array'"
                                 [type]="types.get(f.sType)"
-                                [value]="getStructValue(f.offset)"
+                                [value]="getStructValue(i)"
                                 [valueMap]="valueMap"
                                 [editable]="editable"
                                 [lineNum]="lineNum"
@@ -54,7 +54,7 @@ import {FunctionTypeDisplay} from "./function-type-display.component";
                         <spice-pointer-type-display
                                 *ngSwitchCase="'pointer'"
                                 [type]="types.get(f.sType)"
-                                [value]="getStructValue(f.offset)"
+                                [value]="getStructValue(i)"
                                 [valueMap]="valueMap"
                                 [editable]="editable"
                                 [lineNum]="lineNum"
@@ -63,7 +63,7 @@ import {FunctionTypeDisplay} from "./function-type-display.component";
                         <spice-function-type-display
                                 *ngSwitchCase="'function'"
                                 [type]="types.get(f.sType)"
-                                [value]="getStructValue(f.offset)"
+                                [value]="getStructValue(i)"
                                 [editable]="editable"
                                 [compact]="compact && !editable"
                                 [types]="types"></spice-function-type-display>
@@ -121,34 +121,34 @@ export class StructTypeDisplay implements OnInit {
             let arraDs = this.arrayDisplays.toArray().reverse();
             let struDs = this.structDisplays.toArray().reverse();
             let funcDs = this.functionDisplays.toArray().reverse();
-            for(let f of this.type.data.fields) {
+            this.type.data.fields.forEach((f, i) => {
                 let t = this.types.get(f.sType)!;
                 switch(t.data.tType) {
                     case "primitive":
-                        outVal.value[f.offset] = primDs.pop()!.getValue(parameters);
+                        outVal.value[i] = primDs.pop()!.getValue(parameters);
                         break;
                     case "pointer":
-                        outVal.value[f.offset] = poinDs.pop()!.getValue(parameters);
+                        outVal.value[i] = poinDs.pop()!.getValue(parameters);
                         break;
                     case "array":
-                        outVal.value[f.offset] = arraDs.pop()!.getValue(parameters);
+                        outVal.value[i] = arraDs.pop()!.getValue(parameters);
                         break;
                     case "struct":
-                        outVal.value[f.offset] = struDs.pop()!.getValue(parameters);
+                        outVal.value[i] = struDs.pop()!.getValue(parameters);
                         break;
                     case "function":
-                        outVal.value[f.offset] = funcDs.pop()!.getValue(parameters);
+                        outVal.value[i] = funcDs.pop()!.getValue(parameters);
 
                 }
-            }
+            });
             return outVal;
         }
         return undefined;
     }
 
-    public getStructValue(offset:number):Value | null {
+    public getStructValue(index:number):Value | null {
         if(this.activeVal && this.activeVal.value) {
-            let val = this.activeVal.value[offset];
+            let val = this.activeVal.value[index];
             if (val) {
                 return val;
             }
@@ -19,7 +19,12 @@ import {FunctionTypeDisplay} from "./function-type-display.component";
 @Component({
     selector: 'spice-variable-display',
     template: `        
-        <span *ngIf="!!debugState && !!type" class="variable-display" [ngClass]="{'normal-size':!compact || editable, 'compact-size':compact && !editable}" [ngSwitch]="type.data.tType">
+        <span *ngIf="!!debugState && !!type" class="variable-display" [ngClass]="{'normal-size':!compact || editable, 'compact-size':compact && !editable}" [ngSwitch]="isUnavailable() ? 'unavailable' : isVisualized() ? 'visualized' : type.data.tType">
+            <span *ngSwitchCase="'unavailable'" class="unavailable-value">{{unavailableText()}}</span>
+            <spice-visualized-type-display
+                    *ngSwitchCase="'visualized'"
+                    [value]="value"
+                    [valueMap]="valueMap"></spice-visualized-type-display>
             <spice-struct-type-display
                     *ngSwitchCase="'struct'"
                     [type]="type"
@@ -109,6 +114,29 @@ export class VariableDisplayComponent implements OnInit {
         }
     }
 
+    public isUnavailable():boolean {
+        return !this.editable && !!this.value && Value.isUnavailable(this.value.value);
+    }
+
+    public isVisualized():boolean {
+        return !this.editable && !!this.value && Value.isVisualized(this.value.value);
+    }
+
+    public unavailableText():string {
+        if(!this.value || !Value.isUnavailable(this.value.value)) {
+            return '';
+        }
+        switch(this.value.value.state) {
+            case 'uninitialized':
+                return '<uninitialized>';
+            case 'unreadable':
+                return this.value.value.address !== null ? `<unreadable at ${this.value.value.address}>` : '<unreadable>';
+            case 'optimizedOut':
+                return '<optimized out>';
+        }
+        return '';
+    }
+
     public applyValue(parameters:{[address: number]: Value}) {
         let val:Value|undefined = undefined;
         if(this.debugState && this.type) {
@@ -0,0 +1,81 @@
+import {Component, Input} from "@angular/core";
+import {Value, VisualizedValue} from "../../../models/Value";
+
+/**
+ * Visualized Type Display Component
+ * This component is responsible for displaying standard library containers, smart pointers and
+ * structs with user-defined visualizers by their logical contents, which the server sends in place
+ * of their raw structs.
+ * It is generated through the Variable Display Components chain of generated variable displays.
+ */
+
+@Component({
+    selector: 'spice-visualized-type-display',
+    template: `
+        <span class="visualized">
+            <span class="visualizer">{{visualized().visualizer}}</span>
+            <span class="display" *ngIf="visualized().display !== null">{{visualized().display}}</span>
+            <span class="item" *ngFor="let item of visualized().items">
+                {{item.name}}: {{valueAsString(item.value)}}
+            </span>
+            <span class="contents" *ngIf="hasContents()">{{contentsAsString()}}</span>
+        </span>
+    `
+})
+export class VisualizedTypeDisplay {
+    @Input()
+    public value:Value;
+
+    @Input()
+    public valueMap:{ [sVariable: number]: Value};
+
+    constructor(){}
+
+    public visualized():VisualizedValue {
+        return this.value.value as VisualizedValue;
+    }
+
+    public hasContents():boolean {
+        let visualized = this.visualized();
+        return visualized.visualizer !== 'custom' || visualized.contents.length > 0;
+    }
+
+    public contentsAsString():string {
+        let visualized = this.visualized();
+        let contents = this.valueAsString(visualized.contents);
+        return visualized.truncated ? contents.replace(/[\]}]$/, ', ...$&') : contents;
+    }
+
+    public valueAsString(val:any):string {
+        if(val === null) {
+            return 'null';
+        }
+        if(Value.isText(val)) {
+            return `"${val.text}"` + (val.truncated ? '...' : '');
+        }
+        if(Value.isVisualized(val)) {
+            return val.display !== null ? val.display : this.valueAsString(val.contents);
+        }
+        if(Value.isUnavailable(val)) {
+            return `<${val.state}>`;
+        }
+        if(Array.isArray(val)) {
+            let visualizer = this.visualized().visualizer;
+            if(visualizer === 'map' || visualizer === 'unorderedMap') {
+                return '{' + val.map(entry => Array.isArray(entry) && entry.length === 2
+                    ? `${this.valueAsString(entry[0])}: ${this.valueAsString(entry[1])}`
+                    : this.valueAsString(entry)).join(', ') + '}';
+            }
+            return '[' + val.map(v => this.valueAsString(v)).join(', ') + ']';
+        }
+        if(typeof val === 'object') {
+            return '{' + Object.keys(val).map(k => this.valueAsString(val[k])).join(', ') + '}';
+        }
+        let visualizer = this.visualized().visualizer;
+        if((visualizer === 'uniquePtr' || visualizer === 'sharedPtr') && val === this.visualized().contents) {
+            let pointee:Value | undefined = this.valueMap && (<any> this.valueMap)[val];
+            return val === '0' ? 'nullptr' : `-> ${pointee ? this.valueAsString(pointee.value) : val}`;
+        }
+        return val.toString();
+    }
+}
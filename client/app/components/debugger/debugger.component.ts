@@ -1,13 +1,13 @@
 import {Component, QueryList, ViewChild, ViewChildren} from "@angular/core";
 import {Execution, FunctionData} from "../../models/Execution";
-import {LineData, Trace} from "../../models/Trace";
+import {describePointerTarget, LineData, Trace} from "../../models/Trace";
 import {Observable} from "rxjs/Observable";
 import {SourceFunction, SourceFunctionId} from "../../models/SourceFunction";
 import {Response} from "@angular/http";
 import {ViewService} from "../../services/view.service";
 import {FileSystemService} from "../../services/file-system.service";
 import {DataXY, LineGraphComponent} from "../common/line-graph.component";
-import {SourceVariableId} from "../../models/SourceVariable";
+import {SourceVariable, SourceVariableId} from "../../models/SourceVariable";
 import {Subscriber} from "rxjs/Subscriber";
 import {LoopData, TraceGroup} from "./trace-loop.component";
 import {
@@ -23,6 +23,7 @@ import {DataEdge, DataNode, GraphData, GraphDisplayComponent} from "../common/gr
 import {SourceType} from "../../models/SourceType";
 import {PointerValue, StructValue, Value} from "../../models/Value";
 import {MatchMaxHeightDirective} from "../../directives/MatchMaxHeight.directive";
+import {ArrayView, VariablePath} from "../../models/ArrayView";
 
 /**
  * Debugger Component
@@ -62,6 +63,9 @@ export class DebuggerComponent {
 	public nodeGraphDataOffset: number | null = null;
 	public nodeGraphTrackedNode: SourceVariableId | null = null;
 
+	public arrayViewPointer: SourceVariableId | null = null;
+	public arrayViewLength: string = '';
+
 	public currentExecution: Execution | null = null;
 	public currentSession: number;
 
@@ -375,12 +379,13 @@ export class DebuggerComponent {
 					(t: Trace) => {
 						if(t.data.tType === 'line') {
 							let lineData: LineData = t.data;
-							let rootStructAddress: PointerValue | null = null;
+							let rootStructAddress: number | null = null;
 							if(lineData.state[this.nodeGraphVariable!]) {
-								rootStructAddress = lineData.state[this.nodeGraphVariable!].value as PointerValue;
-								if(Array.isArray(rootStructAddress)) {
-									rootStructAddress = rootStructAddress[0].value as PointerValue;
+								let rootValue: any = lineData.state[this.nodeGraphVariable!].value;
+								if(Array.isArray(rootValue)) {
+									rootValue = rootValue[0].value;
 								}
+								rootStructAddress = parseInt(rootValue as PointerValue);
 							}
 							let processedNodes = new Set<number>();
 							//for all nodes in the graph changed in this trace (and the root), remove old edges and nodes, add new edges and nodes
@@ -425,7 +430,7 @@ export class DebuggerComponent {
 									nodeStructValue = nodeStructValue[0].value;
 								}
 								Array.from(this.nodeGraphFieldOffsets.values()).forEach((offset: number) => {
-									let nodeEdgePointer = nodeStructValue[offset].value as PointerValue;
+									let nodeEdgePointer = parseInt(nodeStructValue[offset].value as PointerValue);
 									let edgeId = `${nodeStructAddress},${nodeEdgePointer}`;
 
 									if(nodeObj.edgesOut[offset] && nodeObj.edgesOut[offset].target.id !== nodeEdgePointer) {
@@ -440,9 +445,11 @@ export class DebuggerComponent {
 										//update edges
 										let edgeIdx = this.nodeGraphData.edges.findIndex((n:DataEdge) => n.id === edgeId);
 										let edgeObj: DataEdge;
+										let pointerTarget = lineData.pointers && lineData.pointers[nodeEdgePointer];
+										let label = pointerTarget ? describePointerTarget(pointerTarget) : null;
 										if(edgeIdx === -1) {
 											//add
-											edgeObj = {id: edgeId, source: nodeStructAddress as any, target: nodeEdgePointer as any};
+											edgeObj = {id: edgeId, label: label, source: nodeStructAddress as any, target: nodeEdgePointer as any};
 											this.nodeGraphData.edges.push(edgeObj);
 											nodeObj.edgesOut[offset] = edgeObj;
 										} else {
@@ -513,6 +520,89 @@ export class DebuggerComponent {
 		this.nodeGraphTrackedNode = null;
 	}
 
+	public getPointerVariables(): SourceVariable[] {
+		if(!this.sourceFunction) {
+			return [];
+		}
+		return this.sourceFunction.locals.concat(this.sourceFunction.parameters)
+			.sort((a, b) => a.name.localeCompare(b.name))
+			.filter(v => {
+				let sourceType = this.getVariableType(v.address);
+				return !!sourceType && sourceType.data.tType === 'pointer';
+			});
+	}
+
+	public getArrayViews(): ArrayView[] {
+		if(!this.sourceFunction || !this.debuggerService.currentDebuggerState) {
+			return [];
+		}
+		return this.debuggerService.currentDebuggerState.arrayViews.get(this.sourceFunction.address) || [];
+	}
+
+	public DescribeArrayView(view: ArrayView): string {
+		let length = typeof view.length === 'number' ? view.length.toString() : this.describeVariablePath(view.length);
+		return `${this.describeVariablePath(view.pointer)}[${length}]`;
+	}
+
+	protected describeVariablePath(path: VariablePath): string {
+		let variables = this.sourceFunction ? this.sourceFunction.locals.concat(this.sourceFunction.parameters) : [];
+		let variable = variables.find(v => v.address === path.variable);
+		let name = variable ? variable.name : '?';
+		let sourceType = variable ? this.getVariableType(variable.address) : null;
+		for(let i of path.fields) {
+			let field = sourceType && sourceType.data.tType === 'struct' ? sourceType.data.fields[i] : undefined;
+			name += field ? `.${field.name}` : '.?';
+			sourceType = field ? this.debuggerService.currentDebuggerState!.sourceTypes.get(field.sType) || null : null;
+		}
+		return name;
+	}
+
+	/**
+	 * View the selected pointer variable as an array, with either a constant length or the length held in another variable.
+	 * This replaces any view the pointer already had, and applies to traces from now on.
+	 */
+	public SetArrayView(): void {
+		if(!this.sourceFunction || !this.debuggerService.currentDebuggerState || this.arrayViewPointer === null) {
+			return;
+		}
+		let input = this.arrayViewLength.trim();
+		let length: number | VariablePath;
+		if(/^[0-9]+$/.test(input)) {
+			length = parseInt(input);
+		} else {
+			let variable = this.sourceFunction.locals.concat(this.sourceFunction.parameters).find(v => v.name === input);
+			if(!variable) {
+				console.error(`No variable named ${input}`);
+				return;
+			}
+			length = Object.assign(new VariablePath(), {variable: variable.address, fields: []});
+		}
+		let pointer = Object.assign(new VariablePath(), {variable: this.arrayViewPointer, fields: []});
+		let views = this.getArrayViews()
+			.filter(view => view.pointer.variable !== pointer.variable || view.pointer.fields.length > 0)
+			.concat([Object.assign(new ArrayView(), {pointer, length})]);
+		this.debuggerService.currentDebuggerState.setArrayViews(this.sourceFunction.address, views)
+			.subscribe(() => {
+				this.arrayViewPointer = null;
+				this.arrayViewLength = '';
+			}, (e: any) => {
+				//TODO: error handling
+				console.error(e);
+			});
+	}
+
+	public RemoveArrayView(view: ArrayView): void {
+		if(!this.sourceFunction || !this.debuggerService.currentDebuggerState) {
+			return;
+		}
+		let views = this.getArrayViews().filter(v => v !== view);
+		this.debuggerService.currentDebuggerState.setArrayViews(this.sourceFunction.address, views)
+			.subscribe(() => {}, (e: any) => {
+				//TODO: error handling
+				console.error(e);
+			});
+	}
+
 	public sortSourceFunctions(arr: SourceFunction[]) {
 		return arr.sort((a,b)=> a.name.localeCompare(b.name));
 	}
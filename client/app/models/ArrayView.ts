@@ -0,0 +1,24 @@
+import { Deserialize, fromJSON } from "../util/SpiceValidator";
+import { SourceVariableId } from "./SourceVariable";
+
+export class VariablePath {
+    @Deserialize()
+    variable: SourceVariableId;
+
+    @Deserialize({ element: Number })
+    fields: number[];
+}
+
+export class ArrayView {
+    @Deserialize()
+    pointer: VariablePath;
+
+    /** a constant, or the variable or field holding the length */
+    length: number | VariablePath;
+
+    static deserialize(json: any): ArrayView {
+        let view = fromJSON(json, ArrayView);
+        view.length = typeof json.length === 'number' ? json.length : fromJSON(json.length, VariablePath);
+        return view;
+    }
+}
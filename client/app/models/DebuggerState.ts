@@ -1,6 +1,7 @@
 import { DebugInfo, DebugId } from "./DebugInfo";
 import { Execution, ExecutionId } from "./Execution";
 import { Breakpoint } from "./Breakpoint";
+import { ArrayView } from "./ArrayView";
 import { SourceFunction, SourceFunctionId } from "./SourceFunction";
 import { SourceVariable, SourceVariableId } from "./SourceVariable";
 import { Observable } from "rxjs/Observable";
@@ -27,6 +28,7 @@ export class DebuggerState {
 
 	public executions: Map<ExecutionId, Execution>;
 	public breakpoints: Map<SourceFunctionId, Breakpoint>;
+	public arrayViews: Map<SourceFunctionId, ArrayView[]>;
 	public sourceFunctions: Map<SourceFunctionId, SourceFunction>;
 	public sourceVariables: Map<SourceVariableId, SourceVariable>;
 	public traces: Map<ExecutionId, Observable<Trace>>;
@@ -38,6 +40,7 @@ export class DebuggerState {
 	constructor(public info: DebugInfo, protected debuggerHttp: DebuggerHttpService) {
 		this.executions = new Map<ExecutionId, Execution>();
 		this.breakpoints = new Map<SourceFunctionId, Breakpoint>();
+		this.arrayViews = new Map<SourceFunctionId, ArrayView[]>();
 		this.sourceFunctions = new Map<SourceFunctionId, SourceFunction>();
 		this.sourceVariables = new Map<SourceVariableId, SourceVariable>();
 		this.sourceTypes = new Map<SourceTypeId, SourceType>();
@@ -179,4 +182,16 @@ export class DebuggerState {
 			});
 	}
 
+	public setArrayViews(id: SourceFunctionId, views: ArrayView[]): Observable<ArrayView[]> {
+		return this.debuggerHttp.setArrayViews(this.info.id, id, views)
+			.map(vs => {
+				if(vs.length > 0) {
+					this.arrayViews.set(id, vs);
+				} else {
+					this.arrayViews.delete(id);
+				}
+				return vs;
+			});
+	}
+
 }
@@ -70,7 +70,7 @@ export class SourceType {
 	}
 }
 
-type PrimitiveBaseType = "void" | "bool" | "int" | "uint" | "float";
+type PrimitiveBaseType = "void" | "bool" | "char" | "uchar" | "int" | "uint" | "float" | "extended" | "complex";
 
 interface PrimitiveType {
 	tType: 'primitive';
@@ -106,4 +106,7 @@ export class Field {
 	name: string;
 	sType: number;
 	offset: number;
+	bitOffset: number | null;
+	bitSize: number | null;
+	baseClass: boolean;
 }
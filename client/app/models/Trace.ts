@@ -16,7 +16,14 @@ export class Trace {
 
 export interface LineData {
     tType: "line";
-    state: { [sVariable: number]: Value}
+    state: { [sVariable: number]: Value};
+    pointers: { [address: string]: PointerTarget };
+    loops: LoopIteration[];
+}
+
+export interface LoopIteration {
+    loop: number;
+    iteration: number;
 }
 
 export interface CallData {
@@ -27,6 +34,7 @@ export interface CallData {
 export interface ReturnData {
     tType: "return";
     value: Value;
+    pointers: { [address: string]: PointerTarget };
 }
 
 export interface CallData {
@@ -53,6 +61,29 @@ export interface CrashData {
     stack: string;
 }
 
+export type PointerTarget =
+    { kind: "null" } |
+    { kind: "function", sFunction: number | null, name: string, offset: number } |
+    { kind: "global", name: string | null, offset: number } |
+    { kind: "stack", frame: number, sFunction: number | null, functionName: string | null, variable: string | null, offset: number } |
+    { kind: "heap" } |
+    { kind: "unmapped" };
+
+/** A short label for what a pointer points into, such as "main: value+4" or "heap" */
+export function describePointerTarget(target: PointerTarget): string {
+    let offset = (o: number) => o ? `+${o}` : '';
+    switch(target.kind) {
+        case "function":
+            return `${target.name}${offset(target.offset)}()`;
+        case "global":
+            return target.name ? `${target.name}${offset(target.offset)}` : 'static data';
+        case "stack":
+            return `${target.functionName || 'frame ' + target.frame}: ${target.variable ? target.variable + offset(target.offset) : 'stack'}`;
+        default:
+            return target.kind;
+    }
+}
+
 export interface ErrorData {
     tType: "error";
     error: SpiceError;
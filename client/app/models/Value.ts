@@ -1,14 +1,16 @@
 import { Deserialize } from "../util/SpiceValidator";
 export class Value {
 	@Deserialize()
-	value:  PrimitiveValue | PointerValue | ArrayValue | StructValue;
+	value:  PrimitiveValue | PointerValue | ArrayValue | StructValue | TextValue | UnavailableValue | VisualizedValue;
 
 	static getSerialized(val:Value | null):any {
 		if(!val) {
 			return null;
 		}
 		if(val.value !== null && typeof val.value === 'object'){
-			if(Array.isArray(val.value)) {
+			if(Value.isText(val.value) || Value.isUnavailable(val.value) || Value.isVisualized(val.value)) {
+				return val.value;
+			} else if(Array.isArray(val.value)) {
 				let outArr = [];
 				for(let v of <ArrayValue> val.value) {
 					outArr.push(Value.getSerialized(v));
@@ -32,6 +34,9 @@ export class Value {
 			if(Array.isArray((val))) {
 				return { value: val.map((v:any) => Value.deserialize(v))};
 			}
+			else if(Value.isText(val) || Value.isUnavailable(val) || Value.isVisualized(val)) {
+				return {value: val};
+			}
 			else {
 				return {value : Object.keys(val).reduce((o, v) => { o[v] = Value.deserialize(val[v]); return o}, {})};
 			}
@@ -39,9 +44,37 @@ export class Value {
 		return {value: val};
 	}
 
+	static isText(val:any):val is TextValue {
+		return val !== null && typeof val === 'object' && typeof val.text === 'string';
+	}
+
+	static isUnavailable(val:any):val is UnavailableValue {
+		return val !== null && typeof val === 'object' && typeof val.state === 'string';
+	}
+
+	static isVisualized(val:any):val is VisualizedValue {
+		return val !== null && typeof val === 'object' && typeof val.visualizer === 'string';
+	}
+
 }
 
-export type PrimitiveValue = boolean | number | null;
-export type PointerValue = number;
+export type PrimitiveValue = boolean | number | string | null;
+export type PointerValue = string; // decimal, so addresses survive JavaScript's doubles
 export type ArrayValue = Value[];
 export type StructValue = { [offset: number]: Value };
+export type TextValue = { text: string; bytes: number[] | null; truncated: boolean };
+export type UnavailableValue =
+	{ state: 'uninitialized' } |
+	{ state: 'unreadable'; address: string | null } |
+	{ state: 'optimizedOut' };
+// contents and raw are left as the server sent them
+export type VisualizedValue = {
+	visualizer: string;
+	sType: number | null;
+	keyType: number | null;
+	display: string | null;
+	items: { name: string; sType: number | null; value: any }[];
+	contents: any;
+	truncated: boolean;
+	raw: any;
+};
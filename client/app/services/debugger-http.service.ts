@@ -8,6 +8,7 @@ import {Observable} from "rxjs/Observable";
 import {DebuggerState} from "../models/DebuggerState";
 import {Execution, ExecutionId} from "../models/Execution";
 import {Breakpoint} from "../models/Breakpoint";
+import {ArrayView} from "../models/ArrayView";
 import {LineData, Trace} from "../models/Trace";
 import {Subscriber} from "rxjs/Subscriber";
 import {Process} from "../models/Process";
@@ -126,6 +127,23 @@ export class DebuggerHttpService {
 			.publishLast().refCount();
 	}
 
+	/**
+	 * Array views
+	 */
+	public getArrayViews(id: DebugId, sFunction: SourceFunctionId): Observable<ArrayView[]> {
+		return this.http.get(`http://${host}:${port}/api/v1/debug/${id}/arrays/${sFunction}`)
+			.map(res => res.json().map((json: any) => ArrayView.deserialize(json)))
+			.catch(DebuggerHttpService.handleServerDataError('ArrayView'))
+			.publishLast().refCount();
+	}
+
+	public setArrayViews(id: DebugId, sFunction: SourceFunctionId, views: ArrayView[]): Observable<ArrayView[]> {
+		return this.http.put(`http://${host}:${port}/api/v1/debug/${id}/arrays/${sFunction}`, views)
+			.map(res => res.json().map((json: any) => ArrayView.deserialize(json)))
+			.catch(DebuggerHttpService.handleServerDataError('ArrayView'))
+			.publishLast().refCount();
+	}
+
 	/**
 	 * Executions
 	 */
@@ -266,12 +266,16 @@ export class DebuggerService {
 		this.debuggerHttp.attachBinary(event.lastDebuggerState.binaryPath)
 			.subscribe(
 				ds => {
-					Observable.forkJoin(Array.from(event.lastDebuggerState.breakpoints.keys()).map(bId => ds.setBreakpoint(bId))).defaultIfEmpty([])
+					let lastState = event.lastDebuggerState;
+					Observable.forkJoin(
+						...Array.from(lastState.breakpoints.keys()).map(bId => ds.setBreakpoint(bId).map(() => null)),
+						...Array.from(lastState.arrayViews.entries()).map(([fId, views]) => ds.setArrayViews(fId, views).map(() => null)))
+						.defaultIfEmpty([])
 						.subscribe(
 							() => {
-								this.onAttach(ds, event.lastDebuggerState.name, event.lastDebuggerState.binaryPath, event.lastDebuggerState.isBinary);
+								this.onAttach(ds, lastState.name, lastState.binaryPath, lastState.isBinary);
 							},
-							(err) => {console.error(`Failed to set breakpoint ${err}`);});
+							(err) => {console.error(`Failed to restore breakpoints and array views ${err}`);});
 				},
 				err => { console.error(`Failed to reattach to binary: ${err}`); });
 	}
@@ -41,6 +41,7 @@ import {PrimitiveTypeDisplay} from "./components/common/variable-display/primiti
 import {ArrayTypeDisplay} from "./components/common/variable-display/array-type-display.component";
 import {PointerTypeDisplay} from "./components/common/variable-display/pointer-type-display.component";
 import {FunctionTypeDisplay} from "./components/common/variable-display/function-type-display.component";
+import {VisualizedTypeDisplay} from "./components/common/variable-display/visualized-type-display.component";
 import {GraphDisplayComponent} from "./components/common/graph-display.component";
 
 @NgModule({
@@ -70,6 +71,7 @@ import {GraphDisplayComponent} from "./components/common/graph-display.component
         ArrayTypeDisplay,
         PointerTypeDisplay,
         FunctionTypeDisplay,
+        VisualizedTypeDisplay,
         GraphDisplayComponent,
     ],
     providers: [
@@ -82,7 +82,7 @@ fn main() {
                 println!("{}", string);
             }
 
-            Exception { first_chance, code, address } => {
+            Exception { first_chance, code, address, .. } => {
                 if !attached {
                     attached = true;
                 } else if !first_chance {
@@ -71,11 +71,18 @@ impl<'a> fmt::Display for Type<'a> {
                 match base {
                     Void => write!(fmt, "()"),
                     Bool => write!(fmt, "bool"),
+                    Char { signed } => {
+                        let prefix = if signed { "c" } else { "uc" };
+                        write!(fmt, "{}{}", prefix, 8 * size)
+                    }
                     Int { signed } => {
                         let prefix = if signed { "i" } else { "u" };
                         write!(fmt, "{}{}", prefix, 8 * size)
                     }
                     Float => write!(fmt, "f{}", 8 * size),
+                    Extended => write!(fmt, "f80"),
+                    Complex { extended: false } => write!(fmt, "complex<f{}>", 4 * size),
+                    Complex { extended: true } => write!(fmt, "complex<f80>"),
                 }
             }
 
@@ -93,6 +100,13 @@ impl<'a> fmt::Display for Type<'a> {
                 write!(fmt, "[{}; {}]", Type(element, symbols, module), count)
             }
 
+            &Vector { type_index, count } => {
+                let element = symbols.type_from_index(module, type_index)
+                    .map_err(|_| fmt::Error)?;
+
+                write!(fmt, "simd<{}; {}>", Type(element, symbols, module), count)
+            }
+
             &Function { type_index, ref args, .. } => {
                 write!(fmt, "fn (")?;
 
@@ -112,7 +126,11 @@ impl<'a> fmt::Display for Type<'a> {
                 Ok(())
             }
 
-            &Struct { ref name, .. } => {
+            &Struct { ref name, .. } | &Union { ref name, .. } => {
+                write!(fmt, "{}", name.to_string_lossy())
+            }
+
+            &Enum { ref name, .. } => {
                 write!(fmt, "{}", name.to_string_lossy())
             }
         }
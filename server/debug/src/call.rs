@@ -1,10 +1,8 @@
-use std::{mem, io};
+use std::{cmp, mem, io};
 use std::collections::{HashMap, VecDeque};
 
-use winapi;
-
 use AsBytes;
-use {Child, Context, Value, Type, Primitive, SymbolHandler, Symbol};
+use {Child, Context, Register, Value, Type, Primitive, SymbolHandler, Symbol};
 
 /// A captured function call.
 ///
@@ -12,9 +10,31 @@ use {Child, Context, Value, Type, Primitive, SymbolHandler, Symbol};
 /// completion or cancellation.
 pub struct Call {
     return_type: Type,
+    convention: CallingConvention,
     context: Option<Context>,
 }
 
+/// The calling conventions `Call` knows how to synthesize
+///
+/// Each platform's debug info only names one of them.
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum CallingConvention {
+    /// The Microsoft x64 calling convention
+    Win64,
+    /// The System V AMD64 ABI
+    SysV,
+}
+
+/// How an eightbyte of a value is passed under the System V AMD64 ABI
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Class {
+    Integer,
+    Sse,
+    /// The upper half of the SSE register used by the previous eightbyte
+    SseUp,
+}
+
 pub trait IntoValue {
     /// Convert a client-specific value into two things:
     /// - A byte buffer containing its representation for the target program
@@ -30,10 +50,10 @@ impl Call {
     /// Capture a function call that has already happened,
     /// in preparation for extracting its return value.
     pub fn capture(symbols: &SymbolHandler, function: &Symbol) -> io::Result<Call> {
-        let (module, return_type, _) = get_function_types(symbols, function)?;
+        let (module, convention, return_type, _) = get_function_types(symbols, function)?;
         let return_type = symbols.type_from_index(module, return_type)?;
 
-        Ok(Call { return_type, context: None })
+        Ok(Call { return_type, convention, context: None })
     }
 
     /// Synthesize a function call on the stack of the thread with the given context.
@@ -43,10 +63,10 @@ impl Call {
         child: &Child, symbols: &SymbolHandler,
         old_context: &mut Context, function: &Symbol, mut arg_values: HashMap<usize, A>
     ) -> io::Result<Call> {
-        let (module, return_type, arg_types) = get_function_types(symbols, function)?;
+        let (module, convention, return_type, arg_types) = get_function_types(symbols, function)?;
         let mut arg_offsets = vec![];
         symbols.enumerate_locals(function.address, |symbol, _| {
-            if symbol.flags & winapi::SYMFLAG_PARAMETER != 0 {
+            if symbol.is_parameter() {
                 arg_offsets.push(symbol.address);
             }
             true
@@ -69,6 +89,12 @@ impl Call {
 
         let mut context = old_context.clone();
 
+        // skip the interrupted function's red zone, which may hold live data
+        if convention == CallingConvention::SysV {
+            let stack_pointer = context.stack_pointer() - 128;
+            context.set_stack_pointer(stack_pointer);
+        }
+
         // write indirect values to the stack
         while let Some((offset, type_index)) = pointers.pop_front() {
             if addresses.contains_key(&offset) {
@@ -97,87 +123,16 @@ impl Call {
         }
 
         // write direct arguments to registers and the stack
-        //
-        // TODO: this is a simplistic implemenation of the win64 calling convention.
-        // it is probably missing some details and doesn't support other CCs.
-
-        let mut args = args.into_iter();
-
         let return_type = symbols.type_from_index(module, return_type)?;
-        let return_size = return_type.size(symbols, module);
-        if return_size > 8 {
-            let stack_pointer = context.stack_pointer() - return_size;
-            context.set_stack_pointer(stack_pointer);
-
-            let context = context.as_raw_mut();
-            context.Rcx = stack_pointer as winapi::DWORD64;
-        } else if let Some((arg, arg_type, offsets)) = args.next() {
-            let (value, float) = write_value(
-                arg, &arg_type, child, &mut context, &addresses, &offsets
-            )?;
-
-            let context = context.as_raw_mut();
-            if !float {
-                context.Rcx = value as winapi::DWORD64;
-            } else {
-                context.FltSave.XmmRegisters[0].Low = value as winapi::ULONGLONG;
-            }
-        }
-
-        if let Some((arg, arg_type, offsets)) = args.next() {
-            let (value, float) = write_value(
-                arg, &arg_type, child, &mut context, &addresses, &offsets
-            )?;
-
-            let context = context.as_raw_mut();
-            if !float {
-                context.Rdx = value as winapi::DWORD64;
-            } else {
-                context.FltSave.XmmRegisters[1].Low = value as winapi::ULONGLONG;
-            }
-        }
-
-        if let Some((arg, arg_type, offsets)) = args.next() {
-            let (value, float) = write_value(
-                arg, &arg_type, child, &mut context, &addresses, &offsets
-            )?;
-
-            let context = context.as_raw_mut();
-            if !float {
-                context.R8 = value as winapi::DWORD64;
-            } else {
-                context.FltSave.XmmRegisters[2].Low = value as winapi::ULONGLONG;
-            }
+        match convention {
+            CallingConvention::Win64 => win64_arguments(
+                child, symbols, module, &mut context, &return_type, args, &addresses
+            )?,
+            CallingConvention::SysV => sysv_arguments(
+                child, symbols, module, &mut context, &return_type, args, &addresses
+            )?,
         }
 
-        if let Some((arg, arg_type, offsets)) = args.next() {
-            let (value, float) = write_value(
-                arg, &arg_type, child, &mut context, &addresses, &offsets
-            )?;
-
-            let context = context.as_raw_mut();
-            if !float {
-                context.R9 = value as winapi::DWORD64;
-            } else {
-                context.FltSave.XmmRegisters[3].Low = value as winapi::ULONGLONG;
-            }
-        }
-
-        // large values passed by pointer need to be allocated before any stack args
-        let mut values = vec![];
-        for (arg, arg_type, offsets) in args {
-            let (value, _) = write_value(
-                arg, &arg_type, child, &mut context, &addresses, &offsets
-            )?;
-            values.push(value);
-        }
-        for value in values {
-            child.stack_push(&mut context, value)?;
-        }
-
-        let stack_pointer = context.stack_pointer();
-        context.set_stack_pointer(stack_pointer - 4 * mem::size_of::<u64>());
-
         let return_address = context.instruction_pointer();
         child.stack_push(&mut context, return_address)?;
 
@@ -185,6 +140,7 @@ impl Call {
 
         Ok(Call {
             return_type: return_type,
+            convention: convention,
             context: Some(mem::replace(old_context, context))
         })
     }
@@ -193,7 +149,7 @@ impl Call {
     pub fn teardown(
         self, child: &Child, context: &Context, symbols: &SymbolHandler
     ) -> io::Result<(Value, Option<Context>)> {
-        let value = Value::read_return(child, context.as_raw(), symbols, self.return_type)?;
+        let value = read_return(child, context, symbols, self.convention, self.return_type)?;
         Ok((value, self.context))
     }
 
@@ -203,7 +159,7 @@ impl Call {
 }
 
 fn get_function_types(symbols: &SymbolHandler, function: &Symbol) ->
-    io::Result<(usize, u32, Vec<u32>)>
+    io::Result<(usize, CallingConvention, u32, Vec<u32>)>
 {
     let module = symbols.module_from_address(function.address)?;
     let function_type = symbols.type_from_index(module, function.type_index)?;
@@ -214,11 +170,401 @@ fn get_function_types(symbols: &SymbolHandler, function: &Symbol) ->
         _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot call a non-function")),
     };
 
-    if calling_convention != 0 { // CV_CALL_NEAR_C
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported calling convention"));
+    let convention = CallingConvention::from_raw(calling_convention)
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData, "unsupported calling convention"))?;
+
+    Ok((module, convention, return_type, arg_types))
+}
+
+impl CallingConvention {
+    #[cfg(windows)]
+    fn from_raw(calling_convention: u32) -> Option<CallingConvention> {
+        match calling_convention {
+            0 => Some(CallingConvention::Win64), // CV_CALL_NEAR_C
+            _ => None,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn from_raw(calling_convention: u32) -> Option<CallingConvention> {
+        match calling_convention {
+            1 => Some(CallingConvention::SysV), // DW_CC_normal
+            _ => None,
+        }
+    }
+}
+
+/// Write direct arguments to registers and the stack, following the Win64 calling convention.
+///
+/// TODO: this is a simplistic implemenation of the win64 calling convention.
+/// it is probably missing some details.
+fn win64_arguments(
+    child: &Child, symbols: &SymbolHandler, module: usize, context: &mut Context,
+    return_type: &Type, args: Vec<(Value, Type, HashMap<usize, usize>)>,
+    addresses: &HashMap<usize, usize>
+) -> io::Result<()> {
+    let mut args = args.into_iter();
+
+    let return_size = return_type.size(symbols, module);
+    if return_size > 8 {
+        let stack_pointer = context.stack_pointer() - return_size;
+        context.set_stack_pointer(stack_pointer);
+
+        context.set_register(Register::Rcx, stack_pointer as u64);
+    } else if let Some((arg, arg_type, offsets)) = args.next() {
+        let (value, float) = write_value(arg, &arg_type, child, context, addresses, &offsets)?;
+
+        if !float {
+            context.set_register(Register::Rcx, value as u64);
+        } else {
+            context.set_xmm(0, xmm_value(value));
+        }
+    }
+
+    if let Some((arg, arg_type, offsets)) = args.next() {
+        let (value, float) = write_value(arg, &arg_type, child, context, addresses, &offsets)?;
+
+        if !float {
+            context.set_register(Register::Rdx, value as u64);
+        } else {
+            context.set_xmm(1, xmm_value(value));
+        }
+    }
+
+    if let Some((arg, arg_type, offsets)) = args.next() {
+        let (value, float) = write_value(arg, &arg_type, child, context, addresses, &offsets)?;
+
+        if !float {
+            context.set_register(Register::R8, value as u64);
+        } else {
+            context.set_xmm(2, xmm_value(value));
+        }
+    }
+
+    if let Some((arg, arg_type, offsets)) = args.next() {
+        let (value, float) = write_value(arg, &arg_type, child, context, addresses, &offsets)?;
+
+        if !float {
+            context.set_register(Register::R9, value as u64);
+        } else {
+            context.set_xmm(3, xmm_value(value));
+        }
+    }
+
+    // large values passed by pointer need to be allocated before any stack args
+    let mut values = vec![];
+    for (arg, arg_type, offsets) in args {
+        let (value, _) = write_value(arg, &arg_type, child, context, addresses, &offsets)?;
+        values.push(value);
+    }
+    for value in values {
+        child.stack_push(context, value)?;
     }
 
-    Ok((module, return_type, arg_types))
+    // shadow space
+    let stack_pointer = context.stack_pointer();
+    context.set_stack_pointer(stack_pointer - 4 * mem::size_of::<u64>());
+
+    Ok(())
+}
+
+/// The registers used for INTEGER class arguments, in order
+const SYSV_INTEGER_REGISTERS: [Register; 6] = [
+    Register::Rdi, Register::Rsi, Register::Rdx, Register::Rcx, Register::R8, Register::R9,
+];
+
+/// The number of SSE registers used for arguments
+const SYSV_SSE_REGISTERS: usize = 8;
+
+/// Write direct arguments to registers and the stack, following the System V AMD64 ABI.
+fn sysv_arguments(
+    child: &Child, symbols: &SymbolHandler, module: usize, context: &mut Context,
+    return_type: &Type, args: Vec<(Value, Type, HashMap<usize, usize>)>,
+    addresses: &HashMap<usize, usize>
+) -> io::Result<()> {
+    let mut integer = 0;
+    let mut sse = 0;
+
+    // values returned in memory are written to caller-allocated space, passed as a hidden first
+    // argument
+    let return_size = return_type.size(symbols, module);
+    if return_size > 0 && classify(symbols, module, return_type)?.is_none() {
+        let stack_pointer = (context.stack_pointer() - return_size) & !0xf;
+        context.set_stack_pointer(stack_pointer);
+
+        context.set_register(SYSV_INTEGER_REGISTERS[integer], stack_pointer as u64);
+        integer += 1;
+    }
+
+    // each argument goes entirely in registers if there are enough left, or else in memory
+    let mut memory = vec![];
+    for (arg, arg_type, offsets) in args {
+        let arg = prepare_value(arg, &arg_type, addresses, &offsets)?;
+        let classes = match classify(symbols, module, &arg_type)? {
+            Some(classes) => classes,
+            None => {
+                memory.push(arg);
+                continue;
+            }
+        };
+
+        let integers = classes.iter().filter(|&&class| class == Class::Integer).count();
+        let sses = classes.iter().filter(|&&class| class == Class::Sse).count();
+        if integer + integers > SYSV_INTEGER_REGISTERS.len() || sse + sses > SYSV_SSE_REGISTERS {
+            memory.push(arg);
+            continue;
+        }
+
+        for (&class, eightbyte) in Iterator::zip(classes.iter(), arg.data.chunks(8)) {
+            let mut value = [0u8; 16];
+            value[..eightbyte.len()].copy_from_slice(eightbyte);
+
+            match class {
+                Class::Integer => {
+                    let value = unsafe { *(value.as_ptr() as *const u64) };
+                    context.set_register(SYSV_INTEGER_REGISTERS[integer], value);
+                    integer += 1;
+                }
+                Class::Sse => {
+                    context.set_xmm(sse, value);
+                    sse += 1;
+                }
+                Class::SseUp => {
+                    let mut xmm = context.xmm(sse - 1);
+                    xmm[8..].copy_from_slice(&value[..8]);
+                    context.set_xmm(sse - 1, xmm);
+                }
+            }
+        }
+    }
+
+    // memory arguments are laid out in order, each padded to an eightbyte, starting at a 16-byte
+    // aligned stack pointer
+    let size: usize = memory.iter().map(|arg| (arg.data.len() + 7) & !7).sum();
+    let mut address = (context.stack_pointer() - size) & !0xf;
+    context.set_stack_pointer(address);
+    for arg in memory {
+        child.write_memory(address, &arg.data)?;
+        address += (arg.data.len() + 7) & !7;
+    }
+
+    // variadic functions expect the number of SSE registers used in `al`
+    context.set_register(Register::Rax, sse as u64);
+
+    Ok(())
+}
+
+/// Whether a value is an x87 float or complex number, rather than an SSE one.
+fn is_x87(data_type: &Type) -> bool {
+    match *data_type {
+        Type::Base { base: Primitive::Extended, .. } |
+        Type::Base { base: Primitive::Complex { extended: true }, .. } => true,
+        _ => false,
+    }
+}
+
+/// Classify each eightbyte of a value under the System V AMD64 ABI.
+///
+/// Returns `None` if the value is passed in memory.
+fn classify(
+    symbols: &SymbolHandler, module: usize, data_type: &Type
+) -> io::Result<Option<Vec<Class>>> {
+    let size = data_type.size(symbols, module);
+    if size > 16 {
+        return Ok(None);
+    }
+
+    let mut classes = vec![None; (size + 7) / 8];
+    if !classify_fields(symbols, module, data_type, 0, &mut classes)? {
+        return Ok(None);
+    }
+
+    // eightbytes made up entirely of padding can go anywhere
+    let classes = classes.into_iter().map(|class| class.unwrap_or(Class::Integer)).collect();
+    Ok(Some(classes))
+}
+
+/// Merge the classes of a value's scalar fields into the eightbytes they occupy.
+///
+/// Returns `false` if the value must be passed in memory.
+fn classify_fields(
+    symbols: &SymbolHandler, module: usize, data_type: &Type, offset: usize,
+    classes: &mut [Option<Class>]
+) -> io::Result<bool> {
+    let class = match *data_type {
+        Type::Base { base: Primitive::Void, .. } => return Ok(true),
+
+        // quadruple precision floats and vectors fill a whole SSE register
+        Type::Base { base: Primitive::Float, size: 16 } | Type::Vector { .. } => {
+            let size = data_type.size(symbols, module);
+            if offset % size != 0 {
+                return Ok(false);
+            }
+
+            classes[offset / 8] = Some(Class::Sse);
+            if size > 8 {
+                classes[offset / 8 + 1] = Some(Class::SseUp);
+            }
+            return Ok(true);
+        }
+
+        Type::Base { base: Primitive::Float, .. } => Class::Sse,
+
+        // complex numbers are classified as a pair of floats
+        Type::Base { base: Primitive::Complex { extended: false }, size } => {
+            let part = Type::Base { base: Primitive::Float, size: size / 2 };
+            return Ok(
+                classify_fields(symbols, module, &part, offset, classes)? &&
+                classify_fields(symbols, module, &part, offset + size / 2, classes)?
+            );
+        }
+
+        // x87 values are passed in memory; returns are handled by `read_return`
+        Type::Base { base: Primitive::Extended, .. } |
+        Type::Base { base: Primitive::Complex { extended: true }, .. } => return Ok(false),
+
+        Type::Base { .. } | Type::Pointer { .. } | Type::Enum { .. } => Class::Integer,
+
+        Type::Array { type_index, count } => {
+            let element_type = symbols.type_from_index(module, type_index)?;
+            let size = element_type.size(symbols, module);
+            for offset in (0..count).map(|i| offset + i * size) {
+                if !classify_fields(symbols, module, &element_type, offset, classes)? {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
+
+        Type::Struct { ref fields, .. } | Type::Union { ref fields, .. } => {
+            for field in fields {
+                let offset = offset + field.offset as usize;
+
+                // bitfields are integers, and may straddle their type's alignment
+                if let Some(bits) = field.bits {
+                    let start = 8 * offset + bits.offset as usize;
+                    let end = start + bits.size as usize;
+                    for eightbyte in &mut classes[start / 64..(end + 63) / 64] {
+                        *eightbyte = Some(Class::Integer);
+                    }
+                    continue;
+                }
+
+                let field_type = symbols.type_from_index(module, field.type_index)?;
+                if !classify_fields(symbols, module, &field_type, offset, classes)? {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
+
+        Type::Function { .. } => return Ok(false),
+    };
+
+    // unaligned fields force the whole value into memory
+    let size = data_type.size(symbols, module);
+    if offset % size != 0 {
+        return Ok(false);
+    }
+
+    for eightbyte in &mut classes[offset / 8..(offset + size + 7) / 8] {
+        *eightbyte = match (*eightbyte, class) {
+            (Some(Class::Integer), _) | (_, Class::Integer) => Some(Class::Integer),
+            _ => Some(Class::Sse),
+        };
+    }
+
+    Ok(true)
+}
+
+/// Read a value that was returned from a function.
+fn read_return(
+    child: &Child, context: &Context, symbols: &SymbolHandler, convention: CallingConvention,
+    data_type: Type
+) -> io::Result<Value> {
+    let module = symbols.module_from_address(context.instruction_pointer())?;
+    let data_size = data_type.size(symbols, module);
+
+    let mut data = vec![0u8; data_size];
+    match convention {
+        CallingConvention::Win64 => {
+            let float = match data_type {
+                Type::Base { base: Primitive::Float, .. } => true,
+                _ => false,
+            };
+
+            match data_type {
+                Type::Base { .. } | Type::Pointer { .. } | Type::Enum { .. } |
+                Type::Struct { .. } | Type::Union { .. }
+                if data_size <= 8 => {
+                    let source = if !float {
+                        let rax = context.register(Register::Rax);
+                        let mut source = [0u8; 16];
+                        source[..8].copy_from_slice(rax.as_bytes());
+                        source
+                    } else {
+                        context.xmm(0)
+                    };
+                    data.copy_from_slice(&source[..data_size]);
+                }
+
+                Type::Struct { .. } | Type::Union { .. } if data_size > 8 => {
+                    child.read_memory(context.register(Register::Rax) as usize, &mut data)?;
+                }
+
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other, "cannot return a dynamically sized value"
+                    ));
+                }
+            }
+        }
+
+        // x87 values are returned in `st0`, and complex ones in `st0` and `st1`
+        CallingConvention::SysV if is_x87(&data_type) => {
+            for (index, part) in data.chunks_mut(16).enumerate() {
+                let size = cmp::min(part.len(), 10);
+                part[..size].copy_from_slice(&context.st(index)[..size]);
+            }
+        }
+
+        CallingConvention::SysV => match classify(symbols, module, &data_type)? {
+            // the hidden return pointer is returned in `rax`
+            None => {
+                child.read_memory(context.register(Register::Rax) as usize, &mut data)?;
+            }
+
+            // eightbytes are returned in `rax` and `rdx`, or `xmm0` and `xmm1`
+            Some(classes) => {
+                let mut integer = [Register::Rax, Register::Rdx].iter();
+                let mut sse = 0;
+                for (&class, eightbyte) in Iterator::zip(classes.iter(), data.chunks_mut(8)) {
+                    let source = match class {
+                        Class::Integer => {
+                            let value = context.register(*integer.next().unwrap());
+                            let mut source = [0u8; 16];
+                            source[..8].copy_from_slice(value.as_bytes());
+                            source
+                        }
+                        Class::Sse => {
+                            sse += 1;
+                            context.xmm(sse - 1)
+                        }
+                        Class::SseUp => {
+                            let mut source = [0u8; 16];
+                            source[..8].copy_from_slice(&context.xmm(sse - 1)[8..]);
+                            source
+                        }
+                    };
+
+                    let size = eightbyte.len();
+                    eightbyte.copy_from_slice(&source[..size]);
+                }
+            }
+        },
+    }
+
+    Ok(Value { data, data_type, module })
 }
 
 /// Prepare a value to become an argument.
@@ -227,26 +573,20 @@ fn get_function_types(symbols: &SymbolHandler, function: &Symbol) ->
 /// If would fit in a register, return its bit pattern (this may be pushed onto the stack later).
 /// If it needs to be on the stack, push it and return its address.
 fn write_value(
-    mut arg: Value, arg_type: &Type, child: &Child, context: &mut Context,
+    arg: Value, arg_type: &Type, child: &Child, context: &mut Context,
     addresses: &HashMap<usize, usize>, offsets: &HashMap<usize, usize>
 ) -> io::Result<(usize, bool)> {
-    if &arg.data_type != arg_type {
-        return Err(io::Error::new(io::ErrorKind::InvalidInput, "argument types do not match"));
-    }
+    let arg = prepare_value(arg, arg_type, addresses, offsets)?;
 
     let float = match *arg_type {
         Type::Base { base: Primitive::Float, .. } => true,
         _ => false,
     };
 
-    for (&offset, &target) in offsets {
-        let bytes = addresses[&target].as_bytes();
-        arg.data[offset..bytes.len()].copy_from_slice(bytes);
-    }
-
     let value = arg.data.as_ptr();
     let value = match *arg_type {
-        Type::Base { .. } | Type::Pointer { .. } | Type::Struct { .. }
+        Type::Base { .. } | Type::Pointer { .. } | Type::Enum { .. } |
+        Type::Struct { .. } | Type::Union { .. }
         if arg.data.len() <= 8 => {
             match arg.data.len() {
                 1 => unsafe { *(value as *const u8) as usize },
@@ -257,7 +597,7 @@ fn write_value(
             }
         }
 
-        Type::Struct { .. } if arg.data.len() > 8 => {
+        Type::Struct { .. } | Type::Union { .. } if arg.data.len() > 8 => {
             child.stack_push(context, &arg)?;
             context.stack_pointer()
         }
@@ -271,3 +611,178 @@ fn write_value(
 
     Ok((value, float))
 }
+
+/// Check an argument's type, and point the pointers it contains at their targets on the stack.
+fn prepare_value(
+    mut arg: Value, arg_type: &Type,
+    addresses: &HashMap<usize, usize>, offsets: &HashMap<usize, usize>
+) -> io::Result<Value> {
+    if &arg.data_type != arg_type {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "argument types do not match"));
+    }
+
+    for (&offset, &target) in offsets {
+        let bytes = addresses[&target].as_bytes();
+        arg.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    Ok(arg)
+}
+
+/// Widen a floating point argument's bit pattern to fill an SSE register.
+fn xmm_value(value: usize) -> [u8; 16] {
+    let mut xmm = [0u8; 16];
+    xmm[..8].copy_from_slice((value as u64).as_bytes());
+    xmm
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{env, fs};
+    use std::fs::File;
+    use std::io::Write;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+    use libc;
+
+    use {Child, Context, Register, SymbolHandler, Type, AsBytes, read_float, write_float};
+    use super::*;
+    use super::Class::*;
+
+    /// Where test programs' symbols are loaded; nothing is mapped there
+    const BASE: usize = 0x1000_0000;
+
+    static NEXT: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    /// Compile C declarations with debug info, and load the program's symbols
+    fn compile(source: &str) -> SymbolHandler {
+        let id = NEXT.fetch_add(1, Ordering::SeqCst);
+        let pid = unsafe { libc::getpid() };
+        let path = env::temp_dir().join(format!("spice-call-{}-{}", pid, id));
+        let source_path = path.with_extension("c");
+
+        let mut file = File::create(&source_path).unwrap();
+        write!(file, "{}\nint main(void) {{ return 0; }}\n", source).unwrap();
+        let status = Command::new("cc").arg("-g").arg("-o").arg(&path).arg(&source_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let symbols = SymbolHandler::empty();
+        symbols.load_module(&File::open(&path).unwrap(), BASE).unwrap();
+        let _ = fs::remove_file(&source_path);
+        let _ = fs::remove_file(&path);
+        symbols
+    }
+
+    /// The type of a global named `value`, compiled as declared by `source`
+    fn value_type(source: &str) -> (SymbolHandler, Type) {
+        let symbols = compile(&format!("{} value;", source));
+        let symbol = symbols.symbol_from_name("value").unwrap();
+        let data_type = symbols.type_from_index(BASE, symbol.type_index).unwrap();
+        (symbols, data_type)
+    }
+
+    fn classes(source: &str) -> Option<Vec<Class>> {
+        let (symbols, data_type) = value_type(source);
+        classify(&symbols, BASE, &data_type).unwrap()
+    }
+
+    /// Read a value of the type declared by `source` as returned in `context`
+    fn returned(source: &str, context: &Context) -> Vec<u8> {
+        let (symbols, data_type) = value_type(source);
+        let mut context = context.clone();
+        context.set_instruction_pointer(BASE);
+        let value = read_return(&Child::current(), &context, &symbols, CallingConvention::SysV,
+            data_type);
+        value.unwrap().data
+    }
+
+    fn xmm(low: &[u8]) -> [u8; 16] {
+        let mut xmm = [0u8; 16];
+        xmm[..low.len()].copy_from_slice(low);
+        xmm
+    }
+
+    #[test]
+    fn classify_registers() {
+        assert!(classes("struct { double d; long l; }") == Some(vec![Sse, Integer]));
+        assert!(classes("struct { float a, b; int c; }") == Some(vec![Sse, Integer]));
+        assert!(classes("struct { float a; int b; float c, d; }") == Some(vec![Integer, Sse]));
+        assert!(classes("struct { char c[8]; float f; }") == Some(vec![Integer, Sse]));
+        assert!(classes("__int128") == Some(vec![Integer, Integer]));
+        assert!(classes("__float128") == Some(vec![Sse, SseUp]));
+    }
+
+    #[test]
+    fn classify_memory() {
+        assert!(classes("struct { long a, b, c; }").is_none());
+        assert!(classes("struct __attribute__((packed)) { char c; double d; }").is_none());
+        assert!(classes("long double").is_none());
+        assert!(classes("_Complex long double").is_none());
+    }
+
+    #[test]
+    fn classify_straddling_bitfields() {
+        // `s` spills into the second eightbyte, which would otherwise only hold a float
+        let source = "struct __attribute__((packed)) {
+            char c[7]; unsigned s : 9; unsigned : 31; float f;
+        }";
+        assert!(classes(source) == Some(vec![Integer, Integer]));
+
+        let source = "struct { unsigned a : 8; unsigned b : 24; float f; float g; }";
+        assert!(classes(source) == Some(vec![Integer, Sse]));
+    }
+
+    #[test]
+    fn return_integer_pair() {
+        let mut context = Context::empty();
+        context.set_register(Register::Rax, 0x1111111111111111);
+        context.set_register(Register::Rdx, 0x2222222222222222);
+
+        let expected = [[0x11; 8], [0x22; 8]].concat();
+        assert_eq!(returned("__int128", &context), expected);
+        assert_eq!(returned("struct { long a; char b[8]; }", &context), expected);
+        assert_eq!(returned("struct { int a; short b; }", &context), &expected[..8]);
+    }
+
+    #[test]
+    fn return_sse_pair() {
+        let mut context = Context::empty();
+        context.set_xmm(0, xmm(&[1.5f32.as_bytes(), 2.5f32.as_bytes()].concat()));
+        context.set_xmm(1, xmm((-2.0f64).as_bytes()));
+        context.set_register(Register::Rax, 7);
+
+        let data = returned("struct { float a, b; double c; }", &context);
+        assert_eq!(data, [1.5f32.as_bytes(), 2.5f32.as_bytes(), (-2.0f64).as_bytes()].concat());
+
+        // the integer eightbyte still comes from `rax`, and the float one from `xmm0`
+        let data = returned("struct { long a; float b, c; }", &context);
+        assert_eq!(data, [7u64.as_bytes(), 1.5f32.as_bytes(), 2.5f32.as_bytes()].concat());
+
+        // a quadruple precision float fills all of `xmm0`
+        let bytes: Vec<_> = (1..17).collect();
+        context.set_xmm(0, xmm(&bytes));
+        assert_eq!(returned("__float128", &context), bytes);
+    }
+
+    #[test]
+    fn return_x87() {
+        let mut context = Context::empty();
+        let mut st = [0u8; 16];
+        write_float(1.5, true, &mut st[..10]);
+        context.set_st(0, st);
+        write_float(-2.0, true, &mut st[..10]);
+        context.set_st(1, st);
+
+        let data = returned("long double", &context);
+        assert_eq!(data.len(), 16);
+        assert_eq!(read_float(&data, true), 1.5);
+
+        let data = returned("_Complex long double", &context);
+        assert_eq!(data.len(), 32);
+        assert_eq!(read_float(&data[..16], true), 1.5);
+        assert_eq!(read_float(&data[16..], true), -2.0);
+    }
+}
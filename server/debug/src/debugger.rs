@@ -0,0 +1,99 @@
+use std::io;
+use std::collections::HashMap;
+
+use {Child, Command, Event, Thread, Context, SymbolHandler, Symbol, StackFrames};
+use {Call, IntoValue, Value};
+
+/// A platform's debugging facilities, bundled behind a single interface.
+///
+/// The server's trace engine is written against this trait, so it does not need to know which OS
+/// it is running on. Porting Spice to another platform means providing another implementation,
+/// along with the `Child`, `Event`, `Context` and `SymbolHandler` types it is expressed in terms
+/// of. `Native` names the implementation for the current platform.
+pub trait Debugger: Sized {
+    // process control
+
+    /// Launch a program and begin debugging it.
+    fn launch(command: &mut Command) -> io::Result<Self>;
+
+    /// Begin debugging an already-running process.
+    fn attach(pid: u32) -> io::Result<Self>;
+
+    /// The target process, for memory access and breakpoints.
+    fn child(&self) -> &Child;
+
+    /// Kill the target process and release its resources.
+    fn terminate(self) -> io::Result<()>;
+
+    // events
+
+    /// Block until the target process generates a debug event.
+    fn wait_event(&self) -> io::Result<Event>;
+
+    // registers
+
+    /// Read a suspended thread's CPU state.
+    fn get_context(&self, thread: Thread) -> io::Result<Context>;
+
+    /// Write a suspended thread's CPU state.
+    fn set_context(&self, thread: Thread, context: &Context) -> io::Result<()>;
+
+    // symbols
+
+    /// The symbol handler for the target process's modules.
+    fn symbols(&self) -> &SymbolHandler;
+
+    /// Iterate through the frames of a suspended thread's stack.
+    fn walk_stack(&self, thread: Thread) -> io::Result<StackFrames> {
+        self.symbols().walk_stack(thread)
+    }
+
+    // calls
+
+    /// Capture a call that the target process has already made. See `Call::capture`.
+    fn capture_call(&self, function: &Symbol) -> io::Result<Call> {
+        Call::capture(self.symbols(), function)
+    }
+
+    /// Synthesize a call on a suspended thread's stack. See `Call::setup`.
+    fn setup_call<A: IntoValue>(
+        &self, context: &mut Context, function: &Symbol, arguments: HashMap<usize, A>
+    ) -> io::Result<Call> {
+        Call::setup(self.child(), self.symbols(), context, function, arguments)
+    }
+
+    /// Extract the return value of a call once it has returned. See `Call::teardown`.
+    fn finish_call(&self, call: Call, context: &Context) -> io::Result<(Value, Option<Context>)> {
+        call.teardown(self.child(), context, self.symbols())
+    }
+}
+
+/// The x86-64 general-purpose registers, in DWARF numbering order.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Register {
+    Rax, Rdx, Rcx, Rbx, Rsi, Rdi, Rbp, Rsp,
+    R8, R9, R10, R11, R12, R13, R14, R15,
+    Rip,
+}
+
+impl Register {
+    /// Map a DWARF register number onto a general-purpose register
+    pub fn from_dwarf(register: u16) -> Option<Register> {
+        use Register::*;
+        let registers = [
+            Rax, Rdx, Rcx, Rbx, Rsi, Rdi, Rbp, Rsp,
+            R8, R9, R10, R11, R12, R13, R14, R15,
+            Rip,
+        ];
+        registers.get(register as usize).cloned()
+    }
+
+    /// Whether the System V ABI requires a function to preserve the register for its caller
+    pub fn is_callee_saved(self) -> bool {
+        use Register::*;
+        match self {
+            Rbx | Rbp | Rsp | R12 | R13 | R14 | R15 | Rip => true,
+            _ => false,
+        }
+    }
+}
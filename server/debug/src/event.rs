@@ -5,6 +5,42 @@ use std::os::windows::io::{RawHandle, FromRawHandle};
 use winapi;
 use kernel32;
 
+use {Thread, Fault, Access};
+
+/// The exception code of an `int3` breakpoint
+pub const EXCEPTION_BREAKPOINT: u32 = winapi::EXCEPTION_BREAKPOINT;
+
+/// The exception code of a single-step trap
+pub const EXCEPTION_SINGLE_STEP: u32 = winapi::EXCEPTION_SINGLE_STEP;
+
+/// The name of an exception code, if it is a well-known one
+pub fn exception_name(code: u32) -> Option<&'static str> {
+    let name = match code {
+        winapi::EXCEPTION_ACCESS_VIOLATION => "EXCEPTION_ACCESS_VIOLATION",
+        winapi::EXCEPTION_ARRAY_BOUNDS_EXCEEDED => "EXCEPTION_ARRAY_BOUNDS_EXCEEDED",
+        winapi::EXCEPTION_BREAKPOINT => "EXCEPTION_BREAKPOINT",
+        winapi::EXCEPTION_DATATYPE_MISALIGNMENT => "EXCEPTION_DATATYPE_MISALIGNMENT",
+        winapi::EXCEPTION_FLT_DENORMAL_OPERAND => "EXCEPTION_FLT_DENORMAL_OPERAND",
+        winapi::EXCEPTION_FLT_DIVIDE_BY_ZERO => "EXCEPTION_FLT_DIVIDE_BY_ZERO",
+        winapi::EXCEPTION_FLT_INEXACT_RESULT => "EXCEPTION_FLT_INEXACT_RESULT",
+        winapi::EXCEPTION_FLT_INVALID_OPERATION => "EXCEPTION_FLT_INVALID_OPERATION",
+        winapi::EXCEPTION_FLT_OVERFLOW => "EXCEPTION_FLT_OVERFLOW",
+        winapi::EXCEPTION_FLT_STACK_CHECK => "EXCEPTION_FLT_STACK_CHECK",
+        winapi::EXCEPTION_FLT_UNDERFLOW => "EXCEPTION_FLT_UNDERFLOW",
+        winapi::EXCEPTION_ILLEGAL_INSTRUCTION => "EXCEPTION_ILLEGAL_INSTRUCTION",
+        winapi::EXCEPTION_IN_PAGE_ERROR => "EXCEPTION_IN_PAGE_ERROR",
+        winapi::EXCEPTION_INT_DIVIDE_BY_ZERO => "EXCEPTION_INT_DIVIDE_BY_ZERO",
+        winapi::EXCEPTION_INT_OVERFLOW => "EXCEPTION_INT_OVERFLOW",
+        winapi::EXCEPTION_INVALID_DISPOSITION => "EXCEPTION_INVALID_DISPOSITION",
+        winapi::EXCEPTION_NONCONTINUABLE_EXCEPTION => "EXCEPTION_NONCONTINUABLE_EXCEPTION",
+        winapi::EXCEPTION_PRIV_INSTRUCTION => "EXCEPTION_PRIV_INSTRUCTION",
+        winapi::EXCEPTION_SINGLE_STEP => "EXCEPTION_SINGLE_STEP",
+        winapi::EXCEPTION_STACK_OVERFLOW => "EXCEPTION_STACK_OVERFLOW",
+        _ => return None,
+    };
+    Some(name)
+}
+
 /// An event received from a child process
 pub struct Event {
     pub process_id: u32,
@@ -18,13 +54,13 @@ pub enum EventInfo {
     CreateProcess {
         file: Option<File>,
         process: RawHandle,
-        main_thread: RawHandle,
+        main_thread: Thread,
         base: usize,
         start_address: usize,
     },
     ExitProcess { exit_code: u32 },
 
-    CreateThread { thread: RawHandle, start_address: usize },
+    CreateThread { thread: Thread, start_address: usize },
     ExitThread { exit_code: u32 },
 
     LoadDll { file: Option<File>, base: usize },
@@ -36,6 +72,7 @@ pub enum EventInfo {
         first_chance: bool,
         code: u32,
         address: usize,
+        fault: Fault,
     },
 
     Rip { error: u32, error_type: u32 },
@@ -128,7 +165,25 @@ impl Event {
                     let first_chance = e.dwFirstChance != 0;
                     let code = er.ExceptionCode;
                     let address = er.ExceptionAddress as usize;
-                    Exception { first_chance, code, address }
+                    let fault = match code {
+                        winapi::EXCEPTION_ACCESS_VIOLATION | winapi::EXCEPTION_IN_PAGE_ERROR if
+                            er.NumberParameters >= 2
+                        => {
+                            let access = match er.ExceptionInformation[0] {
+                                0 => Some(Access::Read),
+                                1 => Some(Access::Write),
+                                8 => Some(Access::Execute),
+                                _ => None,
+                            };
+                            let address = er.ExceptionInformation[1] as usize;
+                            Fault::AccessViolation { access, address }
+                        }
+                        winapi::EXCEPTION_INT_DIVIDE_BY_ZERO |
+                        winapi::EXCEPTION_FLT_DIVIDE_BY_ZERO => Fault::DivideByZero,
+                        winapi::EXCEPTION_STACK_OVERFLOW => Fault::StackOverflow,
+                        _ => Fault::Other,
+                    };
+                    Exception { first_chance, code, address, fault }
                 },
 
                 winapi::RIP_EVENT => {
@@ -0,0 +1,20 @@
+/// What went wrong in an exception, as far as the platform describes it
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Fault {
+    /// A memory access the process was not allowed to make
+    AccessViolation { access: Option<Access>, address: usize },
+    /// Integer or floating-point division by zero
+    DivideByZero,
+    /// The thread ran past the end of its stack
+    StackOverflow,
+    /// Some other exception or signal
+    Other,
+}
+
+/// The kind of memory access that caused an access violation
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
@@ -1,38 +1,63 @@
 #![feature(optin_builtin_traits)]
 #![feature(associated_consts)]
+#![feature(i128_type)]
 
 ///! # Spice debug library
 ///!
-///! This crate should contain (but does not, yet) all the platform-specific debugging code used by
-///! Spice. It should be possible to port Spice to another platform solely by providing another
-///! implementation of this code.
+///! This crate contains all the platform-specific debugging code used by Spice. The server's trace
+///! engine only uses it through the `Debugger` trait and the types it names, so it should be
+///! possible to port Spice to another platform solely by providing another implementation of it.
 
-extern crate winapi;
-extern crate kernel32;
-extern crate dbghelp;
-extern crate advapi32;
+#[cfg(windows)] extern crate winapi;
+#[cfg(windows)] extern crate kernel32;
+#[cfg(windows)] extern crate dbghelp;
+#[cfg(windows)] extern crate advapi32;
 
+#[cfg(target_os = "linux")] extern crate libc;
+#[cfg(target_os = "linux")] extern crate gimli;
+#[cfg(target_os = "linux")] extern crate object;
+
+#[cfg(windows)]
 #[macro_use]
 extern crate lazy_static;
 
 use std::{mem, slice};
-use std::ffi::OsString;
-use std::os::windows::ffi::OsStringExt;
+#[cfg(windows)] use std::ffi::OsString;
+#[cfg(windows)] use std::os::windows::ffi::OsStringExt;
 
 pub use process::*;
 pub use event::*;
 pub use symbol::*;
 pub use types::*;
 pub use value::*;
+pub use visualizer::*;
+pub use location::*;
+pub use exception::*;
 pub use call::*;
+pub use debugger::*;
+pub use native::*;
 
+#[cfg_attr(target_os = "linux", path = "linux/process.rs")]
 mod process;
+#[cfg_attr(target_os = "linux", path = "linux/event.rs")]
 mod event;
+#[cfg_attr(target_os = "linux", path = "linux/symbol.rs")]
 mod symbol;
 mod types;
 mod value;
+mod visualizer;
+mod location;
+mod exception;
 mod call;
+mod debugger;
+#[cfg_attr(target_os = "linux", path = "linux/native.rs")]
+mod native;
+
+#[cfg(target_os = "linux")]
+#[path = "linux/sys.rs"]
+mod sys;
 
+#[cfg(windows)]
 trait FromWide where Self: Sized {
     fn from_wide(wide: &[u16]) -> Self;
 
@@ -54,6 +79,7 @@ trait FromWide where Self: Sized {
     }
 }
 
+#[cfg(windows)]
 impl FromWide for OsString {
     fn from_wide(wide: &[u16]) -> OsString {
         OsStringExt::from_wide(wide)
@@ -69,12 +95,14 @@ pub trait AsBytes: Sized {
 impl AsBytes for bool {}
 
 impl AsBytes for usize {}
+impl AsBytes for u128 {}
 impl AsBytes for u64 {}
 impl AsBytes for u32 {}
 impl AsBytes for u16 {}
 impl AsBytes for u8 {}
 
 impl AsBytes for isize {}
+impl AsBytes for i128 {}
 impl AsBytes for i64 {}
 impl AsBytes for i32 {}
 impl AsBytes for i16 {}
@@ -0,0 +1,348 @@
+use std::{io, fs};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+
+use libc;
+
+use {Thread, Fault, sys};
+
+/// The exception code of an `int3` breakpoint
+pub const EXCEPTION_BREAKPOINT: u32 = 0x80000003;
+
+/// The exception code of a single-step trap
+pub const EXCEPTION_SINGLE_STEP: u32 = 0x80000004;
+
+// `si_code` values for `SIGTRAP`
+const SI_KERNEL: libc::c_int = 0x80;
+const TRAP_BRKPT: libc::c_int = 1;
+const TRAP_TRACE: libc::c_int = 2;
+
+// `si_code` values for `SIGFPE`
+const FPE_INTDIV: libc::c_int = 1;
+const FPE_FLTDIV: libc::c_int = 3;
+
+/// The name of a signal, which Linux uses as its exception code
+pub fn exception_name(code: u32) -> Option<&'static str> {
+    let name = match code as libc::c_int {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGSTKFLT => "SIGSTKFLT",
+        libc::SIGCHLD => "SIGCHLD",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGSTOP => "SIGSTOP",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        libc::SIGURG => "SIGURG",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGVTALRM => "SIGVTALRM",
+        libc::SIGPROF => "SIGPROF",
+        libc::SIGWINCH => "SIGWINCH",
+        libc::SIGIO => "SIGIO",
+        libc::SIGPWR => "SIGPWR",
+        libc::SIGSYS => "SIGSYS",
+        _ => match code {
+            EXCEPTION_BREAKPOINT => "EXCEPTION_BREAKPOINT",
+            EXCEPTION_SINGLE_STEP => "EXCEPTION_SINGLE_STEP",
+            _ => return None,
+        },
+    };
+    Some(name)
+}
+
+/// An event received from a child process
+///
+/// Other exceptions are signals, and use the signal number as their code.
+pub struct Event {
+    pub process_id: u32,
+    pub thread_id: u32,
+    pub info: EventInfo,
+
+    /// Whether the thread is in a ptrace stop and needs to be resumed
+    stopped: bool,
+    /// The signal to deliver to the thread if it does not handle the event
+    signal: libc::c_int,
+}
+
+impl !Send for Event {}
+
+/// Mirrors the Win32 debug events, so the same trace engine can drive both.
+///
+/// There is no loader breakpoint to report shared library loads, and no `OutputDebugString`, so
+/// `LoadDll`, `UnloadDll` and `OutputDebugString` are never produced.
+pub enum EventInfo {
+    CreateProcess {
+        file: Option<File>,
+        main_thread: Thread,
+        base: usize,
+        start_address: usize,
+    },
+    ExitProcess { exit_code: u32 },
+
+    CreateThread { thread: Thread, start_address: usize },
+    ExitThread { exit_code: u32 },
+
+    LoadDll { file: Option<File>, base: usize },
+    UnloadDll { base: usize },
+
+    OutputDebugString { data: usize, length: usize, unicode: bool },
+
+    Exception {
+        first_chance: bool,
+        code: u32,
+        address: usize,
+        fault: Fault,
+    },
+}
+
+impl Event {
+    /// Retrieve the next `Event` from the child process attached to the current thread
+    ///
+    /// Unlike the Win32 API, ptrace only stops the thread that generated the event; the rest of
+    /// the process keeps running.
+    pub fn wait_event() -> io::Result<Event> {
+        loop {
+            if let Some(event) = sys::with_tracee(|tracee| tracee.pending.pop_front())? {
+                return Ok(event);
+            }
+
+            let (thread, status) = sys::waitpid(-1, libc::__WALL)?;
+            if let Some(event) = Event::from_status(thread, status)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Translate a `waitpid` result, or resume the thread and return `None` if it isn't
+    /// interesting to the debugger.
+    fn from_status(thread: libc::pid_t, status: libc::c_int) -> io::Result<Option<Event>> {
+        let (process, attached, first_stop) = sys::with_tracee(|tracee| {
+            let first_stop = libc::WIFSTOPPED(status) && tracee.threads.insert(thread);
+            if !libc::WIFSTOPPED(status) {
+                tracee.threads.remove(&thread);
+            }
+            (tracee.process, tracee.attached, first_stop)
+        })?;
+
+        let process_id = process as u32;
+        let thread_id = thread as u32;
+
+        use self::EventInfo::*;
+
+        if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+            let exit_code = if libc::WIFEXITED(status) {
+                libc::WEXITSTATUS(status) as u32
+            } else {
+                128 + libc::WTERMSIG(status) as u32
+            };
+
+            let info = if thread == process {
+                ExitProcess { exit_code }
+            } else {
+                ExitThread { exit_code }
+            };
+
+            return Ok(Some(Event { process_id, thread_id, info, stopped: false, signal: 0 }));
+        }
+
+        // The first stop is the `SIGTRAP` after `execve` for launched processes, or the `SIGSTOP`
+        // from `PTRACE_ATTACH` or `clone` otherwise. Neither is passed on.
+        if first_stop {
+            let options = libc::PTRACE_O_TRACECLONE | libc::PTRACE_O_EXITKILL;
+            sys::ptrace(libc::PTRACE_SETOPTIONS, thread, 0, options as usize)?;
+
+            if thread != process {
+                let start_address = sys::get_regs(thread)?.rip as usize;
+                let info = CreateThread { thread, start_address };
+                return Ok(Some(Event { process_id, thread_id, info, stopped: true, signal: 0 }));
+            }
+
+            let file = File::open(format!("/proc/{}/exe", process)).ok();
+            let main_thread = thread;
+            let base = image_base(process)?;
+            let start_address = entry_point(process)?;
+            let info = CreateProcess { file, main_thread, base, start_address };
+
+            // An attached process has no loader breakpoint, so report one in its place. The
+            // thread stays stopped until that event is continued.
+            if attached {
+                let info = Exception {
+                    first_chance: true, code: EXCEPTION_BREAKPOINT, address: 0, fault: Fault::Other
+                };
+                let event = Event { process_id, thread_id, info, stopped: true, signal: 0 };
+                sys::with_tracee(|tracee| tracee.pending.push_back(event))?;
+            }
+
+            let stopped = !attached;
+            return Ok(Some(Event { process_id, thread_id, info, stopped, signal: 0 }));
+        }
+
+        // `PTRACE_EVENT_CLONE` stops; the new thread is reported from its own first stop
+        if status >> 16 != 0 {
+            sys::ptrace(libc::PTRACE_CONT, thread, 0, 0)?;
+            return Ok(None);
+        }
+
+        let signal = libc::WSTOPSIG(status);
+        let info = match signal {
+            libc::SIGTRAP => {
+                let rip = sys::get_regs(thread)?.rip as usize;
+                let (code, address) = match sys::get_siginfo(thread)?.si_code {
+                    TRAP_TRACE => (EXCEPTION_SINGLE_STEP, rip),
+                    SI_KERNEL | TRAP_BRKPT => (EXCEPTION_BREAKPOINT, rip - 1),
+
+                    // sent by `kill`, e.g. from a `Cancel`
+                    _ => (EXCEPTION_BREAKPOINT, 0),
+                };
+                Exception { first_chance: true, code, address, fault: Fault::Other }
+            }
+
+            // group-stops are not interesting to the debugger
+            libc::SIGSTOP | libc::SIGTSTP | libc::SIGTTIN | libc::SIGTTOU => {
+                sys::ptrace(libc::PTRACE_CONT, thread, 0, 0)?;
+                return Ok(None);
+            }
+
+            // Signals are delivered directly to their handler, so there is no second chance.
+            // Instead, report signals that will kill the process as last-chance exceptions.
+            _ => {
+                let first_chance = !is_fatal(process, signal)?;
+                let code = signal as u32;
+                let address = sys::get_regs(thread)?.rip as usize;
+                let fault = fault(thread, signal)?;
+                Exception { first_chance, code, address, fault }
+            }
+        };
+
+        Ok(Some(Event { process_id, thread_id, info, stopped: true, signal }))
+    }
+
+    /// Continue the child process that generated this event
+    ///
+    /// For `EventInfo::Exception` events, `handled` signifies whether the debugger handled
+    /// the exception. If `handled == false`, the signal is passed on to the child process.
+    pub fn continue_event(self, handled: bool) -> io::Result<()> {
+        if !self.stopped {
+            return Ok(());
+        }
+
+        let signal = if handled { 0 } else { self.signal };
+        sys::ptrace(libc::PTRACE_CONT, self.thread_id as libc::pid_t, 0, signal as usize)?;
+
+        Ok(())
+    }
+}
+
+/// Find the address the main executable was mapped at
+fn image_base(process: libc::pid_t) -> io::Result<usize> {
+    let exe = fs::read_link(format!("/proc/{}/exe", process))?;
+    let maps = BufReader::new(File::open(format!("/proc/{}/maps", process))?);
+
+    for line in maps.lines() {
+        let line = line?;
+
+        // address perms offset dev inode pathname
+        let mut fields = line.split_whitespace();
+        let range = fields.next();
+        let offset = fields.nth(1);
+        let path = fields.nth(2).map(PathBuf::from);
+
+        if let (Some(range), Some(offset), Some(path)) = (range, offset, path) {
+            if path != exe || usize::from_str_radix(offset, 16) != Ok(0) {
+                continue;
+            }
+
+            let start = range.split('-').next().unwrap();
+            return usize::from_str_radix(start, 16)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidData));
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, "executable is not mapped"))
+}
+
+/// Find the main executable's entry point from the process's auxiliary vector
+fn entry_point(process: libc::pid_t) -> io::Result<usize> {
+    let mut auxv = vec![];
+    File::open(format!("/proc/{}/auxv", process))?.read_to_end(&mut auxv)?;
+
+    let entries = auxv.chunks(16).map(|entry| {
+        let key = unsafe { *(entry.as_ptr() as *const u64) };
+        let value = unsafe { *(entry.as_ptr().offset(8) as *const u64) };
+        (key, value)
+    });
+    for (key, value) in entries {
+        if key == libc::AT_ENTRY as u64 {
+            return Ok(value as usize);
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, "no entry point in auxiliary vector"))
+}
+
+/// Decode the cause of a signal
+fn fault(thread: libc::pid_t, signal: libc::c_int) -> io::Result<Fault> {
+    let info = sys::get_siginfo(thread)?;
+    let fault = match signal {
+        libc::SIGSEGV | libc::SIGBUS => {
+            let address = unsafe { info.si_addr() } as usize;
+            let stack_pointer = sys::get_regs(thread)?.rsp as usize;
+
+            // the kernel grows stacks on demand, so faults this close to the stack pointer mean
+            // it has run into the guard gap below the stack
+            let page = 0x1000;
+            let below = address.saturating_add(16 * page) >= stack_pointer;
+            if address < stack_pointer.saturating_add(page) && below {
+                Fault::StackOverflow
+            } else {
+                // the kernel doesn't report whether the access was a read or a write
+                Fault::AccessViolation { access: None, address }
+            }
+        }
+
+        libc::SIGFPE if info.si_code == FPE_INTDIV || info.si_code == FPE_FLTDIV => {
+            Fault::DivideByZero
+        }
+
+        _ => Fault::Other,
+    };
+    Ok(fault)
+}
+
+/// Determine whether delivering a signal will terminate the process, based on its default action
+/// and whether the process catches or ignores it.
+fn is_fatal(process: libc::pid_t, signal: libc::c_int) -> io::Result<bool> {
+    match signal {
+        libc::SIGCHLD | libc::SIGCONT | libc::SIGURG | libc::SIGWINCH => return Ok(false),
+        _ => {}
+    }
+
+    let status = BufReader::new(File::open(format!("/proc/{}/status", process))?);
+
+    let mut handled = 0;
+    for line in status.lines() {
+        let line = line?;
+        if line.starts_with("SigIgn:") || line.starts_with("SigCgt:") {
+            let mask = u64::from_str_radix(line[7..].trim(), 16)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+            handled |= mask;
+        }
+    }
+
+    Ok(handled & (1 << (signal - 1)) == 0)
+}
@@ -0,0 +1,54 @@
+use std::io;
+
+use {Debugger, Child, Command, Event, Thread, Context, SymbolHandler};
+use {get_thread_context, set_thread_context};
+
+/// The debugger implementation for the current platform.
+pub type Native = Ptrace;
+
+/// The Linux ptrace API, with symbols read from the target's ELF modules.
+pub struct Ptrace {
+    child: Child,
+    symbols: SymbolHandler,
+}
+
+impl Ptrace {
+    fn new(child: Child) -> io::Result<Ptrace> {
+        let symbols = SymbolHandler::initialize(&child)?;
+        Ok(Ptrace { child, symbols })
+    }
+}
+
+impl Debugger for Ptrace {
+    fn launch(command: &mut Command) -> io::Result<Ptrace> {
+        Ptrace::new(command.debug()?)
+    }
+
+    fn attach(pid: u32) -> io::Result<Ptrace> {
+        Ptrace::new(Child::attach(pid)?)
+    }
+
+    fn child(&self) -> &Child { &self.child }
+
+    fn terminate(self) -> io::Result<()> {
+        let Ptrace { child, symbols } = self;
+        let result = child.terminate();
+        drop(symbols);
+
+        result
+    }
+
+    fn wait_event(&self) -> io::Result<Event> {
+        Event::wait_event()
+    }
+
+    fn get_context(&self, thread: Thread) -> io::Result<Context> {
+        get_thread_context(thread)
+    }
+
+    fn set_context(&self, thread: Thread, context: &Context) -> io::Result<()> {
+        set_thread_context(thread, context)
+    }
+
+    fn symbols(&self) -> &SymbolHandler { &self.symbols }
+}
@@ -0,0 +1,843 @@
+use std::{mem, ptr, iter, io, env, fs, str, thread};
+use std::ffi::{CStr, CString, OsString, OsStr};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, Duration, UNIX_EPOCH};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use libc;
+
+use {AsBytes, Register};
+use sys;
+
+/// A running or exited debugee process, created via a `Command`
+pub struct Child {
+    pid: libc::pid_t,
+    memory: File,
+    output: Option<Arc<Capture>>,
+}
+
+/// The id of a thread in a child process
+pub type Thread = libc::pid_t;
+
+/// A handle to a process that can inject breakpoints
+pub struct Cancel(libc::pid_t);
+
+impl Child {
+    /// Open a traced process's address space
+    ///
+    /// `/proc/<pid>/mem` refers to the address space that existed when it was opened, so this must
+    /// happen after a launched process has completed `execve`.
+    fn open(pid: libc::pid_t) -> io::Result<Child> {
+        let memory = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(format!("/proc/{}/mem", pid))?;
+
+        Ok(Child { pid, memory, output: None })
+    }
+
+    /// This process, for tests that read their own memory
+    #[cfg(test)]
+    pub fn current() -> Child {
+        Child::open(unsafe { libc::getpid() }).unwrap()
+    }
+
+    /// The process id
+    pub fn id(&self) -> u32 {
+        self.pid as u32
+    }
+
+    /// A handle to the process's captured stdout and stderr, which can be read from any thread
+    ///
+    /// Processes launched without `Command::capture_output` have none.
+    pub fn output(&self) -> Option<Output> {
+        self.output.clone().map(Output)
+    }
+
+    /// Read `buffer.len()` bytes from a process's address space at `address`
+    pub fn read_memory(&self, address: usize, buffer: &mut [u8]) -> io::Result<usize> {
+        self.memory.read_exact_at(buffer, address as u64)?;
+        Ok(buffer.len())
+    }
+
+    /// Write `buffer.len()` bytes into a process's address space at `address`
+    ///
+    /// Writes through `/proc/<pid>/mem` ignore page protection, so this also works on code.
+    pub fn write_memory(&self, address: usize, buffer: &[u8]) -> io::Result<usize> {
+        self.memory.write_all_at(buffer, address as u64)?;
+        Ok(buffer.len())
+    }
+
+    /// Save the first byte of an instruction and replace it with `int3`
+    pub fn set_breakpoint(&self, address: usize) -> io::Result<Breakpoint> {
+        let mut saved = [0u8; 1];
+        self.read_memory(address, &mut saved)?;
+        self.write_memory(address, &[0xCCu8])?;
+        Ok(Breakpoint { address, saved })
+    }
+
+    /// Restore an instruction that has been replaced with `int3`
+    pub fn remove_breakpoint(&self, breakpoint: Breakpoint) -> io::Result<()> {
+        self.write_memory(breakpoint.address, &breakpoint.saved)?;
+        Ok(())
+    }
+
+    pub fn stack_push<B: AsBytes>(&self, context: &mut Context, value: B) -> io::Result<()> {
+        let bytes = value.as_bytes();
+        let address = context.stack_pointer() - bytes.len();
+
+        context.set_stack_pointer(address);
+        self.write_memory(address, &bytes)?;
+
+        Ok(())
+    }
+
+    pub fn get_cancel(&self) -> Cancel {
+        Cancel(self.pid)
+    }
+
+    /// Attach to each thread of a running process.
+    ///
+    /// Threads created while this is running may be missed.
+    pub fn attach(pid: u32) -> io::Result<Child> {
+        let pid = pid as libc::pid_t;
+
+        let mut threads = vec![];
+        for entry in fs::read_dir(format!("/proc/{}/task", pid))? {
+            let tid = entry?.file_name().to_str()
+                .and_then(|tid| tid.parse().ok())
+                .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+            threads.push(tid);
+        }
+
+        // attach to the main thread first, so it is the first to stop
+        threads.sort_by_key(|&tid| tid != pid);
+        for tid in threads {
+            sys::ptrace(libc::PTRACE_ATTACH, tid, 0, 0)?;
+        }
+
+        sys::track(pid, true);
+        Child::open(pid)
+    }
+
+    pub fn terminate(self) -> io::Result<()> {
+        sys::kill(self.pid, libc::SIGKILL)?;
+
+        // reap the process, so it does not linger as a zombie
+        loop {
+            let (pid, status) = match sys::waitpid(-1, libc::__WALL) {
+                Err(ref e) if e.raw_os_error() == Some(libc::ECHILD) => break,
+                result => result?,
+            };
+
+            if pid == self.pid && (libc::WIFEXITED(status) || libc::WIFSIGNALED(status)) {
+                break;
+            }
+        }
+
+        sys::untrack();
+        Ok(())
+    }
+}
+
+impl Cancel {
+    /// Inject a breakpoint into the target process.
+    ///
+    /// This sends `SIGTRAP` rather than executing an `int3`, so the debugger can distinguish it
+    /// from another breakpoint. It is reported with an address of 0.
+    pub fn trigger_breakpoint(&self) -> io::Result<()> {
+        sys::kill(self.0, libc::SIGTRAP)
+    }
+}
+
+/// An enabled breakpoint in a child process
+pub struct Breakpoint {
+    address: usize,
+    saved: [u8; 1],
+}
+
+/// The state of a suspended thread
+#[derive(Clone)]
+pub struct Context {
+    regs: libc::user_regs_struct,
+    fpregs: libc::user_fpregs_struct,
+}
+
+impl Context {
+    /// A context with every register cleared
+    #[cfg(test)]
+    pub fn empty() -> Context {
+        unsafe { mem::zeroed() }
+    }
+
+    pub fn stack_pointer(&self) -> usize {
+        self.regs.rsp as usize
+    }
+
+    pub fn set_stack_pointer(&mut self, address: usize) {
+        self.regs.rsp = address as u64;
+    }
+
+    pub fn instruction_pointer(&self) -> usize {
+        self.regs.rip as usize
+    }
+
+    pub fn set_instruction_pointer(&mut self, address: usize) {
+        self.regs.rip = address as u64;
+    }
+
+    pub fn frame_pointer(&self) -> usize {
+        self.regs.rbp as usize
+    }
+
+    pub fn set_singlestep(&mut self, singlestep: bool) {
+        if singlestep {
+            self.regs.eflags |= 0x100;
+        } else {
+            self.regs.eflags &= !0x100;
+        }
+    }
+
+    pub fn register(&self, register: Register) -> u64 {
+        use Register::*;
+        let regs = &self.regs;
+        match register {
+            Rax => regs.rax, Rdx => regs.rdx, Rcx => regs.rcx, Rbx => regs.rbx,
+            Rsi => regs.rsi, Rdi => regs.rdi, Rbp => regs.rbp, Rsp => regs.rsp,
+            R8 => regs.r8, R9 => regs.r9, R10 => regs.r10, R11 => regs.r11,
+            R12 => regs.r12, R13 => regs.r13, R14 => regs.r14, R15 => regs.r15,
+            Rip => regs.rip,
+        }
+    }
+
+    pub fn set_register(&mut self, register: Register, value: u64) {
+        use Register::*;
+        let regs = &mut self.regs;
+        let target = match register {
+            Rax => &mut regs.rax, Rdx => &mut regs.rdx, Rcx => &mut regs.rcx, Rbx => &mut regs.rbx,
+            Rsi => &mut regs.rsi, Rdi => &mut regs.rdi, Rbp => &mut regs.rbp, Rsp => &mut regs.rsp,
+            R8 => &mut regs.r8, R9 => &mut regs.r9, R10 => &mut regs.r10, R11 => &mut regs.r11,
+            R12 => &mut regs.r12, R13 => &mut regs.r13, R14 => &mut regs.r14, R15 => &mut regs.r15,
+            Rip => &mut regs.rip,
+        };
+        *target = value;
+    }
+
+    /// Read the 16 bytes of an SSE register
+    pub fn xmm(&self, index: usize) -> [u8; 16] {
+        let mut xmm = [0u32; 4];
+        xmm.copy_from_slice(&self.fpregs.xmm_space[index * 4..index * 4 + 4]);
+        unsafe { mem::transmute(xmm) }
+    }
+
+    /// Read the 16 bytes an x87 register is saved in, relative to the top of the stack
+    pub fn st(&self, index: usize) -> [u8; 16] {
+        let mut st = [0u32; 4];
+        st.copy_from_slice(&self.fpregs.st_space[index * 4..index * 4 + 4]);
+        unsafe { mem::transmute(st) }
+    }
+
+    /// Write the 16 bytes an x87 register is saved in, relative to the top of the stack
+    #[cfg(test)]
+    pub fn set_st(&mut self, index: usize, value: [u8; 16]) {
+        let st: [u32; 4] = unsafe { mem::transmute(value) };
+        self.fpregs.st_space[index * 4..index * 4 + 4].copy_from_slice(&st);
+    }
+
+    /// Write the 16 bytes of an SSE register
+    pub fn set_xmm(&mut self, index: usize, value: [u8; 16]) {
+        let xmm: [u32; 4] = unsafe { mem::transmute(value) };
+        self.fpregs.xmm_space[index * 4..index * 4 + 4].copy_from_slice(&xmm);
+    }
+
+    pub fn into_raw(self) -> libc::user_regs_struct { self.regs }
+    pub fn as_raw(&self) -> &libc::user_regs_struct { &self.regs }
+    pub fn as_raw_mut(&mut self) -> &mut libc::user_regs_struct { &mut self.regs }
+}
+
+/// Read a stopped thread's CPU state
+pub fn get_thread_context(thread: Thread) -> io::Result<Context> {
+    let regs = sys::get_regs(thread)?;
+    let fpregs = sys::get_fpregs(thread)?;
+    Ok(Context { regs, fpregs })
+}
+
+/// Write a stopped thread's CPU state
+pub fn set_thread_context(thread: Thread, context: &Context) -> io::Result<()> {
+    sys::ptrace(libc::PTRACE_SETREGS, thread, 0, &context.regs as *const _ as usize)?;
+    sys::ptrace(libc::PTRACE_SETFPREGS, thread, 0, &context.fpregs as *const _ as usize)?;
+    Ok(())
+}
+
+/// Custom implementation of `std::process::Command` to debug child processes.
+/// Lacks some features that we don't need:
+///
+/// * Does not look up `program` in `PATH`
+/// * Child shares the server's stdout and stderr, unless they are captured
+pub struct Command {
+    program: OsString,
+    args: Vec<OsString>,
+    env: Option<HashMap<OsString, OsString>>,
+    cwd: Option<OsString>,
+    input: Option<Vec<u8>>,
+    capture: bool,
+}
+
+impl Command {
+    /// Construct a new `Command` with default configuration:
+    ///
+    /// * No arguments
+    /// * Inherit parent environment
+    /// * Inherit parent working directory
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Command {
+        Command {
+            program: program.as_ref().to_os_string(),
+            args: vec![],
+            env: None,
+            cwd: None,
+            input: None,
+            capture: false,
+        }
+    }
+
+    /// Add an argument to pass to the program.
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Command {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    fn init_env_map(&mut self) {
+        if self.env.is_none() {
+            self.env = Some(env::vars_os().collect());
+        }
+    }
+
+    /// Add a variable to the child process's environment.
+    pub fn env<K, V>(&mut self, key: K, value: V) -> &mut Command
+        where K: AsRef<OsStr>, V: AsRef<OsStr>
+    {
+        self.init_env_map();
+        self.env.as_mut().unwrap().insert(
+            key.as_ref().to_os_string(), value.as_ref().to_os_string()
+        );
+        self
+    }
+
+    /// Clear the environment of the child process.
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.env = Some(HashMap::new());
+        self
+    }
+
+    /// Set the working directory of the child process.
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
+        self.cwd = Some(dir.as_ref().as_os_str().to_os_string());
+        self
+    }
+
+    /// Write `input` to the child process's stdin, then close it.
+    ///
+    /// By default the child shares the server's stdin.
+    pub fn input<T: Into<Vec<u8>>>(&mut self, input: T) -> &mut Command {
+        self.input = Some(input.into());
+        self
+    }
+
+    /// Capture the child process's stdout and stderr, to be read through `Child::output`.
+    ///
+    /// Stdout is a pty, so the C runtime flushes it at the end of each line as it would for a
+    /// terminal. Stderr is a pipe.
+    pub fn capture_output(&mut self) -> &mut Command {
+        self.capture = true;
+        self
+    }
+
+    /// Execute the command as a child process and return a handle to it.
+    ///
+    /// The child stops with a `SIGTRAP` once `execve` completes, which `Event::wait_event`
+    /// reports as `EventInfo::CreateProcess`.
+    pub fn debug(&mut self) -> io::Result<Child> {
+        let program = make_cstring(&self.program)?;
+        let args = iter::once(&self.program).chain(self.args.iter())
+            .map(make_cstring)
+            .collect::<io::Result<Vec<_>>>()?;
+        let env = match self.env {
+            Some(ref env) => Some(env.iter()
+                .map(|(key, value)| {
+                    let mut pair = key.clone();
+                    pair.push("=");
+                    pair.push(value);
+                    make_cstring(&pair)
+                })
+                .collect::<io::Result<Vec<_>>>()?),
+            None => None,
+        };
+
+        let cwd = match self.cwd {
+            Some(ref cwd) => Some(make_cstring(cwd)?),
+            None => None,
+        };
+
+        let output = if self.capture { Some(make_output()?) } else { None };
+
+        let argv = make_pointers(&args);
+        let envp = env.as_ref().map(|env| make_pointers(env));
+
+        unsafe {
+            // the child reports `execve` failure over this pipe; success closes it
+            let (reader, writer) = make_pipe()?;
+
+            let stdin = match self.input {
+                Some(_) => match make_pipe() {
+                    Ok(stdin) => Some(stdin),
+                    Err(error) => {
+                        libc::close(reader);
+                        libc::close(writer);
+                        return Err(error);
+                    }
+                },
+                None => None,
+            };
+
+            let pid = libc::fork();
+            if pid == -1 {
+                let error = io::Error::last_os_error();
+                libc::close(reader);
+                libc::close(writer);
+                if let Some((stdin_reader, stdin_writer)) = stdin {
+                    libc::close(stdin_reader);
+                    libc::close(stdin_writer);
+                }
+                return Err(error);
+            }
+
+            if pid == 0 {
+                libc::close(reader);
+                libc::ptrace(
+                    libc::PTRACE_TRACEME, 0, ptr::null_mut::<libc::c_void>(),
+                    ptr::null_mut::<libc::c_void>()
+                );
+
+                // `dup2` clears `O_CLOEXEC`, so only the new stdin survives `execve`
+                let mut redirected = match stdin {
+                    Some((stdin_reader, _)) => libc::dup2(stdin_reader, libc::STDIN_FILENO) != -1,
+                    None => true,
+                };
+                if let Some((_, ref stdout, ref stderr)) = output {
+                    redirected = redirected &&
+                        libc::dup2(stdout.as_raw_fd(), libc::STDOUT_FILENO) != -1 &&
+                        libc::dup2(stderr.as_raw_fd(), libc::STDERR_FILENO) != -1;
+                }
+                let changed = match cwd {
+                    Some(ref cwd) => libc::chdir(cwd.as_ptr()) != -1,
+                    None => true,
+                };
+
+                if redirected && changed {
+                    match envp {
+                        Some(ref envp) => {
+                            libc::execve(program.as_ptr(), argv.as_ptr(), envp.as_ptr())
+                        }
+                        None => libc::execv(program.as_ptr(), argv.as_ptr()),
+                    };
+                }
+
+                let error = *libc::__errno_location();
+                libc::write(
+                    writer, &error as *const _ as *const libc::c_void, mem::size_of_val(&error)
+                );
+                libc::_exit(127);
+            }
+
+            libc::close(writer);
+            let output = output.map(|(capture, _, _)| capture);
+            let stdin = stdin.map(|(stdin_reader, stdin_writer)| {
+                libc::close(stdin_reader);
+                File::from_raw_fd(stdin_writer)
+            });
+
+            let mut error: libc::c_int = 0;
+            let read = libc::read(
+                reader, &mut error as *mut _ as *mut libc::c_void, mem::size_of_val(&error)
+            );
+            libc::close(reader);
+
+            if read > 0 {
+                let mut status = 0;
+                libc::waitpid(pid, &mut status, 0);
+                return Err(io::Error::from_raw_os_error(error));
+            }
+
+            // the child may not read its input until it is resumed, so write it in the background
+            if let (Some(mut stdin), Some(input)) = (stdin, self.input.clone()) {
+                thread::spawn(move || {
+                    let _ = stdin.write_all(&input);
+                });
+            }
+
+            sys::track(pid, false);
+            let mut child = Child::open(pid)?;
+            if let Some(capture) = output {
+                child.output = Some(Capture::start(capture));
+            }
+            Ok(child)
+        }
+    }
+}
+
+fn make_cstring(s: &OsString) -> io::Result<CString> {
+    CString::new(s.as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nul byte found in provided data"))
+}
+
+/// Create the parent's end of the output capture, along with the child's stdout and stderr
+fn make_output() -> io::Result<(Capture, File, File)> {
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if master == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = File::from_raw_fd(master);
+        if libc::grantpt(master.as_raw_fd()) == -1 || libc::unlockpt(master.as_raw_fd()) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut name = [0 as libc::c_char; 64];
+        let error = libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len());
+        if error != 0 {
+            return Err(io::Error::from_raw_os_error(error));
+        }
+        let slave = libc::open(name.as_ptr(), libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if slave == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let slave = File::from_raw_fd(slave);
+
+        // leave newlines alone rather than translating them to "\r\n"
+        let mut termios = mem::zeroed();
+        if libc::tcgetattr(slave.as_raw_fd(), &mut termios) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        termios.c_oflag &= !libc::OPOST;
+        if libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let (reader, writer) = make_pipe()?;
+        let (reader, writer) = (File::from_raw_fd(reader), File::from_raw_fd(writer));
+
+        for file in &[&master, &reader] {
+            let fd = file.as_raw_fd();
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        let capture = Capture {
+            pipes: vec![(Stream::Stdout, master), (Stream::Stderr, reader)],
+            chunks: Mutex::new(vec![]),
+        };
+        Ok((capture, slave, writer))
+    }
+}
+
+/// Create a pipe whose ends are both closed on `execve`
+fn make_pipe() -> io::Result<(libc::c_int, libc::c_int)> {
+    let mut pipe = [0; 2];
+    if unsafe { libc::pipe2(pipe.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((pipe[0], pipe[1]))
+}
+
+fn make_pointers(strings: &[CString]) -> Vec<*const libc::c_char> {
+    strings.iter().map(|s| s.as_ptr()).chain(iter::once(ptr::null())).collect()
+}
+
+/// One of a child process's output streams
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A child process's captured stdout and stderr
+#[derive(Clone)]
+pub struct Output(Arc<Capture>);
+
+impl Output {
+    /// Take everything the process has written so far
+    ///
+    /// While the process is stopped, this includes all of its output up to that point.
+    pub fn read(&self) -> Vec<(Stream, Vec<u8>)> {
+        self.0.read()
+    }
+}
+
+/// The parent's end of a child process's stdout and stderr
+///
+/// Each pipe has a thread that drains it as soon as it becomes readable, so the child never blocks
+/// on a full pipe. All reads happen with `chunks` locked, so chunks stay in the order they were
+/// written and a reader can also drain the pipes itself to catch up with a stopped process.
+struct Capture {
+    pipes: Vec<(Stream, File)>,
+    chunks: Mutex<Vec<(Stream, Vec<u8>)>>,
+}
+
+impl Capture {
+    fn start(capture: Capture) -> Arc<Capture> {
+        let capture = Arc::new(capture);
+        for index in 0..capture.pipes.len() {
+            let capture = capture.clone();
+            thread::spawn(move || capture.drain_pipe(index));
+        }
+        capture
+    }
+
+    fn drain_pipe(&self, index: usize) {
+        let mut poll = libc::pollfd {
+            fd: self.pipes[index].1.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        loop {
+            if unsafe { libc::poll(&mut poll, 1, -1) } == -1 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted { continue; }
+                return;
+            }
+
+            let mut chunks = self.chunks.lock().unwrap();
+            if !read_pipe(&self.pipes[index], &mut chunks) {
+                return;
+            }
+        }
+    }
+
+    fn read(&self) -> Vec<(Stream, Vec<u8>)> {
+        let mut chunks = self.chunks.lock().unwrap();
+        for pipe in &self.pipes {
+            read_pipe(pipe, &mut chunks);
+        }
+        mem::replace(&mut *chunks, vec![])
+    }
+}
+
+/// Read everything currently available from a non-blocking pipe, returning whether it is still
+/// open
+fn read_pipe(&(stream, ref file): &(Stream, File), chunks: &mut Vec<(Stream, Vec<u8>)>) -> bool {
+    let mut buffer = [0; 4096];
+    loop {
+        let size = match (&*file).read(&mut buffer) {
+            Ok(0) => return false,
+            Ok(size) => size,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // a pty reports `EIO` once the child closes its end
+            Err(_) => return false,
+        };
+
+        match chunks.last_mut() {
+            Some(&mut (last, ref mut data)) if last == stream => {
+                data.extend_from_slice(&buffer[..size]);
+                continue;
+            }
+            _ => {}
+        }
+        chunks.push((stream, buffer[..size].to_vec()));
+    }
+}
+
+/// A running process
+///
+/// Details that can't be read, usually for lack of permission, are left as `None`.
+pub struct Process {
+    pub id: u32,
+    pub name: OsString,
+    pub parent_id: u32,
+    pub command_line: Option<OsString>,
+    pub path: Option<PathBuf>,
+    pub owner: Option<OsString>,
+    pub start_time: Option<SystemTime>,
+    /// Whether `Child::attach` is expected to succeed
+    pub attachable: bool,
+}
+
+impl Process {
+    /// Iterate over currently-running processes
+    pub fn running() -> io::Result<Processes> {
+        Ok(Processes(fs::read_dir("/proc")?))
+    }
+
+    /// Look up a running process by its id
+    pub fn get(id: u32) -> io::Result<Process> {
+        Process::read(id as libc::pid_t)
+            .ok_or(io::Error::new(io::ErrorKind::NotFound, "no such process"))
+    }
+
+    /// Read a process's details from `/proc`, or `None` if it has exited
+    fn read(id: libc::pid_t) -> Option<Process> {
+        let mut name = None;
+        let mut parent_id = None;
+        let mut uid = None;
+        let mut tracer = None;
+
+        let status = BufReader::new(File::open(format!("/proc/{}/status", id)).ok()?);
+        for line in status.split(b'\n') {
+            let line = line.ok()?;
+            let mut fields = line.splitn(2, |&c| c == b':');
+            let (key, value) = match (fields.next(), fields.next()) {
+                (Some(key), Some(value)) => (key, trim(value)),
+                _ => continue,
+            };
+
+            match key {
+                b"Name" => name = Some(OsStr::from_bytes(value).to_owned()),
+                b"PPid" => parent_id = first_number(value),
+                b"Uid" => uid = first_number(value),
+                b"TracerPid" => tracer = first_number(value),
+                _ => {}
+            }
+        }
+        let name = name?;
+        let parent_id: u32 = parent_id?;
+        let uid: libc::uid_t = uid?;
+        let tracer: libc::pid_t = tracer?;
+
+        // arguments are separated and terminated by nuls
+        let mut command_line = vec![];
+        File::open(format!("/proc/{}/cmdline", id)).ok()?.read_to_end(&mut command_line).ok()?;
+        let command_line = if command_line.is_empty() {
+            None
+        } else {
+            command_line.pop();
+            for c in &mut command_line {
+                if *c == 0 { *c = b' '; }
+            }
+            Some(OsString::from_vec(command_line))
+        };
+
+        let path = fs::read_link(format!("/proc/{}/exe", id)).ok();
+        let owner = user_name(uid);
+        let start_time = start_time(id);
+
+        // kernel threads have no executable and can't be traced
+        let attachable = path.is_some() && tracer == 0 && id != unsafe { libc::getpid() } &&
+            may_trace(uid);
+
+        Some(Process {
+            id: id as u32, name, parent_id, command_line, path, owner, start_time, attachable
+        })
+    }
+}
+
+/// An iterator over the processes in `/proc`
+pub struct Processes(fs::ReadDir);
+
+impl Iterator for Processes {
+    type Item = Process;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for entry in &mut self.0 {
+            let id = entry.ok()
+                .and_then(|entry| entry.file_name().to_str().and_then(|name| name.parse().ok()));
+            let id = match id {
+                Some(id) => id,
+                None => continue,
+            };
+
+            // processes can exit while we read them
+            if let Some(process) = Process::read(id) {
+                return Some(process);
+            }
+        }
+
+        None
+    }
+}
+
+fn trim(mut bytes: &[u8]) -> &[u8] {
+    while let Some((&c, rest)) = bytes.split_first() {
+        if c != b' ' && c != b'\t' { break; }
+        bytes = rest;
+    }
+    while let Some((&c, rest)) = bytes.split_last() {
+        if c != b' ' && c != b'\t' { break; }
+        bytes = rest;
+    }
+    bytes
+}
+
+/// Parse the first whitespace-separated number in a `/proc` status value
+fn first_number<T: str::FromStr>(value: &[u8]) -> Option<T> {
+    str::from_utf8(value).ok()?.split_whitespace().next()?.parse().ok()
+}
+
+fn user_name(uid: libc::uid_t) -> Option<OsString> {
+    unsafe {
+        let mut passwd: libc::passwd = mem::zeroed();
+        let mut buffer = vec![0 as libc::c_char; 1024];
+        let mut result = ptr::null_mut();
+        libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result);
+        if result.is_null() {
+            return Some(OsString::from(uid.to_string()));
+        }
+
+        Some(OsStr::from_bytes(CStr::from_ptr(passwd.pw_name).to_bytes()).to_owned())
+    }
+}
+
+/// Find a process's start time from its age in clock ticks since boot
+fn start_time(id: libc::pid_t) -> Option<SystemTime> {
+    let mut stat = String::new();
+    File::open(format!("/proc/{}/stat", id)).ok()?.read_to_string(&mut stat).ok()?;
+
+    // the command name may contain spaces, so skip past it before splitting
+    let fields = &stat[stat.rfind(')')? + 1..];
+    let ticks: u64 = fields.split_whitespace().nth(19)?.parse().ok()?;
+
+    let mut boot = String::new();
+    File::open("/proc/stat").ok()?.read_to_string(&mut boot).ok()?;
+    let boot: u64 = boot.lines()
+        .find(|line| line.starts_with("btime "))?
+        .split_whitespace().nth(1)?
+        .parse().ok()?;
+
+    let frequency = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
+    if frequency == 0 {
+        return None;
+    }
+
+    let seconds = ticks / frequency;
+    let nanos = (ticks % frequency) * 1_000_000_000 / frequency;
+    Some(UNIX_EPOCH + Duration::new(boot + seconds, nanos as u32))
+}
+
+/// Determine whether ptrace permissions allow this process to attach to a process owned by `uid`
+///
+/// Under Yama's `ptrace_scope`, unprivileged processes may only trace their descendants, which
+/// `Child::attach` never does.
+fn may_trace(uid: libc::uid_t) -> bool {
+    let euid = unsafe { libc::geteuid() };
+    let privileged = euid == 0;
+
+    let mut scope = String::new();
+    let scope = File::open("/proc/sys/kernel/yama/ptrace_scope")
+        .and_then(|mut file| file.read_to_string(&mut scope))
+        .ok()
+        .and_then(|_| scope.trim().parse().ok())
+        .unwrap_or(0);
+
+    match scope {
+        0 => privileged || euid == uid,
+        1 | 2 => privileged,
+        _ => false,
+    }
+}
@@ -0,0 +1,1645 @@
+use std::{io, mem};
+use std::fs::File;
+use std::io::Read;
+use std::rc::Rc;
+use std::cell::{Ref, RefCell};
+use std::path::PathBuf;
+use std::collections::HashMap;
+use std::ffi::{OsString, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileExt;
+
+use gimli::{self, Reader as GimliReader};
+use object::{self, Object, ObjectSection, ObjectSegment, ObjectSymbol};
+
+use types::{Type, Primitive, Field, Bits, Enumerator};
+use AsBytes;
+use {Child, Thread, Context, Location, Register, Visualizer, get_thread_context};
+
+type Reader = gimli::EndianRcSlice<gimli::RunTimeEndian>;
+
+/// A symbol handler for ELF modules, reading DWARF debug info
+///
+/// Type indices are offsets into a module's `.debug_info` section. Index 0 is never a valid DIE
+/// offset, so it is used for `void`.
+pub struct SymbolHandler {
+    memory: File,
+    modules: RefCell<Vec<Module>>,
+    visualizers: RefCell<Vec<Visualizer>>,
+}
+
+struct Module {
+    /// The address the module's first segment was mapped at
+    base: usize,
+    /// The difference between runtime addresses and those in the module's debug info
+    bias: usize,
+    /// The size of the module's mapped image
+    size: usize,
+
+    dwarf: gimli::Dwarf<Reader>,
+    units: Vec<gimli::Unit<Reader>>,
+    eh_frame: Option<(gimli::EhFrame<Reader>, gimli::BaseAddresses)>,
+
+    /// Functions and global variables, sorted by address
+    functions: Vec<Global>,
+    variables: Vec<Global>,
+    /// `.symtab` entries, used for code without debug info
+    elf_symbols: Vec<Global>,
+
+    /// Line table rows, sorted by address
+    lines: Vec<LineRow>,
+    files: Vec<OsString>,
+}
+
+/// A function or variable, with its address relative to the module's debug info
+struct Global {
+    name: OsString,
+    address: usize,
+    size: usize,
+    type_index: u32,
+}
+
+struct LineRow {
+    address: usize,
+    file: usize,
+    line: u32,
+    is_stmt: bool,
+    end_sequence: bool,
+}
+
+impl SymbolHandler {
+    /// Initialize the process's symbol handler
+    pub fn initialize(process: &Child) -> io::Result<SymbolHandler> {
+        let memory = File::open(format!("/proc/{}/mem", process.id()))?;
+        let modules = RefCell::new(vec![]);
+        let visualizers = RefCell::new(vec![]);
+        Ok(SymbolHandler { memory, modules, visualizers })
+    }
+
+    /// A symbol handler for this process, with no modules loaded
+    #[cfg(test)]
+    pub fn empty() -> SymbolHandler {
+        let memory = File::open("/proc/self/mem").unwrap();
+        let modules = RefCell::new(vec![]);
+        let visualizers = RefCell::new(vec![]);
+        SymbolHandler { memory, modules, visualizers }
+    }
+
+    /// Add rules for showing structs by their logical contents
+    ///
+    /// Visualizers added first take precedence.
+    pub fn add_visualizers<I: IntoIterator<Item = Visualizer>>(&self, visualizers: I) {
+        self.visualizers.borrow_mut().extend(visualizers);
+    }
+
+    /// Find the visualizer for structs named `name`
+    pub fn visualizer(&self, name: &OsStr) -> Option<Ref<Visualizer>> {
+        let visualizers = self.visualizers.borrow();
+        let index = visualizers.iter().position(|visualizer| visualizer.matches(name));
+        index.map(|index| Ref::map(visualizers, |visualizers| &visualizers[index]))
+    }
+
+    /// Load the symbols for a module
+    pub fn load_module(&self, file: &File, base: usize) -> io::Result<()> {
+        let mut data = vec![];
+        file.try_clone()?.read_to_end(&mut data)?;
+
+        let module = Module::load(&data, base).map_err(invalid_data)?;
+        self.modules.borrow_mut().push(module);
+
+        // variable sizes come from their types, which can only be read once the module is loaded
+        let sizes: Vec<_> = {
+            let module = self.module(base)?;
+            module.variables.iter()
+                .map(|variable| {
+                    self.type_from_index(base, variable.type_index)
+                        .map(|data_type| data_type.size(self, base))
+                        .unwrap_or(0)
+                })
+                .collect()
+        };
+        let mut modules = self.modules.borrow_mut();
+        let module = modules.iter_mut().find(|module| module.base == base).unwrap();
+        for (variable, size) in module.variables.iter_mut().zip(sizes) {
+            variable.size = size;
+        }
+
+        Ok(())
+    }
+
+    /// Unload the symbols for a module
+    pub fn unload_module(&self, base: usize) -> io::Result<()> {
+        self.modules.borrow_mut().retain(|module| module.base != base);
+        Ok(())
+    }
+
+    /// Find the module containing the given address
+    pub fn module_from_address(&self, address: usize) -> io::Result<usize> {
+        let module = self.module(address)?;
+        Ok(module.base)
+    }
+
+    /// Retrieve the symbol and byte offset into that symbol of an address
+    pub fn symbol_from_address(&self, address: usize) -> io::Result<(Symbol, usize)> {
+        let module = self.module(address)?;
+        let relative = address - module.bias;
+
+        let global = find_global(&module.functions, relative)
+            .or_else(|| find_global(&module.variables, relative))
+            .or_else(|| find_global(&module.elf_symbols, relative))
+            .ok_or(not_found())?;
+
+        let symbol = global.symbol(module.bias, 0);
+        let displacement = address - symbol.address;
+        Ok((symbol, displacement))
+    }
+
+    pub fn symbol_from_name<S: AsRef<OsStr>>(&self, name: S) -> io::Result<Symbol> {
+        let name = name.as_ref();
+
+        let modules = self.modules.borrow();
+        for module in modules.iter() {
+            let global = Iterator::chain(module.functions.iter(), module.variables.iter())
+                .chain(module.elf_symbols.iter())
+                .find(|global| global.name == name);
+            if let Some(global) = global {
+                return Ok(global.symbol(module.bias, 0));
+            }
+        }
+
+        Err(not_found())
+    }
+
+    pub fn enumerate_globals<F>(&self, mut f: F) -> io::Result<()>
+        where F: FnMut(Symbol, usize) -> bool
+    {
+        let modules = self.modules.borrow();
+        for module in modules.iter() {
+            for global in Iterator::chain(module.functions.iter(), module.variables.iter()) {
+                if !f(global.symbol(module.bias, 0), global.size) {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Convert a type index into its symbolic representation
+    pub fn type_from_index(&self, module: usize, type_index: u32) -> io::Result<Type> {
+        if type_index == 0 {
+            return Ok(Type::Base { base: Primitive::Void, size: 0 });
+        }
+
+        let module = self.module(module)?;
+        let (unit, offset) = module.unit_offset(type_index)?;
+        let entry = unit.entry(offset).map_err(invalid_data)?;
+
+        match entry.tag() {
+            gimli::DW_TAG_base_type => {
+                let encoding = entry.attr_value(gimli::DW_AT_encoding).map_err(invalid_data)?;
+                let encoding = match encoding {
+                    Some(gimli::AttributeValue::Encoding(encoding)) => encoding,
+                    _ => return Err(unsupported_type()),
+                };
+                let size = udata(&entry, gimli::DW_AT_byte_size)?.unwrap_or(0) as usize;
+
+                // `wchar_t` is an ordinary integer encoding, and `long double` shares its size with
+                // `__float128`, so both are told apart by name
+                let name = module.name_attr(unit, &entry)?.unwrap_or(OsString::new());
+                let name = name.to_string_lossy();
+                let quad = name.contains("128");
+
+                let base = match encoding {
+                    gimli::DW_ATE_boolean => Primitive::Bool,
+                    gimli::DW_ATE_signed_char => Primitive::Char { signed: true },
+                    gimli::DW_ATE_unsigned_char | gimli::DW_ATE_UTF =>
+                        Primitive::Char { signed: false },
+                    gimli::DW_ATE_signed if name == "wchar_t" => Primitive::Char { signed: true },
+                    gimli::DW_ATE_unsigned if name == "wchar_t" =>
+                        Primitive::Char { signed: false },
+                    gimli::DW_ATE_signed => Primitive::Int { signed: true },
+                    gimli::DW_ATE_unsigned => Primitive::Int { signed: false },
+                    gimli::DW_ATE_float if size >= 10 && !quad => Primitive::Extended,
+                    gimli::DW_ATE_float => Primitive::Float,
+                    gimli::DW_ATE_complex_float =>
+                        Primitive::Complex { extended: size >= 24 && !quad },
+                    _ => return Err(unsupported_type()),
+                };
+                if !base.supports_size(size) {
+                    return Err(unsupported_type());
+                }
+
+                Ok(Type::Base { base, size })
+            }
+
+            gimli::DW_TAG_pointer_type |
+            gimli::DW_TAG_reference_type |
+            gimli::DW_TAG_rvalue_reference_type => {
+                let type_index = module.type_attr(unit, &entry)?;
+                Ok(Type::Pointer { type_index })
+            }
+
+            // pointers to members and `std::nullptr_t` are kept only for their size, which
+            // compilers often leave out: a pointer to a member function also holds an adjustment
+            // to `this`
+            gimli::DW_TAG_ptr_to_member_type | gimli::DW_TAG_unspecified_type => {
+                let size = match udata(&entry, gimli::DW_AT_byte_size)? {
+                    Some(size) => size as usize,
+                    None if entry.tag() == gimli::DW_TAG_ptr_to_member_type => {
+                        let target = module.type_attr(unit, &entry)?;
+                        match self.type_from_index(module.base, target) {
+                            Ok(Type::Function { .. }) => 2 * mem::size_of::<usize>(),
+                            _ => mem::size_of::<usize>(),
+                        }
+                    }
+                    None => mem::size_of::<usize>(),
+                };
+                Ok(Type::Base { base: Primitive::Opaque, size })
+            }
+
+            gimli::DW_TAG_array_type => {
+                let type_index = module.type_attr(unit, &entry)?;
+
+                // multidimensional arrays are flattened
+                let mut count = 1;
+                let mut tree = unit.entries_tree(Some(offset)).map_err(invalid_data)?;
+                let mut children = tree.root().map_err(invalid_data)?.children();
+                while let Some(child) = children.next().map_err(invalid_data)? {
+                    let child = child.entry();
+                    if child.tag() != gimli::DW_TAG_subrange_type {
+                        continue;
+                    }
+
+                    let length = match udata(child, gimli::DW_AT_count)? {
+                        Some(length) => length,
+                        None => udata(child, gimli::DW_AT_upper_bound)?.map(|bound| bound + 1)
+                            .unwrap_or(0),
+                    };
+                    count *= length as usize;
+                }
+
+                let vector = entry.attr_value(gimli::DW_AT_GNU_vector).map_err(invalid_data)?;
+                if vector.is_some() {
+                    Ok(Type::Vector { type_index, count })
+                } else {
+                    Ok(Type::Array { type_index, count })
+                }
+            }
+
+            gimli::DW_TAG_subprogram | gimli::DW_TAG_subroutine_type => {
+                let calling_convention = udata(&entry, gimli::DW_AT_calling_convention)?
+                    .unwrap_or(gimli::DW_CC_normal.0 as u64) as u32;
+                let type_index = module.type_attr(unit, &entry)?;
+
+                let mut args = vec![];
+                let mut tree = unit.entries_tree(Some(offset)).map_err(invalid_data)?;
+                let mut children = tree.root().map_err(invalid_data)?.children();
+                while let Some(child) = children.next().map_err(invalid_data)? {
+                    let child = child.entry();
+                    if child.tag() == gimli::DW_TAG_formal_parameter {
+                        args.push(module.type_attr(unit, child)?);
+                    }
+                }
+
+                Ok(Type::Function { calling_convention, type_index, args })
+            }
+
+            gimli::DW_TAG_structure_type | gimli::DW_TAG_class_type | gimli::DW_TAG_union_type => {
+                let name = module.name_attr(unit, &entry)?.unwrap_or(OsString::new());
+                let size = udata(&entry, gimli::DW_AT_byte_size)?.unwrap_or(0) as usize;
+
+                let mut fields = vec![];
+                let mut type_params = vec![];
+                let mut tree = unit.entries_tree(Some(offset)).map_err(invalid_data)?;
+                let mut children = tree.root().map_err(invalid_data)?.children();
+                while let Some(child) = children.next().map_err(invalid_data)? {
+                    let child = child.entry();
+                    match child.tag() {
+                        gimli::DW_TAG_member => {
+                            // static members have no location in the struct
+                            let external = child.attr_value(gimli::DW_AT_external)
+                                .map_err(invalid_data)?;
+                            if external.is_some() {
+                                continue;
+                            }
+
+                            let name = module.name_attr(unit, child)?.unwrap_or(OsString::new());
+                            let type_index = module.type_attr(unit, child)?;
+                            let (offset, bits) = member_location(child)?;
+                            let field = Field { name, type_index, offset, bits, base_class: false };
+                            fields.push(field);
+                        }
+
+                        gimli::DW_TAG_inheritance => {
+                            // virtual base classes are located at runtime
+                            let offset = match udata(child, gimli::DW_AT_data_member_location)? {
+                                Some(offset) => offset as u32,
+                                None => continue,
+                            };
+
+                            let type_index = module.type_attr(unit, child)?;
+                            let name = match self.type_from_index(module.base, type_index)? {
+                                Type::Struct { name, .. } => name,
+                                _ => return Err(unsupported_type()),
+                            };
+                            fields.push(Field {
+                                name, type_index, offset, bits: None, base_class: true
+                            });
+                        }
+
+                        gimli::DW_TAG_template_type_parameter => {
+                            type_params.push(module.type_attr(unit, child)?);
+                        }
+
+                        _ => {}
+                    }
+                }
+
+                if entry.tag() == gimli::DW_TAG_union_type {
+                    Ok(Type::Union { name, size, fields })
+                } else {
+                    Ok(Type::Struct { name, size, fields, type_params })
+                }
+            }
+
+            gimli::DW_TAG_enumeration_type => {
+                let name = module.name_attr(unit, &entry)?.unwrap_or(OsString::new());
+
+                // without an explicit underlying type, an enum is a signed integer of its size
+                let base = module.type_attr(unit, &entry)?;
+                let base_type = if base != 0 {
+                    self.type_from_index(module.base, base)?
+                } else {
+                    let size = udata(&entry, gimli::DW_AT_byte_size)?.unwrap_or(4) as usize;
+                    Type::Base { base: Primitive::Int { signed: true }, size }
+                };
+                let (base, size) = match base_type {
+                    // enums with a character type underlying them hold integers
+                    Type::Base { base: Primitive::Char { signed }, size } =>
+                        (Primitive::Int { signed }, size),
+                    Type::Base { base, size } if size <= 8 => (base, size),
+                    _ => return Err(unsupported_type()),
+                };
+
+                let mut enumerators = vec![];
+                let mut tree = unit.entries_tree(Some(offset)).map_err(invalid_data)?;
+                let mut children = tree.root().map_err(invalid_data)?.children();
+                while let Some(child) = children.next().map_err(invalid_data)? {
+                    let child = child.entry();
+                    if child.tag() != gimli::DW_TAG_enumerator {
+                        continue;
+                    }
+
+                    // compilers only use `DW_FORM_sdata` for values that need a sign
+                    let value = child.attr_value(gimli::DW_AT_const_value).map_err(invalid_data)?;
+                    let value = match value {
+                        Some(gimli::AttributeValue::Sdata(value)) => value,
+                        Some(value) => value.udata_value().ok_or(unsupported_type())? as i64,
+                        None => continue,
+                    };
+
+                    let name = module.name_attr(unit, child)?.unwrap_or(OsString::new());
+                    enumerators.push(Enumerator::new(name, value, base, size));
+                }
+
+                Ok(Type::Enum { name, base, size, enumerators })
+            }
+
+            // qualifiers and typedefs are transparent
+            gimli::DW_TAG_typedef |
+            gimli::DW_TAG_const_type |
+            gimli::DW_TAG_volatile_type |
+            gimli::DW_TAG_restrict_type |
+            gimli::DW_TAG_atomic_type => {
+                let target = module.type_attr(unit, &entry)?;
+                self.type_from_index(module.base, target)
+            }
+
+            _ => Err(unsupported_type()),
+        }
+    }
+
+    /// Retrieve the source line and byte offset of an instruction address
+    pub fn line_from_address(&self, address: usize) -> io::Result<(Line, usize)> {
+        let module = self.module(address)?;
+        let relative = address - module.bias;
+
+        let index = match module.lines.binary_search_by_key(&relative, |row| row.address) {
+            Ok(index) => index,
+            Err(0) => return Err(not_found()),
+            Err(index) => index - 1,
+        };
+        let row = &module.lines[index];
+        if row.end_sequence {
+            return Err(not_found());
+        }
+
+        let line = row.to_line(&module);
+        let displacement = address - line.address;
+        Ok((line, displacement))
+    }
+
+    /// Iterate through the source lines of a function
+    pub fn lines_from_symbol(&self, symbol: &Symbol) -> io::Result<Lines> {
+        let module = self.module(symbol.address)?;
+        let start = symbol.address - module.bias;
+        let end = start + symbol.size;
+
+        let first = match module.lines.binary_search_by_key(&start, |row| row.address) {
+            Ok(index) | Err(index) => index,
+        };
+
+        let mut lines: Vec<Line> = vec![];
+        for row in module.lines[first..].iter().take_while(|row| row.address < end) {
+            if !row.is_stmt || row.end_sequence {
+                continue;
+            }
+
+            // skip rows that continue the same line
+            if let Some(last) = lines.last() {
+                if last.line == row.line && last.file == module.files[row.file] {
+                    continue;
+                }
+            }
+
+            lines.push(row.to_line(&module));
+        }
+
+        Ok(Lines { lines: lines.into_iter() })
+    }
+
+    /// Iterate through the frames of a thread's stack
+    ///
+    /// The thread should be part of an attached child process which is currently stopped to handle
+    /// a debug event. Frames are unwound using `.eh_frame`, falling back to the frame pointer
+    /// chain for code without unwind info.
+    pub fn walk_stack(&self, thread: Thread) -> io::Result<StackFrames> {
+        let mut context = get_thread_context(thread)?;
+
+        let mut frames = vec![];
+        let mut pc = context.instruction_pointer();
+        let mut sp = context.stack_pointer();
+        let mut fp = context.frame_pointer();
+        while pc != 0 && frames.len() < 1024 {
+            // return addresses point after the call instruction, which may be in the next
+            // function or line, so look up the call itself
+            let lookup = if frames.is_empty() { pc } else { pc - 1 };
+
+            let (cfa, return_address, caller_fp) = match self.unwind_frame(lookup, sp, fp) {
+                Ok(frame) => frame,
+                Err(_) => match (self.read_usize(fp + 8), self.read_usize(fp)) {
+                    (Ok(return_address), Ok(caller_fp)) => (fp + 16, return_address, caller_fp),
+                    _ => break,
+                },
+            };
+
+            // the caller's context is the same, apart from the registers the callee saved
+            let mut caller = context.clone();
+            self.restore_registers(lookup, cfa, &mut caller);
+            caller.set_instruction_pointer(return_address);
+            caller.set_stack_pointer(cfa);
+            caller.set_register(Register::Rbp, caller_fp as u64);
+
+            frames.push(StackFrame {
+                instruction_pointer: pc,
+                return_address,
+                stack_pointer: sp,
+                frame_pointer: fp,
+                context,
+            });
+
+            if cfa <= sp {
+                break;
+            }
+            pc = return_address;
+            sp = cfa;
+            fp = caller_fp;
+            context = caller;
+        }
+
+        Ok(StackFrames { frames: frames.into_iter() })
+    }
+
+    /// Enumerate the local symbols of a stack frame
+    ///
+    /// Locals at a fixed offset from the frame pointer use that offset as their address. Others
+    /// move around or live in registers, so their address is just the `.debug_info` offset that
+    /// identifies them. Either way, use `locate_symbol` to find their current location.
+    pub fn enumerate_locals<F>(&self, address: usize, mut f: F) -> io::Result<()>
+        where F: FnMut(Symbol, usize) -> bool
+    {
+        let mut locals = vec![];
+        {
+            let module = self.module(address)?;
+            let relative = address - module.bias;
+
+            let function = find_global(&module.functions, relative).ok_or(not_found())?;
+            let (unit, offset) = module.unit_offset(function.type_index)?;
+
+            let frame_base = {
+                let entry = unit.entry(offset).map_err(invalid_data)?;
+                match entry.attr_value(gimli::DW_AT_frame_base).map_err(invalid_data)? {
+                    Some(gimli::AttributeValue::Exprloc(expression)) =>
+                        frame_base_offset(unit, expression),
+                    _ => None,
+                }
+            };
+
+            let mut tree = unit.entries_tree(Some(offset)).map_err(invalid_data)?;
+            let root = tree.root().map_err(invalid_data)?;
+            module.collect_locals(unit, root, relative, frame_base, &mut locals)?;
+        }
+
+        let module = self.module_from_address(address)?;
+        for mut symbol in locals {
+            symbol.size = match self.type_from_index(module, symbol.type_index) {
+                Ok(data_type) => data_type.size(self, module),
+                Err(_) => continue,
+            };
+
+            let size = symbol.size;
+            if !f(symbol, size) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Determine where a symbol's value lives at the context's current instruction
+    ///
+    /// `frame` is the context's index in the stack, innermost first. Locals are found by
+    /// evaluating their DWARF location description. Returns `None` if the symbol is optimized
+    /// out there, including when an outer frame keeps it in a register its callee may clobber.
+    pub fn locate_symbol(
+        &self, _child: &Child, context: &Context, frame: usize, symbol: &Symbol
+    ) -> io::Result<Option<Location>> {
+        if !symbol.is_local() {
+            return Ok(Some(Location::Address(symbol.address)));
+        }
+
+        let frame = Frame {
+            pc: context.instruction_pointer(),
+            sp: context.stack_pointer(),
+            fp: context.frame_pointer(),
+            outer: frame > 0,
+            context: Some(context),
+        };
+
+        let module = self.module(frame.lookup())?;
+        let relative = frame.lookup() - module.bias;
+        let function = find_global(&module.functions, relative).ok_or(not_found())?;
+        let (unit, function) = module.unit_offset(function.type_index)?;
+        let (_, offset) = module.unit_offset(symbol.entry)?;
+        let entry = unit.entry(offset).map_err(invalid_data)?;
+
+        let expression = match entry.attr_value(gimli::DW_AT_location).map_err(invalid_data)? {
+            Some(gimli::AttributeValue::Exprloc(expression)) => expression,
+
+            // location lists have an expression for each range of addresses where the variable
+            // is available
+            Some(value) => {
+                let mut locations = module.dwarf.attr_locations(unit, value)
+                    .map_err(invalid_data)?
+                    .ok_or(unsupported_location())?;
+
+                let mut expression = None;
+                while let Some(location) = locations.next().map_err(invalid_data)? {
+                    let range = location.range;
+                    if range.begin <= relative as u64 && (relative as u64) < range.end {
+                        expression = Some(location.data);
+                        break;
+                    }
+                }
+                match expression {
+                    Some(expression) => expression,
+                    None => return Ok(None),
+                }
+            }
+
+            // constants can be folded away entirely
+            None => {
+                let value = entry.attr_value(gimli::DW_AT_const_value).map_err(invalid_data)?;
+                let data = match value {
+                    Some(gimli::AttributeValue::Block(block)) =>
+                        block.to_slice().map_err(invalid_data)?.to_vec(),
+                    Some(gimli::AttributeValue::Sdata(value)) => value.as_bytes().to_vec(),
+                    Some(value) => match value.udata_value() {
+                        Some(value) => value.as_bytes().to_vec(),
+                        None => return Err(unsupported_location()),
+                    },
+                    None => return Ok(None),
+                };
+                return Ok(Some(Location::Value(data)));
+            }
+        };
+
+        let location = match self.evaluate(&module, unit, expression, &frame, Some(function))? {
+            Some(pieces) => location_from_pieces(pieces)?,
+            None => None,
+        };
+
+        // only the callee-saved registers are restored when unwinding
+        match location {
+            Some(ref location) if frame.outer && !location.survives_calls() => Ok(None),
+            location => Ok(location),
+        }
+    }
+
+    fn module(&self, address: usize) -> io::Result<Ref<Module>> {
+        let modules = self.modules.borrow();
+        let index = modules.iter()
+            .position(|module| module.base <= address && address < module.base + module.size)
+            .ok_or(not_found())?;
+
+        Ok(Ref::map(modules, |modules| &modules[index]))
+    }
+
+    /// Find the canonical frame address, return address and caller's frame pointer of a frame
+    fn unwind_frame(&self, pc: usize, sp: usize, fp: usize) -> io::Result<(usize, usize, usize)> {
+        use gimli::UnwindSection;
+
+        let module = self.module(pc)?;
+        let (ref eh_frame, ref bases) = *module.eh_frame.as_ref().ok_or(not_found())?;
+
+        let mut context = gimli::UnwindContext::new();
+        let row = eh_frame.unwind_info_for_address(
+            bases, &mut context, (pc - module.bias) as u64, gimli::EhFrame::cie_from_offset
+        ).map_err(|_| not_found())?;
+
+        let cfa = match *row.cfa() {
+            gimli::CfaRule::RegisterAndOffset { register, offset } => {
+                let base = match register {
+                    gimli::X86_64::RSP => sp,
+                    gimli::X86_64::RBP => fp,
+                    _ => return Err(not_found()),
+                };
+                (base as i64 + offset) as usize
+            }
+            _ => return Err(not_found()),
+        };
+
+        let return_address = match row.register(gimli::X86_64::RA) {
+            gimli::RegisterRule::Offset(offset) => self.read_usize((cfa as i64 + offset) as usize)?,
+            _ => return Err(not_found()),
+        };
+
+        let caller_fp = match row.register(gimli::X86_64::RBP) {
+            gimli::RegisterRule::Offset(offset) => self.read_usize((cfa as i64 + offset) as usize)?,
+            _ => fp,
+        };
+
+        Ok((cfa, return_address, caller_fp))
+    }
+
+    /// Restore the callee-saved registers that `.eh_frame` says a frame saved on the stack
+    ///
+    /// Registers without unwind info are left alone, so they may be stale in the caller.
+    fn restore_registers(&self, pc: usize, cfa: usize, context: &mut Context) {
+        use gimli::UnwindSection;
+
+        let module = match self.module(pc) {
+            Ok(module) => module,
+            Err(_) => return,
+        };
+        let (eh_frame, bases) = match module.eh_frame {
+            Some((ref eh_frame, ref bases)) => (eh_frame, bases),
+            None => return,
+        };
+
+        let mut unwind = gimli::UnwindContext::new();
+        let row = match eh_frame.unwind_info_for_address(
+            bases, &mut unwind, (pc - module.bias) as u64, gimli::EhFrame::cie_from_offset
+        ) {
+            Ok(row) => row,
+            Err(_) => return,
+        };
+
+        let registers = [
+            (gimli::X86_64::RBX, Register::Rbx),
+            (gimli::X86_64::R12, Register::R12),
+            (gimli::X86_64::R13, Register::R13),
+            (gimli::X86_64::R14, Register::R14),
+            (gimli::X86_64::R15, Register::R15),
+        ];
+        for &(dwarf, register) in &registers {
+            if let gimli::RegisterRule::Offset(offset) = row.register(dwarf) {
+                if let Ok(value) = self.read_usize((cfa as i64 + offset) as usize) {
+                    context.set_register(register, value as u64);
+                }
+            }
+        }
+    }
+
+    /// Evaluate a DWARF expression in a stack frame
+    ///
+    /// Returns `None` if the expression depends on something that isn't available in the frame.
+    fn evaluate(
+        &self, module: &Module, unit: &gimli::Unit<Reader>, expression: gimli::Expression<Reader>,
+        frame: &Frame, function: Option<gimli::UnitOffset>
+    ) -> io::Result<Option<Vec<gimli::Piece<Reader>>>> {
+        use gimli::EvaluationResult::*;
+
+        let mut evaluation = expression.evaluation(unit.encoding());
+        let mut result = evaluation.evaluate().map_err(invalid_data)?;
+        loop {
+            result = match result {
+                Complete => break,
+
+                RequiresMemory { address, size, space: None, base_type } if base_type.0 == 0 => {
+                    let mut buffer = [0u8; 8];
+                    let size = (size as usize).min(buffer.len());
+                    self.memory.read_exact_at(&mut buffer[..size], address)?;
+                    let value = unsafe { *(buffer.as_ptr() as *const u64) };
+                    evaluation.resume_with_memory(gimli::Value::Generic(value))
+                }
+
+                RequiresRegister { register, base_type } if base_type.0 == 0 => {
+                    match frame.register(register) {
+                        Some(value) =>
+                            evaluation.resume_with_register(gimli::Value::Generic(value)),
+                        None => return Ok(None),
+                    }
+                }
+
+                RequiresFrameBase => {
+                    let function = function.ok_or(unsupported_location())?;
+                    match self.frame_base(module, unit, function, frame)? {
+                        Some(base) => evaluation.resume_with_frame_base(base),
+                        None => return Ok(None),
+                    }
+                }
+
+                RequiresCallFrameCfa => {
+                    match self.unwind_frame(frame.lookup(), frame.sp, frame.fp) {
+                        Ok((cfa, _, _)) => evaluation.resume_with_call_frame_cfa(cfa as u64),
+                        Err(_) => return Ok(None),
+                    }
+                }
+
+                RequiresRelocatedAddress(address) =>
+                    evaluation.resume_with_relocated_address(address + module.bias as u64),
+
+                RequiresIndexedAddress { index, relocate } => {
+                    let address = module.dwarf.address(unit, index).map_err(invalid_data)?;
+                    let bias = if relocate { module.bias as u64 } else { 0 };
+                    evaluation.resume_with_indexed_address(address + bias)
+                }
+
+                RequiresEntryValue(expression) => {
+                    match self.entry_value(unit, expression, frame)? {
+                        Some(value) =>
+                            evaluation.resume_with_entry_value(gimli::Value::Generic(value)),
+                        None => return Ok(None),
+                    }
+                }
+
+                // thread-local storage, typed values and references to other DIEs
+                _ => return Ok(None),
+            }.map_err(invalid_data)?;
+        }
+
+        Ok(Some(evaluation.result()))
+    }
+
+    /// Evaluate a function's `DW_AT_frame_base` in a stack frame
+    fn frame_base(
+        &self, module: &Module, unit: &gimli::Unit<Reader>, function: gimli::UnitOffset,
+        frame: &Frame
+    ) -> io::Result<Option<u64>> {
+        let entry = unit.entry(function).map_err(invalid_data)?;
+        let expression = match entry.attr_value(gimli::DW_AT_frame_base).map_err(invalid_data)? {
+            Some(gimli::AttributeValue::Exprloc(expression)) => expression,
+            _ => return Ok(None),
+        };
+
+        let pieces = match self.evaluate(module, unit, expression, frame, None)? {
+            Some(pieces) => pieces,
+            None => return Ok(None),
+        };
+
+        // the frame base is either the contents of a register, or an address
+        match pieces.first().map(|piece| &piece.location) {
+            Some(&gimli::Location::Register { register }) => Ok(frame.register(register)),
+            Some(&gimli::Location::Address { address }) => Ok(Some(address)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Recover the value a register held on entry to the frame's function, from the parameters
+    /// its caller describes at the call site
+    fn entry_value(
+        &self, unit: &gimli::Unit<Reader>, expression: gimli::Expression<Reader>, frame: &Frame
+    ) -> io::Result<Option<u64>> {
+        let register = match single_register(unit, expression) {
+            Some(register) => register,
+            None => return Ok(None),
+        };
+
+        let caller = match self.unwind_frame(frame.lookup(), frame.sp, frame.fp) {
+            Ok((cfa, return_address, caller_fp)) =>
+                Frame { pc: return_address, sp: cfa, fp: caller_fp, outer: true, context: None },
+            Err(_) => return Ok(None),
+        };
+
+        let module = match self.module(caller.lookup()) {
+            Ok(module) => module,
+            Err(_) => return Ok(None),
+        };
+        let relative = caller.pc - module.bias;
+        let function = match find_global(&module.functions, relative - 1) {
+            Some(function) => function,
+            None => return Ok(None),
+        };
+        let (unit, function) = module.unit_offset(function.type_index)?;
+
+        // find the call site by its return address
+        let mut call_site = None;
+        let mut entries = unit.entries_at_offset(function).map_err(invalid_data)?;
+        let mut depth = 0;
+        while let Some((delta, entry)) = entries.next_dfs().map_err(invalid_data)? {
+            depth += delta;
+            if depth <= 0 && entry.offset() != function {
+                break;
+            }
+
+            match entry.tag() {
+                gimli::DW_TAG_call_site | gimli::DW_TAG_GNU_call_site => {}
+                _ => continue,
+            }
+
+            let return_pc = match entry.attr_value(gimli::DW_AT_call_return_pc)
+                .and_then(|pc| Ok(pc.or(entry.attr_value(gimli::DW_AT_low_pc)?)))
+                .map_err(invalid_data)?
+            {
+                Some(value) => module.dwarf.attr_address(unit, value).map_err(invalid_data)?,
+                None => None,
+            };
+            if return_pc == Some(relative as u64) {
+                call_site = Some(entry.offset());
+                break;
+            }
+        }
+        let call_site = match call_site {
+            Some(call_site) => call_site,
+            None => return Ok(None),
+        };
+
+        // find the parameter passed in `register`, and evaluate its value in the caller
+        let mut tree = unit.entries_tree(Some(call_site)).map_err(invalid_data)?;
+        let mut children = tree.root().map_err(invalid_data)?.children();
+        while let Some(child) = children.next().map_err(invalid_data)? {
+            let entry = child.entry();
+            match entry.tag() {
+                gimli::DW_TAG_call_site_parameter | gimli::DW_TAG_GNU_call_site_parameter => {}
+                _ => continue,
+            }
+
+            match entry.attr_value(gimli::DW_AT_location).map_err(invalid_data)? {
+                Some(gimli::AttributeValue::Exprloc(location)) => {
+                    if single_register(unit, location) != Some(register) {
+                        continue;
+                    }
+                }
+                _ => continue,
+            }
+
+            let value = match entry.attr_value(gimli::DW_AT_call_value)
+                .and_then(|value| Ok(value.or(entry.attr_value(gimli::DW_AT_GNU_call_site_value)?)))
+                .map_err(invalid_data)?
+            {
+                Some(gimli::AttributeValue::Exprloc(value)) => value,
+                _ => return Ok(None),
+            };
+
+            let pieces = match self.evaluate(&module, unit, value, &caller, Some(function))? {
+                Some(pieces) => pieces,
+                None => return Ok(None),
+            };
+
+            // the value is left on the expression stack
+            return Ok(match pieces.first().map(|piece| &piece.location) {
+                Some(&gimli::Location::Address { address }) => Some(address),
+                Some(&gimli::Location::Value { value }) => value.to_u64(!0).ok(),
+                _ => None,
+            });
+        }
+
+        Ok(None)
+    }
+
+    fn read_usize(&self, address: usize) -> io::Result<usize> {
+        let mut buffer = [0u8; 8];
+        self.memory.read_exact_at(&mut buffer, address as u64)?;
+        Ok(unsafe { *(buffer.as_ptr() as *const usize) })
+    }
+}
+
+impl Module {
+    fn load(data: &[u8], base: usize) -> Result<Module, Box<::std::error::Error + Send + Sync>> {
+        let file = object::File::parse(data)?;
+        let endian = if file.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+
+        // the first segment is mapped at `base`, rounded down to a page
+        let segments: Vec<_> = file.segments()
+            .map(|segment| (segment.address(), segment.size()))
+            .collect();
+        let start = segments.iter().map(|&(address, _)| address).min().unwrap_or(0) & !0xfff;
+        let end = segments.iter().map(|&(address, size)| address + size).max().unwrap_or(0);
+        let bias = base - start as usize;
+        let size = (end - start) as usize;
+
+        let load_section = |id: gimli::SectionId| -> Result<Reader, object::Error> {
+            let data = match file.section_by_name(id.name()) {
+                Some(section) => Rc::from(&*section.uncompressed_data()?),
+                None => Rc::from(&[][..]),
+            };
+            Ok(gimli::EndianRcSlice::new(data, endian))
+        };
+        let dwarf = gimli::Dwarf::load(|id| load_section(id))?;
+
+        let eh_frame = match file.section_by_name(".eh_frame") {
+            Some(section) => {
+                let mut bases = gimli::BaseAddresses::default().set_eh_frame(section.address());
+                if let Some(text) = file.section_by_name(".text") {
+                    bases = bases.set_text(text.address());
+                }
+                let data = gimli::EndianRcSlice::new(Rc::from(section.data()?), endian);
+                Some((gimli::EhFrame::from(data), bases))
+            }
+            None => None,
+        };
+
+        let mut units = vec![];
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next()? {
+            units.push(dwarf.unit(header)?);
+        }
+
+        let mut elf_symbols: Vec<_> = file.symbols()
+            .filter(|symbol| symbol.kind() == object::SymbolKind::Text && symbol.address() != 0)
+            .filter_map(|symbol| {
+                let name = OsStr::from_bytes(symbol.name_bytes().ok()?).to_owned();
+                let address = symbol.address() as usize;
+                let size = symbol.size() as usize;
+                Some(Global { name, address, size, type_index: 0 })
+            })
+            .collect();
+        elf_symbols.sort_by_key(|global| global.address);
+
+        let mut module = Module {
+            base, bias, size,
+            dwarf, units, eh_frame,
+            functions: vec![], variables: vec![], elf_symbols,
+            lines: vec![], files: vec![],
+        };
+        module.load_globals()?;
+        module.load_lines()?;
+
+        Ok(module)
+    }
+
+    /// Collect functions and global variables from each compilation unit
+    fn load_globals(&mut self) -> gimli::Result<()> {
+        let mut functions = vec![];
+        let mut variables = vec![];
+
+        for unit in &self.units {
+            let mut entries = unit.entries();
+            let mut depth = 0;
+            while let Some((delta, entry)) = entries.next_dfs()? {
+                depth += delta;
+
+                let offset = match entry.offset().to_debug_info_offset(&unit.header) {
+                    Some(offset) => offset.0 as u32,
+                    None => continue,
+                };
+
+                match entry.tag() {
+                    gimli::DW_TAG_subprogram => {
+                        let mut ranges = self.dwarf.die_ranges(unit, entry)?;
+                        let mut start = !0;
+                        let mut end = 0;
+                        while let Some(range) = ranges.next()? {
+                            start = start.min(range.begin);
+                            end = end.max(range.end);
+                        }
+                        if start >= end || start == 0 {
+                            continue;
+                        }
+
+                        let name = match self.name_attr(unit, entry) {
+                            Ok(Some(name)) => name,
+                            _ => continue,
+                        };
+                        let address = start as usize;
+                        let size = (end - start) as usize;
+                        functions.push(Global { name, address, size, type_index: offset });
+                    }
+
+                    // only variables at namespace scope are global
+                    gimli::DW_TAG_variable if depth == 1 => {
+                        let address = match entry.attr_value(gimli::DW_AT_location)? {
+                            Some(gimli::AttributeValue::Exprloc(expression)) => {
+                                match static_address(unit, expression) {
+                                    Some(address) => address,
+                                    None => continue,
+                                }
+                            }
+                            _ => continue,
+                        };
+
+                        let name = match self.name_attr(unit, entry) {
+                            Ok(Some(name)) => name,
+                            _ => continue,
+                        };
+                        let type_index = match self.type_attr(unit, entry) {
+                            Ok(type_index) => type_index,
+                            Err(_) => continue,
+                        };
+                        variables.push(Global { name, address, size: 0, type_index });
+                    }
+
+                    _ => {}
+                }
+            }
+        }
+
+        functions.sort_by_key(|global| global.address);
+        variables.sort_by_key(|global| global.address);
+        self.functions = functions;
+        self.variables = variables;
+        Ok(())
+    }
+
+    /// Collect every compilation unit's line table into a single sorted table
+    fn load_lines(&mut self) -> gimli::Result<()> {
+        let mut lines = vec![];
+        let mut files = vec![];
+        let mut file_indices = HashMap::new();
+
+        for unit in &self.units {
+            let program = match unit.line_program {
+                Some(ref program) => program.clone(),
+                None => continue,
+            };
+
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row()? {
+                let file = match row.file(header) {
+                    Some(file) => file,
+                    None => continue,
+                };
+
+                let mut path = PathBuf::new();
+                if let Some(ref directory) = unit.comp_dir {
+                    path.push(OsStr::from_bytes(&directory.to_slice()?));
+                }
+                if let Some(directory) = file.directory(header) {
+                    let directory = self.dwarf.attr_string(unit, directory)?;
+                    path.push(OsStr::from_bytes(&directory.to_slice()?));
+                }
+                let name = self.dwarf.attr_string(unit, file.path_name())?;
+                path.push(OsStr::from_bytes(&name.to_slice()?));
+
+                let path = path.into_os_string();
+                let file = match file_indices.get(&path) {
+                    Some(&index) => index,
+                    None => {
+                        files.push(path.clone());
+                        file_indices.insert(path, files.len() - 1);
+                        files.len() - 1
+                    }
+                };
+
+                lines.push(LineRow {
+                    address: row.address() as usize,
+                    file,
+                    line: row.line().map(|line| line.get() as u32).unwrap_or(0),
+                    is_stmt: row.is_stmt(),
+                    end_sequence: row.end_sequence(),
+                });
+            }
+        }
+
+        // when sequences are adjacent, the end of one must sort before the start of the next
+        lines.sort_by_key(|row| (row.address, !row.end_sequence));
+
+        // later rows at the same address replace earlier ones
+        let mut deduped: Vec<LineRow> = vec![];
+        for row in lines {
+            if let Some(last) = deduped.last_mut() {
+                if last.address == row.address && !last.end_sequence {
+                    *last = row;
+                    continue;
+                }
+            }
+            deduped.push(row);
+        }
+
+        self.lines = deduped;
+        self.files = files;
+        Ok(())
+    }
+
+    /// Find the unit containing a `.debug_info` offset, and the offset within that unit
+    fn unit_offset(
+        &self, type_index: u32
+    ) -> io::Result<(&gimli::Unit<Reader>, gimli::UnitOffset)> {
+        let offset = gimli::DebugInfoOffset(type_index as usize);
+        for unit in &self.units {
+            if let Some(unit_offset) = offset.to_unit_offset(&unit.header) {
+                return Ok((unit, unit_offset));
+            }
+        }
+
+        Err(io::Error::new(io::ErrorKind::NotFound, "no such type"))
+    }
+
+    /// Read a DIE's `DW_AT_type` as a type index, or 0 for `void`
+    fn type_attr(
+        &self, unit: &gimli::Unit<Reader>, entry: &gimli::DebuggingInformationEntry<Reader>
+    ) -> io::Result<u32> {
+        match entry.attr_value(gimli::DW_AT_type).map_err(invalid_data)? {
+            Some(gimli::AttributeValue::UnitRef(offset)) => {
+                let offset = offset.to_debug_info_offset(&unit.header).ok_or(not_found())?;
+                return Ok(offset.0 as u32);
+            }
+            Some(gimli::AttributeValue::DebugInfoRef(offset)) => return Ok(offset.0 as u32),
+            Some(_) => return Err(unsupported_type()),
+            None => {}
+        }
+
+        // out-of-line instances of inlined functions keep their types in the abstract instance
+        if let Some(gimli::AttributeValue::UnitRef(offset)) =
+            entry.attr_value(gimli::DW_AT_abstract_origin).map_err(invalid_data)?
+        {
+            let entry = unit.entry(offset).map_err(invalid_data)?;
+            return self.type_attr(unit, &entry);
+        }
+
+        Ok(0)
+    }
+
+    /// Read a DIE's name, following declarations and abstract origins
+    fn name_attr(
+        &self, unit: &gimli::Unit<Reader>, entry: &gimli::DebuggingInformationEntry<Reader>
+    ) -> io::Result<Option<OsString>> {
+        if let Some(name) = entry.attr_value(gimli::DW_AT_name).map_err(invalid_data)? {
+            let name = self.dwarf.attr_string(unit, name).map_err(invalid_data)?;
+            let name = name.to_slice().map_err(invalid_data)?;
+            return Ok(Some(OsStr::from_bytes(&name).to_owned()));
+        }
+
+        for &attr in &[gimli::DW_AT_specification, gimli::DW_AT_abstract_origin] {
+            if let Some(gimli::AttributeValue::UnitRef(offset)) =
+                entry.attr_value(attr).map_err(invalid_data)?
+            {
+                let entry = unit.entry(offset).map_err(invalid_data)?;
+                return self.name_attr(unit, &entry);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Recursively collect the parameters and variables of a function that are in scope at
+    /// `address`
+    fn collect_locals(
+        &self, unit: &gimli::Unit<Reader>, node: gimli::EntriesTreeNode<Reader>, address: usize,
+        frame_base: Option<i64>, locals: &mut Vec<Symbol>
+    ) -> io::Result<()> {
+        let mut children = node.children();
+        while let Some(child) = children.next().map_err(invalid_data)? {
+            let (tag, in_scope) = {
+                let entry = child.entry();
+                let in_scope = match entry.tag() {
+                    gimli::DW_TAG_lexical_block => {
+                        let mut ranges = self.dwarf.die_ranges(unit, entry).map_err(invalid_data)?;
+                        let mut in_scope = false;
+                        while let Some(range) = ranges.next().map_err(invalid_data)? {
+                            in_scope |= range.begin as usize <= address &&
+                                address < range.end as usize;
+                        }
+                        in_scope
+                    }
+                    _ => false,
+                };
+                (entry.tag(), in_scope)
+            };
+
+            match tag {
+                gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
+                    let entry = child.entry();
+
+                    // `extern` declarations refer to a global defined elsewhere
+                    if entry.attr_value(gimli::DW_AT_declaration).map_err(invalid_data)?.is_some() {
+                        continue;
+                    }
+
+                    let die = entry.offset().to_debug_info_offset(&unit.header)
+                        .ok_or(not_found())?.0 as u32;
+
+                    // locals without a fixed location are identified by their DIE instead,
+                    // including those that are optimized out entirely
+                    let (address, flags) = match entry.attr_value(gimli::DW_AT_location)
+                        .map_err(invalid_data)?
+                    {
+                        Some(gimli::AttributeValue::Exprloc(expression)) => {
+                            match fixed_location(unit, expression, frame_base) {
+                                Some(FixedLocation::Frame(offset)) =>
+                                    (offset as usize, SYMFLAG_LOCAL | SYMFLAG_REGREL),
+                                Some(FixedLocation::Static(address)) => (address + self.bias, 0),
+                                None => (die as usize, SYMFLAG_LOCAL),
+                            }
+                        }
+                        _ => (die as usize, SYMFLAG_LOCAL),
+                    };
+
+                    let name = self.name_attr(unit, entry)?.unwrap_or(OsString::new());
+                    let type_index = self.type_attr(unit, entry)?;
+                    let flags = if tag == gimli::DW_TAG_formal_parameter {
+                        flags | SYMFLAG_PARAMETER
+                    } else {
+                        flags
+                    };
+
+                    locals.push(Symbol { name, address, size: 0, type_index, flags, entry: die });
+                }
+
+                gimli::DW_TAG_lexical_block if in_scope => {
+                    self.collect_locals(unit, child, address, frame_base, locals)?;
+                }
+
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Global {
+    fn symbol(&self, bias: usize, flags: u32) -> Symbol {
+        Symbol {
+            name: self.name.clone(),
+            address: self.address + bias,
+            size: self.size,
+            type_index: self.type_index,
+            flags,
+            entry: 0,
+        }
+    }
+}
+
+impl LineRow {
+    fn to_line(&self, module: &Module) -> Line {
+        Line {
+            file: module.files[self.file].clone(),
+            line: self.line,
+            address: self.address + module.bias,
+        }
+    }
+}
+
+fn find_global(globals: &[Global], address: usize) -> Option<&Global> {
+    let index = match globals.binary_search_by_key(&address, |global| global.address) {
+        Ok(index) => index,
+        Err(0) => return None,
+        Err(index) => index - 1,
+    };
+
+    let global = &globals[index];
+    if address < global.address + global.size.max(1) {
+        Some(global)
+    } else {
+        None
+    }
+}
+
+/// The registers of a stack frame, for evaluating location expressions in it
+struct Frame<'a> {
+    pc: usize,
+    sp: usize,
+    fp: usize,
+
+    /// Whether `pc` is a return address into a caller, rather than where the thread stopped
+    outer: bool,
+
+    /// The register set, with only the callee-saved registers meaningful in an outer frame
+    context: Option<&'a Context>,
+}
+
+impl<'a> Frame<'a> {
+    /// The address to look up the frame's function and unwind info with
+    ///
+    /// Return addresses point after the call instruction, which may be in the next function.
+    fn lookup(&self) -> usize {
+        if self.outer { self.pc - 1 } else { self.pc }
+    }
+
+    fn register(&self, register: gimli::Register) -> Option<u64> {
+        match register {
+            gimli::X86_64::RSP => Some(self.sp as u64),
+            gimli::X86_64::RBP => Some(self.fp as u64),
+            gimli::X86_64::RA => Some(self.pc as u64),
+            _ => {
+                let context = self.context?;
+                let register = Register::from_dwarf(register.0)?;
+                if self.outer && !register.is_callee_saved() {
+                    return None;
+                }
+                Some(context.register(register))
+            }
+        }
+    }
+}
+
+/// Convert the result of evaluating a location description into a `Location`
+///
+/// A variable with any of its pieces optimized out is treated as optimized out entirely.
+fn location_from_pieces(pieces: Vec<gimli::Piece<Reader>>) -> io::Result<Option<Location>> {
+    if pieces.len() == 1 && pieces[0].size_in_bits.is_none() {
+        let piece = pieces.into_iter().next().unwrap();
+        return piece_location(piece.location);
+    }
+
+    let mut locations = vec![];
+    for piece in pieces {
+        let size = match (piece.size_in_bits, piece.bit_offset) {
+            (Some(bits), None) if bits % 8 == 0 => (bits / 8) as usize,
+            _ => return Err(unsupported_location()),
+        };
+
+        match piece_location(piece.location)? {
+            Some(location) => locations.push((location, size)),
+            None => return Ok(None),
+        }
+    }
+
+    if locations.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Location::Pieces(locations)))
+}
+
+fn piece_location(location: gimli::Location<Reader>) -> io::Result<Option<Location>> {
+    let location = match location {
+        gimli::Location::Empty => return Ok(None),
+
+        // registers 17 to 32 are `xmm0` to `xmm15`
+        gimli::Location::Register { register } => match Register::from_dwarf(register.0) {
+            Some(register) => Location::Register(register),
+            None if 17 <= register.0 && register.0 <= 32 =>
+                Location::Xmm(register.0 as usize - 17),
+            None => return Err(unsupported_location()),
+        },
+
+        gimli::Location::Address { address } => Location::Address(address as usize),
+
+        gimli::Location::Value { value } => Location::Value(match value {
+            gimli::Value::Generic(value) | gimli::Value::U64(value) => value.as_bytes().to_vec(),
+            gimli::Value::I8(value) => value.as_bytes().to_vec(),
+            gimli::Value::U8(value) => value.as_bytes().to_vec(),
+            gimli::Value::I16(value) => value.as_bytes().to_vec(),
+            gimli::Value::U16(value) => value.as_bytes().to_vec(),
+            gimli::Value::I32(value) => value.as_bytes().to_vec(),
+            gimli::Value::U32(value) => value.as_bytes().to_vec(),
+            gimli::Value::I64(value) => value.as_bytes().to_vec(),
+            gimli::Value::F32(value) => value.as_bytes().to_vec(),
+            gimli::Value::F64(value) => value.as_bytes().to_vec(),
+        }),
+
+        gimli::Location::Bytes { value } =>
+            Location::Value(value.to_slice().map_err(invalid_data)?.to_vec()),
+
+        // a pointer to an object that was itself optimized out
+        gimli::Location::ImplicitPointer { .. } => return Ok(None),
+    };
+
+    Ok(Some(location))
+}
+
+/// Decode an expression consisting only of `DW_OP_reg<n>`
+fn single_register(
+    unit: &gimli::Unit<Reader>, expression: gimli::Expression<Reader>
+) -> Option<gimli::Register> {
+    let mut operations = expression.operations(unit.encoding());
+    match (operations.next(), operations.next()) {
+        (Ok(Some(gimli::Operation::Register { register })), Ok(None)) => Some(register),
+        _ => None,
+    }
+}
+
+/// A location that doesn't depend on the current instruction
+enum FixedLocation {
+    /// Relative to the frame pointer
+    Frame(i64),
+    /// Relative to the module's debug info
+    Static(usize),
+}
+
+/// The distance from the frame pointer to a function's `DW_AT_frame_base`
+///
+/// This assumes the standard prologue, where the frame pointer is pushed immediately below the
+/// return address.
+fn frame_base_offset(
+    unit: &gimli::Unit<Reader>, expression: gimli::Expression<Reader>
+) -> Option<i64> {
+    let mut operations = expression.operations(unit.encoding());
+    let offset = match operations.next().ok()? {
+        Some(gimli::Operation::CallFrameCFA) => 16,
+        Some(gimli::Operation::Register { register: gimli::X86_64::RBP }) => 0,
+        Some(gimli::Operation::RegisterOffset { register: gimli::X86_64::RBP, offset, .. }) =>
+            offset,
+        _ => return None,
+    };
+
+    match operations.next() {
+        Ok(None) => Some(offset),
+        _ => None,
+    }
+}
+
+/// Decode the simple location expressions used by unoptimized code
+fn fixed_location(
+    unit: &gimli::Unit<Reader>, expression: gimli::Expression<Reader>, frame_base: Option<i64>
+) -> Option<FixedLocation> {
+    let mut operations = expression.operations(unit.encoding());
+    let location = match operations.next().ok()? {
+        Some(gimli::Operation::FrameOffset { offset }) =>
+            FixedLocation::Frame(frame_base? + offset),
+        Some(gimli::Operation::RegisterOffset { register: gimli::X86_64::RBP, offset, .. }) =>
+            FixedLocation::Frame(offset),
+        Some(gimli::Operation::Address { address }) => FixedLocation::Static(address as usize),
+        _ => return None,
+    };
+
+    match operations.next() {
+        Ok(None) => Some(location),
+        _ => None,
+    }
+}
+
+fn static_address(
+    unit: &gimli::Unit<Reader>, expression: gimli::Expression<Reader>
+) -> Option<usize> {
+    match fixed_location(unit, expression, None) {
+        Some(FixedLocation::Static(address)) => Some(address),
+        _ => None,
+    }
+}
+
+/// Read a data member's byte offset, and its bit position relative to that byte if it is a bitfield
+fn member_location(
+    entry: &gimli::DebuggingInformationEntry<Reader>
+) -> io::Result<(u32, Option<Bits>)> {
+    let bit_size = match udata(entry, gimli::DW_AT_bit_size)? {
+        Some(bit_size) => bit_size,
+        None => {
+            let offset = udata(entry, gimli::DW_AT_data_member_location)?.unwrap_or(0);
+            return Ok((offset as u32, None));
+        }
+    };
+
+    // DWARF 4 counts bits from the start of the struct, while DWARF 2 counts them from the most
+    // significant bit of a storage unit
+    let bit_offset = match udata(entry, gimli::DW_AT_data_bit_offset)? {
+        Some(bit_offset) => bit_offset,
+        None => {
+            let offset = udata(entry, gimli::DW_AT_data_member_location)?.unwrap_or(0);
+            let storage_size = udata(entry, gimli::DW_AT_byte_size)?.ok_or(unsupported_type())?;
+            let storage_offset = udata(entry, gimli::DW_AT_bit_offset)?.unwrap_or(0);
+            8 * offset + (8 * storage_size - storage_offset - bit_size)
+        }
+    };
+
+    let bits = Bits { offset: (bit_offset % 8) as u32, size: bit_size as u32 };
+    Ok(((bit_offset / 8) as u32, Some(bits)))
+}
+
+fn udata(
+    entry: &gimli::DebuggingInformationEntry<Reader>, attr: gimli::DwAt
+) -> io::Result<Option<u64>> {
+    let value = entry.attr_value(attr).map_err(invalid_data)?;
+    Ok(value.and_then(|value| value.udata_value()))
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+    where E: Into<Box<::std::error::Error + Send + Sync>>
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn not_found() -> io::Error {
+    io::Error::from(io::ErrorKind::NotFound)
+}
+
+fn unsupported_type() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "unsupported type")
+}
+
+fn unsupported_location() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "unsupported location")
+}
+
+/// The name and address of a debugging symbol
+///
+/// Will be expanded on to include type information, etc.
+#[derive(Clone)]
+pub struct Symbol {
+    pub name: OsString,
+    pub address: usize,
+    pub size: usize,
+    pub type_index: u32,
+    pub flags: u32,
+
+    /// The `.debug_info` offset of a local's DIE, which describes its location
+    entry: u32,
+}
+
+/// `Symbol::flags` bits, with the same meaning as their dbghelp counterparts
+pub const SYMFLAG_REGREL: u32 = 0x10;
+pub const SYMFLAG_PARAMETER: u32 = 0x40;
+pub const SYMFLAG_LOCAL: u32 = 0x80;
+
+impl Symbol {
+    /// Whether this symbol is a function parameter, rather than some other local
+    pub fn is_parameter(&self) -> bool {
+        self.flags & SYMFLAG_PARAMETER != 0
+    }
+
+    /// Whether this symbol's address is relative to the frame pointer
+    pub fn is_regrel(&self) -> bool {
+        self.flags & SYMFLAG_REGREL != 0
+    }
+
+    /// Whether this symbol is a function local or parameter, and so only exists in a stack frame
+    pub fn is_local(&self) -> bool {
+        self.flags & (SYMFLAG_LOCAL | SYMFLAG_PARAMETER) != 0
+    }
+}
+
+/// The file, line number, and first instruction address of a source line
+pub struct Line {
+    pub file: OsString,
+    pub line: u32,
+    pub address: usize,
+}
+
+/// Iterator of source lines
+pub struct Lines {
+    lines: ::std::vec::IntoIter<Line>,
+}
+
+impl Iterator for Lines {
+    type Item = Line;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next()
+    }
+}
+
+/// An iterator of the frames in a thread's stack
+pub struct StackFrames {
+    frames: ::std::vec::IntoIter<StackFrame>,
+}
+
+/// A single stack frame
+pub struct StackFrame {
+    instruction_pointer: usize,
+    return_address: usize,
+    stack_pointer: usize,
+    frame_pointer: usize,
+    context: Context,
+}
+
+impl StackFrame {
+    /// The address of the next instruction to execute in this frame
+    pub fn instruction_pointer(&self) -> usize { self.instruction_pointer }
+
+    /// The address this frame will return to
+    pub fn return_address(&self) -> usize { self.return_address }
+
+    pub fn stack_pointer(&self) -> usize { self.stack_pointer }
+
+    pub fn frame_pointer(&self) -> usize { self.frame_pointer }
+
+    /// The registers as they were when this frame was executing, for reading its locals
+    ///
+    /// Only the registers a callee saves are recovered for outer frames.
+    pub fn context(&self) -> Context { self.context.clone() }
+}
+
+impl Iterator for StackFrames {
+    type Item = StackFrame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.frames.next()
+    }
+}
@@ -0,0 +1,121 @@
+//! Thin wrappers around the ptrace API, along with the bookkeeping each debug thread needs to turn
+//! `waitpid` results into `Event`s.
+
+use std::{io, mem};
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+
+use libc;
+
+use Event;
+
+/// The state of the process being debugged by the current thread
+///
+/// ptrace ties a tracee to the thread that attached to it, so like the Win32 debugging API, each
+/// debug thread handles a single child process.
+pub struct Tracee {
+    pub process: libc::pid_t,
+    pub attached: bool,
+
+    /// Threads that have reported their first stop
+    pub threads: HashSet<libc::pid_t>,
+
+    /// Synthetic events to report before waiting on the child again
+    pub pending: VecDeque<Event>,
+}
+
+thread_local! {
+    static TRACEE: RefCell<Option<Tracee>> = RefCell::new(None);
+}
+
+/// Begin tracking a child process on the current thread
+pub fn track(process: libc::pid_t, attached: bool) {
+    TRACEE.with(|tracee| {
+        *tracee.borrow_mut() = Some(Tracee {
+            process, attached,
+            threads: HashSet::new(),
+            pending: VecDeque::new(),
+        });
+    });
+}
+
+/// Stop tracking the current thread's child process
+pub fn untrack() {
+    TRACEE.with(|tracee| *tracee.borrow_mut() = None);
+}
+
+/// Access the current thread's child process
+pub fn with_tracee<F, T>(f: F) -> io::Result<T> where F: FnOnce(&mut Tracee) -> T {
+    TRACEE.with(|tracee| {
+        let mut tracee = tracee.borrow_mut();
+        let tracee = tracee.as_mut()
+            .ok_or(io::Error::new(io::ErrorKind::NotFound, "no child process on this thread"))?;
+        Ok(f(tracee))
+    })
+}
+
+pub fn ptrace(
+    request: libc::c_uint, pid: libc::pid_t, address: usize, data: usize
+) -> io::Result<libc::c_long> {
+    unsafe {
+        let result = libc::ptrace(
+            request, pid, address as *mut libc::c_void, data as *mut libc::c_void
+        );
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(result)
+    }
+}
+
+/// Wait for a state change in any child of the current thread
+pub fn waitpid(pid: libc::pid_t, options: libc::c_int) -> io::Result<(libc::pid_t, libc::c_int)> {
+    loop {
+        let mut status = 0;
+        let pid = unsafe { libc::waitpid(pid, &mut status, options | libc::__WNOTHREAD) };
+        if pid == -1 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(error);
+        }
+
+        return Ok((pid, status));
+    }
+}
+
+pub fn get_regs(thread: libc::pid_t) -> io::Result<libc::user_regs_struct> {
+    unsafe {
+        let mut regs = mem::zeroed();
+        ptrace(libc::PTRACE_GETREGS, thread, 0, &mut regs as *mut _ as usize)?;
+        Ok(regs)
+    }
+}
+
+pub fn get_fpregs(thread: libc::pid_t) -> io::Result<libc::user_fpregs_struct> {
+    unsafe {
+        let mut fpregs = mem::zeroed();
+        ptrace(libc::PTRACE_GETFPREGS, thread, 0, &mut fpregs as *mut _ as usize)?;
+        Ok(fpregs)
+    }
+}
+
+pub fn get_siginfo(thread: libc::pid_t) -> io::Result<libc::siginfo_t> {
+    unsafe {
+        let mut info = mem::zeroed();
+        ptrace(libc::PTRACE_GETSIGINFO, thread, 0, &mut info as *mut _ as usize)?;
+        Ok(info)
+    }
+}
+
+pub fn kill(pid: libc::pid_t, signal: libc::c_int) -> io::Result<()> {
+    unsafe {
+        if libc::kill(pid, signal) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
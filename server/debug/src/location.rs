@@ -0,0 +1,77 @@
+use std::io;
+use {Child, Context, Register, AsBytes};
+
+/// Where a variable lives at a particular instruction
+pub enum Location {
+    /// In memory, at an absolute address
+    Address(usize),
+    /// In a general-purpose register
+    Register(Register),
+    /// In an SSE register
+    Xmm(usize),
+    /// Nowhere in the target; the debug info describes the value itself
+    Value(Vec<u8>),
+    /// Split across several locations, each holding the given number of bytes
+    Pieces(Vec<(Location, usize)>),
+}
+
+impl Location {
+    /// Read a variable's bytes from its location in a stopped thread
+    pub fn read(&self, child: &Child, context: &Context, data: &mut [u8]) -> io::Result<()> {
+        match *self {
+            Location::Address(address) => {
+                child.read_memory(address, data)?;
+                Ok(())
+            }
+
+            Location::Register(register) => {
+                let value = context.register(register);
+                copy_prefix(value.as_bytes(), data);
+                Ok(())
+            }
+
+            Location::Xmm(index) => {
+                copy_prefix(&context.xmm(index), data);
+                Ok(())
+            }
+
+            Location::Value(ref value) => {
+                copy_prefix(value, data);
+                Ok(())
+            }
+
+            Location::Pieces(ref pieces) => {
+                let mut offset = 0;
+                for &(ref piece, size) in pieces {
+                    let start = offset.min(data.len());
+                    let end = (offset + size).min(data.len());
+                    piece.read(child, context, &mut data[start..end])?;
+                    offset += size;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the location still holds its value after a call returns
+    ///
+    /// Callees may clobber `xmm` and the caller-saved general-purpose registers.
+    pub fn survives_calls(&self) -> bool {
+        match *self {
+            Location::Address(_) | Location::Value(_) => true,
+            Location::Register(register) => register.is_callee_saved(),
+            Location::Xmm(_) => false,
+            Location::Pieces(ref pieces) =>
+                pieces.iter().all(|&(ref piece, _)| piece.survives_calls()),
+        }
+    }
+}
+
+/// Fill `data` from the start of `source`, zero-extending if it is too short
+fn copy_prefix(source: &[u8], data: &mut [u8]) {
+    let size = source.len().min(data.len());
+    data[..size].copy_from_slice(&source[..size]);
+    for byte in &mut data[size..] {
+        *byte = 0;
+    }
+}
@@ -0,0 +1,59 @@
+use std::io;
+
+use winapi;
+
+use {Debugger, Child, Command, Event, Thread, Context, SymbolHandler};
+use {get_thread_context, set_thread_context};
+
+/// The debugger implementation for the current platform.
+pub type Native = Win32;
+
+/// The Win32 debugging API, with symbols provided by dbghelp.
+pub struct Win32 {
+    child: Child,
+    symbols: SymbolHandler,
+}
+
+impl Win32 {
+    fn new(child: Child) -> io::Result<Win32> {
+        let options = SymbolHandler::get_options();
+        SymbolHandler::set_options(winapi::SYMOPT_DEBUG | winapi::SYMOPT_LOAD_LINES | options);
+
+        let symbols = SymbolHandler::initialize(&child)?;
+        Ok(Win32 { child, symbols })
+    }
+}
+
+impl Debugger for Win32 {
+    fn launch(command: &mut Command) -> io::Result<Win32> {
+        Win32::new(command.debug()?)
+    }
+
+    fn attach(pid: u32) -> io::Result<Win32> {
+        Win32::new(Child::attach(pid)?)
+    }
+
+    fn child(&self) -> &Child { &self.child }
+
+    fn terminate(self) -> io::Result<()> {
+        let Win32 { child, symbols } = self;
+        let result = child.terminate();
+        drop(symbols);
+
+        result
+    }
+
+    fn wait_event(&self) -> io::Result<Event> {
+        Event::wait_event()
+    }
+
+    fn get_context(&self, thread: Thread) -> io::Result<Context> {
+        get_thread_context(thread, winapi::CONTEXT_FULL)
+    }
+
+    fn set_context(&self, thread: Thread, context: &Context) -> io::Result<()> {
+        set_thread_context(thread, context)
+    }
+
+    fn symbols(&self) -> &SymbolHandler { &self.symbols }
+}
@@ -1,23 +1,37 @@
-use std::{mem, ptr, iter, io, env};
+use std::{mem, ptr, iter, io, env, fs, cmp, slice, thread};
 use std::ffi::{OsString, OsStr};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, Duration, UNIX_EPOCH};
 use std::os::windows::ffi::OsStrExt;
-use std::os::windows::io::{RawHandle, AsRawHandle, IntoRawHandle};
+use std::os::windows::io::{RawHandle, AsRawHandle, IntoRawHandle, FromRawHandle};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 use winapi;
 use kernel32;
 use advapi32;
 
-use {FromWide, AsBytes};
+use {FromWide, AsBytes, Register};
 
 /// A running or exited debugee process, created via a `Command`
-pub struct Child(RawHandle);
+pub struct Child(RawHandle, Option<Arc<Capture>>);
+
+/// A handle to a thread in a child process
+pub type Thread = RawHandle;
 
 /// A handle to a process that can inject breakpoints
 pub struct Cancel(RawHandle);
 unsafe impl Send for Cancel {}
 
 impl Child {
+    /// A handle to the process's captured stdout and stderr, which can be read from any thread
+    ///
+    /// Processes launched without `Command::capture_output` have none.
+    pub fn output(&self) -> Option<Output> {
+        self.1.clone().map(Output)
+    }
+
     /// Read `buffer.len()` bytes from a process's address space at `address`
     pub fn read_memory(&self, address: usize, buffer: &mut [u8]) -> io::Result<usize> {
         unsafe {
@@ -121,7 +135,7 @@ impl Child {
                 panic!("Error in AdjustTokenPrivileges");
             }
 
-            Ok(Child(process))
+            Ok(Child(process, None))
         }
     }
 
@@ -185,6 +199,10 @@ impl Context {
         self.0.Rip = address as winapi::DWORD64;
     }
 
+    pub fn frame_pointer(&self) -> usize {
+        self.0.Rbp as usize
+    }
+
     pub fn set_singlestep(&mut self, singlestep: bool) {
         if singlestep {
             self.0.EFlags |= 0x100;
@@ -193,6 +211,56 @@ impl Context {
         }
     }
 
+    pub fn register(&self, register: Register) -> u64 {
+        use Register::*;
+        let context = &self.0;
+        match register {
+            Rax => context.Rax, Rdx => context.Rdx, Rcx => context.Rcx, Rbx => context.Rbx,
+            Rsi => context.Rsi, Rdi => context.Rdi, Rbp => context.Rbp, Rsp => context.Rsp,
+            R8 => context.R8, R9 => context.R9, R10 => context.R10, R11 => context.R11,
+            R12 => context.R12, R13 => context.R13, R14 => context.R14, R15 => context.R15,
+            Rip => context.Rip,
+        }
+    }
+
+    pub fn set_register(&mut self, register: Register, value: u64) {
+        use Register::*;
+        let context = &mut self.0;
+        let target = match register {
+            Rax => &mut context.Rax, Rdx => &mut context.Rdx,
+            Rcx => &mut context.Rcx, Rbx => &mut context.Rbx,
+            Rsi => &mut context.Rsi, Rdi => &mut context.Rdi,
+            Rbp => &mut context.Rbp, Rsp => &mut context.Rsp,
+            R8 => &mut context.R8, R9 => &mut context.R9,
+            R10 => &mut context.R10, R11 => &mut context.R11,
+            R12 => &mut context.R12, R13 => &mut context.R13,
+            R14 => &mut context.R14, R15 => &mut context.R15,
+            Rip => &mut context.Rip,
+        };
+        *target = value;
+    }
+
+    /// Read the 16 bytes of an SSE register
+    pub fn xmm(&self, index: usize) -> [u8; 16] {
+        let xmm = &self.0.FltSave.XmmRegisters[index];
+        unsafe { mem::transmute([xmm.Low, xmm.High as u64]) }
+    }
+
+    /// Read the 16 bytes an x87 register is saved in, relative to the top of the stack
+    pub fn st(&self, index: usize) -> [u8; 16] {
+        let st = &self.0.FltSave.FloatRegisters[index];
+        unsafe { mem::transmute([st.Low, st.High as u64]) }
+    }
+
+    /// Write the 16 bytes of an SSE register
+    pub fn set_xmm(&mut self, index: usize, value: [u8; 16]) {
+        let value: [u64; 2] = unsafe { mem::transmute(value) };
+        let xmm = &mut self.0.FltSave.XmmRegisters[index];
+        xmm.Low = value[0];
+        xmm.High = value[1] as i64;
+    }
+
+    pub fn from_raw(context: winapi::CONTEXT) -> Context { Context(context) }
     pub fn into_raw(self) -> winapi::CONTEXT { self.0 }
     pub fn as_raw(&self) -> &winapi::CONTEXT { &self.0 }
     pub fn as_raw_mut(&mut self) -> &mut winapi::CONTEXT { &mut self.0 }
@@ -228,11 +296,14 @@ pub fn set_thread_context(thread: winapi::HANDLE, context: &Context) -> io::Resu
 /// Lacks some features that we don't need:
 ///
 /// * Does not look up `program` in `PATH`
-/// * Child does not inherit stdio handles
+/// * Child gets its own console, and inherits only the stdio handles that are redirected
 pub struct Command {
     program: OsString,
     args: Vec<OsString>,
     env: Option<HashMap<OsString, OsString>>,
+    cwd: Option<OsString>,
+    input: Option<Vec<u8>>,
+    capture: bool,
 }
 
 impl Command {
@@ -246,6 +317,9 @@ impl Command {
             program: program.as_ref().to_os_string(),
             args: vec![],
             env: None,
+            cwd: None,
+            input: None,
+            capture: false,
         }
     }
 
@@ -278,6 +352,26 @@ impl Command {
         self
     }
 
+    /// Set the working directory of the child process.
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
+        self.cwd = Some(dir.as_ref().as_os_str().to_os_string());
+        self
+    }
+
+    /// Write `input` to the child process's stdin, then close it.
+    ///
+    /// By default the child gets the stdin of its new console.
+    pub fn input<T: Into<Vec<u8>>>(&mut self, input: T) -> &mut Command {
+        self.input = Some(input.into());
+        self
+    }
+
+    /// Capture the child process's stdout and stderr, to be read through `Child::output`.
+    pub fn capture_output(&mut self) -> &mut Command {
+        self.capture = true;
+        self
+    }
+
     /// Execute the command as a child process and return a handle to it.
     pub fn debug(&mut self) -> io::Result<Child> {
         let mut si = winapi::STARTUPINFOW {
@@ -287,19 +381,74 @@ impl Command {
 
         let mut cmd = make_command_line(&self.program, &self.args)?;
         let (env, _env) = make_env(self.env.as_ref())?;
+        let cwd = match self.cwd {
+            Some(ref cwd) => Some(make_path(cwd)?),
+            None => None,
+        };
+
+        let stdin = match self.input {
+            Some(_) => Some(make_pipe(true)?),
+            None => None,
+        };
+        let output = if self.capture {
+            Some((make_pipe(false)?, make_pipe(false)?))
+        } else {
+            None
+        };
+
+        // handles that aren't redirected refer to the server's stdio
+        let redirect = stdin.is_some() || output.is_some();
+        if redirect {
+            si.dwFlags = winapi::STARTF_USESTDHANDLES;
+            unsafe {
+                si.hStdInput = match stdin {
+                    Some((ref reader, _)) => reader.as_raw_handle() as winapi::HANDLE,
+                    None => kernel32::GetStdHandle(winapi::STD_INPUT_HANDLE),
+                };
+                match output {
+                    Some(((_, ref stdout), (_, ref stderr))) => {
+                        si.hStdOutput = stdout.as_raw_handle() as winapi::HANDLE;
+                        si.hStdError = stderr.as_raw_handle() as winapi::HANDLE;
+                    }
+                    None => {
+                        si.hStdOutput = kernel32::GetStdHandle(winapi::STD_OUTPUT_HANDLE);
+                        si.hStdError = kernel32::GetStdHandle(winapi::STD_ERROR_HANDLE);
+                    }
+                }
+            }
+        }
 
         let mut pi = unsafe { mem::zeroed() };
         unsafe {
-            if kernel32::CreateProcessW(
-                ptr::null(), cmd.as_mut_ptr(), ptr::null_mut(), ptr::null_mut(), winapi::FALSE,
-                winapi::CREATE_NEW_CONSOLE | winapi::DEBUG_ONLY_THIS_PROCESS,
-                env as *mut winapi::VOID, ptr::null(), &mut si, &mut pi
-            ) != winapi::TRUE {
-                return Err(io::Error::last_os_error())
+            let created = kernel32::CreateProcessW(
+                ptr::null(), cmd.as_mut_ptr(), ptr::null_mut(), ptr::null_mut(),
+                if redirect { winapi::TRUE } else { winapi::FALSE },
+                winapi::CREATE_NEW_CONSOLE | winapi::CREATE_UNICODE_ENVIRONMENT |
+                    winapi::DEBUG_ONLY_THIS_PROCESS,
+                env as *mut winapi::VOID, cwd.as_ref().map_or(ptr::null(), |cwd| cwd.as_ptr()),
+                &mut si, &mut pi
+            );
+            let error = io::Error::last_os_error();
+
+            // close the child's ends of the pipes, so they report EOF once it exits
+            let stdin = stdin.map(|(_, writer)| writer);
+            let output = output.map(|((stdout, _), (stderr, _))| Capture {
+                pipes: vec![(Stream::Stdout, stdout), (Stream::Stderr, stderr)],
+                chunks: Mutex::new(vec![]),
+            });
+            if created != winapi::TRUE {
+                return Err(error);
+            }
+
+            // the child may not read its input until it is resumed, so write it in the background
+            if let (Some(mut stdin), Some(input)) = (stdin, self.input.clone()) {
+                thread::spawn(move || {
+                    let _ = stdin.write_all(&input);
+                });
             }
 
             kernel32::CloseHandle(pi.hThread);
-            Ok(Child(pi.hProcess))
+            Ok(Child(pi.hProcess, output.map(Capture::start)))
         }
     }
 }
@@ -367,6 +516,42 @@ fn make_env(
     }
 }
 
+fn make_path(path: &OsStr) -> io::Result<Vec<u16>> {
+    let mut path: Vec<_> = ensure_no_nuls(path)?.encode_wide().collect();
+    path.push(0);
+    Ok(path)
+}
+
+/// Create a pipe, returning its read and write ends
+///
+/// Only the end for the child process is inheritable- the read end if `child_reads` is set, or
+/// the write end otherwise.
+fn make_pipe(child_reads: bool) -> io::Result<(fs::File, fs::File)> {
+    let mut attributes = winapi::SECURITY_ATTRIBUTES {
+        nLength: mem::size_of::<winapi::SECURITY_ATTRIBUTES>() as winapi::DWORD,
+        lpSecurityDescriptor: ptr::null_mut(),
+        bInheritHandle: winapi::TRUE,
+    };
+
+    unsafe {
+        let mut reader = ptr::null_mut();
+        let mut writer = ptr::null_mut();
+        if kernel32::CreatePipe(&mut reader, &mut writer, &mut attributes, 0) == winapi::FALSE {
+            return Err(io::Error::last_os_error());
+        }
+        let reader = fs::File::from_raw_handle(reader as RawHandle);
+        let writer = fs::File::from_raw_handle(writer as RawHandle);
+
+        let parent = if child_reads { &writer } else { &reader };
+        let parent = parent.as_raw_handle() as winapi::HANDLE;
+        if kernel32::SetHandleInformation(parent, winapi::HANDLE_FLAG_INHERIT, 0) == winapi::FALSE {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok((reader, writer))
+    }
+}
+
 fn ensure_no_nuls<S: AsRef<OsStr>>(s: S) -> io::Result<S> {
     if s.as_ref().encode_wide().any(|b| b == 0) {
         Err(io::Error::new(io::ErrorKind::InvalidInput, "nul byte found in provided data"))
@@ -375,13 +560,125 @@ fn ensure_no_nuls<S: AsRef<OsStr>>(s: S) -> io::Result<S> {
     }
 }
 
+/// One of a child process's output streams
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A child process's captured stdout and stderr
+#[derive(Clone)]
+pub struct Output(Arc<Capture>);
+
+impl Output {
+    /// Take everything the process has written so far
+    ///
+    /// While the process is stopped, this includes all of its output up to that point.
+    pub fn read(&self) -> Vec<(Stream, Vec<u8>)> {
+        self.0.read()
+    }
+}
+
+/// The parent's end of a child process's stdout and stderr
+///
+/// Anonymous pipes can't be waited on without blocking a read, so a thread polls them for data to
+/// keep the child from blocking on a full pipe. All reads happen with `chunks` locked, so chunks
+/// stay in the order they were written and a reader can also drain the pipes itself to catch up
+/// with a stopped process.
+struct Capture {
+    pipes: Vec<(Stream, fs::File)>,
+    chunks: Mutex<Vec<(Stream, Vec<u8>)>>,
+}
+
+impl Capture {
+    fn start(capture: Capture) -> Arc<Capture> {
+        let capture = Arc::new(capture);
+        let poll = capture.clone();
+        thread::spawn(move || poll.drain_pipes());
+        capture
+    }
+
+    fn drain_pipes(&self) {
+        loop {
+            {
+                let mut chunks = self.chunks.lock().unwrap();
+                let mut open = false;
+                for pipe in &self.pipes {
+                    open |= read_pipe(pipe, &mut chunks);
+                }
+                if !open {
+                    return;
+                }
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn read(&self) -> Vec<(Stream, Vec<u8>)> {
+        let mut chunks = self.chunks.lock().unwrap();
+        for pipe in &self.pipes {
+            read_pipe(pipe, &mut chunks);
+        }
+        mem::replace(&mut *chunks, vec![])
+    }
+}
+
+/// Read everything currently available from a pipe without blocking, returning whether it is
+/// still open
+fn read_pipe(
+    &(stream, ref file): &(Stream, fs::File), chunks: &mut Vec<(Stream, Vec<u8>)>
+) -> bool {
+    let handle = file.as_raw_handle() as winapi::HANDLE;
+    loop {
+        // this fails with `ERROR_BROKEN_PIPE` once the child's end is closed and the pipe is empty
+        let mut available = 0;
+        let peeked = unsafe {
+            kernel32::PeekNamedPipe(
+                handle, ptr::null_mut(), 0, ptr::null_mut(), &mut available, ptr::null_mut()
+            )
+        };
+        if peeked == winapi::FALSE {
+            return false;
+        }
+        if available == 0 {
+            return true;
+        }
+
+        let mut buffer = vec![0; cmp::min(available as usize, 4096)];
+        let size = match (&*file).read(&mut buffer) {
+            Ok(0) | Err(_) => return false,
+            Ok(size) => size,
+        };
+        buffer.truncate(size);
+
+        match chunks.last_mut() {
+            Some(&mut (last, ref mut data)) if last == stream => {
+                data.extend_from_slice(&buffer);
+                continue;
+            }
+            _ => {}
+        }
+        chunks.push((stream, buffer));
+    }
+}
+
 /// A running process
+///
+/// Details that can't be read, usually for lack of permission, are left as `None`.
 pub struct Process {
     pub id: u32,
     pub name: OsString,
+    pub parent_id: u32,
+    pub command_line: Option<OsString>,
+    pub path: Option<PathBuf>,
+    pub owner: Option<OsString>,
+    pub start_time: Option<SystemTime>,
+    /// Whether `Child::attach` is expected to succeed
+    pub attachable: bool,
 }
 
-#[cfg(windows)]
 impl Process {
     /// Iterate over currently-running processes
     pub fn running() -> io::Result<Processes> {
@@ -404,15 +701,80 @@ impl Process {
             Ok(Processes { snap: snap, pe32: pe32 })
         }
     }
+
+    /// Look up a running process by its id
+    pub fn get(id: u32) -> io::Result<Process> {
+        // only the matching entry is opened for its details
+        let Processes { snap, mut pe32 } = Process::running()?;
+        let mut found = None;
+        unsafe {
+            loop {
+                if pe32.th32ProcessID == id {
+                    let name = OsString::from_wide_null(&pe32.szExeFile);
+                    found = Some((name, pe32.th32ParentProcessID));
+                    break;
+                }
+                if kernel32::Process32NextW(snap, &mut pe32) == winapi::FALSE {
+                    break;
+                }
+            }
+            kernel32::CloseHandle(snap);
+        }
+
+        match found {
+            Some((name, parent_id)) => Ok(Process::query(id, name, parent_id)),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no such process")),
+        }
+    }
+
+    /// Fill in the details that require opening the process
+    fn query(id: u32, name: OsString, parent_id: u32) -> Process {
+        let mut process = Process {
+            id, name, parent_id,
+            command_line: None,
+            path: None,
+            owner: None,
+            start_time: None,
+            attachable: false,
+        };
+
+        unsafe {
+            let access = winapi::PROCESS_QUERY_LIMITED_INFORMATION | winapi::PROCESS_VM_READ;
+            let mut handle = kernel32::OpenProcess(access, winapi::FALSE, id);
+            if handle == ptr::null_mut() {
+                let access = winapi::PROCESS_QUERY_LIMITED_INFORMATION;
+                handle = kernel32::OpenProcess(access, winapi::FALSE, id);
+            }
+            if handle != ptr::null_mut() {
+                process.command_line = process_command_line(handle);
+                process.path = process_path(handle);
+                process.owner = process_owner(handle);
+                process.start_time = process_start_time(handle);
+                kernel32::CloseHandle(handle);
+            }
+
+            // the same access `Child::attach` asks for
+            let access =
+                winapi::PROCESS_VM_OPERATION |
+                winapi::PROCESS_VM_READ |
+                winapi::PROCESS_VM_WRITE |
+                winapi::PROCESS_QUERY_INFORMATION;
+            let handle = kernel32::OpenProcess(access, winapi::FALSE, id);
+            if handle != ptr::null_mut() {
+                process.attachable = id != kernel32::GetCurrentProcessId();
+                kernel32::CloseHandle(handle);
+            }
+        }
+
+        process
+    }
 }
 
-#[cfg(windows)]
 pub struct Processes {
     snap: winapi::HANDLE,
     pe32: winapi::PROCESSENTRY32W,
 }
 
-#[cfg(windows)]
 impl Iterator for Processes {
     type Item = Process;
 
@@ -421,10 +783,11 @@ impl Iterator for Processes {
             return None;
         }
 
-        let result = Process {
-            id: self.pe32.th32ProcessID,
-            name: OsString::from_wide_null(&self.pe32.szExeFile),
-        };
+        let result = Process::query(
+            self.pe32.th32ProcessID,
+            OsString::from_wide_null(&self.pe32.szExeFile),
+            self.pe32.th32ParentProcessID,
+        );
 
         unsafe {
             if kernel32::Process32NextW(self.snap, &mut self.pe32) == winapi::FALSE {
@@ -437,53 +800,135 @@ impl Iterator for Processes {
     }
 }
 
-#[cfg(unix)]
-impl Process {
-    /// Iterate over currently-running processes
-    pub fn running() -> io::Result<Processes> {
-        Processes(fs::read_dir("/proc")?)
+#[repr(C)]
+#[allow(non_snake_case)]
+struct PROCESS_BASIC_INFORMATION {
+    ExitStatus: winapi::LONG,
+    PebBaseAddress: winapi::PVOID,
+    AffinityMask: winapi::ULONG_PTR,
+    BasePriority: winapi::LONG,
+    UniqueProcessId: winapi::ULONG_PTR,
+    InheritedFromUniqueProcessId: winapi::ULONG_PTR,
+}
+
+#[link(name = "ntdll")]
+extern "system" {
+    fn NtQueryInformationProcess(
+        ProcessHandle: winapi::HANDLE, ProcessInformationClass: winapi::ULONG,
+        ProcessInformation: winapi::PVOID, ProcessInformationLength: winapi::ULONG,
+        ReturnLength: *mut winapi::ULONG
+    ) -> winapi::LONG;
+}
+
+/// Read a process's command line out of its `RTL_USER_PROCESS_PARAMETERS`
+unsafe fn process_command_line(process: winapi::HANDLE) -> Option<OsString> {
+    // offsets into the x64 `PEB` and `RTL_USER_PROCESS_PARAMETERS`
+    const PROCESS_PARAMETERS: usize = 0x20;
+    const COMMAND_LINE: usize = 0x70;
+
+    let mut info: PROCESS_BASIC_INFORMATION = mem::zeroed();
+    let size = mem::size_of::<PROCESS_BASIC_INFORMATION>() as winapi::ULONG;
+    let status = NtQueryInformationProcess(
+        process, 0, &mut info as *mut _ as winapi::PVOID, size, ptr::null_mut()
+    ); // ProcessBasicInformation
+    if status < 0 || info.PebBaseAddress == ptr::null_mut() {
+        return None;
     }
+
+    let read = |address: usize, buffer: &mut [u8]| -> Option<()> {
+        let mut read = 0;
+        if kernel32::ReadProcessMemory(
+            process, address as winapi::LPCVOID,
+            buffer.as_mut_ptr() as winapi::LPVOID, buffer.len() as winapi::SIZE_T, &mut read
+        ) == winapi::FALSE || read as usize != buffer.len() {
+            return None;
+        }
+        Some(())
+    };
+
+    let mut parameters = [0u8; 8];
+    read(info.PebBaseAddress as usize + PROCESS_PARAMETERS, &mut parameters)?;
+    let parameters = *(parameters.as_ptr() as *const usize);
+
+    // UNICODE_STRING { Length: USHORT, MaximumLength: USHORT, Buffer: PWSTR }
+    let mut command_line = [0u8; 16];
+    read(parameters + COMMAND_LINE, &mut command_line)?;
+    let length = *(command_line.as_ptr() as *const u16) as usize;
+    let buffer = *(command_line.as_ptr().offset(8) as *const usize);
+
+    let mut wide = vec![0u16; length / 2];
+    read(buffer, slice::from_raw_parts_mut(wide.as_mut_ptr() as *mut u8, length & !1))?;
+    Some(OsString::from_wide(&wide))
 }
 
-#[cfg(unix)]
-struct Processes(fs::ReadDir);
+unsafe fn process_path(process: winapi::HANDLE) -> Option<PathBuf> {
+    let mut path = [0u16; winapi::MAX_PATH * 2];
+    let mut length = path.len() as winapi::DWORD;
+    if kernel32::QueryFullProcessImageNameW(process, 0, path.as_mut_ptr(), &mut length) ==
+        winapi::FALSE
+    {
+        return None;
+    }
 
-#[cfg(unix)]
-impl Iterator for Processes {
-    type Item = Process;
+    Some(PathBuf::from(OsString::from_wide(&path[..length as usize])))
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0
-            .flat_map(|entry| entry)
-            .filter(|entry| entry.metadata().is_dir())
-            .flat_map(|dir| {
-                let path = dir.path();
-                path.push("status");
-
-                let status = fs::File::open(path).map_err(|_| ())?;
-                let reader = io::BufReader::new(status);
-
-                let mut id = None;
-                let mut name = None;
-                for line in reader.lines() {
-                    let line = line.map_err(|_| ())?;
-                    if line.starts_with("Name:") {
-                        name = Some(line.trim_left_matches("Name:").trim().to_owned());
-                    } else if line.starts_with("Pid:") {
-                        id = Some(line.trim_left_matches("Pid:").trim().to_owned());
-                    }
+/// Find the `DOMAIN\user` that owns a process
+unsafe fn process_owner(process: winapi::HANDLE) -> Option<OsString> {
+    let mut token = ptr::null_mut();
+    if advapi32::OpenProcessToken(process, winapi::TOKEN_QUERY, &mut token) == winapi::FALSE {
+        return None;
+    }
 
-                    if id.is_some() && name.is_some() {
-                        break;
-                    }
-                }
+    // `TOKEN_USER` is followed by the variable-length `SID` it points to
+    let mut user = [0u64; 64];
+    let mut length = 0;
+    let result = advapi32::GetTokenInformation(
+        token, winapi::TokenUser, user.as_mut_ptr() as winapi::LPVOID,
+        mem::size_of_val(&user) as winapi::DWORD, &mut length
+    );
+    kernel32::CloseHandle(token);
+    if result == winapi::FALSE {
+        return None;
+    }
+    let user = &*(user.as_ptr() as *const winapi::TOKEN_USER);
+
+    let mut name = [0u16; 256];
+    let mut name_length = name.len() as winapi::DWORD;
+    let mut domain = [0u16; 256];
+    let mut domain_length = domain.len() as winapi::DWORD;
+    let mut kind = mem::zeroed();
+    if advapi32::LookupAccountSidW(
+        ptr::null(), user.User.Sid,
+        name.as_mut_ptr(), &mut name_length,
+        domain.as_mut_ptr(), &mut domain_length,
+        &mut kind
+    ) == winapi::FALSE {
+        return None;
+    }
 
-                let id = id.ok_or(())?
-                    .parse::<u32>().map_err(|_| ())?;
-                let name = name.ok_or(())?;
+    let mut owner = OsString::from_wide(&domain[..domain_length as usize]);
+    owner.push("\\");
+    owner.push(OsString::from_wide(&name[..name_length as usize]));
+    Some(owner)
+}
 
-                Ok(Process { id: id, name: name })
-            })
-            .next()
+unsafe fn process_start_time(process: winapi::HANDLE) -> Option<SystemTime> {
+    let mut creation = mem::zeroed();
+    let mut exit = mem::zeroed();
+    let mut kernel = mem::zeroed();
+    let mut user = mem::zeroed();
+    if kernel32::GetProcessTimes(
+        process, &mut creation, &mut exit, &mut kernel, &mut user
+    ) == winapi::FALSE {
+        return None;
     }
+
+    // `FILETIME`s count 100ns intervals since 1601
+    const UNIX_EPOCH_INTERVALS: u64 = 116444736000000000;
+    let winapi::FILETIME { dwLowDateTime: low, dwHighDateTime: high } = creation;
+    let intervals = ((high as u64) << 32 | low as u64).checked_sub(UNIX_EPOCH_INTERVALS)?;
+    let seconds = intervals / 10_000_000;
+    let nanos = (intervals % 10_000_000) * 100;
+    Some(UNIX_EPOCH + Duration::new(seconds, nanos as u32))
 }
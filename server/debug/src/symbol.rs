@@ -1,6 +1,7 @@
 use std::{io, iter, mem, ptr};
 use std::fs::File;
 use std::sync::Mutex;
+use std::cell::{Ref, RefCell};
 use std::ffi::{OsString, OsStr};
 use std::os::windows::ffi::OsStrExt;
 use std::os::windows::io::{RawHandle, AsRawHandle};
@@ -9,8 +10,8 @@ use winapi;
 use kernel32;
 use dbghelp;
 
-use types::{Type, Primitive, Field};
-use {Child, FromWide};
+use types::{Type, Primitive, Field, Bits, Enumerator};
+use {Child, Thread, Context, Location, Visualizer, FromWide};
 
 lazy_static! {
     static ref HANDLE: Mutex<Handle> = Mutex::new(Handle(None));
@@ -20,7 +21,7 @@ struct Handle(Option<RawHandle>);
 unsafe impl Send for Handle {}
 unsafe impl Sync for Handle {}
 
-pub struct SymbolHandler(RawHandle);
+pub struct SymbolHandler(RawHandle, RefCell<Vec<Visualizer>>);
 
 impl SymbolHandler {
     pub fn get_options() -> winapi::DWORD {
@@ -50,7 +51,21 @@ impl SymbolHandler {
         }
 
         *handle = Some(process);
-        Ok(SymbolHandler(process))
+        Ok(SymbolHandler(process, RefCell::new(vec![])))
+    }
+
+    /// Add rules for showing structs by their logical contents
+    ///
+    /// Visualizers added first take precedence.
+    pub fn add_visualizers<I: IntoIterator<Item = Visualizer>>(&self, visualizers: I) {
+        self.1.borrow_mut().extend(visualizers);
+    }
+
+    /// Find the visualizer for structs named `name`
+    pub fn visualizer(&self, name: &OsStr) -> Option<Ref<Visualizer>> {
+        let visualizers = self.1.borrow();
+        let index = visualizers.iter().position(|visualizer| visualizer.matches(name));
+        index.map(|index| Ref::map(visualizers, |visualizers| &visualizers[index]))
     }
 
     /// Load the symbols for a module
@@ -193,13 +208,19 @@ impl SymbolHandler {
             let base = match base {
                 NoType | Void => Primitive::Void,
                 Bool => Primitive::Bool,
-                Char | WChar | Int | Long => Primitive::Int { signed: true },
+                Char => Primitive::Char { signed: true },
+                WChar | Char16 | Char32 | Char8 => Primitive::Char { signed: false },
+                Int | Long | Hresult => Primitive::Int { signed: true },
                 UInt | ULong => Primitive::Int { signed: false },
                 Float => Primitive::Float,
+                Complex => Primitive::Complex { extended: false },
                 _ => return Err(io::Error::new(io::ErrorKind::Other, "unsupported type")),
             };
 
             let TypeLength(size) = self.get_type_info(module, type_index)?;
+            if !base.supports_size(size as usize) {
+                return Err(io::Error::new(io::ErrorKind::Other, "unsupported type"));
+            }
 
             Ok(Type::Base { base: base, size: size as usize })
         } else if tag == winapi::SymTagPointerType {
@@ -226,29 +247,85 @@ impl SymbolHandler {
         } else if tag == winapi::SymTagUDT {
             let name = self.get_type_name(module, type_index)?;
             let TypeLength(size) = self.get_type_info(module, type_index)?;
+            let UdtKind(kind) = self.get_type_info(module, type_index)?;
+
+            let mut fields = vec![];
+            for field in self.get_type_children(module, type_index)? {
+                let tag: winapi::SymTag = self.get_type_info(module, field)?;
+                if tag == winapi::SymTagBaseClass {
+                    // virtual base classes are located at runtime
+                    let VirtualBaseClass(virtual_base) = self.get_type_info(module, field)?;
+                    if virtual_base != winapi::FALSE {
+                        continue;
+                    }
 
-            let fields = self.get_type_children(module, type_index)?;
-            let fields: io::Result<Vec<_>> = fields.iter()
-                .filter(|&field| {
-                    // TODO: this would more accurately filter for data fields, not "typed things"
-                    // that would need to be expanded to support base classes
-                    match self.get_type_info(module, *field) {
-                        Ok(TypeIndex(_)) => true,
-                        Err(_) => false,
+                    let TypeIndex(base_type) = self.get_type_info(module, field)?;
+                    let TypeOffset(offset) = self.get_type_info(module, field)?;
+                    fields.push(Field {
+                        name: self.get_type_name(module, base_type)?,
+                        type_index: base_type,
+                        offset: offset,
+                        bits: None,
+                        base_class: true,
+                    });
+                } else if tag == winapi::SymTagData {
+                    // skip static members, which live outside the struct
+                    let DataKind(kind) = self.get_type_info(module, field)?;
+                    if kind != DATA_IS_MEMBER {
+                        continue;
                     }
-                })
-                .map(|&field| {
-                    let name = self.get_type_name(module, field)?;
+
                     let TypeIndex(field_type) = self.get_type_info(module, field)?;
-                    let TypeOffset(offset) = self.get_type_info(module, field)?;
-                    Ok(Field { name: name, type_index: field_type, offset: offset })
-                })
-                .collect();
+                    let TypeOffset(mut offset) = self.get_type_info(module, field)?;
+
+                    // bitfields are positioned relative to a storage unit of their type
+                    let bits = match self.get_type_info(module, field) {
+                        Ok(BitPosition(position)) => {
+                            let TypeLength(length) = self.get_type_info(module, field)?;
+                            offset += position / 8;
+                            Some(Bits { offset: position % 8, size: length as u32 })
+                        }
+                        Err(_) => None,
+                    };
+
+                    fields.push(Field {
+                        name: self.get_type_name(module, field)?,
+                        type_index: field_type,
+                        offset: offset,
+                        bits: bits,
+                        base_class: false,
+                    });
+                }
+            }
 
-            Ok(Type::Struct { name: name, size: size as usize, fields: fields? })
+            if kind == UDT_UNION {
+                Ok(Type::Union { name: name, size: size as usize, fields: fields })
+            } else {
+                // PDBs don't describe template arguments
+                let type_params = vec![];
+                Ok(Type::Struct { name: name, size: size as usize, fields: fields, type_params })
+            }
         } else if tag == winapi::SymTagEnum {
+            let name = self.get_type_name(module, type_index)?;
             let TypeIndex(base) = self.get_type_info(module, type_index)?;
-            self.type_from_index(module, base)
+            let (base, size) = match self.type_from_index(module, base)? {
+                // enums with a character type underlying them hold integers
+                Type::Base { base: Primitive::Char { signed }, size } =>
+                    (Primitive::Int { signed }, size),
+                Type::Base { base, size } if size <= 8 => (base, size),
+                _ => return Err(io::Error::new(io::ErrorKind::Other, "unsupported type")),
+            };
+
+            let enumerators = self.get_type_children(module, type_index)?;
+            let enumerators: io::Result<Vec<_>> = enumerators.iter()
+                .map(|&enumerator| {
+                    let name = self.get_type_name(module, enumerator)?;
+                    let value: TypeValue = self.get_type_info(module, enumerator)?;
+                    Ok(Enumerator::new(name, value.integer()?, base, size))
+                })
+                .collect();
+
+            Ok(Type::Enum { name: name, base: base, size: size, enumerators: enumerators? })
         } else {
             Err(io::Error::new(io::ErrorKind::Other, "unsupported type"))
         }
@@ -345,7 +422,7 @@ impl SymbolHandler {
     ///
     /// The thread should be part of an attached child process which is currently paused to handle
     /// a debug event.
-    pub fn walk_stack(&self, thread: RawHandle) -> io::Result<StackFrames> {
+    pub fn walk_stack(&self, thread: Thread) -> io::Result<StackFrames> {
         unsafe {
             let context = ::get_thread_context(thread, winapi::CONTEXT_FULL)?.into_raw();
 
@@ -410,6 +487,41 @@ impl SymbolHandler {
             if f(symbol, SymbolSize as usize) { winapi::TRUE } else { winapi::FALSE }
         }
     }
+
+    /// Determine where a symbol's value lives at the context's current instruction
+    ///
+    /// `frame` is the context's index in the stack, innermost first. Returns `None` if the
+    /// symbol is optimized out there.
+    pub fn locate_symbol(
+        &self, child: &Child, context: &Context, frame: usize, symbol: &Symbol
+    ) -> io::Result<Option<Location>> {
+        // TODO: locate register-held symbols
+        if !symbol.is_regrel() {
+            return Ok(Some(Location::Address(symbol.address)));
+        }
+
+        // offsets below the frame pointer are stored in two's complement
+        let address = context.frame_pointer().wrapping_add(symbol.address);
+
+        // structs and unions larger than 8 bytes are passed by reference
+        let pc = context.instruction_pointer();
+        let lookup = if frame == 0 { pc } else { pc - 1 };
+        let module = self.module_from_address(lookup)?;
+        let data_type = self.type_from_index(module, symbol.type_index)?;
+        let by_reference = symbol.is_parameter() && symbol.size > 8;
+        match data_type {
+            Type::Struct { .. } | Type::Union { .. } if by_reference => {
+                let mut buffer = [0u8; 8]; // TODO: mem::size_of::<usize>()
+                child.read_memory(address, &mut buffer)?;
+                let address = unsafe { *(buffer.as_ptr() as *const usize) };
+                return Ok(Some(Location::Address(address)));
+            }
+
+            _ => {}
+        }
+
+        Ok(Some(Location::Address(address)))
+    }
 }
 
 impl Drop for SymbolHandler {
@@ -436,6 +548,23 @@ pub struct Symbol {
     pub flags: winapi::ULONG,
 }
 
+impl Symbol {
+    /// Whether this symbol is a function parameter, rather than some other local
+    pub fn is_parameter(&self) -> bool {
+        self.flags & winapi::SYMFLAG_PARAMETER != 0
+    }
+
+    /// Whether this symbol's address is relative to the frame pointer
+    pub fn is_regrel(&self) -> bool {
+        self.flags & winapi::SYMFLAG_REGREL != 0
+    }
+
+    /// Whether this symbol is a function local or parameter, and so only exists in a stack frame
+    pub fn is_local(&self) -> bool {
+        self.flags & (winapi::SYMFLAG_LOCAL | winapi::SYMFLAG_PARAMETER) != 0
+    }
+}
+
 trait DebugProperty { const PROPERTY: winapi::IMAGEHLP_SYMBOL_TYPE_INFO; }
 macro_rules! debug_property {
     ($t: ty, $p: expr) => {
@@ -483,6 +612,9 @@ enum BasicType {
     Bit = 29,
     Bstr = 30,
     Hresult = 31,
+    Char16 = 32,
+    Char32 = 33,
+    Char8 = 34,
 }
 debug_property!(BasicType, winapi::TI_GET_BASETYPE);
 
@@ -491,6 +623,30 @@ debug_property!(BasicType, winapi::TI_GET_BASETYPE);
 struct TypeOffset(winapi::DWORD);
 debug_property!(TypeOffset, winapi::TI_GET_OFFSET);
 
+#[repr(C)]
+#[allow(dead_code)]
+struct BitPosition(winapi::DWORD);
+debug_property!(BitPosition, winapi::TI_GET_BITPOSITION);
+
+#[repr(C)]
+#[allow(dead_code)]
+struct VirtualBaseClass(winapi::BOOL);
+debug_property!(VirtualBaseClass, winapi::TI_GET_VIRTUALBASECLASS);
+
+#[repr(C)]
+#[allow(dead_code)]
+struct DataKind(winapi::DWORD);
+debug_property!(DataKind, winapi::TI_GET_DATAKIND);
+
+const DATA_IS_MEMBER: winapi::DWORD = 7;
+
+#[repr(C)]
+#[allow(dead_code)]
+struct UdtKind(winapi::DWORD);
+debug_property!(UdtKind, winapi::TI_GET_UDTKIND);
+
+const UDT_UNION: winapi::DWORD = 2;
+
 #[repr(C)]
 #[allow(dead_code)]
 struct TypeCount(winapi::DWORD);
@@ -506,6 +662,46 @@ debug_property!(TypeChildren, winapi::TI_GET_CHILDRENCOUNT);
 struct TypeCallingConvention(winapi::DWORD);
 debug_property!(TypeCallingConvention, winapi::TI_GET_CALLING_CONVENTION);
 
+/// A `VARIANT` holding the value of a constant, such as an enumerator
+#[repr(C)]
+#[allow(dead_code)]
+struct TypeValue {
+    vt: winapi::WORD,
+    reserved: [winapi::WORD; 3],
+    data: [u64; 2],
+}
+debug_property!(TypeValue, winapi::TI_GET_VALUE);
+
+impl TypeValue {
+    fn integer(&self) -> io::Result<i64> {
+        const VT_I2: winapi::WORD = 2;
+        const VT_I4: winapi::WORD = 3;
+        const VT_I1: winapi::WORD = 16;
+        const VT_UI1: winapi::WORD = 17;
+        const VT_UI2: winapi::WORD = 18;
+        const VT_UI4: winapi::WORD = 19;
+        const VT_I8: winapi::WORD = 20;
+        const VT_UI8: winapi::WORD = 21;
+        const VT_INT: winapi::WORD = 22;
+        const VT_UINT: winapi::WORD = 23;
+
+        let data = self.data[0];
+        let value = match self.vt {
+            VT_I1 => data as i8 as i64,
+            VT_I2 => data as i16 as i64,
+            VT_I4 | VT_INT => data as i32 as i64,
+            VT_I8 => data as i64,
+            VT_UI1 => data as u8 as i64,
+            VT_UI2 => data as u16 as i64,
+            VT_UI4 | VT_UINT => data as u32 as i64,
+            VT_UI8 => data as i64,
+            _ => return Err(io::Error::new(io::ErrorKind::Other, "unsupported constant")),
+        };
+
+        Ok(value)
+    }
+}
+
 /// The file, line number, and first instruction address of a source line
 pub struct Line {
     pub file: OsString,
@@ -558,6 +754,31 @@ pub struct StackFrame {
     pub stack: winapi::STACKFRAME64,
 }
 
+impl StackFrame {
+    /// The address of the next instruction to execute in this frame
+    pub fn instruction_pointer(&self) -> usize {
+        self.stack.AddrPC.Offset as usize
+    }
+
+    /// The address this frame will return to
+    pub fn return_address(&self) -> usize {
+        self.stack.AddrReturn.Offset as usize
+    }
+
+    pub fn stack_pointer(&self) -> usize {
+        self.stack.AddrStack.Offset as usize
+    }
+
+    pub fn frame_pointer(&self) -> usize {
+        self.stack.AddrFrame.Offset as usize
+    }
+
+    /// The registers as they were when this frame was executing, for reading its locals
+    pub fn context(&self) -> Context {
+        Context::from_raw(self.context)
+    }
+}
+
 impl Iterator for StackFrames {
     type Item = StackFrame;
 
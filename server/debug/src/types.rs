@@ -1,7 +1,7 @@
 use std::mem;
 use std::ffi::OsString;
 
-use SymbolHandler;
+use {SymbolHandler, AsBytes};
 
 /// A symbolic representation of a type in the target program.
 #[derive(Clone, PartialEq, Eq)]
@@ -9,23 +9,79 @@ pub enum Type {
     Base { base: Primitive, size: usize },
     Pointer { type_index: u32 },
     Array { type_index: u32, count: usize },
+    /// A SIMD vector, which is laid out like an array but passed in vector registers
+    Vector { type_index: u32, count: usize },
     Function { calling_convention: u32, type_index: u32, args: Vec<u32> },
-    Struct { name: OsString, size: usize, fields: Vec<Field> },
+    /// A struct or class, with the types of its template's type arguments where debug info
+    /// records them
+    Struct { name: OsString, size: usize, fields: Vec<Field>, type_params: Vec<u32> },
+    Union { name: OsString, size: usize, fields: Vec<Field> },
+    Enum { name: OsString, base: Primitive, size: usize, enumerators: Vec<Enumerator> },
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Primitive {
     Void,
     Bool,
+    /// A character type, whose values are code units the size of the type
+    Char { signed: bool },
     Int { signed: bool },
+    /// An IEEE 754 binary float of half, single, double or quadruple precision
     Float,
+    /// An x87 80-bit extended precision float, padded out to the size of the type
+    Extended,
+    /// A real part followed by an imaginary part, each half the size of the type
+    Complex { extended: bool },
+    /// A value the debugger can't interpret, such as a pointer to member, kept for its size
+    Opaque,
 }
 
+/// A data member or base class subobject of a struct or union
 #[derive(Clone, PartialEq, Eq)]
 pub struct Field {
     pub name: OsString,
     pub type_index: u32,
     pub offset: u32,
+    /// The position of a bitfield, relative to the byte at `offset`
+    pub bits: Option<Bits>,
+    /// Whether this is a base class subobject, named after its type
+    pub base_class: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Bits {
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// A named value of an enum type
+///
+/// Values of unsigned enums are zero-extended to 64 bits, and those of signed enums sign-extended.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Enumerator {
+    pub name: OsString,
+    pub value: i64,
+}
+
+impl Primitive {
+    /// Whether values of this primitive can be `size` bytes
+    ///
+    /// Symbol handlers reject any other sizes as unsupported types.
+    pub fn supports_size(self, size: usize) -> bool {
+        use self::Primitive::*;
+        match (self, size) {
+            (Void, _) | (Opaque, _) | (Bool, 1) => true,
+            (Char { .. }, 1) | (Char { .. }, 2) | (Char { .. }, 4) => true,
+            (Int { .. }, 1) | (Int { .. }, 2) | (Int { .. }, 4) | (Int { .. }, 8) |
+            (Int { .. }, 16) => true,
+            (Float, 2) | (Float, 4) | (Float, 8) | (Float, 16) => true,
+            (Extended, 10) | (Extended, 12) | (Extended, 16) => true,
+            (Complex { extended: false }, 4) | (Complex { extended: false }, 8) |
+            (Complex { extended: false }, 16) | (Complex { extended: false }, 32) => true,
+            (Complex { extended: true }, 24) | (Complex { extended: true }, 32) => true,
+            _ => false,
+        }
+    }
 }
 
 impl Type {
@@ -34,11 +90,108 @@ impl Type {
         match *self {
             Base { size, .. } => size,
             Pointer { .. } | Function { .. } => mem::size_of::<usize>(),
-            Array { type_index, count } => {
-                let element_type = symbols.type_from_index(module, type_index).unwrap();
-                element_type.size(symbols, module) * count
+            // an array of elements whose type can't be read is left empty
+            Array { type_index, count } | Vector { type_index, count } => {
+                symbols.type_from_index(module, type_index)
+                    .map(|element_type| element_type.size(symbols, module) * count)
+                    .unwrap_or(0)
+            }
+            Struct { size, .. } | Union { size, .. } | Enum { size, .. } => size,
+        }
+    }
+}
+
+impl Field {
+    /// Copy this field's value out of the struct containing it
+    ///
+    /// A bitfield is shifted down and sign- or zero-extended to the `size` of its type.
+    pub fn read(&self, data: &[u8], field_type: &Type, size: usize) -> Vec<u8> {
+        let offset = self.offset as usize;
+        let Bits { offset: bit_offset, size: bit_size } = match self.bits {
+            Some(bits) => bits,
+            None => return data[offset..offset + size].to_vec(),
+        };
+
+        let mut value = 0u64;
+        for bit in 0..bit_size {
+            let position = (bit_offset + bit) as usize;
+            if data[offset + position / 8] >> (position % 8) & 1 != 0 {
+                value |= 1 << bit;
+            }
+        }
+
+        let signed = match *field_type {
+            Type::Base { base: Primitive::Int { signed }, .. } |
+            Type::Base { base: Primitive::Char { signed }, .. } |
+            Type::Enum { base: Primitive::Int { signed }, .. } => signed,
+            _ => false,
+        };
+        if signed && bit_size > 0 && bit_size < 64 && value >> (bit_size - 1) != 0 {
+            value |= !0 << bit_size;
+        }
+
+        value.as_bytes()[..size].to_vec()
+    }
+
+    /// Copy a value into this field of the struct containing it
+    ///
+    /// A bitfield only takes as many of the value's low bits as it has room for.
+    pub fn write(&self, data: &mut [u8], value: &[u8]) {
+        let offset = self.offset as usize;
+        let Bits { offset: bit_offset, size: bit_size } = match self.bits {
+            Some(bits) => bits,
+            None => return data[offset..offset + value.len()].copy_from_slice(value),
+        };
+
+        for bit in 0..bit_size as usize {
+            let position = bit_offset as usize + bit;
+            let byte = &mut data[offset + position / 8];
+            let mask = 1 << (position % 8);
+            if bit < 8 * value.len() && value[bit / 8] >> (bit % 8) & 1 != 0 {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
             }
-            Struct { size, .. } => size,
         }
     }
 }
+
+impl Enumerator {
+    /// Create an enumerator of an enum with the given underlying type, truncating its value to
+    /// that type's size and then extending it the same way values of the enum are read
+    pub fn new(name: OsString, value: i64, base: Primitive, size: usize) -> Enumerator {
+        let value = if size == 0 || size >= 8 {
+            value
+        } else {
+            let shift = 64 - 8 * size as u32;
+            match base {
+                Primitive::Int { signed: true } => (value << shift) >> shift,
+                _ => ((value as u64) << shift >> shift) as i64,
+            }
+        };
+
+        Enumerator { name, value }
+    }
+
+    /// Find the enumerators that name a value of their enum
+    ///
+    /// This is either a single enumerator with exactly that value, or a set of flags that OR
+    /// together to make it.
+    pub fn describe(enumerators: &[Enumerator], value: i64) -> Option<Vec<&Enumerator>> {
+        if let Some(enumerator) = enumerators.iter().find(|enumerator| enumerator.value == value) {
+            return Some(vec![enumerator]);
+        }
+
+        let mut flags = vec![];
+        let mut covered = 0;
+        for enumerator in enumerators {
+            let bits = enumerator.value;
+            if bits != 0 && value & bits == bits && bits & !covered != 0 {
+                flags.push(enumerator);
+                covered |= bits;
+            }
+        }
+
+        if !flags.is_empty() && covered == value { Some(flags) } else { None }
+    }
+}
@@ -1,7 +1,5 @@
-use std::{ptr, io, fmt};
-use {Child, SymbolHandler, Symbol, Context, Type, Primitive, Field, AsBytes};
-
-use winapi;
+use std::{io, fmt, cmp, char, f64};
+use {Child, SymbolHandler, Symbol, Context, Type, Primitive, Enumerator, AsBytes};
 
 /// A byte buffer and its type, which describes how to interpret it.
 ///
@@ -30,74 +28,74 @@ impl Value {
         Ok(Value { data, data_type, module })
     }
 
-    /// Read a value via a symbol, which can be a function local or argument.
-    pub fn read_symbol(
-        child: &Child, context: &Context, symbols: &SymbolHandler, symbol: &Symbol
+    /// Read a NUL-terminated string from an absolute address, as an array of its character type.
+    ///
+    /// Reads at most `max_length` code units, stopping after a NUL or before unreadable memory.
+    pub fn read_string(
+        child: &Child, symbols: &SymbolHandler, address: usize, module: usize, type_index: u32,
+        max_length: usize
     ) -> io::Result<Value> {
-        let context = context.as_raw();
-
-        let regrel = symbol.flags & winapi::SYMFLAG_REGREL != 0;
-        let parameter = symbol.flags & winapi::SYMFLAG_PARAMETER != 0;
-        let _register = symbol.flags & winapi::SYMFLAG_REGISTER != 0; // TODO: read from regs?
+        const PAGE_SIZE: usize = 0x1000;
 
-        let module_address = if regrel { context.Rip as usize } else { symbol.address };
-        let module = symbols.module_from_address(module_address)?;
-        let data_type = symbols.type_from_index(module, symbol.type_index)?;
+        let size = symbols.type_from_index(module, type_index)?.size(symbols, module);
+        if size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "zero-sized character type"));
+        }
 
-        let address = if regrel {
-            let address = context.Rbp as usize + symbol.address;
-            if let (&Type::Struct { .. }, true) = (&data_type, parameter && symbol.size > 8) {
-                let mut buffer = [0u8; 8]; // TODO: mem::size_of::<usize>()
-                child.read_memory(address, &mut buffer)?;
-                unsafe { *(buffer.as_ptr() as *const usize) }
-            } else {
-                address
+        // read a page at a time, so a string running into unmapped memory is read up to it
+        let limit = max_length * size;
+        let mut data = vec![];
+        while data.len() < limit {
+            let start = data.len();
+            let address = address + start;
+            let page = PAGE_SIZE - address % PAGE_SIZE;
+            let length = cmp::min(cmp::max(page - page % size, size), limit - start);
+
+            data.resize(start + length, 0);
+            if let Err(e) = child.read_memory(address, &mut data[start..]) {
+                data.truncate(start);
+                if start == 0 {
+                    return Err(e);
+                }
+                break;
             }
-        } else {
-            symbol.address
-        };
 
-        let mut data = vec![0u8; symbol.size];
-        child.read_memory(address, &mut data)?;
+            let end = data[start..].chunks(size).position(|unit| unit.iter().all(|&b| b == 0));
+            if let Some(end) = end {
+                data.truncate(start + (end + 1) * size);
+                break;
+            }
+        }
 
+        let data_type = Type::Array { type_index, count: data.len() / size };
         Ok(Value { data, data_type, module })
     }
 
-    /// Read a value that was returned from a function.
-    pub fn read_return(
-        child: &Child, context: &winapi::CONTEXT, symbols: &SymbolHandler, data_type: Type
-    ) -> io::Result<Value> {
-        let module = symbols.module_from_address(context.Rip as usize)?;
-        let data_size = data_type.size(symbols, module);
-
-        let float = match data_type {
-            Type::Base { base: Primitive::Float, .. } => true,
-            _ => false,
+    /// Read a value via a symbol, which can be a function local or argument.
+    ///
+    /// `frame` is the context's index in the stack, innermost first. Returns `None` if the symbol
+    /// is optimized out at the context's current instruction.
+    pub fn read_symbol(
+        child: &Child, context: &Context, frame: usize, symbols: &SymbolHandler, symbol: &Symbol
+    ) -> io::Result<Option<Value>> {
+        // return addresses point after the call instruction, which may be in the next function
+        let module_address = match (symbol.is_local(), context.instruction_pointer()) {
+            (true, pc) if frame > 0 => pc - 1,
+            (true, pc) => pc,
+            (false, _) => symbol.address,
         };
+        let module = symbols.module_from_address(module_address)?;
+        let data_type = symbols.type_from_index(module, symbol.type_index)?;
 
-        let mut data = vec![0u8; data_size];
-        match data_type {
-            Type::Base { .. } | Type::Pointer { .. } | Type::Struct { .. } if data_size <= 8 => {
-                let source = if !float {
-                    &context.Rax as *const _ as *const u8
-                } else {
-                    &context.FltSave.XmmRegisters[0].Low as *const _ as *const u8
-                };
-                unsafe { ptr::copy(source, data.as_mut_ptr(), data.len()) };
-            }
-
-            Type::Struct { .. } if data_size > 8 => {
-                child.read_memory(context.Rax as usize, &mut data)?;
-            }
+        let location = match symbols.locate_symbol(child, context, frame, symbol)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
 
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other, "cannot return a dynamically sized value"
-                ));
-            }
-        }
+        let mut data = vec![0u8; symbol.size];
+        location.read(child, context, &mut data)?;
 
-        Ok(Value { data, data_type, module })
+        Ok(Some(Value { data, data_type, module }))
     }
 
     pub fn display<'a, 'b>(&'a self, symbols: &'b SymbolHandler) -> ValueDisplay<'a, 'a, 'b> {
@@ -117,36 +115,47 @@ impl<'a, 'b, 'c> fmt::Display for ValueDisplay<'a, 'b, 'c> {
         match *data_type {
             Base { base, size } => {
                 use Primitive::*;
+                let data = &data[..size];
                 match base {
                     Void => write!(fmt, "void"),
+                    Opaque => write!(fmt, "?"),
                     Bool => write!(fmt, "{}", unsafe { *(value as *const bool) }),
-                    Int { signed: true } => match size {
-                        1 => write!(fmt, "{}", unsafe { *(value as *const i8) }),
-                        2 => write!(fmt, "{}", unsafe { *(value as *const i16) }),
-                        4 => write!(fmt, "{}", unsafe { *(value as *const i32) }),
-                        8 => write!(fmt, "{}", unsafe { *(value as *const i64) }),
-                        _ => unreachable!(),
-                    },
-                    Int { signed: false } => match size {
-                        1 => write!(fmt, "{}", unsafe { *(value as *const u8) }),
-                        2 => write!(fmt, "{}", unsafe { *(value as *const u16) }),
-                        4 => write!(fmt, "{}", unsafe { *(value as *const u32) }),
-                        8 => write!(fmt, "{}", unsafe { *(value as *const u64) }),
-                        _ => unreachable!(),
-                    },
-                    Float => match size {
-                        4 => write!(fmt, "{}", unsafe { *(value as *const f32) }),
-                        8 => write!(fmt, "{}", unsafe { *(value as *const f64) }),
-                        _ => unreachable!(),
-                    },
+                    Char { signed } => {
+                        // single bytes are only characters on their own if they're ASCII
+                        let code = format_int(data, signed);
+                        match code.parse().ok().and_then(char::from_u32) {
+                            Some(c) if !c.is_control() && (size > 1 || (c as u32) < 0x80) =>
+                                write!(fmt, "{} {:?}", code, c),
+                            _ => write!(fmt, "{}", code),
+                        }
+                    }
+                    Int { signed } => write!(fmt, "{}", format_int(data, signed)),
+                    Float => write!(fmt, "{}", read_float(data, false)),
+                    Extended => write!(fmt, "{}", read_float(data, true)),
+                    Complex { extended } => {
+                        let (real, imaginary) = data.split_at(size / 2);
+                        let real = read_float(real, extended);
+                        let imaginary = read_float(imaginary, extended);
+                        write!(fmt, "{}{:+}i", real, imaginary)
+                    }
                 }
             }
 
             Pointer { .. } => {
-                write!(fmt, "0x{:x}", unsafe { *(value as *const u64) })
+                let address = unsafe { *(value as *const u64) };
+                write!(fmt, "0x{:x}", address)?;
+
+                // name the function or global variable the pointer points into, if any
+                match symbols.symbol_from_address(address as usize) {
+                    Ok((ref symbol, 0)) => write!(fmt, " <{}>", symbol.name.to_string_lossy()),
+                    Ok((ref symbol, offset)) if offset < symbol.size => {
+                        write!(fmt, " <{}+0x{:x}>", symbol.name.to_string_lossy(), offset)
+                    }
+                    _ => Ok(()),
+                }
             }
 
-            Array { type_index, count } => {
+            Array { type_index, count } | Vector { type_index, count } => {
                 let element_type = symbols.type_from_index(module, type_index)
                     .map_err(|_| fmt::Error)?;
                 let size = element_type.size(symbols, module);
@@ -163,25 +172,369 @@ impl<'a, 'b, 'c> fmt::Display for ValueDisplay<'a, 'b, 'c> {
                 Ok(())
             }
 
-            Struct { ref name, ref fields, .. } => {
+            Struct { ref name, ref fields, .. } | Union { ref name, ref fields, .. } => {
+                let visualizer = match *data_type {
+                    Struct { .. } => symbols.visualizer(name),
+                    _ => None,
+                };
+                let display = visualizer.and_then(|visualizer| {
+                    visualizer.display(data, data_type, None, symbols, module)
+                });
+                if let Some(display) = display {
+                    return write!(fmt, "{}", display);
+                }
+
                 write!(fmt, "{} {{ ", name.to_string_lossy())?;
-                for &Field { ref name, type_index, offset } in fields {
-                    let field_type = symbols.type_from_index(module, type_index)
+                for field in fields {
+                    let field_type = symbols.type_from_index(module, field.type_index)
                         .map_err(|_| fmt::Error)?;
 
-                    let offset = offset as usize;
                     let size = field_type.size(symbols, module);
-                    let data = &data[offset..offset + size];
+                    let data = field.read(data, &field_type, size);
 
-                    let value = ValueDisplay(data, &field_type, symbols, module);
-                    write!(fmt, "{}: {}, ", name.to_string_lossy(), value)?;
+                    let value = ValueDisplay(&data, &field_type, symbols, module);
+                    write!(fmt, "{}: {}, ", field.name.to_string_lossy(), value)?;
                 }
                 write!(fmt, "}}")?;
 
                 Ok(())
             }
 
+            Enum { base: Primitive::Int { signed }, size, ref enumerators, .. } => {
+                let value = read_integer(&data[..size], signed);
+                match Enumerator::describe(enumerators, value) {
+                    Some(flags) => {
+                        for (i, enumerator) in flags.iter().enumerate() {
+                            let prefix = if i == 0 { "" } else { " | " };
+                            write!(fmt, "{}{}", prefix, enumerator.name.to_string_lossy())?;
+                        }
+
+                        Ok(())
+                    }
+
+                    None if signed => write!(fmt, "{}", value),
+                    None => write!(fmt, "{}", value as u64),
+                }
+            }
+
+            Enum { base, size, .. } => {
+                write!(fmt, "{}", ValueDisplay(data, &Base { base, size }, symbols, module))
+            }
+
             _ => write!(fmt, "?"),
         }
     }
 }
+
+/// Read a signed or unsigned integer, extending it to 64 bits
+pub fn read_integer(data: &[u8], signed: bool) -> i64 {
+    let value = data.as_ptr();
+    match (signed, data.len()) {
+        (true, 1) => unsafe { *(value as *const i8) as i64 },
+        (true, 2) => unsafe { *(value as *const i16) as i64 },
+        (true, 4) => unsafe { *(value as *const i32) as i64 },
+        (true, 8) => unsafe { *(value as *const i64) },
+        (false, 1) => unsafe { *(value as *const u8) as i64 },
+        (false, 2) => unsafe { *(value as *const u16) as i64 },
+        (false, 4) => unsafe { *(value as *const u32) as i64 },
+        (false, 8) => unsafe { *(value as *const u64) as i64 },
+        _ => unreachable!(),
+    }
+}
+
+/// Format a signed or unsigned integer of up to 128 bits in decimal
+pub fn format_int(data: &[u8], signed: bool) -> String {
+    let bits = read_bits(data);
+
+    if !signed {
+        return bits.to_string();
+    }
+
+    let width = 8 * data.len() as u32;
+    let bits = if width > 0 && width < 128 && bits >> (width - 1) & 1 != 0 {
+        bits | !0 << width
+    } else {
+        bits
+    };
+    (bits as i128).to_string()
+}
+
+/// Parse a decimal integer into `data`, which must be wide enough to hold it
+pub fn parse_int(text: &str, signed: bool, data: &mut [u8]) -> io::Result<()> {
+    let invalid = |_| io::Error::new(io::ErrorKind::InvalidInput, "invalid integer");
+    let out_of_range = || io::Error::new(io::ErrorKind::InvalidInput, "integer out of range");
+
+    let width = 8 * data.len() as u32;
+    let bits = if signed {
+        let value: i128 = text.trim().parse().map_err(invalid)?;
+        let high = if width > 0 && width < 128 { value >> (width - 1) } else { 0 };
+        if high != 0 && high != -1 {
+            return Err(out_of_range());
+        }
+        value as u128
+    } else {
+        let value: u128 = text.trim().parse().map_err(invalid)?;
+        if width < 128 && value >> width != 0 {
+            return Err(out_of_range());
+        }
+        value
+    };
+
+    let size = data.len();
+    data.copy_from_slice(&bits.as_bytes()[..size]);
+    Ok(())
+}
+
+/// Read a float of any supported size, rounding it to an `f64`
+///
+/// `data` holds an IEEE 754 binary float of 2, 4, 8 or 16 bytes, or if `extended` is set, an
+/// x87 80-bit float followed by any padding.
+pub fn read_float(data: &[u8], extended: bool) -> f64 {
+    let value = data.as_ptr();
+    let (exponent_bits, fraction_bits) = match (extended, data.len()) {
+        (false, 4) => return unsafe { *(value as *const f32) } as f64,
+        (false, 8) => return unsafe { *(value as *const f64) },
+        (false, 2) => (5, 10),
+        (false, 16) => (15, 112),
+        (true, _) => (15, 63),
+        _ => unreachable!(),
+    };
+
+    // unlike the IEEE formats, x87 floats store the leading bit of their significand
+    let mantissa_bits = fraction_bits + extended as u32;
+    let bits = read_bits(&data[..(1 + exponent_bits + mantissa_bits) as usize / 8]);
+    let max_exponent = (1 << exponent_bits) - 1;
+    let bias = max_exponent >> 1;
+
+    let sign = if bits >> (exponent_bits + mantissa_bits) & 1 != 0 { -1.0 } else { 1.0 };
+    let exponent = (bits >> mantissa_bits) as i32 & max_exponent;
+    let fraction = bits & ((1 << fraction_bits) - 1);
+    if exponent == max_exponent {
+        return if fraction == 0 { sign * f64::INFINITY } else { f64::NAN };
+    }
+
+    let significand = if extended {
+        bits & ((1 << mantissa_bits) - 1)
+    } else if exponent != 0 {
+        fraction | 1 << fraction_bits
+    } else {
+        fraction
+    };
+
+    // scale in two steps so neither power of two overflows before the result would
+    let scale = cmp::max(exponent, 1) - bias - fraction_bits as i32;
+    sign * significand as f64 * 2f64.powi(scale / 2) * 2f64.powi(scale - scale / 2)
+}
+
+/// Write an `f64` into a float of any supported size, rounding it to the nearest value
+///
+/// The inverse of `read_float`. Values out of range become infinities.
+pub fn write_float(value: f64, extended: bool, data: &mut [u8]) {
+    let (exponent_bits, fraction_bits) = match (extended, data.len()) {
+        (false, 4) => return data.copy_from_slice((value as f32).as_bytes()),
+        (false, 8) => return data.copy_from_slice(value.as_bytes()),
+        (false, 2) => (5, 10),
+        (false, 16) => (15, 112),
+        (true, _) => (15, 63),
+        _ => unreachable!(),
+    };
+
+    let mantissa_bits = fraction_bits + extended as u32;
+    let max_exponent: u128 = (1 << exponent_bits) - 1;
+    let bias = (max_exponent >> 1) as i32;
+    let integer_bit = (extended as u128) << fraction_bits;
+
+    let magnitude = if value.is_nan() {
+        max_exponent << mantissa_bits | integer_bit | 1 << (fraction_bits - 1)
+    } else if value.is_infinite() {
+        max_exponent << mantissa_bits | integer_bit
+    } else if value == 0.0 {
+        0
+    } else {
+        // normalize to a 64-bit significand with its leading bit set, times 2^(exponent - 63)
+        let magnitude = value.abs();
+        let bits = unsafe { *(&magnitude as *const f64 as *const u64) };
+        let (significand, exponent) = if bits >> 52 == 0 {
+            let shift = bits.leading_zeros();
+            (bits << shift, -1011 - shift as i32)
+        } else {
+            ((bits & ((1 << 52) - 1) | 1 << 52) << 11, (bits >> 52) as i32 - 1023)
+        };
+        let exponent = exponent + bias;
+
+        if extended {
+            (exponent as u128) << mantissa_bits | significand as u128
+        } else {
+            // values below the smallest normal exponent lose precision as denormals
+            let shift = 63 - fraction_bits as i32 + cmp::max(1 - exponent, 0);
+            let significand = if shift <= 0 {
+                (significand as u128) << -shift
+            } else {
+                round_shift(significand as u128, cmp::min(shift as u32, 65))
+            };
+
+            // a significand rounded up past its leading bit carries into the exponent
+            let bits = ((cmp::max(exponent, 1) - 1) as u128) << fraction_bits;
+            cmp::min(bits + significand, max_exponent << mantissa_bits)
+        }
+    };
+
+    let sign = (value.is_sign_negative() as u128) << (exponent_bits + mantissa_bits);
+    let size = data.len();
+    data.copy_from_slice(&(sign | magnitude).as_bytes()[..size]);
+}
+
+/// Format an x87 80-bit float exactly, as a hexadecimal float like `-0x1.8p+3`
+///
+/// `data` may be followed by padding. Infinities and NaNs are left to `read_float`.
+pub fn format_extended(data: &[u8]) -> String {
+    let bits = read_bits(&data[..10]);
+    let sign = if bits >> 79 & 1 != 0 { "-" } else { "" };
+    let significand = bits as u64;
+
+    // the leading bit is explicit, so denormals just have the smallest exponent
+    let exponent = cmp::max((bits >> 64) as i32 & 0x7fff, 1) - 16383;
+    let fraction = significand << 1;
+    if fraction == 0 {
+        format!("{}0x{}p{:+}", sign, significand >> 63, exponent)
+    } else {
+        let digits = format!("{:016x}", fraction);
+        let digits = digits.trim_end_matches('0');
+        format!("{}0x{}.{}p{:+}", sign, significand >> 63, digits, exponent)
+    }
+}
+
+/// Parse a hexadecimal float like `-0x1.8p+3` into an x87 80-bit float followed by any padding
+///
+/// The inverse of `format_extended`. Values with more precision are rounded to nearest.
+pub fn parse_extended(text: &str, data: &mut [u8]) -> io::Result<()> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid hexadecimal float");
+    let out_of_range = || io::Error::new(io::ErrorKind::InvalidInput, "float out of range");
+
+    let text = text.trim();
+    let (negative, text) = match text.as_bytes().first() {
+        Some(&b'-') => (true, &text[1..]),
+        Some(&b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    if !text.starts_with("0x") && !text.starts_with("0X") {
+        return Err(invalid());
+    }
+    let text = &text[2..];
+    let split = text.find(|c| c == 'p' || c == 'P').ok_or_else(invalid)?;
+    let digits = &text[..split];
+    let mut exponent: i64 = text[split + 1..].parse().map_err(|_| invalid())?;
+
+    // the value is `significand * 2^exponent`
+    let mut significand: u128 = 0;
+    let (mut point, mut any) = (false, false);
+    for c in digits.chars() {
+        if c == '.' && !point {
+            point = true;
+            continue;
+        }
+        let digit = c.to_digit(16).ok_or_else(invalid)? as u128;
+        any = true;
+
+        if significand >> 124 == 0 {
+            significand = significand << 4 | digit;
+            if point { exponent -= 4; }
+        } else {
+            // digits past what fits only matter for rounding
+            significand |= (digit != 0) as u128;
+            if !point { exponent += 4; }
+        }
+    }
+    if !any {
+        return Err(invalid());
+    }
+
+    let magnitude = if significand == 0 {
+        0
+    } else {
+        // move the leading bit to the top, then keep 64 bits, or fewer for denormals
+        let zeros = significand.leading_zeros();
+        let significand = significand << zeros;
+        let biased = exponent + 127 - zeros as i64 + 16383;
+        let shift = 64 + cmp::max(1 - biased, 0);
+        let significand = match shift {
+            shift if shift < 128 => round_shift(significand, shift as u32),
+            128 => (significand > 1 << 127) as u128,
+            _ => 0,
+        };
+
+        // a significand rounded up past its 64 bits carries into the exponent
+        let (significand, biased) = if significand >> 64 != 0 {
+            (significand >> 1, cmp::max(biased, 1) + 1)
+        } else if significand >> 63 != 0 {
+            (significand, cmp::max(biased, 1))
+        } else {
+            (significand, 0)
+        };
+        if biased >= 0x7fff {
+            return Err(out_of_range());
+        }
+        (biased as u128) << 64 | significand
+    };
+
+    let sign = (negative as u128) << 79;
+    let size = data.len();
+    data.copy_from_slice(&(sign | magnitude).as_bytes()[..size]);
+    Ok(())
+}
+
+/// Read up to 16 little-endian bytes
+fn read_bits(data: &[u8]) -> u128 {
+    data.iter().enumerate().fold(0, |bits, (i, &byte)| bits | (byte as u128) << (8 * i))
+}
+
+/// Shift a significand right by 1 to 127 bits, rounding to nearest with ties to even
+fn round_shift(significand: u128, shift: u32) -> u128 {
+    let kept = significand >> shift;
+    let rest = significand & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    if rest > half || rest == half && kept & 1 != 0 { kept + 1 } else { kept }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_round_trips_exactly() {
+        let cases = [
+            ("0x1p+0", 1.0),
+            ("-0x1.8p+3", -12.0),
+            ("0x1.0000000000000002p+0", 1.0),
+            ("0x1.fffffffffffffffep+16383", f64::INFINITY),
+            ("0x0.0000000000000002p-16382", 0.0),
+        ];
+        for &(text, approximate) in &cases {
+            let mut data = [0u8; 16];
+            parse_extended(text, &mut data).unwrap();
+            assert_eq!(format_extended(&data), text);
+            assert_eq!(read_float(&data, true), approximate);
+            assert!(data[10..].iter().all(|&byte| byte == 0));
+        }
+    }
+
+    #[test]
+    fn extended_parses_other_forms() {
+        let mut expected = [0u8; 10];
+        write_float(0.1, true, &mut expected);
+
+        let mut data = [0u8; 10];
+        parse_extended("0x1.999999999999ap-4", &mut data).unwrap();
+        assert_eq!(data, expected);
+        parse_extended("0X3.3333333333334P-5", &mut data).unwrap();
+        assert_eq!(data, expected);
+
+        // digits past 64 bits round to nearest
+        parse_extended("0x1.00000000000000018p+0", &mut data).unwrap();
+        assert_eq!(format_extended(&data), "0x1.0000000000000002p+0");
+
+        assert!(parse_extended("0x1p+16384", &mut data).is_err());
+        assert!(parse_extended("1.5", &mut data).is_err());
+        assert!(parse_extended("0xp+0", &mut data).is_err());
+    }
+}
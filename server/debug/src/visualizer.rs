@@ -0,0 +1,832 @@
+use std::{io, mem, cmp, fmt};
+use std::fmt::Write;
+use std::ffi::OsStr;
+use std::collections::HashSet;
+
+use {Child, SymbolHandler, Value, Type, Primitive, AsBytes, read_integer};
+
+/// A user-defined rule for showing a struct by its logical contents
+///
+/// This follows the model of Visual Studio's natvis files. Every string other than `name` is an
+/// expression over the struct's members, written in a small subset of C++: member names, `this`,
+/// `.`, `->`, unary `*`, indexing, integer literals, and `+ - * / %` on integers and pointers.
+#[derive(Clone)]
+pub struct Visualizer {
+    /// The struct name to match, where `*` stands for any text, such as template arguments
+    pub name: String,
+    /// Text shown in place of the struct, with expressions in braces
+    ///
+    /// `{{` and `}}` stand for literal braces. An expression may be followed by `,x` to show an
+    /// integer in hexadecimal; other format specifiers are ignored.
+    pub display: Option<String>,
+    /// How to find the struct's contents
+    pub items: Vec<Items>,
+}
+
+/// One part of a visualizer's expansion
+#[derive(Clone)]
+pub enum Items {
+    /// A single named value
+    Item { name: String, value: String },
+    /// `size` elements, starting at the address held by `pointer`
+    Array { size: String, pointer: String },
+    /// Nodes from the address held by `head`, each followed by the node `next` points to
+    ///
+    /// `next` and `value` are evaluated against each node, and `value` is shown for it. The list
+    /// ends at a null pointer, at `head` again, or after `size` nodes.
+    LinkedList { size: Option<String>, head: String, next: String, value: String },
+    /// An in-order walk of the binary tree whose root `head` points to
+    ///
+    /// `left`, `right` and `value` are evaluated against each node.
+    Tree { size: Option<String>, head: String, left: String, right: String, value: String },
+}
+
+/// The contents of a struct, as found by one of its visualizer's `Items`
+pub enum Expanded {
+    /// A named value, and its type unless an expression computed it as an integer
+    Item { name: String, value: Value, type_index: Option<u32> },
+    /// Elements, in order, or the addresses of those that couldn't be read
+    Elements { elements: Vec<Result<Value, usize>>, type_index: Option<u32>, truncated: bool },
+}
+
+impl Visualizer {
+    /// Whether this visualizer applies to structs named `name`
+    ///
+    /// Whitespace is ignored. PDBs qualify names, while DWARF leaves them to the enclosing
+    /// namespaces, so a pattern's leading namespaces may be left off the name.
+    pub fn matches(&self, name: &OsStr) -> bool {
+        let pattern: String = self.name.chars().filter(|c| !c.is_whitespace()).collect();
+        let name: String = name.to_string_lossy().chars().filter(|c| !c.is_whitespace()).collect();
+
+        glob(pattern.as_bytes(), name.as_bytes()) ||
+            glob(unqualified(&pattern).as_bytes(), name.as_bytes())
+    }
+
+    /// Fill in this visualizer's display string for a struct, if it has one
+    ///
+    /// Without a `child`, expressions can't read memory outside the struct. Expressions that fail
+    /// to evaluate are shown as `?`.
+    pub fn display(
+        &self, data: &[u8], data_type: &Type, child: Option<&Child>, symbols: &SymbolHandler,
+        module: usize
+    ) -> Option<String> {
+        let display = match self.display {
+            Some(ref display) => display,
+            None => return None,
+        };
+
+        let scope = Scope { child, symbols, module, data, data_type, type_index: None };
+        let mut text = String::new();
+        let mut chars = display.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => { chars.next(); text.push('{'); }
+                '}' if chars.peek() == Some(&'}') => { chars.next(); text.push('}'); }
+                '{' => {
+                    let hole: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    let (expression, format) = match hole.rfind(',') {
+                        Some(comma) => (&hole[..comma], hole[comma + 1..].trim()),
+                        None => (&hole[..], ""),
+                    };
+
+                    match scope.evaluate(expression) {
+                        Ok(operand) => { let _ = scope.format(&mut text, operand, format); }
+                        Err(_) => text.push('?'),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+
+        Some(text)
+    }
+
+    /// Evaluate this visualizer's items for a struct
+    ///
+    /// Items whose expressions fail to evaluate are left out. At most `max_elements` elements are
+    /// read from each array, list or tree.
+    pub fn expand(
+        &self, data: &[u8], data_type: &Type, child: &Child, symbols: &SymbolHandler,
+        module: usize, max_elements: usize
+    ) -> Vec<Expanded> {
+        let child = Some(child);
+        let scope = Scope { child, symbols, module, data, data_type, type_index: None };
+        self.items.iter()
+            .filter_map(|items| scope.expand(items, max_elements).ok())
+            .collect()
+    }
+}
+
+/// Match a name against a pattern where `*` stands for any text
+fn glob(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        Some((&b'*', rest)) => (0..name.len() + 1).any(|start| glob(rest, &name[start..])),
+        Some((&c, rest)) => name.first() == Some(&c) && glob(rest, &name[1..]),
+        None => name.is_empty(),
+    }
+}
+
+/// A name without the namespaces before its template arguments
+fn unqualified(name: &str) -> &str {
+    let end = name.find('<').unwrap_or(name.len());
+    match name[..end].rfind("::") {
+        Some(separator) => &name[separator + 2..],
+        None => name,
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn overflow() -> io::Error {
+    invalid("arithmetic overflow in expression")
+}
+
+/// The result of an expression
+enum Operand {
+    /// An integer computed by the expression itself
+    Integer(i64),
+    /// A value copied out of the struct or the target's memory
+    Object { data: Vec<u8>, data_type: Type, type_index: Option<u32> },
+}
+
+/// The struct or node an expression is evaluated against
+struct Scope<'a> {
+    child: Option<&'a Child>,
+    symbols: &'a SymbolHandler,
+    module: usize,
+    data: &'a [u8],
+    data_type: &'a Type,
+    type_index: Option<u32>,
+}
+
+impl<'a> Scope<'a> {
+    fn evaluate(&self, expression: &str) -> io::Result<Operand> {
+        let mut parser = Parser { tokens: tokenize(expression)?, position: 0, scope: self };
+        let operand = parser.additive()?;
+        if parser.position < parser.tokens.len() {
+            return Err(invalid("unexpected token in expression"));
+        }
+
+        Ok(operand)
+    }
+
+    /// Evaluate an expression that should produce an integer, such as a size
+    fn evaluate_size(&self, expression: &str) -> io::Result<usize> {
+        let size = self.integer(self.evaluate(expression)?)?;
+        if size < 0 {
+            return Err(invalid("negative size"));
+        }
+
+        Ok(size as usize)
+    }
+
+    /// Evaluate an expression that should produce a pointer
+    fn evaluate_pointer(&self, expression: &str) -> io::Result<(usize, u32)> {
+        match self.evaluate(expression)? {
+            Operand::Object { ref data, data_type: Type::Pointer { type_index }, .. } => {
+                Ok((read_address(data), type_index))
+            }
+            _ => Err(invalid("expected a pointer")),
+        }
+    }
+
+    fn expand(&self, items: &Items, max_elements: usize) -> io::Result<Expanded> {
+        match *items {
+            Items::Item { ref name, ref value } => {
+                let (value, type_index) = self.value(self.evaluate(value)?)?;
+                Ok(Expanded::Item { name: name.clone(), value, type_index })
+            }
+
+            Items::Array { ref size, ref pointer } => {
+                let size = self.evaluate_size(size)?;
+                let (address, type_index) = self.evaluate_pointer(pointer)?;
+                let element_size = self.size_of(type_index)?;
+                let count = cmp::min(size, max_elements);
+                count.checked_mul(element_size)
+                    .and_then(|length| address.checked_add(length))
+                    .ok_or_else(overflow)?;
+
+                let elements = (0..count)
+                    .map(|i| address + i * element_size)
+                    .map(|address| {
+                        self.read(address, type_index)
+                            .map(|(data, data_type)| Value { data, data_type, module: self.module })
+                            .map_err(|_| address)
+                    })
+                    .collect();
+
+                let type_index = Some(type_index);
+                Ok(Expanded::Elements { elements, type_index, truncated: size > max_elements })
+            }
+
+            Items::LinkedList { ref size, ref head, ref next, ref value } => {
+                let size = match *size {
+                    Some(ref size) => Some(self.evaluate_size(size)?),
+                    None => None,
+                };
+                let (head, node_type) = self.evaluate_pointer(head)?;
+
+                let mut elements = vec![];
+                let mut type_index = None;
+                let mut address = head;
+                let mut truncated = false;
+                while address != 0 && size.map(|size| elements.len() < size).unwrap_or(true) {
+                    if elements.len() == max_elements {
+                        truncated = true;
+                        break;
+                    }
+
+                    let (data, data_type) = match self.read(address, node_type) {
+                        Ok(node) => node,
+                        Err(_) => {
+                            elements.push(Err(address));
+                            break;
+                        }
+                    };
+
+                    let node = self.node(&data, &data_type, node_type);
+                    let (element, element_type) = node.value(node.evaluate(value)?)?;
+                    type_index = type_index.or(element_type);
+                    elements.push(Ok(element));
+
+                    address = node.evaluate_pointer(next)?.0;
+                    if address == head {
+                        break;
+                    }
+                }
+
+                Ok(Expanded::Elements { elements, type_index, truncated })
+            }
+
+            Items::Tree { ref size, ref head, ref left, ref right, ref value } => {
+                let size = match *size {
+                    Some(ref size) => Some(self.evaluate_size(size)?),
+                    None => None,
+                };
+                let limit = cmp::min(size.unwrap_or(max_elements), max_elements);
+                let (root, node_type) = self.evaluate_pointer(head)?;
+
+                let mut elements = vec![];
+                let mut type_index = None;
+                let mut visited = HashSet::new();
+                let mut stack = vec![];
+                let mut address = root;
+                let mut truncated = false;
+                loop {
+                    // descend to the leftmost unvisited node, remembering the way back up
+                    while address != 0 && visited.insert(address) {
+                        match self.read(address, node_type) {
+                            Ok((data, data_type)) => {
+                                let node = self.node(&data, &data_type, node_type);
+                                let left = node.evaluate_pointer(left)?.0;
+                                stack.push((address, data, data_type));
+                                address = left;
+                            }
+                            Err(_) => {
+                                elements.push(Err(address));
+                                address = 0;
+                            }
+                        }
+                    }
+
+                    let (_, data, data_type) = match stack.pop() {
+                        Some(node) => node,
+                        None => break,
+                    };
+                    if elements.len() == limit {
+                        truncated = size.map(|size| size > max_elements).unwrap_or(true);
+                        break;
+                    }
+
+                    let node = self.node(&data, &data_type, node_type);
+                    let (element, element_type) = node.value(node.evaluate(value)?)?;
+                    type_index = type_index.or(element_type);
+                    elements.push(Ok(element));
+
+                    address = node.evaluate_pointer(right)?.0;
+                }
+
+                Ok(Expanded::Elements { elements, type_index, truncated })
+            }
+        }
+    }
+
+    /// A scope for evaluating expressions against a list or tree node
+    fn node<'b>(&self, data: &'b [u8], data_type: &'b Type, type_index: u32) -> Scope<'b>
+        where 'a: 'b
+    {
+        Scope {
+            child: self.child,
+            symbols: self.symbols,
+            module: self.module,
+            data,
+            data_type,
+            type_index: Some(type_index),
+        }
+    }
+
+    /// Convert an operand into a `Value` and its type index, if it has one
+    ///
+    /// The struct itself is rejected, since showing it would visualize it again.
+    fn value(&self, operand: Operand) -> io::Result<(Value, Option<u32>)> {
+        match operand {
+            Operand::Integer(value) => {
+                let data = value.as_bytes().to_vec();
+                let data_type = Type::Base { base: Primitive::Int { signed: true }, size: 8 };
+                Ok((Value { data, data_type, module: self.module }, None))
+            }
+            Operand::Object { ref data_type, .. } if self.type_index.is_none() &&
+                *data_type == *self.data_type => Err(invalid("visualizer refers to itself")),
+            Operand::Object { data, data_type, type_index } => {
+                Ok((Value { data, data_type, module: self.module }, type_index))
+            }
+        }
+    }
+
+    /// Write an operand into a display string
+    fn format(&self, text: &mut String, operand: Operand, format: &str) -> fmt::Result {
+        let integer = match operand {
+            Operand::Object { ref data_type, .. } => is_integer(data_type),
+            Operand::Integer(_) => false,
+        };
+        let operand = if integer {
+            Operand::Integer(self.integer(operand).map_err(|_| fmt::Error)?)
+        } else {
+            operand
+        };
+
+        match operand {
+            Operand::Integer(value) if format == "x" || format == "X" => {
+                write!(text, "0x{:x}", value)
+            }
+            Operand::Integer(value) => write!(text, "{}", value),
+
+            // showing the struct itself through its display string would never end
+            Operand::Object { ref data, ref data_type, .. } if *data_type == *self.data_type => {
+                let fields = match *data_type {
+                    Type::Struct { ref fields, .. } => fields,
+                    _ => return write!(text, "?"),
+                };
+
+                write!(text, "{{ ")?;
+                for field in fields {
+                    let field_type = self.symbols.type_from_index(self.module, field.type_index)
+                        .map_err(|_| fmt::Error)?;
+                    let size = field_type.size(self.symbols, self.module);
+                    let value = Value {
+                        data: field.read(data, &field_type, size),
+                        data_type: field_type,
+                        module: self.module,
+                    };
+                    let name = field.name.to_string_lossy();
+                    write!(text, "{}: {}, ", name, value.display(self.symbols))?;
+                }
+                write!(text, "}}")
+            }
+
+            Operand::Object { data, data_type, .. } => {
+                let value = Value { data, data_type, module: self.module };
+                write!(text, "{}", value.display(self.symbols))
+            }
+        }
+    }
+
+    fn integer(&self, operand: Operand) -> io::Result<i64> {
+        match operand {
+            Operand::Integer(value) => Ok(value),
+            Operand::Object { ref data, ref data_type, .. } => match *data_type {
+                Type::Base { base: Primitive::Int { signed }, size } |
+                Type::Base { base: Primitive::Char { signed }, size } |
+                Type::Enum { base: Primitive::Int { signed }, size, .. } if size <= 8 => {
+                    Ok(read_integer(&data[..size], signed))
+                }
+                Type::Base { base: Primitive::Bool, size } => {
+                    Ok(read_integer(&data[..size], false))
+                }
+                _ => Err(invalid("expected an integer")),
+            },
+        }
+    }
+
+    /// Find a member by name, in a struct's fields, its anonymous members and its base classes
+    fn member(&self, operand: Operand, name: &str) -> io::Result<Operand> {
+        let (data, data_type) = match operand {
+            Operand::Object { data, data_type, .. } => (data, data_type),
+            _ => return Err(invalid("expected a struct")),
+        };
+        let fields = match data_type {
+            Type::Struct { fields, .. } | Type::Union { fields, .. } => fields,
+            _ => return Err(invalid("expected a struct")),
+        };
+
+        for field in &fields {
+            if field.name != *name && !field.base_class && !field.name.is_empty() {
+                continue;
+            }
+
+            let field_type = self.symbols.type_from_index(self.module, field.type_index)?;
+            let size = field_type.size(self.symbols, self.module);
+            if field.offset as usize + size > data.len() {
+                continue;
+            }
+
+            let operand = Operand::Object {
+                data: field.read(&data, &field_type, size),
+                data_type: field_type,
+                type_index: Some(field.type_index),
+            };
+            if field.name == *name {
+                return Ok(operand);
+            }
+            if let Ok(member) = self.member(operand, name) {
+                return Ok(member);
+            }
+        }
+
+        Err(invalid("no such member"))
+    }
+
+    /// Read the element at `index` of an array or a pointer
+    fn index(&self, operand: Operand, index: i64) -> io::Result<Operand> {
+        match operand {
+            Operand::Object { data_type: Type::Pointer { type_index }, data, .. } => {
+                let size = self.size_of(type_index)? as i64;
+                let offset = index.checked_mul(size).ok_or_else(overflow)?;
+                let address = offset_address(read_address(&data), offset)?;
+                let (data, data_type) = self.read(address, type_index)?;
+                Ok(Operand::Object { data, data_type, type_index: Some(type_index) })
+            }
+
+            Operand::Object { data_type: Type::Array { type_index, count }, data, .. } => {
+                if index < 0 || index as usize >= count {
+                    return Err(invalid("array index out of bounds"));
+                }
+
+                let data_type = self.symbols.type_from_index(self.module, type_index)?;
+                let size = data_type.size(self.symbols, self.module);
+                let offset = index as usize * size;
+                let data = data[offset..offset + size].to_vec();
+                Ok(Operand::Object { data, data_type, type_index: Some(type_index) })
+            }
+
+            _ => Err(invalid("expected a pointer or array")),
+        }
+    }
+
+    /// Add an integer to a pointer, or two integers
+    fn add(&self, left: Operand, right: Operand, sign: i64) -> io::Result<Operand> {
+        match (left, right) {
+            (Operand::Object { data_type: Type::Pointer { type_index }, data, .. }, offset) => {
+                let size = self.size_of(type_index)? as i64;
+                let offset = self.integer(offset)?.checked_mul(sign * size).ok_or_else(overflow)?;
+                let address = offset_address(read_address(&data), offset)?;
+                Ok(pointer(address, type_index))
+            }
+
+            (left, right) => {
+                let (left, right) = (self.integer(left)?, self.integer(right)?);
+                right.checked_mul(sign)
+                    .and_then(|right| left.checked_add(right))
+                    .map(Operand::Integer)
+                    .ok_or_else(overflow)
+            }
+        }
+    }
+
+    /// Subtract an integer from a pointer, two integers, or two pointers to find the number of
+    /// elements between them
+    fn subtract(&self, left: Operand, right: Operand) -> io::Result<Operand> {
+        match (left, right) {
+            (
+                Operand::Object { data_type: Type::Pointer { type_index }, data: ref left, .. },
+                Operand::Object { data_type: Type::Pointer { .. }, data: ref right, .. }
+            ) => {
+                let size = self.size_of(type_index)? as i64;
+                let (left, right) = (read_address(left) as i64, read_address(right) as i64);
+                let difference = left.wrapping_sub(right);
+                Ok(Operand::Integer(difference / size))
+            }
+
+            (left, right) => self.add(left, right, -1),
+        }
+    }
+
+    fn deref(&self, operand: Operand) -> io::Result<Operand> {
+        match operand {
+            // `this` is held as the struct it points to, so `*this` and `this->` are that struct
+            Operand::Object { data_type: Type::Struct { .. }, .. } => Ok(operand),
+            operand => self.index(operand, 0),
+        }
+    }
+
+    /// The struct or node itself
+    fn this(&self) -> Operand {
+        Operand::Object {
+            data: self.data.to_vec(),
+            data_type: self.data_type.clone(),
+            type_index: self.type_index,
+        }
+    }
+
+    fn size_of(&self, type_index: u32) -> io::Result<usize> {
+        let size = self.symbols.type_from_index(self.module, type_index)?
+            .size(self.symbols, self.module);
+
+        // pointers to `void` are stepped through a byte at a time
+        Ok(cmp::max(size, 1))
+    }
+
+    fn read(&self, address: usize, type_index: u32) -> io::Result<(Vec<u8>, Type)> {
+        let child = match self.child {
+            Some(child) => child,
+            None => return Err(invalid("no process to read from")),
+        };
+
+        let value = Value::read_pointer(child, self.symbols, address, self.module, type_index)?;
+        Ok((value.data, value.data_type))
+    }
+}
+
+fn pointer(address: usize, type_index: u32) -> Operand {
+    Operand::Object {
+        data: address.as_bytes().to_vec(),
+        data_type: Type::Pointer { type_index },
+        type_index: None,
+    }
+}
+
+/// Move an address by a signed number of bytes, failing if it leaves the address space
+fn offset_address(address: usize, offset: i64) -> io::Result<usize> {
+    let address = if offset < 0 {
+        address.checked_sub(offset.wrapping_neg() as u64 as usize)
+    } else {
+        address.checked_add(offset as usize)
+    };
+    address.ok_or_else(overflow)
+}
+
+fn read_address(data: &[u8]) -> usize {
+    read_integer(&data[..mem::size_of::<usize>()], false) as usize
+}
+
+fn is_integer(data_type: &Type) -> bool {
+    match *data_type {
+        Type::Base { base: Primitive::Int { .. }, size } |
+        Type::Base { base: Primitive::Char { .. }, size } => size <= 8,
+        Type::Base { base: Primitive::Bool, .. } => true,
+        _ => false,
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(text: &str) -> io::Result<Vec<Token>> {
+    const SYMBOLS: &[&str] = &["->", ".", "*", "/", "%", "+", "-", "(", ")", "[", "]"];
+
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length = if c.is_digit(10) {
+            let length = rest.find(|c: char| !c.is_alphanumeric()).unwrap_or(rest.len());
+            let digits = rest[..length].trim_end_matches(|c: char| "uUlL".contains(c));
+            let number = if digits.starts_with("0x") || digits.starts_with("0X") {
+                i64::from_str_radix(&digits[2..], 16)
+            } else {
+                digits.parse()
+            };
+            tokens.push(Token::Number(number.map_err(|_| invalid("invalid integer literal"))?));
+            length
+        } else if c.is_alphabetic() || c == '_' {
+            let length = rest.find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..length].to_string()));
+            length
+        } else {
+            match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                Some(&symbol) => {
+                    tokens.push(Token::Symbol(symbol));
+                    symbol.len()
+                }
+                None => return Err(invalid("unsupported expression syntax")),
+            }
+        };
+
+        rest = rest[length..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// A recursive descent parser that evaluates expressions as it goes
+struct Parser<'a, 'b: 'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    scope: &'a Scope<'b>,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: &str) -> io::Result<()> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            _ => Err(invalid("unexpected token in expression")),
+        }
+    }
+
+    fn name(&mut self) -> io::Result<String> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(name),
+            _ => Err(invalid("expected a member name")),
+        }
+    }
+
+    fn additive(&mut self) -> io::Result<Operand> {
+        let mut left = self.multiplicative()?;
+        loop {
+            left = match self.peek() {
+                Some(&Token::Symbol("+")) => {
+                    self.next();
+                    let right = self.multiplicative()?;
+                    self.scope.add(left, right, 1)?
+                }
+                Some(&Token::Symbol("-")) => {
+                    self.next();
+                    let right = self.multiplicative()?;
+                    self.scope.subtract(left, right)?
+                }
+                _ => return Ok(left),
+            };
+        }
+    }
+
+    fn multiplicative(&mut self) -> io::Result<Operand> {
+        let mut left = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some(&Token::Symbol(s)) if s == "*" || s == "/" || s == "%" => s,
+                _ => return Ok(left),
+            };
+            self.next();
+
+            let left_value = self.scope.integer(left)?;
+            let right = self.scope.integer(self.unary()?)?;
+            let value = match operator {
+                "*" => left_value.checked_mul(right),
+                _ if right == 0 => return Err(invalid("division by zero")),
+                "/" => left_value.checked_div(right),
+                _ => left_value.checked_rem(right),
+            };
+            left = Operand::Integer(value.ok_or_else(overflow)?);
+        }
+    }
+
+    fn unary(&mut self) -> io::Result<Operand> {
+        match self.peek() {
+            Some(&Token::Symbol("*")) => {
+                self.next();
+                let operand = self.unary()?;
+                self.scope.deref(operand)
+            }
+            Some(&Token::Symbol("-")) => {
+                self.next();
+                let operand = self.unary()?;
+                let value = self.scope.integer(operand)?.checked_neg().ok_or_else(overflow)?;
+                Ok(Operand::Integer(value))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> io::Result<Operand> {
+        let mut operand = self.primary()?;
+        loop {
+            operand = match self.peek() {
+                Some(&Token::Symbol(".")) => {
+                    self.next();
+                    let name = self.name()?;
+                    self.scope.member(operand, &name)?
+                }
+                Some(&Token::Symbol("->")) => {
+                    self.next();
+                    let name = self.name()?;
+                    let operand = self.scope.deref(operand)?;
+                    self.scope.member(operand, &name)?
+                }
+                Some(&Token::Symbol("[")) => {
+                    self.next();
+                    let index = self.additive()?;
+                    let index = self.scope.integer(index)?;
+                    self.expect("]")?;
+                    self.scope.index(operand, index)?
+                }
+                _ => return Ok(operand),
+            };
+        }
+    }
+
+    fn primary(&mut self) -> io::Result<Operand> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Operand::Integer(value)),
+            Some(Token::Name(ref name)) if name == "this" => Ok(self.scope.this()),
+            Some(Token::Name(name)) => self.scope.member(self.scope.this(), &name),
+            Some(Token::Symbol("(")) => {
+                let operand = self.additive()?;
+                self.expect(")")?;
+                Ok(operand)
+            }
+            _ => Err(invalid("unexpected token in expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use {SymbolHandler, Type};
+    use super::*;
+
+    #[test]
+    fn tokenize_literals_and_symbols() {
+        let tokens = tokenize(" a->b[0x1f] - 10UL*c.d ").unwrap();
+        assert!(tokens == vec![
+            Token::Name("a".to_string()), Token::Symbol("->"), Token::Name("b".to_string()),
+            Token::Symbol("["), Token::Number(0x1f), Token::Symbol("]"), Token::Symbol("-"),
+            Token::Number(10), Token::Symbol("*"), Token::Name("c".to_string()),
+            Token::Symbol("."), Token::Name("d".to_string()),
+        ]);
+
+        assert!(tokenize("a & b").is_err());
+        assert!(tokenize("12abc").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    fn evaluate(expression: &str) -> io::Result<i64> {
+        let symbols = SymbolHandler::empty();
+        let name = OsStr::new("S").to_os_string();
+        let data_type = Type::Struct { name, size: 0, fields: vec![], type_params: vec![] };
+        let scope = Scope {
+            child: None, symbols: &symbols, module: 0, data: &[], data_type: &data_type,
+            type_index: None,
+        };
+        scope.evaluate(expression).and_then(|operand| scope.integer(operand))
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn precedence_and_grouping() {
+        assert_eq!(evaluate("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(evaluate("10 - 4 - 3").unwrap(), 3);
+        assert_eq!(evaluate("17 / 5 % 2").unwrap(), 1);
+        assert_eq!(evaluate("-3 * -(2 + 1)").unwrap(), 9);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn malformed_expressions() {
+        assert!(evaluate("").is_err());
+        assert!(evaluate("1 +").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("1 2").is_err());
+        assert!(evaluate("missing").is_err());
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("1 % 0").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn overflow_is_an_error() {
+        assert!(evaluate("0x7fffffffffffffff + 1").is_err());
+        assert!(evaluate("0x7fffffffffffffff * 2").is_err());
+        assert!(evaluate("-0x7fffffffffffffff - 2").is_err());
+        assert!(evaluate("(-0x7fffffffffffffff - 1) / -1").is_err());
+        assert!(evaluate("-(-0x7fffffffffffffff - 1)").is_err());
+        assert_eq!(evaluate("-0x7fffffffffffffff - 1").unwrap(), i64::min_value());
+    }
+
+    #[test]
+    fn offset_addresses() {
+        assert_eq!(offset_address(0x1000, -0x10).unwrap(), 0xff0);
+        assert_eq!(offset_address(0x1000, 0x10).unwrap(), 0x1010);
+        assert!(offset_address(0x10, -0x20).is_err());
+        assert!(offset_address(usize::max_value(), 1).is_err());
+        assert!(offset_address(0, i64::min_value()).is_err());
+    }
+}
@@ -2,23 +2,82 @@
 
 use std::collections::HashMap;
 
-#[derive(Deserialize)]
+#[derive(Default, Deserialize)]
 pub struct Launch {
-    /// Command line arguments
-    pub args: String,
+    /// Command line arguments, not including the program itself
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// How `env` combines with the server's environment
+    #[serde(default, rename = "envMode")]
+    pub env_mode: EnvMode,
     /// Environment variables
-    pub env: String,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Working directory, or the server's if `None`
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Contents of stdin, or the server's stdin if `None`
+    #[serde(default)]
+    pub stdin: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub enum EnvMode {
+    /// Only the server's environment, ignoring `env`
+    #[serde(rename = "inherit")]
+    Inherit,
+    /// Only `env`
+    #[serde(rename = "clear")]
+    Clear,
+    /// The server's environment, overridden by `env`
+    #[serde(rename = "merge")]
+    Merge,
+}
+
+impl Default for EnvMode {
+    fn default() -> EnvMode { EnvMode::Clear }
 }
 
 #[derive(Deserialize)]
 pub struct Call {
     pub arguments: HashMap<usize, Value>,
+    /// Which of the functions it calls to trace as well, or none if `None`
+    #[serde(default)]
+    pub callees: Option<Callees>,
 }
 
-#[derive(Serialize)]
+/// The callees of a traced function that are traced along with it
+///
+/// Only functions in the target's own executable are traced. If any `files` or `modules` are
+/// given, only the functions defined in one of them are.
+#[derive(Clone, Deserialize)]
+pub struct Callees {
+    /// How many calls deep to trace, counting the called function's own callees as depth 1, or no
+    /// limit if `None`
+    #[serde(default)]
+    pub depth: Option<u32>,
+    /// Source files whose functions are traced, matched against the ends of their paths
+    #[serde(default)]
+    pub files: Vec<String>,
+    /// Source directories whose functions are traced, including those in subdirectories
+    #[serde(default)]
+    pub modules: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
 pub struct Process {
     pub id: u32,
     pub name: String,
+    #[serde(rename = "parentId")]
+    pub parent_id: u32,
+    #[serde(rename = "commandLine")]
+    pub command_line: Option<String>,
+    pub path: Option<String>,
+    pub owner: Option<String>,
+    /// Milliseconds since the Unix epoch
+    #[serde(rename = "startTime")]
+    pub start_time: Option<u64>,
+    pub attachable: bool,
 }
 
 #[derive(Serialize)]
@@ -44,7 +103,7 @@ pub struct DebugInfo {
     pub attached_process: Process,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Function {
     pub address: usize,
     pub name: String,
@@ -60,7 +119,7 @@ pub struct Function {
     pub locals: Vec<Variable>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Variable {
     pub name: String,
     #[serde(rename = "sType")]
@@ -84,6 +143,12 @@ pub enum Type {
         type_index: u32,
         count: usize,
     },
+    #[serde(rename = "vector")]
+    Vector {
+        #[serde(rename = "sType")]
+        type_index: u32,
+        count: usize,
+    },
     #[serde(rename = "function")]
     Function {
         #[serde(rename = "callingConvention")]
@@ -94,6 +159,10 @@ pub enum Type {
     },
     #[serde(rename = "struct")]
     Struct { name: String, size: usize, fields: Vec<Field> },
+    #[serde(rename = "union")]
+    Union { name: String, size: usize, fields: Vec<Field> },
+    #[serde(rename = "enum")]
+    Enum { name: String, base: Primitive, size: usize, enumerators: Vec<Enumerator> },
 }
 
 #[derive(Serialize)]
@@ -102,12 +171,22 @@ pub enum Primitive {
     Void,
     #[serde(rename = "bool")]
     Bool,
+    #[serde(rename = "char")]
+    Char,
+    #[serde(rename = "uchar")]
+    Uchar,
     #[serde(rename = "int")]
     Int,
     #[serde(rename = "uint")]
     Uint,
     #[serde(rename = "float")]
     Float,
+    #[serde(rename = "extended")]
+    Extended,
+    #[serde(rename = "complex")]
+    Complex,
+    #[serde(rename = "opaque")]
+    Opaque,
 }
 
 #[derive(Serialize)]
@@ -116,6 +195,18 @@ pub struct Field {
     #[serde(rename = "sType")]
     pub type_index: u32,
     pub offset: u32,
+    #[serde(rename = "bitOffset")]
+    pub bit_offset: Option<u32>,
+    #[serde(rename = "bitSize")]
+    pub bit_size: Option<u32>,
+    #[serde(rename = "baseClass")]
+    pub base_class: bool,
+}
+
+#[derive(Serialize)]
+pub struct Enumerator {
+    pub name: String,
+    pub value: i64,
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
@@ -125,23 +216,160 @@ pub enum Value {
     Boolean(bool),
     Integer(i64),
     Number(f64),
+    String(String),
+    Unavailable(Unavailable),
+    Visualized(Visualized),
+    Text(Text),
     Array(Vec<Value>),
     Struct(HashMap<u32, Value>),
 }
 
+/// Why a variable or pointed-to value has no contents to show
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state")]
+pub enum Unavailable {
+    /// Every byte matches the debug runtime's fill pattern
+    #[serde(rename = "uninitialized")]
+    Uninitialized,
+    /// Reading the memory failed, at a decimal address if the value lives in memory
+    #[serde(rename = "unreadable")]
+    Unreadable {
+        #[serde(default)]
+        address: Option<String>,
+    },
+    /// The compiler left the variable with no location at this instruction
+    #[serde(rename = "optimizedOut")]
+    OptimizedOut,
+    /// The value's type is one the debugger can't show, such as a pointer to member
+    #[serde(rename = "unsupported")]
+    Unsupported,
+}
+
+/// What a pointer's address points into
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum PointerTarget {
+    #[serde(rename = "null")]
+    Null,
+    /// Code, with the function's identifier if the address is its entry point
+    #[serde(rename = "function")]
+    Function {
+        #[serde(rename = "sFunction")]
+        function: Option<usize>,
+        name: String,
+        offset: usize,
+    },
+    /// A global or static variable, or unnamed static data such as a string literal
+    #[serde(rename = "global")]
+    Global {
+        name: Option<String>,
+        offset: usize,
+    },
+    /// A stack frame, counted from the innermost, and the local it falls in if any
+    #[serde(rename = "stack")]
+    Stack {
+        frame: usize,
+        #[serde(rename = "sFunction")]
+        function: Option<usize>,
+        #[serde(rename = "functionName")]
+        function_name: Option<String>,
+        variable: Option<String>,
+        offset: usize,
+    },
+    /// Readable memory outside the stack and the loaded modules
+    #[serde(rename = "heap")]
+    Heap,
+    #[serde(rename = "unmapped")]
+    Unmapped,
+}
+
+/// A standard library container or smart pointer, or a struct with a user-defined visualizer,
+/// shown by its logical contents
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Visualized {
+    /// The kind of container, such as "vector", "string" or "map", or "custom"
+    pub visualizer: String,
+    /// The element type, the mapped type of maps, or the pointed-to type, if known
+    #[serde(rename = "sType", default)]
+    pub type_index: Option<u32>,
+    #[serde(rename = "keyType", default)]
+    pub key_type_index: Option<u32>,
+    /// A user-defined visualizer's display string
+    #[serde(default)]
+    pub display: Option<String>,
+    /// A user-defined visualizer's named values
+    #[serde(default)]
+    pub items: Vec<Item>,
+    /// An array of elements or `[key, value]` entries, text, or a pointer
+    pub contents: Box<Value>,
+    /// Whether the elements were cut off at the element limit
+    #[serde(default)]
+    pub truncated: bool,
+    /// The value as the struct it's laid out as
+    pub raw: Box<Value>,
+}
+
+/// A named value found by a user-defined visualizer
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub name: String,
+    /// The value's type, unless the visualizer computed it as an integer
+    #[serde(rename = "sType", default)]
+    pub type_index: Option<u32>,
+    pub value: Value,
+}
+
+/// The contents of a character array, or of memory pointed to by a character pointer
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Text {
+    /// Decoded up to the first NUL, with invalid code units replaced
+    pub text: String,
+    /// The raw code units, if they aren't valid UTF-8, UTF-16 or UTF-32
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
+    /// Whether the text was cut off at the length limit
+    #[serde(default)]
+    pub truncated: bool,
+}
+
 #[derive(Serialize)]
 pub struct Breakpoint {
     #[serde(rename = "sFunction")]
     pub function: usize,
 }
 
-#[derive(Serialize)]
+/// A pointer shown as an array of `length` elements, instead of the single value it points to
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArrayView {
+    pub pointer: VariablePath,
+    pub length: ArrayLength,
+}
+
+/// A variable, or a field nested inside it
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariablePath {
+    /// The variable's `address`
+    pub variable: usize,
+    /// Indices into the fields of nested structs, as in `Value`s
+    #[serde(default)]
+    pub fields: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ArrayLength {
+    Constant(usize),
+    /// An integer variable or field, read at each line
+    Variable(VariablePath),
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Execution {
     pub id: i32,
     pub data: ExecutionData,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(tag = "eType")]
 pub enum ExecutionData {
     #[serde(rename = "process")]
@@ -164,14 +392,22 @@ pub struct Trace {
 #[serde(tag = "tType")]
 pub enum TraceData {
     #[serde(rename = "line")]
-    Line { state: HashMap<usize, Value> },
+    Line {
+        state: HashMap<usize, Value>,
+        pointers: HashMap<usize, PointerTarget>,
+        loops: Vec<LoopIteration>,
+    },
     #[serde(rename = "call")]
     Call {
         #[serde(rename = "sFunction")]
         function: usize,
     },
     #[serde(rename = "return")]
-    Return { value: Value, data: HashMap<usize, Value> },
+    Return {
+        value: Value,
+        data: HashMap<usize, Value>,
+        pointers: HashMap<usize, PointerTarget>,
+    },
     #[serde(rename = "break")]
     Break {
         #[serde(rename = "nextExecution")]
@@ -182,11 +418,171 @@ pub enum TraceData {
     #[serde(rename = "cancel")]
     Cancel,
     #[serde(rename = "crash")]
-    Crash { stack: String },
+    Crash { exception: Exception, stack: Vec<Frame> },
+    #[serde(rename = "output")]
+    Output { stream: Stream, text: String },
     #[serde(rename = "error")]
     Error { error: Error },
 }
 
+/// A loop a line trace is in, and which of its iterations
+#[derive(Serialize)]
+pub struct LoopIteration {
+    #[serde(rename = "loop")]
+    pub loop_id: usize,
+    pub iteration: usize,
+}
+
+/// A loop found in a function's trace
+#[derive(Serialize)]
+pub struct Loop {
+    pub id: usize,
+    /// The line jumped back to at the start of each iteration
+    pub line: u32,
+    /// The furthest line jumped back from
+    #[serde(rename = "lineEnd")]
+    pub line_end: u32,
+    /// The loop this one is nested in
+    pub parent: Option<usize>,
+    pub iterations: Vec<Iteration>,
+}
+
+#[derive(Serialize)]
+pub struct Iteration {
+    /// Counted from 1 each time the loop is entered
+    pub iteration: usize,
+    /// Trace indices of the iteration's first and last lines
+    pub start: i32,
+    pub end: i32,
+    /// Parameters and locals as of the iteration's last line, keyed by their `address`
+    pub values: HashMap<usize, Value>,
+}
+
+/// Everything known at one step of a recorded trace
+#[derive(Serialize)]
+pub struct TraceState {
+    pub index: i32,
+    pub line: u32,
+    /// The latest value of every variable and pointed-to value, keyed by address
+    pub state: HashMap<usize, Value>,
+    /// What each pointer in `state` points into, keyed by address
+    pub pointers: HashMap<usize, PointerTarget>,
+}
+
+/// A step of a recorded trace at which a variable or pointed-to value took a new value
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub index: i32,
+    pub line: u32,
+    pub value: Value,
+}
+
+/// How two executions of the same function differ, with their line traces aligned by the lines
+/// they ran
+#[derive(Serialize)]
+pub struct ExecutionDiff {
+    /// Runs of lines only one execution ran, between lines both ran
+    pub divergences: Vec<Divergence>,
+    /// Aligned steps at which a parameter or local held different values
+    pub differences: Vec<StepDifference>,
+    /// The return values, if they differ
+    #[serde(rename = "returnValue")]
+    pub return_value: Option<ValueDifference>,
+    /// The values pointed to by the return values or the arguments that differ, paired up by
+    /// following the same pointers in each execution and keyed by the left one's address, or the
+    /// right one's if only it has the value
+    #[serde(rename = "returnData")]
+    pub return_data: HashMap<usize, ValueDifference>,
+}
+
+#[derive(Serialize)]
+pub struct Divergence {
+    pub left: Vec<Step>,
+    pub right: Vec<Step>,
+}
+
+#[derive(Serialize)]
+pub struct Step {
+    pub index: i32,
+    pub line: u32,
+}
+
+#[derive(Serialize)]
+pub struct StepDifference {
+    /// Trace index in each execution
+    pub left: i32,
+    pub right: i32,
+    pub line: u32,
+    /// Parameters and locals that differ, keyed by their `address`
+    pub variables: HashMap<usize, ValueDifference>,
+}
+
+/// A value in each execution, or `None` where one has no value
+#[derive(Serialize)]
+pub struct ValueDifference {
+    pub left: Option<Value>,
+    pub right: Option<Value>,
+}
+
+#[derive(Serialize)]
+pub struct Exception {
+    pub code: u32,
+    pub name: String,
+    /// Address of the faulting instruction
+    pub address: usize,
+    pub fault: Fault,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "fType")]
+pub enum Fault {
+    #[serde(rename = "accessViolation")]
+    AccessViolation { access: Option<Access>, address: usize },
+    #[serde(rename = "divideByZero")]
+    DivideByZero,
+    #[serde(rename = "stackOverflow")]
+    StackOverflow,
+    #[serde(rename = "other")]
+    Other,
+}
+
+#[derive(Serialize)]
+pub enum Access {
+    #[serde(rename = "read")]
+    Read,
+    #[serde(rename = "write")]
+    Write,
+    #[serde(rename = "execute")]
+    Execute,
+}
+
+#[derive(Serialize)]
+pub struct Frame {
+    #[serde(rename = "sFunction")]
+    pub function: Option<usize>,
+    #[serde(rename = "functionName")]
+    pub function_name: Option<String>,
+    pub address: usize,
+    #[serde(rename = "sourcePath")]
+    pub source_path: Option<String>,
+    pub line: Option<u32>,
+    /// Parameters and locals, along with the values they point to
+    pub data: HashMap<usize, Value>,
+    /// What each pointer in `data` points into, keyed by address
+    pub pointers: HashMap<usize, PointerTarget>,
+}
+
+#[derive(Serialize)]
+pub enum Stream {
+    #[serde(rename = "stdout")]
+    Stdout,
+    #[serde(rename = "stderr")]
+    Stderr,
+    /// Strings passed to `OutputDebugString`
+    #[serde(rename = "debug")]
+    Debug,
+}
+
 #[derive(Serialize)]
 pub struct Error {
     pub message: String,
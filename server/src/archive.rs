@@ -0,0 +1,324 @@
+use std::{io, env, fs};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+
+use serde_json;
+
+use child::{self, ServerMessage, DebugMessage};
+use timeline::Timeline;
+use loops::LoopTracker;
+use api;
+
+/// Finished executions, kept on disk so their traces can be replayed after the live run
+///
+/// Each debug session gets a directory of its own, holding four files per execution:
+/// `:id.execution.json` describes it, `:id.trace.json` is the array of traces exactly as it was
+/// streamed, `:id.timeline.json` indexes the traces by step, and `:id.symbols.json` holds the
+/// functions and types those traces refer to, so they can still be described once the session has
+/// ended.
+pub struct Archive {
+    directory: PathBuf,
+}
+
+/// The functions and types referred to by an archived execution, keyed by their identifiers
+///
+/// Types are read back as plain JSON, which is all the server needs to send them on.
+#[derive(Serialize)]
+struct Symbols<'a> {
+    functions: &'a HashMap<usize, api::Function>,
+    types: &'a HashMap<u32, api::Type>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedSymbols {
+    functions: HashMap<String, api::Function>,
+    types: HashMap<String, serde_json::Value>,
+}
+
+/// A trace being streamed, collected for the archive
+pub struct Recording {
+    execution: api::Execution,
+    traces: Vec<u8>,
+    timeline: Timeline,
+    loops: LoopTracker,
+    functions: HashSet<usize>,
+    /// How many calls deep the trace is, counting the traced function itself
+    depth: usize,
+}
+
+impl Archive {
+    /// Use the directory named by `SPICE_ARCHIVE`, or `archive` in the working directory
+    ///
+    /// The directory is created when the first execution is saved.
+    pub fn open() -> Archive {
+        let directory = env::var_os("SPICE_ARCHIVE")
+            .map(PathBuf::from)
+            .unwrap_or(PathBuf::from("archive"));
+
+        Archive { directory }
+    }
+
+    /// The lowest debug session id with nothing archived at or above it
+    ///
+    /// Sessions are numbered from here, so a restarted server doesn't reuse the ids of sessions
+    /// whose executions are still in the archive.
+    pub fn next_session(&self) -> usize {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(_) => return 0,
+        };
+
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str().and_then(|name| name.parse().ok()))
+            .map(|session: usize| session + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The archived executions of a debug session, in the order they ran
+    pub fn executions(&self, debug_id: usize) -> io::Result<Vec<api::Execution>> {
+        let entries = fs::read_dir(self.session(debug_id))
+            .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "no such session"))?;
+
+        let mut executions = vec![];
+        for entry in entries {
+            let name = entry?.file_name();
+            let id = name.to_str()
+                .and_then(|name| name.split('.').next())
+                .and_then(|id| id.parse().ok());
+            if let (Some(id), true) = (id, name.to_string_lossy().ends_with(".execution.json")) {
+                executions.push(self.execution(debug_id, id)?);
+            }
+        }
+
+        executions.sort_by_key(|execution| execution.id);
+        Ok(executions)
+    }
+
+    pub fn execution(&self, debug_id: usize, id: i32) -> io::Result<api::Execution> {
+        let file = self.open_file(debug_id, id, "execution")?;
+        serde_json::from_reader(file).map_err(invalid)
+    }
+
+    /// The archived traces of an execution, as the JSON array that was streamed
+    pub fn trace(&self, debug_id: usize, id: i32) -> io::Result<Vec<u8>> {
+        let mut trace = vec![];
+        self.open_file(debug_id, id, "trace")?.read_to_end(&mut trace)?;
+        Ok(trace)
+    }
+
+    /// The index over an archived execution's traces
+    pub fn timeline(&self, debug_id: usize, id: i32) -> io::Result<Timeline> {
+        let file = self.open_file(debug_id, id, "timeline")?;
+        serde_json::from_reader(file).map_err(invalid)
+    }
+
+    /// Describe a function from any of the session's archived executions
+    pub fn function(&self, debug_id: usize, address: usize) -> io::Result<api::Function> {
+        let key = address.to_string();
+        for execution in self.executions(debug_id)? {
+            let mut symbols = self.symbols(debug_id, execution.id)?;
+            if let Some(function) = symbols.functions.remove(&key) {
+                return Ok(function);
+            }
+        }
+
+        Err(io::Error::new(io::ErrorKind::NotFound, "no such function"))
+    }
+
+    /// Describe types from the session's archived executions
+    pub fn types(
+        &self, debug_id: usize, ids: &[u32]
+    ) -> io::Result<HashMap<u32, serde_json::Value>> {
+        let mut types = HashMap::new();
+        for execution in self.executions(debug_id)? {
+            let mut symbols = self.symbols(debug_id, execution.id)?;
+            for &id in ids {
+                if let Some(data_type) = symbols.types.remove(&id.to_string()) {
+                    types.insert(id, data_type);
+                }
+            }
+        }
+
+        if types.len() < ids.len() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such type"));
+        }
+        Ok(types)
+    }
+
+    /// Archive a finished execution, first asking its debug thread to describe the functions it
+    /// refers to and the types they use
+    ///
+    /// A debug thread that has already stopped leaves those descriptions out, but the execution and
+    /// its traces are still archived.
+    pub fn save(&self, child: &child::Thread, recording: Recording) -> io::Result<()> {
+        let Recording { execution, mut traces, mut timeline, loops, functions: addresses, .. } =
+            recording;
+        traces.extend_from_slice(b"\n]");
+        timeline.set_loops(loops.finish());
+
+        let mut functions = HashMap::new();
+        let mut pending = vec![];
+        for address in addresses {
+            let function = match request(child, ServerMessage::DescribeFunction { address }) {
+                Some(DebugMessage::Function(function)) => function,
+                Some(DebugMessage::Error(_)) | None => continue,
+                _ => unreachable!(),
+            };
+
+            pending.push(function.type_index);
+            pending.extend(function.parameters.iter().map(|variable| variable.type_index));
+            pending.extend(function.locals.iter().map(|variable| variable.type_index));
+            functions.insert(address, function);
+        }
+
+        // follow the types those refer to, one level at a time
+        let mut types = HashMap::new();
+        while !pending.is_empty() {
+            pending.sort();
+            pending.dedup();
+            pending.retain(|type_index| !types.contains_key(type_index));
+
+            let listed = list_types(child, pending.drain(..).collect());
+            for (type_index, data_type) in listed {
+                pending.extend(referenced_types(&data_type));
+                types.insert(type_index, data_type);
+            }
+        }
+
+        let directory = self.session(child.session);
+        fs::create_dir_all(&directory)?;
+        let id = execution.id;
+        let symbols = Symbols { functions: &functions, types: &types };
+        self.create_file(child.session, id, "symbols")?
+            .write_all(&serde_json::to_vec(&symbols).unwrap())?;
+        self.create_file(child.session, id, "trace")?.write_all(&traces)?;
+        self.create_file(child.session, id, "timeline")?
+            .write_all(&serde_json::to_vec(&timeline).unwrap())?;
+
+        // the execution file goes last, so only complete executions are listed
+        self.create_file(child.session, id, "execution")?
+            .write_all(&serde_json::to_vec(&execution).unwrap())?;
+        Ok(())
+    }
+
+    fn session(&self, debug_id: usize) -> PathBuf {
+        self.directory.join(debug_id.to_string())
+    }
+
+    fn path(&self, debug_id: usize, id: i32, kind: &str) -> PathBuf {
+        self.session(debug_id).join(format!("{}.{}.json", id, kind))
+    }
+
+    fn open_file(&self, debug_id: usize, id: i32, kind: &str) -> io::Result<fs::File> {
+        fs::File::open(self.path(debug_id, id, kind))
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "no such execution"))
+    }
+
+    fn create_file(&self, debug_id: usize, id: i32, kind: &str) -> io::Result<fs::File> {
+        fs::File::create(self.path(debug_id, id, kind))
+    }
+
+    fn symbols(&self, debug_id: usize, id: i32) -> io::Result<ArchivedSymbols> {
+        let file = self.open_file(debug_id, id, "symbols")?;
+        serde_json::from_reader(file).map_err(invalid)
+    }
+}
+
+impl Recording {
+    pub fn new(execution: api::Execution) -> Recording {
+        let mut functions = HashSet::new();
+        if let api::ExecutionData::Function { function } = execution.data {
+            functions.insert(function);
+        }
+
+        Recording {
+            execution,
+            traces: b"[\n".to_vec(),
+            timeline: Timeline::new(),
+            loops: LoopTracker::new(),
+            functions,
+            depth: 0,
+        }
+    }
+
+    /// Follow the next line of the traced function, returning the loops it is in
+    pub fn loops(&mut self, index: i32, line: u32) -> Vec<api::LoopIteration> {
+        self.loops.step(index, line)
+    }
+
+    /// Add a trace, returning it as JSON to be streamed
+    pub fn push(&mut self, trace: &api::Trace) -> &[u8] {
+        match trace.data {
+            api::TraceData::Call { function } => {
+                self.functions.insert(function);
+                self.depth += 1;
+            }
+            api::TraceData::Crash { ref stack, .. } => {
+                self.functions.extend(stack.iter().filter_map(|frame| frame.function));
+            }
+            _ => (),
+        }
+        self.timeline.push(trace, self.depth > 1);
+        if let api::TraceData::Return { .. } = trace.data {
+            self.depth = self.depth.saturating_sub(1);
+        }
+
+        if self.traces.len() > 2 {
+            self.traces.extend_from_slice(b",\n");
+        }
+        let start = self.traces.len();
+        serde_json::to_writer(&mut self.traces, trace).unwrap();
+        &self.traces[start..]
+    }
+}
+
+/// List types, skipping any that can't be described
+fn list_types(child: &child::Thread, types: Vec<u32>) -> HashMap<u32, api::Type> {
+    if types.is_empty() {
+        return HashMap::new();
+    }
+
+    match request(child, ServerMessage::ListTypes { types: types.clone() }) {
+        Some(DebugMessage::Types(listed)) => listed,
+
+        // the whole list fails along with any one type, so try them individually
+        Some(DebugMessage::Error(_)) if types.len() > 1 => {
+            types.into_iter()
+                .flat_map(|type_index| list_types(child, vec![type_index]))
+                .collect()
+        }
+        Some(DebugMessage::Error(_)) | None => HashMap::new(),
+
+        _ => unreachable!(),
+    }
+}
+
+/// Send a message to the debug thread and wait for its reply, unless the thread has stopped
+fn request(child: &child::Thread, message: ServerMessage) -> Option<DebugMessage> {
+    match child.tx.send(message) {
+        Ok(()) => child.rx.recv().ok(),
+        Err(_) => None,
+    }
+}
+
+fn referenced_types(data_type: &api::Type) -> Vec<u32> {
+    match *data_type {
+        api::Type::Pointer { type_index } |
+        api::Type::Array { type_index, .. } |
+        api::Type::Vector { type_index, .. } => vec![type_index],
+        api::Type::Function { type_index, ref parameters, .. } => {
+            Some(type_index).into_iter().chain(parameters.iter().cloned()).collect()
+        }
+        api::Type::Struct { ref fields, .. } | api::Type::Union { ref fields, .. } => {
+            fields.iter().map(|field| field.type_index).collect()
+        }
+        api::Type::Base { .. } | api::Type::Enum { .. } => vec![],
+    }
+}
+
+fn invalid<E: ToString>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
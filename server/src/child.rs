@@ -1,22 +1,23 @@
 ///! This module implements the core debug loop.
 ///!
-///! It runs on its own thread, which is the only thread the Win32 API allows to call its debugging
-///! APIs. It is generally in one of two states:
+///! It runs on its own thread, which is the only thread the platform debugging APIs (Win32 debug
+///! events, ptrace) allow to control the target. It is generally in one of two states:
 ///! - Waiting for commands or queries, with the target process paused
 ///! - Streaming an execution trace, while the target process runs
+///!
+///! Everything here is written against `debug::Debugger`, so it does not know which OS it runs on.
 
-use std::{io, mem};
-use std::sync::Arc;
+use std::{io, mem, str};
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
-use std::sync::mpsc::{sync_channel, SyncSender, Receiver};
+use std::sync::mpsc::{sync_channel, SyncSender, Receiver, TrySendError};
 use std::cell::RefCell;
 use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
 use std::thread::{self, JoinHandle};
-use std::os::windows::io::RawHandle;
+use std::time::Duration;
 
-use winapi;
-use debug;
+use debug::{self, Debugger};
 
 use trace::*;
 use value;
@@ -55,6 +56,7 @@ pub enum DebugMessage {
     Breakpoints(Vec<usize>),
     Breakpoint,
     BreakpointRemoved,
+    Arrays(Vec<api::ArrayView>),
     Executing,
     Trace(DebugTrace),
     Error(io::Error),
@@ -62,14 +64,15 @@ pub enum DebugMessage {
 
 /// Events that occur while the target process is running
 pub enum DebugTrace {
-    Line(u32, HashMap<usize, api::Value>),
+    Line(u32, HashMap<usize, api::Value>, HashMap<usize, api::PointerTarget>),
     Call(u32, usize),
-    Return(u32, api::Value, HashMap<usize, api::Value>),
+    Return(u32, api::Value, HashMap<usize, api::Value>, HashMap<usize, api::PointerTarget>),
 
     Breakpoint(usize),
     Exit(u32),
     Cancel,
-    Crash(String),
+    Crash(api::Exception, Vec<api::Frame>),
+    Output(u32, api::Stream, String),
 }
 
 /// Messages from the server threads to the debug thread representing commands and queries
@@ -80,9 +83,15 @@ pub enum ServerMessage {
     ListBreakpoints,
     SetBreakpoint { address: usize },
     ClearBreakpoint { address: usize },
+    ListArrays { address: usize },
+    SetArrays { address: usize, arrays: Vec<api::ArrayView> },
     Continue,
-    CallFunction { address: usize, arguments: HashMap<usize, api::Value> },
-    Trace,
+    CallFunction {
+        address: usize,
+        arguments: HashMap<usize, api::Value>,
+        callees: Option<api::Callees>,
+    },
+    Trace { string_length: usize, fill: Option<u8> },
     Quit,
 }
 
@@ -90,24 +99,29 @@ lazy_static! {
     static ref SESSION: AtomicUsize = ATOMIC_USIZE_INIT;
 }
 
+/// Number debug sessions from `first`, before any are started
+pub fn start_sessions_at(first: usize) {
+    SESSION.store(first, Ordering::Relaxed);
+}
+
 impl Thread {
     /// Start a new debug thread by launching a binary
-    pub fn launch(path: PathBuf) -> (Thread, Arc<AtomicBool>) {
+    pub fn launch(path: PathBuf, launch: api::Launch) -> (Thread, Arc<AtomicBool>) {
         Thread::spawn(move |debug_tx, server_rx, cancel| {
-            let child = debug::Command::new(&path)
-                .env_clear()
-                .debug()?;
+            let mut command = debug::Command::new(&path);
+            configure_command(&mut command, launch);
+            let debugger = debug::Native::launch(&mut command)?;
 
-            run(child, debug_tx, server_rx, cancel, true)
+            run(debugger, debug_tx, server_rx, cancel, true)
         })
     }
 
     /// Start a new debug thread by attaching to a running process
     pub fn attach(pid: u32) -> (Thread, Arc<AtomicBool>) {
         Thread::spawn(move |debug_tx, server_rx, cancel| {
-            let child = debug::Child::attach(pid)?;
+            let debugger = debug::Native::attach(pid)?;
 
-            run(child, debug_tx, server_rx, cancel, false)
+            run(debugger, debug_tx, server_rx, cancel, false)
         })
     }
 
@@ -147,17 +161,96 @@ impl Thread {
     }
 }
 
+/// Apply a client's launch options to a `debug::Command`
+fn configure_command(command: &mut debug::Command, launch: api::Launch) {
+    command.capture_output();
+
+    for arg in launch.args {
+        command.arg(arg);
+    }
+
+    let env = match launch.env_mode {
+        api::EnvMode::Inherit => HashMap::new(),
+        api::EnvMode::Clear => {
+            command.env_clear();
+            launch.env
+        }
+        api::EnvMode::Merge => launch.env,
+    };
+    for (key, value) in env {
+        command.env(key, value);
+    }
+
+    if let Some(cwd) = launch.cwd {
+        command.current_dir(cwd);
+    }
+    if let Some(stdin) = launch.stdin {
+        command.input(stdin);
+    }
+}
+
 /// State shared across recursive invocations of `trace_function` by multiple `TraceGuard`s.
 /// It thus must be accessed via immutable reference, unlike `DebugState`.
 ///
 /// `BreakpointSet` has interior mutability for enabling and disabling breakpoints.
-struct TargetState {
-    child: debug::Child,
-    symbols: debug::SymbolHandler,
+struct TargetState<D> {
+    debugger: D,
     module: usize,
 
+    /// The limit on code units read for text, set by the current trace
+    string_length: usize,
+    /// The byte pattern of uninitialized memory, if any, set by the current trace
+    fill: Option<u8>,
+
     breakpoints: BreakpointSet,
     traces: HashMap<usize, BreakpointSet>,
+    /// Array views of each function's pointers, kept across executions
+    arrays: HashMap<usize, Vec<api::ArrayView>>,
+
+    /// The functions traced along with the called function, set by the current execution
+    callees: Callees,
+
+    /// What the process prints, shared with the thread that forwards it during a trace
+    output: Arc<Mutex<Output>>,
+}
+
+/// The callees of a called function to trace along with it
+///
+/// Their breakpoints are only set while the function is traced, so they are kept apart from
+/// `breakpoints` and `traces`. None of them are breakpointed functions, which are always traced.
+#[derive(Default)]
+struct Callees {
+    /// Which callees to trace, as given with the call
+    options: Option<api::Callees>,
+    /// Breakpoints on the callees' entry points
+    entries: BreakpointSet,
+    /// Per-line breakpoints of each callee, as in `TargetState::traces`
+    traces: HashMap<usize, BreakpointSet>,
+}
+
+/// Output from the target process, on its way to the trace being streamed
+///
+/// Both the debug thread and an `OutputReader` forward output, so it is read and sent with this
+/// locked to keep it in order.
+struct Output {
+    capture: Option<debug::Output>,
+    /// The line output is attributed to, as of the last debug event
+    line: u32,
+    /// The start of a UTF-8 sequence split between chunks, for each stream
+    partial: HashMap<debug::Stream, Vec<u8>>,
+    /// Output read while the server wasn't ready to receive it
+    pending: VecDeque<DebugTrace>,
+}
+
+/// Forwards output from its own thread while a trace runs, so it arrives as it is printed rather
+/// than with the next debug event
+///
+/// The server only receives trace messages while streaming a trace, so this must be dropped
+/// before the debug thread takes its next command. Until then, output the server isn't ready for
+/// is left for the debug thread to send.
+struct OutputReader {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
 }
 
 /// State accessed by mutable reference, mostly from `trace_default`, unlike `TargetState`.
@@ -166,10 +259,12 @@ struct TargetState {
 /// performing a sequence of operations that can fail. That way, the event is not lost and other
 /// operations can be attempted.
 struct DebugState {
-    threads: HashMap<winapi::DWORD, RawHandle>,
+    threads: HashMap<u32, debug::Thread>,
     execution: Option<ExecutionState>,
     event: Option<debug::Event>,
     last_call: Option<usize>,
+    /// A callee breakpoint being stepped over, to be set again after the next single step
+    last_callee: Option<usize>,
 }
 
 /// The internal dual of `Execution`, `ExecutionState` holds the state necessary to detect function
@@ -179,7 +274,7 @@ enum ExecutionState {
 
     Function {
         call: debug::Call,
-        thread: RawHandle,
+        thread: debug::Thread,
 
         entry: usize,
         exit: usize,
@@ -190,22 +285,25 @@ enum ExecutionState {
 /// The main debug thread flow.
 ///
 /// Runs the target process up to its entry point, then begins processing commands and queries.
-fn run(
-    child: debug::Child, tx: SyncSender<DebugMessage>, rx: Receiver<ServerMessage>,
+fn run<D: Debugger>(
+    debugger: D, tx: SyncSender<DebugMessage>, rx: Receiver<ServerMessage>,
     cancel: Arc<AtomicBool>, launch: bool
 ) -> io::Result<()> {
-    let options = debug::SymbolHandler::get_options();
-    debug::SymbolHandler::set_options(winapi::SYMOPT_DEBUG | winapi::SYMOPT_LOAD_LINES | options);
-
-    let symbols = debug::SymbolHandler::initialize(&child)?;
-
+    let output = Output::new(debugger.child().output());
     let mut target = TargetState {
-        child: child,
-        symbols: symbols,
+        debugger: debugger,
         module: 0,
 
+        string_length: value::DEFAULT_STRING_LENGTH,
+        fill: value::DEFAULT_FILL,
+
         breakpoints: BreakpointSet::new(),
         traces: HashMap::new(),
+        arrays: HashMap::new(),
+
+        callees: Callees::default(),
+
+        output: Arc::new(Mutex::new(output)),
     };
 
     let mut state = DebugState {
@@ -213,17 +311,21 @@ fn run(
         execution: None,
         event: None,
         last_call: None,
+        last_callee: None,
     };
 
+    // visualizer files are read for each session, so they can be edited without a restart
+    target.debugger.symbols().add_visualizers(value::visualizer::load());
+
     let mut last_thread;
 
-    let mut event = debug::Event::wait_event()?;
+    let mut event = target.debugger.wait_event()?;
     let start_address = if let debug::EventInfo::CreateProcess {
         ref file, main_thread, base, start_address, ..
     } = event.info {
         let _ = file.as_ref()
             .ok_or(io::Error::new(io::ErrorKind::Other, "no file handle for CreateProcess"))
-            .and_then(|file| target.symbols.load_module(file, base));
+            .and_then(|file| target.debugger.symbols().load_module(file, base));
 
         target.module = base;
 
@@ -239,12 +341,16 @@ fn run(
     // This runs past the ntdll built-in "attach" breakpoint, to our own breakpoint set at the
     // process entry point.
 
-    let breakpoint = if launch { Some(target.child.set_breakpoint(start_address)?) } else { None };
+    let breakpoint = if launch {
+        Some(target.debugger.child().set_breakpoint(start_address)?)
+    } else {
+        None
+    };
     let thread;
     loop {
         event.continue_event(true)?;
 
-        event = debug::Event::wait_event()?;
+        event = target.debugger.wait_event()?;
         state.event = Some(event);
 
         match trace_default(&target, &mut state, &tx, &cancel, None, &mut true, true)? {
@@ -265,15 +371,15 @@ fn run(
     // restore the instruction pointer to the entry point
 
     if let Some(breakpoint) = breakpoint {
-        let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+        let mut context = target.debugger.get_context(thread)?;
 
-        target.child.remove_breakpoint(breakpoint)?;
+        target.debugger.child().remove_breakpoint(breakpoint)?;
         context.set_instruction_pointer(start_address);
 
-        debug::set_thread_context(thread, &context)?;
+        target.debugger.set_context(thread, &context)?;
     }
 
-    tx.send(DebugMessage::Attached(target.child.get_cancel())).unwrap();
+    tx.send(DebugMessage::Attached(target.debugger.child().get_cancel())).unwrap();
 
     // main message loop
 
@@ -321,25 +427,42 @@ fn run(
                 tx.send(message).unwrap();
             }
 
+            ServerMessage::ListArrays { address } => {
+                let arrays = target.arrays.get(&address).cloned().unwrap_or(vec![]);
+                tx.send(DebugMessage::Arrays(arrays)).unwrap();
+            }
+
+            ServerMessage::SetArrays { address, arrays } => {
+                let message = set_arrays(&mut target, address, arrays)
+                    .map(DebugMessage::Arrays)
+                    .unwrap_or_else(DebugMessage::Error);
+                tx.send(message).unwrap();
+            }
+
             ServerMessage::Continue => {
+                target.callees = Callees::default();
                 let message = continue_process(&mut state)
                     .map(|()| DebugMessage::Executing)
                     .unwrap_or_else(DebugMessage::Error);
                 tx.send(message).unwrap();
             }
 
-            ServerMessage::CallFunction { address, arguments } => {
+            ServerMessage::CallFunction { address, arguments, callees } => {
                 let thread = last_thread;
                 breakpoint_added = !target.breakpoints.contains_key(&address);
+                target.callees = Callees { options: callees, ..Callees::default() };
                 let message = call_function(&mut target, &mut state, thread, address, arguments)
                     .map(|()| DebugMessage::Executing)
                     .unwrap_or_else(DebugMessage::Error);
                 tx.send(message).unwrap();
             }
 
-            ServerMessage::Trace => {
+            ServerMessage::Trace { string_length, fill } => {
                 assert!(state.event.is_none());
+                target.string_length = string_length;
+                target.fill = fill;
 
+                let reader = OutputReader::start(target.output.clone(), tx.clone());
                 let result = match state.execution.take() {
                     Some(ex @ ExecutionState::Process) => {
                         trace_process(&target, &mut state, &tx, &cancel, ex)
@@ -352,7 +475,16 @@ fn run(
                         };
 
                         last_thread = thread;
-                        trace_function(&target, &mut state, &tx, &cancel, ex, 0)
+                        let result = set_callees(&mut target).and_then(|()| {
+                            let child = target.debugger.child();
+                            let mut entries = TraceGuard::guard(child, &target.callees.entries);
+                            entries.enable_all()?;
+
+                            trace_function(&target, &mut state, &tx, &cancel, ex, 0, 0)
+                        });
+                        target.callees = Callees::default();
+
+                        result
                             .and_then(|_| if breakpoint_added {
                                 breakpoint_added = false;
                                 remove_breakpoint(&mut target, entry)
@@ -363,6 +495,7 @@ fn run(
 
                     None => Err(io::Error::from(io::ErrorKind::NotFound)),
                 };
+                drop(reader);
 
                 if let Err(e) = result {
                     tx.send(DebugMessage::Error(e)).unwrap();
@@ -370,7 +503,7 @@ fn run(
             }
 
             ServerMessage::Quit => {
-                let _ = target.child.terminate();
+                let _ = target.debugger.terminate();
                 break;
             }
         }
@@ -379,8 +512,8 @@ fn run(
     Ok(())
 }
 
-fn list_functions(target: &TargetState) -> io::Result<Vec<api::Function>> {
-    let TargetState { ref symbols, .. } = *target;
+fn list_functions<D: Debugger>(target: &TargetState<D>) -> io::Result<Vec<api::Function>> {
+    let symbols = target.debugger.symbols();
 
     let mut functions = vec![];
     symbols.enumerate_globals(|symbol, _| {
@@ -395,8 +528,10 @@ fn list_functions(target: &TargetState) -> io::Result<Vec<api::Function>> {
     Ok((functions))
 }
 
-fn describe_function(target: &TargetState, address: usize) -> io::Result<api::Function> {
-    let TargetState { ref symbols, .. } = *target;
+fn describe_function<D: Debugger>(
+    target: &TargetState<D>, address: usize
+) -> io::Result<api::Function> {
+    let symbols = target.debugger.symbols();
 
     let (function, _) = symbols.symbol_from_address(address)?;
     let module = symbols.module_from_address(address)?;
@@ -412,7 +547,7 @@ fn describe_function(target: &TargetState, address: usize) -> io::Result<api::Fu
 
     let mut parameters = vec![];
     symbols.enumerate_locals(address, |symbol, _| {
-        if symbol.flags & winapi::SYMFLAG_PARAMETER != 0 {
+        if symbol.is_parameter() {
             let name = symbol.name.to_string_lossy().into();
             let type_index = symbol.type_index;
             let address = symbol.address;
@@ -430,7 +565,7 @@ fn describe_function(target: &TargetState, address: usize) -> io::Result<api::Fu
     let mut locals = HashMap::new();
     for line in symbols.lines_from_symbol(&function)? {
         symbols.enumerate_locals(line.address, |symbol, _| {
-            if symbol.flags & winapi::SYMFLAG_PARAMETER == 0 {
+            if !symbol.is_parameter() {
                 let name = symbol.name.clone();
                 locals.entry(symbol.address).or_insert((symbol.type_index, name));
             }
@@ -456,46 +591,57 @@ fn describe_function(target: &TargetState, address: usize) -> io::Result<api::Fu
     })
 }
 
-fn list_types(target: &TargetState, types: Vec<u32>) -> io::Result<HashMap<u32, api::Type>> {
-    let TargetState { ref symbols, module, .. } = *target;
+fn list_types<D: Debugger>(
+    target: &TargetState<D>, types: Vec<u32>
+) -> io::Result<HashMap<u32, api::Type>> {
+    let symbols = target.debugger.symbols();
+    let module = target.module;
 
     let types: io::Result<_> = types.into_iter()
         .map(|type_index| {
             let data_type = match symbols.type_from_index(module, type_index)? {
                 debug::Type::Base { base, size } => {
-                    let base = match base {
-                        debug::Primitive::Void => api::Primitive::Void,
-                        debug::Primitive::Bool => api::Primitive::Bool,
-                        debug::Primitive::Int { signed: true } => api::Primitive::Int,
-                        debug::Primitive::Int { signed: false } => api::Primitive::Uint,
-                        debug::Primitive::Float => api::Primitive::Float,
-                    };
-
-                    api::Type::Base { base, size }
+                    api::Type::Base { base: api::Primitive::from(base), size }
                 }
 
                 debug::Type::Pointer { type_index } => api::Type::Pointer { type_index },
 
                 debug::Type::Array { type_index, count } => api::Type::Array { type_index, count },
 
+                debug::Type::Vector { type_index, count } =>
+                    api::Type::Vector { type_index, count },
+
                 debug::Type::Function { calling_convention, type_index, args } =>
                     api::Type::Function { calling_convention, type_index, parameters: args },
 
-                debug::Type::Struct { name, size, fields } => {
-                    let fields = fields.into_iter()
-                        .map(|debug::Field { name, type_index, offset }| {
-                            api::Field {
-                                name: name.to_string_lossy().into(),
-                                type_index,
-                                offset,
-                            }
+                debug::Type::Struct { name, size, fields, .. } => {
+                    api::Type::Struct {
+                        name: name.to_string_lossy().into(),
+                        size,
+                        fields: fields.into_iter().map(api::Field::from).collect(),
+                    }
+                }
+
+                debug::Type::Union { name, size, fields } => {
+                    api::Type::Union {
+                        name: name.to_string_lossy().into(),
+                        size,
+                        fields: fields.into_iter().map(api::Field::from).collect(),
+                    }
+                }
+
+                debug::Type::Enum { name, base, size, enumerators } => {
+                    let enumerators = enumerators.into_iter()
+                        .map(|debug::Enumerator { name, value }| {
+                            api::Enumerator { name: name.to_string_lossy().into(), value }
                         })
                         .collect();
 
-                    api::Type::Struct {
+                    api::Type::Enum {
                         name: name.to_string_lossy().into(),
+                        base: api::Primitive::from(base),
                         size,
-                        fields,
+                        enumerators,
                     }
                 }
             };
@@ -507,8 +653,40 @@ fn list_types(target: &TargetState, types: Vec<u32>) -> io::Result<HashMap<u32,
     Ok(types?)
 }
 
-fn set_breakpoint(target: &mut TargetState, address: usize) -> io::Result<()> {
-    let TargetState { ref child, ref symbols, ref mut breakpoints, ref mut traces, .. } = *target;
+impl From<debug::Primitive> for api::Primitive {
+    fn from(base: debug::Primitive) -> api::Primitive {
+        match base {
+            debug::Primitive::Void => api::Primitive::Void,
+            debug::Primitive::Bool => api::Primitive::Bool,
+            debug::Primitive::Char { signed: true } => api::Primitive::Char,
+            debug::Primitive::Char { signed: false } => api::Primitive::Uchar,
+            debug::Primitive::Int { signed: true } => api::Primitive::Int,
+            debug::Primitive::Int { signed: false } => api::Primitive::Uint,
+            debug::Primitive::Float => api::Primitive::Float,
+            debug::Primitive::Extended => api::Primitive::Extended,
+            debug::Primitive::Complex { .. } => api::Primitive::Complex,
+            debug::Primitive::Opaque => api::Primitive::Opaque,
+        }
+    }
+}
+
+impl From<debug::Field> for api::Field {
+    fn from(field: debug::Field) -> api::Field {
+        api::Field {
+            name: field.name.to_string_lossy().into(),
+            type_index: field.type_index,
+            offset: field.offset,
+            bit_offset: field.bits.map(|bits| bits.offset),
+            bit_size: field.bits.map(|bits| bits.size),
+            base_class: field.base_class,
+        }
+    }
+}
+
+fn set_breakpoint<D: Debugger>(target: &mut TargetState<D>, address: usize) -> io::Result<()> {
+    let TargetState { ref debugger, ref mut breakpoints, ref mut traces, .. } = *target;
+    let child = debugger.child();
+    let symbols = debugger.symbols();
 
     let (function, offset) = symbols.symbol_from_address(address)?;
     if offset > 0 {
@@ -532,8 +710,9 @@ fn set_breakpoint(target: &mut TargetState, address: usize) -> io::Result<()> {
     Ok(())
 }
 
-fn remove_breakpoint(target: &mut TargetState, address: usize) -> io::Result<()> {
-    let TargetState { ref child, ref mut breakpoints, ref mut traces, .. } = *target;
+fn remove_breakpoint<D: Debugger>(target: &mut TargetState<D>, address: usize) -> io::Result<()> {
+    let TargetState { ref debugger, ref mut breakpoints, ref mut traces, .. } = *target;
+    let child = debugger.child();
 
     let breakpoint = breakpoints.remove(&address)
         .ok_or(io::Error::new(io::ErrorKind::NotFound, "no such breakpoint"))?;
@@ -545,6 +724,137 @@ fn remove_breakpoint(target: &mut TargetState, address: usize) -> io::Result<()>
     Ok(())
 }
 
+/// Find the callees to trace along with the called function, and their lines
+///
+/// Functions whose calls can't be captured, or that have no line info, are left out.
+fn set_callees<D: Debugger>(target: &mut TargetState<D>) -> io::Result<()> {
+    let TargetState { ref debugger, module, ref breakpoints, ref mut callees, .. } = *target;
+    let symbols = debugger.symbols();
+    let Callees { ref options, ref mut entries, ref mut traces } = *callees;
+
+    // a depth of 0 traces only the called function itself
+    let options = match *options {
+        Some(ref options) if options.depth != Some(0) => options,
+        _ => return Ok(()),
+    };
+
+    let mut functions = vec![];
+    symbols.enumerate_globals(|symbol, _| {
+        functions.push(symbol);
+        true
+    })?;
+
+    for function in functions {
+        let address = function.address;
+        if breakpoints.contains_key(&address) {
+            continue;
+        }
+        if symbols.module_from_address(address).ok() != Some(module) {
+            continue;
+        }
+
+        // only functions in the chosen files and modules get breakpoints
+        let file = match symbols.line_from_address(address) {
+            Ok((line, _)) => line.file.to_string_lossy().into_owned(),
+            Err(_) => continue,
+        };
+        if !in_sources(options, &file) {
+            continue;
+        }
+
+        match symbols.type_from_index(module, function.type_index) {
+            Ok(debug::Type::Function { .. }) => (),
+            _ => continue,
+        }
+        if debugger.capture_call(&function).is_err() {
+            continue;
+        }
+
+        let lines = match symbols.lines_from_symbol(&function) {
+            Ok(lines) => lines,
+            Err(_) => continue,
+        };
+        let mut trace = BreakpointSet::new();
+        for line in lines.skip(1) {
+            trace.insert(line.address, RefCell::new(None));
+        }
+
+        entries.insert(address, RefCell::new(None));
+        traces.insert(address, trace);
+    }
+
+    Ok(())
+}
+
+/// Check a source path against the files and modules callees are limited to, if any
+///
+/// Paths are compared case-insensitively and with either kind of separator, as debug info
+/// records them however the compiler was given them.
+fn in_sources(options: &api::Callees, path: &str) -> bool {
+    if options.files.is_empty() && options.modules.is_empty() {
+        return true;
+    }
+
+    let normalize = |path: &str| path.replace('\\', "/").to_lowercase();
+    let path = normalize(path);
+
+    let in_file = options.files.iter()
+        .map(|file| normalize(&file[..]))
+        .any(|file| path == file || path.ends_with(&format!("/{}", file)));
+    let in_module = options.modules.iter()
+        .map(|module| normalize(&module[..]).trim_end_matches('/').to_string())
+        .any(|module| {
+            path.starts_with(&format!("{}/", module)) || path.contains(&format!("/{}/", module))
+        });
+
+    in_file || in_module
+}
+
+/// Replace a function's array views, after checking them against its variables
+fn set_arrays<D: Debugger>(
+    target: &mut TargetState<D>, address: usize, arrays: Vec<api::ArrayView>
+) -> io::Result<Vec<api::ArrayView>> {
+    let symbols = target.debugger.symbols();
+
+    let (function, offset) = symbols.symbol_from_address(address)?;
+    if offset > 0 {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no such function"));
+    }
+    let module = symbols.module_from_address(address)?;
+
+    // as in `describe_function`, variables in nested block scopes are found line by line
+    let mut types = HashMap::new();
+    for line in symbols.lines_from_symbol(&function)? {
+        symbols.enumerate_locals(line.address, |symbol, _| {
+            types.entry(symbol.address).or_insert(symbol.type_index);
+            true
+        })?;
+    }
+    for view in &arrays {
+        value::check_array_view(symbols, module, &types, view)?;
+    }
+
+    if arrays.is_empty() {
+        target.arrays.remove(&address);
+    } else {
+        target.arrays.insert(address, arrays.clone());
+    }
+    Ok(arrays)
+}
+
+impl Callees {
+    /// The breakpoint at an address, if it is on a callee's entry point or one of its lines
+    fn breakpoint(&self, address: usize) -> Option<&RefCell<Option<debug::Breakpoint>>> {
+        self.entries.get(&address)
+            .or_else(|| self.traces.values().filter_map(|trace| trace.get(&address)).next())
+    }
+
+    /// Whether there is a callee breakpoint set at an address
+    fn enabled(&self, address: usize) -> bool {
+        self.breakpoint(address).map(|breakpoint| breakpoint.borrow().is_some()).unwrap_or(false)
+    }
+}
+
 fn continue_process(state: &mut DebugState) -> io::Result<()> {
     let event = state.event.take()
         .ok_or(io::Error::new(io::ErrorKind::AlreadyExists, "process already running"))?;
@@ -555,8 +865,8 @@ fn continue_process(state: &mut DebugState) -> io::Result<()> {
     Ok(())
 }
 
-fn trace_process(
-    target: &TargetState, state: &mut DebugState,
+fn trace_process<D: Debugger>(
+    target: &TargetState<D>, state: &mut DebugState,
     tx: &SyncSender<DebugMessage>, cancel: &AtomicBool,
     execution: ExecutionState
 ) -> io::Result<()> {
@@ -567,7 +877,8 @@ fn trace_process(
 
     let mut cancelled = false;
     loop {
-        let mut event = debug::Event::wait_event()?;
+        let mut event = target.debugger.wait_event()?;
+        send_output(&target.debugger, &target.output, &event, tx, 0);
         state.event = Some(event);
 
         let trace_event = trace_default(target, state, tx, &cancel, None, &mut true, false)?;
@@ -609,20 +920,20 @@ fn trace_process(
     }
 }
 
-fn call_function(
-    target: &mut TargetState, state: &mut DebugState,
-    thread: RawHandle, address: usize, arguments: HashMap<usize, api::Value>
+fn call_function<D: Debugger>(
+    target: &mut TargetState<D>, state: &mut DebugState,
+    thread: debug::Thread, address: usize, arguments: HashMap<usize, api::Value>
 ) -> io::Result<()> {
     let mut event = state.event.take()
         .ok_or(io::Error::new(io::ErrorKind::AlreadyExists, "process already running"))?;
 
-    let (function, offset) = target.symbols.symbol_from_address(address)?;
+    let (function, offset) = target.debugger.symbols().symbol_from_address(address)?;
     if offset > 0 {
         return Err(io::Error::new(io::ErrorKind::NotFound, "no such function"));
     }
 
     state.event = Some(event);
-    let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+    let mut context = target.debugger.get_context(thread)?;
 
     set_breakpoint(target, address)?;
 
@@ -632,11 +943,11 @@ fn call_function(
 
     // set up the call
     let args = arguments;
-    let call = debug::Call::setup(&target.child, &target.symbols, &mut context, &function, args)?;
+    let call = target.debugger.setup_call(&mut context, &function, args)?;
 
     let stack = context.stack_pointer() + mem::size_of::<usize>();
 
-    debug::set_thread_context(thread, &context)?;
+    target.debugger.set_context(thread, &context)?;
     event = state.event.take().unwrap();
 
     // move to a new execution
@@ -649,17 +960,22 @@ fn call_function(
 /// Higher-level events as detected by `trace_default`
 /// `run`, `trace_process`, and `trace_function` react to them differently
 enum TraceEvent {
-    Attach(RawHandle, usize),
+    Attach(debug::Thread, usize),
     Call(ExecutionState),
+    Callee(ExecutionState),
     Exception,
     Cancel,
     Terminate,
 }
 
-fn trace_function(
-    target: &TargetState, state: &mut DebugState,
+/// Trace a function until it returns, along with the calls it makes to breakpointed functions and
+/// to the callees chosen for the execution
+///
+/// `depth` is how many calls deep this one is from the called function, which is at depth 0.
+fn trace_function<D: Debugger>(
+    target: &TargetState<D>, state: &mut DebugState,
     tx: &SyncSender<DebugMessage>, cancel: &AtomicBool,
-    execution: ExecutionState, last_line: u32
+    execution: ExecutionState, last_line: u32, depth: usize
 ) -> io::Result<Option<TraceEvent>> {
     let (call, thread, entry, exit, stack) = match execution {
         ExecutionState::Function { call, thread, entry, exit, stack } =>
@@ -668,29 +984,40 @@ fn trace_function(
     };
     tx.send(DebugMessage::Trace(DebugTrace::Call(last_line, entry))).unwrap();
 
-    let TargetState { ref child, ref symbols, ref traces, .. } = *target;
+    let TargetState {
+        ref debugger, ref breakpoints, ref traces, ref arrays, ref callees, string_length, fill, ..
+    } = *target;
+    let child = debugger.child();
+    let symbols = debugger.symbols();
+    let arrays = arrays.get(&entry).map(|arrays| &arrays[..]).unwrap_or(&[]);
     let mut ret = Some(BreakpointGuard::new(child, child.set_breakpoint(exit)?));
-    let mut trace = TraceGuard::guard(child, &traces[&entry]);
+    let lines = traces.get(&entry).unwrap_or_else(|| &callees.traces[&entry]);
+    let mut trace = TraceGuard::guard(child, lines);
     trace.enable_all()?;
 
     let mut last_line = symbols.line_from_address(entry).map(|(line, _)| line.line).unwrap_or(0);
     let mut last_breakpoint = None;
-    let mut attached = false;
+
+    // breakpointed functions start by stepping over their own breakpoint, while callees' entry
+    // breakpoints have already been stepped over by `trace_default`
+    let callee = !breakpoints.contains_key(&entry);
+    let mut attached = callee;
     let mut cancelled = false;
     loop {
-        let mut event = debug::Event::wait_event()?;
+        let mut event = debugger.wait_event()?;
+        send_output(debugger, &target.output, &event, tx, last_line);
 
         use debug::EventInfo::*;
         match event.info {
             // per-line breakpoints
 
-            Exception { first_chance: true, code: winapi::EXCEPTION_BREAKPOINT, address } if
+            Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, address, .. } if
                 state.threads[&event.thread_id] == thread && trace.contains_key(&address)
             => {
                 let breakpoint = trace[&address].borrow_mut().take().unwrap();
 
                 state.event = Some(event);
-                let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+                let mut context = debugger.get_context(thread)?;
 
                 // disable and save the breakpoint
                 child.remove_breakpoint(breakpoint)?;
@@ -700,56 +1027,72 @@ fn trace_function(
                 context.set_instruction_pointer(address);
                 context.set_singlestep(true);
 
+                // a callee's recursive calls past the depth limit run untraced, but still hit its
+                // per-line breakpoints
+                let frames: Vec<_> = debugger.walk_stack(thread)?.collect();
+                let untraced = callee && frames.get(1)
+                    .map(|caller| caller.stack_pointer() != stack)
+                    .unwrap_or(false);
+                if untraced {
+                    debugger.set_context(thread, &context)?;
+                    event = state.event.take().unwrap();
+                    event.continue_event(true)?;
+                    continue;
+                }
+
                 // collect locals
 
-                let frame = symbols.walk_stack(thread)?.next().unwrap();
-                let instruction = frame.stack.AddrPC.Offset as usize;
+                let instruction = frames[0].instruction_pointer();
                 let (line, _) = symbols.line_from_address(instruction)?;
 
                 let mut locals = HashMap::new();
+                let mut types = HashMap::new();
                 let mut pointers = VecDeque::new();
                 symbols.enumerate_locals(instruction, |symbol, size| {
                     if size == 0 { return true; }
 
-                    let value = match debug::Value::read_symbol(child, &context, symbols, &symbol) {
-                        Ok(value) => value,
-                        _ => return true,
-                    };
-
-                    if value.data[0] == 0xcc {
-                        return true;
-                    }
-
-                    let local = value::parse(&value, symbols, &mut pointers);
+                    let local = value::parse_symbol(
+                        child, &context, 0, symbols, &symbol, string_length, fill, &mut pointers
+                    );
                     locals.insert(symbol.address, local);
+                    types.insert(symbol.address, symbol.type_index);
 
                     true
                 })?;
 
-                let module = symbols.module_from_address(context.as_raw().Rip as usize)?;
-                let base = context.as_raw().Rbp as usize;
-                value::trace_pointers(child, symbols, module, base, &mut pointers, &mut locals);
-
-                tx.send(DebugMessage::Trace(DebugTrace::Line(last_line, locals))).unwrap();
+                let module = symbols.module_from_address(context.instruction_pointer())?;
+                let base = context.frame_pointer();
+                value::trace_arrays(
+                    child, symbols, module, string_length, fill, arrays, &types, &mut pointers,
+                    &mut locals
+                );
+                let mut targets = value::PointerTargets::new(frames);
+                value::trace_pointers(
+                    child, symbols, module, base, string_length, fill, &mut pointers, &mut locals,
+                    &mut targets
+                );
+
+                let trace = DebugTrace::Line(last_line, locals, targets.take());
+                tx.send(DebugMessage::Trace(trace)).unwrap();
                 last_line = line.line;
 
-                debug::set_thread_context(thread, &context)?;
+                debugger.set_context(thread, &context)?;
                 event = state.event.take().unwrap();
             }
 
-            Exception { first_chance: true, code: winapi::EXCEPTION_SINGLE_STEP, .. } if
+            Exception { first_chance: true, code: debug::EXCEPTION_SINGLE_STEP, .. } if
                 state.threads[&event.thread_id] == thread && last_breakpoint.is_some()
             => {
                 let address = last_breakpoint.take().unwrap();
 
                 state.event = Some(event);
-                let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+                let mut context = debugger.get_context(thread)?;
 
                 // resume normal execution
                 *trace[&address].borrow_mut() = Some(child.set_breakpoint(address)?);
                 context.set_singlestep(false);
 
-                debug::set_thread_context(thread, &context)?;
+                debugger.set_context(thread, &context)?;
                 event = state.event.take().unwrap();
             }
 
@@ -758,13 +1101,13 @@ fn trace_function(
             // may not actually be a return- recursive functions' return addresses are within
             // their bodies. we also need to check the stack pointer to detect return.
 
-            Exception { first_chance: true, code: winapi::EXCEPTION_BREAKPOINT, address } if
+            Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, address, .. } if
                 state.threads[&event.thread_id] == thread && address == exit && ret.is_some()
             => {
                 let breakpoint = ret.take().unwrap();
 
                 state.event = Some(event);
-                let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+                let mut context = debugger.get_context(thread)?;
 
                 // disable and save the breakpoint
                 let breakpoint = breakpoint.into_inner();
@@ -778,46 +1121,51 @@ fn trace_function(
                 if context.stack_pointer() == stack {
                     // collect the return value
 
-                    let (value, restore) = call.teardown(child, &context, symbols)?;
+                    let (value, restore) = debugger.finish_call(call, &context)?;
 
                     let mut values = HashMap::new();
                     let mut pointers = VecDeque::new();
-                    let value = value::parse(&value, symbols, &mut pointers);
+                    let value = value::parse(&value, child, symbols, string_length, &mut pointers);
 
-                    let module = symbols.module_from_address(context.as_raw().Rip as usize)?;
-                    value::trace_pointers(child, symbols, module, 0, &mut pointers, &mut values);
+                    let module = symbols.module_from_address(context.instruction_pointer())?;
+                    let frames = debugger.walk_stack(thread).into_iter().flat_map(|frames| frames);
+                    let mut targets = value::PointerTargets::new(frames);
+                    value::trace_pointers(
+                        child, symbols, module, 0, string_length, fill, &mut pointers, &mut values,
+                        &mut targets
+                    );
 
-                    let trace = DebugTrace::Return(last_line, value, values);
+                    let trace = DebugTrace::Return(last_line, value, values, targets.take());
                     tx.send(DebugMessage::Trace(trace)).unwrap();
 
                     if let Some(context) = restore {
-                        debug::set_thread_context(thread, &context)?;
+                        debugger.set_context(thread, &context)?;
                     } else {
                         context.set_singlestep(false);
-                        debug::set_thread_context(thread, &context)?;
+                        debugger.set_context(thread, &context)?;
                     }
                     return Ok(None);
                 }
 
-                debug::set_thread_context(thread, &context)?;
+                debugger.set_context(thread, &context)?;
                 event = state.event.take().unwrap();
             }
 
-            Exception { first_chance: true, code: winapi::EXCEPTION_SINGLE_STEP, .. } if
+            Exception { first_chance: true, code: debug::EXCEPTION_SINGLE_STEP, .. } if
                 state.threads[&event.thread_id] == thread && ret.is_none()
             => {
                 state.event = Some(event);
-                let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+                let mut context = debugger.get_context(thread)?;
 
                 // resume normal execution
                 ret = Some(BreakpointGuard::new(child, child.set_breakpoint(exit)?));
                 context.set_singlestep(false);
 
-                debug::set_thread_context(thread, &context)?;
+                debugger.set_context(thread, &context)?;
                 event = state.event.take().unwrap();
             }
 
-            // recursive calls and other events
+            // recursive calls, calls to callees, and other events
 
             _ => {
                 state.event = Some(event);
@@ -826,6 +1174,15 @@ fn trace_function(
                     target, state, tx, &cancel, Some(thread), &mut attached, false
                 )?;
 
+                // callees past the depth limit run untraced
+                if let Some(TraceEvent::Callee(ex)) = trace_event {
+                    let traced = callees.options.as_ref()
+                        .and_then(|options| options.depth)
+                        .map(|limit| depth < limit as usize)
+                        .unwrap_or(true);
+                    trace_event = if traced { Some(TraceEvent::Call(ex)) } else { None };
+                }
+
                 if let Some(TraceEvent::Call(ex @ ExecutionState::Function { .. })) = trace_event {
                     event = state.event.take().unwrap();
                     event.continue_event(true)?;
@@ -833,7 +1190,8 @@ fn trace_function(
                     let breakpoint = ret.take().unwrap();
                     child.remove_breakpoint(breakpoint.into_inner())?;
 
-                    trace_event = trace_function(target, state, tx, cancel, ex, last_line)?;
+                    trace_event =
+                        trace_function(target, state, tx, cancel, ex, last_line, depth + 1)?;
                     ret = Some(BreakpointGuard::new(child, child.set_breakpoint(exit)?));
                 }
 
@@ -858,12 +1216,14 @@ fn trace_function(
 
         // after cancellation is signalled, wait to return until all single-step events have
         // completed, re-enabling all per-line breakpoints
-        if cancelled && last_breakpoint.is_none() && state.last_call.is_none() {
+        if cancelled && last_breakpoint.is_none() && state.last_call.is_none() &&
+            state.last_callee.is_none()
+        {
             state.event = Some(event);
 
             let restore = call.cancel();
             if let Some(context) = restore {
-                debug::set_thread_context(thread, &context)?;
+                debugger.set_context(thread, &context)?;
             }
 
             return Ok(Some(TraceEvent::Cancel));
@@ -873,15 +1233,175 @@ fn trace_function(
     }
 }
 
+/// Capture a call that has just reached the function at `entry`, to trace it until it returns
+fn capture_execution<D: Debugger>(
+    debugger: &D, thread: debug::Thread, entry: usize
+) -> io::Result<ExecutionState> {
+    let (function, _) = debugger.symbols().symbol_from_address(entry)?;
+
+    let mut frames = debugger.walk_stack(thread)?;
+    let (callee, caller) = match (frames.next(), frames.next()) {
+        (Some(callee), Some(caller)) => (callee, caller),
+        _ => return Err(io::Error::new(io::ErrorKind::Other, "call has no caller on the stack")),
+    };
+
+    // collect location data
+    let exit = callee.return_address();
+    let stack = caller.stack_pointer();
+
+    // capture the call
+    let call = debugger.capture_call(&function)?;
+
+    Ok(ExecutionState::Function { call, thread, entry, exit, stack })
+}
+
+/// Forward anything the target process has printed before `event`, attributing it to `line`
+///
+/// Output printed from here until the next event is also attributed to `line`.
+fn send_output<D: Debugger>(
+    debugger: &D, output: &Mutex<Output>, event: &debug::Event, tx: &SyncSender<DebugMessage>,
+    line: u32
+) {
+    {
+        let mut output = output.lock().unwrap();
+        output.line = line;
+        output.read();
+        while let Some(trace) = output.pending.pop_front() {
+            tx.send(DebugMessage::Trace(trace)).unwrap();
+        }
+    }
+
+    if let debug::EventInfo::OutputDebugString { data, length, unicode } = event.info {
+        let mut buffer = vec![0; length];
+        if debugger.child().read_memory(data, &mut buffer).is_err() {
+            return;
+        }
+
+        let mut text = if unicode {
+            let wide: Vec<_> = buffer.chunks(2)
+                .map(|c| c[0] as u16 | (c.get(1).cloned().unwrap_or(0) as u16) << 8)
+                .collect();
+            String::from_utf16_lossy(&wide)
+        } else {
+            String::from_utf8_lossy(&buffer).into_owned()
+        };
+        while text.ends_with('\0') {
+            text.pop();
+        }
+
+        let trace = DebugTrace::Output(line, api::Stream::Debug, text);
+        tx.send(DebugMessage::Trace(trace)).unwrap();
+    }
+}
+
+impl Output {
+    fn new(capture: Option<debug::Output>) -> Output {
+        Output { capture, line: 0, partial: HashMap::new(), pending: VecDeque::new() }
+    }
+
+    /// Decode what the process has printed since the last read, queueing it to be sent
+    fn read(&mut self) {
+        let capture = match self.capture {
+            Some(ref capture) => capture,
+            None => return,
+        };
+
+        for (stream, data) in capture.read() {
+            let buffer = self.partial.entry(stream).or_insert(vec![]);
+            buffer.extend_from_slice(&data);
+
+            // hold back a sequence the process hasn't finished writing
+            let end = complete_utf8(buffer);
+            if end == 0 {
+                continue;
+            }
+            let text = String::from_utf8_lossy(&buffer[..end]).into_owned();
+            buffer.drain(..end);
+
+            let stream = match stream {
+                debug::Stream::Stdout => api::Stream::Stdout,
+                debug::Stream::Stderr => api::Stream::Stderr,
+            };
+            self.pending.push_back(DebugTrace::Output(self.line, stream, text));
+        }
+    }
+
+    /// Send as much output as the server is ready for, returning whether it is still connected
+    fn try_send(&mut self, tx: &SyncSender<DebugMessage>) -> bool {
+        self.read();
+        while let Some(trace) = self.pending.pop_front() {
+            match tx.try_send(DebugMessage::Trace(trace)) {
+                Ok(()) => (),
+                Err(TrySendError::Full(DebugMessage::Trace(trace))) => {
+                    self.pending.push_front(trace);
+                    return true;
+                }
+                Err(TrySendError::Full(_)) => unreachable!(),
+                Err(TrySendError::Disconnected(_)) => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// The length of the longest prefix of some UTF-8 that doesn't end partway through a sequence
+///
+/// Invalid bytes don't count as a partial sequence, and are left to be replaced when decoded.
+fn complete_utf8(data: &[u8]) -> usize {
+    // a partial sequence is missing at least one of at most four bytes
+    let tail = data.len().saturating_sub(3);
+    let lead = match data[tail..].iter().rposition(|&byte| byte & 0xc0 != 0x80) {
+        Some(lead) => tail + lead,
+        None => return data.len(),
+    };
+
+    match str::from_utf8(&data[lead..]) {
+        Err(ref e) if e.error_len().is_none() => lead,
+        _ => data.len(),
+    }
+}
+
+impl OutputReader {
+    fn start(output: Arc<Mutex<Output>>, tx: SyncSender<DebugMessage>) -> OutputReader {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let thread = thread::spawn(move || {
+            while !flag.load(Ordering::Relaxed) {
+                if !output.lock().unwrap().try_send(&tx) {
+                    return;
+                }
+
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        OutputReader { stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for OutputReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
 /// Event handlers shared between `run`'s startup code, `trace_process`, and `trace_function`.
 ///
 /// Expects `state.event` to contain the last debug event
-fn trace_default(
-    target: &TargetState, state: &mut DebugState,
+fn trace_default<D: Debugger>(
+    target: &TargetState<D>, state: &mut DebugState,
     tx: &SyncSender<DebugMessage>, cancel: &AtomicBool,
-    current_thread: Option<RawHandle>, capture_calls: &mut bool, startup: bool
+    current_thread: Option<debug::Thread>, capture_calls: &mut bool, startup: bool
 ) -> io::Result<Option<TraceEvent>> {
-    let TargetState { ref child, ref symbols, ref breakpoints, .. } = *target;
+    let TargetState {
+        ref debugger, ref breakpoints, ref arrays, ref callees, string_length, fill, ..
+    } = *target;
+    let child = debugger.child();
+    let symbols = debugger.symbols();
     let DebugState { ref mut threads, .. } = *state;
 
     let event = state.event.as_ref().unwrap();
@@ -902,7 +1422,7 @@ fn trace_default(
         }
         UnloadDll { base } => { let _ = symbols.unload_module(base); }
 
-        Exception { first_chance: true, code: winapi::EXCEPTION_BREAKPOINT, address } if
+        Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, address, .. } if
             startup
         => {
             let thread = threads[&event.thread_id];
@@ -911,43 +1431,30 @@ fn trace_default(
 
         // function call breakpoints
 
-        Exception { first_chance: true, code: winapi::EXCEPTION_BREAKPOINT, address } if
+        Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, address, .. } if
             current_thread.map(|t| threads[&event.thread_id] == t).unwrap_or(true) &&
             breakpoints.contains_key(&address) && *capture_calls
         => {
             let thread = threads[&event.thread_id];
-            let (function, _) = symbols.symbol_from_address(address)?;
 
             // restart the instruction
-            let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+            let mut context = debugger.get_context(thread)?;
             context.set_instruction_pointer(address);
-            debug::set_thread_context(thread, &context)?;
-
-            let mut frames = symbols.walk_stack(thread)?;
-            let callee = frames.next().unwrap();
-            let caller = frames.next().unwrap();
-
-            // collect location data
-            let entry = address;
-            let exit = callee.stack.AddrReturn.Offset as usize;
-            let stack = caller.stack.AddrStack.Offset as usize;
-
-            // capture the call
-            let call = debug::Call::capture(symbols, &function)?;
+            debugger.set_context(thread, &context)?;
 
             // move to a new execution
-            let execution = ExecutionState::Function { call, thread, entry, exit, stack };
+            let execution = capture_execution(debugger, thread, address)?;
             return Ok(Some(TraceEvent::Call(execution)));
         }
 
-        Exception { first_chance: true, code: winapi::EXCEPTION_BREAKPOINT, address } if
+        Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, address, .. } if
             current_thread.map(|t| threads[&event.thread_id] == t).unwrap_or(true) &&
             breakpoints.contains_key(&address)
         => {
             let thread = threads[&event.thread_id];
             let breakpoint = breakpoints[&address].borrow_mut().take().unwrap();
 
-            let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+            let mut context = debugger.get_context(thread)?;
 
             // disable and save the breakpoint
             child.remove_breakpoint(breakpoint)?;
@@ -957,39 +1464,94 @@ fn trace_default(
             context.set_instruction_pointer(address);
             context.set_singlestep(true);
 
-            debug::set_thread_context(thread, &context)?;
+            debugger.set_context(thread, &context)?;
         }
 
-        Exception { first_chance: true, code: winapi::EXCEPTION_SINGLE_STEP, .. } if
+        Exception { first_chance: true, code: debug::EXCEPTION_SINGLE_STEP, .. } if
             current_thread.map(|t| threads[&event.thread_id] == t).unwrap_or(true) &&
             state.last_call.is_some()
         => {
             let thread = threads[&event.thread_id];
             let address = state.last_call.take().unwrap();
 
-            let mut context = debug::get_thread_context(thread, winapi::CONTEXT_FULL)?;
+            let mut context = debugger.get_context(thread)?;
 
             // resume normal execution
             *breakpoints[&address].borrow_mut() = Some(child.set_breakpoint(address)?);
             context.set_singlestep(false);
             *capture_calls = true;
 
-            debug::set_thread_context(thread, &context)?;
+            debugger.set_context(thread, &context)?;
         }
 
-        Exception { first_chance: true, code: winapi::EXCEPTION_BREAKPOINT, address } if
+        Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, address, .. } if
             breakpoints.contains_key(&address)
         => {
             let message = "unsupported concurrent execution of breakpointed function";
             return Err(io::Error::new(io::ErrorKind::Other, message));
         }
 
+        // callee breakpoints
+        //
+        // these are only set while a function is traced, and are always stepped over. calls to
+        // callees are handed to `trace_function`, and their lines are only hit here by calls it
+        // leaves untraced.
+
+        Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, address, .. } if
+            current_thread.map(|t| threads[&event.thread_id] == t).unwrap_or(false) &&
+            callees.enabled(address)
+        => {
+            let thread = threads[&event.thread_id];
+            let breakpoint = callees.breakpoint(address).unwrap().borrow_mut().take().unwrap();
+
+            let mut context = debugger.get_context(thread)?;
+
+            // disable and save the breakpoint
+            child.remove_breakpoint(breakpoint)?;
+            state.last_callee = Some(address);
+
+            // restart the instruction and enable singlestep
+            context.set_instruction_pointer(address);
+            context.set_singlestep(true);
+
+            debugger.set_context(thread, &context)?;
+
+            if callees.entries.contains_key(&address) {
+                let execution = capture_execution(debugger, thread, address)?;
+                return Ok(Some(TraceEvent::Callee(execution)));
+            }
+        }
+
+        Exception { first_chance: true, code: debug::EXCEPTION_SINGLE_STEP, .. } if
+            current_thread.map(|t| threads[&event.thread_id] == t).unwrap_or(false) &&
+            state.last_callee.is_some()
+        => {
+            let thread = threads[&event.thread_id];
+            let address = state.last_callee.take().unwrap();
+
+            let mut context = debugger.get_context(thread)?;
+
+            // resume normal execution
+            *callees.breakpoint(address).unwrap().borrow_mut() =
+                Some(child.set_breakpoint(address)?);
+            context.set_singlestep(false);
+
+            debugger.set_context(thread, &context)?;
+        }
+
+        Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, address, .. } if
+            callees.enabled(address)
+        => {
+            let message = "unsupported concurrent execution of traced callee";
+            return Err(io::Error::new(io::ErrorKind::Other, message));
+        }
+
         // cancellation
         //
         // by this point, we are sure the breakpoint did not happen on the current thread
         // if `cancel` is set, a server thread must have injected it
 
-        Exception { first_chance: true, code: winapi::EXCEPTION_BREAKPOINT, .. } if
+        Exception { first_chance: true, code: debug::EXCEPTION_BREAKPOINT, .. } if
             cancel.load(Ordering::Relaxed)
         => {
             cancel.store(false, Ordering::Relaxed);
@@ -1003,10 +1565,30 @@ fn trace_default(
             return Ok(Some(TraceEvent::Exception));
         }
 
-        // TODO: collect stack trace
-        Exception { first_chance: false, code, address } => {
-            let message = format!("unhandled exception 0x{:x} at 0x{:x}", code, address);
-            tx.send(DebugMessage::Trace(DebugTrace::Crash(message))).unwrap();
+        Exception { first_chance: false, code, address, fault } => {
+            let name = debug::exception_name(code)
+                .map(String::from)
+                .unwrap_or_else(|| format!("0x{:x}", code));
+            let fault = match fault {
+                debug::Fault::AccessViolation { access, address } => {
+                    let access = access.map(|access| match access {
+                        debug::Access::Read => api::Access::Read,
+                        debug::Access::Write => api::Access::Write,
+                        debug::Access::Execute => api::Access::Execute,
+                    });
+                    api::Fault::AccessViolation { access, address }
+                }
+                debug::Fault::DivideByZero => api::Fault::DivideByZero,
+                debug::Fault::StackOverflow => api::Fault::StackOverflow,
+                debug::Fault::Other => api::Fault::Other,
+            };
+            let exception = api::Exception { code, name, address, fault };
+
+            let stack = threads.get(&event.thread_id)
+                .map(|&thread| crash_stack(debugger, thread, arrays, string_length, fill))
+                .unwrap_or(vec![]);
+
+            tx.send(DebugMessage::Trace(DebugTrace::Crash(exception, stack))).unwrap();
             return Ok(Some(TraceEvent::Terminate));
         }
 
@@ -1015,3 +1597,102 @@ fn trace_default(
 
     Ok(None)
 }
+
+/// Walk a crashed thread's stack, collecting each frame's location and variables
+///
+/// This is best-effort; frames that can't be read are reported without that data, and variables
+/// that can't be read are reported as unavailable.
+fn crash_stack<D: Debugger>(
+    debugger: &D, thread: debug::Thread, arrays: &HashMap<usize, Vec<api::ArrayView>>,
+    string_length: usize, fill: Option<u8>
+) -> Vec<api::Frame> {
+    let child = debugger.child();
+    let symbols = debugger.symbols();
+
+    let frames: Vec<_> = match debugger.walk_stack(thread) {
+        Ok(frames) => frames.collect(),
+        Err(_) => return vec![],
+    };
+    let contexts: Vec<_> = frames.iter()
+        .map(|frame| (frame.instruction_pointer(), frame.context()))
+        .collect();
+    let mut targets = value::PointerTargets::new(frames);
+
+    let mut stack = vec![];
+    for (index, (address, context)) in contexts.into_iter().enumerate() {
+        // return addresses point after the call instruction, which may be in the next line
+        let lookup = if index == 0 { address } else { address.saturating_sub(1) };
+
+        let (function, function_name) = match symbols.symbol_from_address(lookup) {
+            Ok((symbol, _)) => {
+                (Some(symbol.address), Some(symbol.name.to_string_lossy().into_owned()))
+            }
+            Err(_) => (None, None),
+        };
+        let (source_path, line) = match symbols.line_from_address(lookup) {
+            Ok((line, _)) => (Some(line.file.to_string_lossy().into_owned()), Some(line.line)),
+            Err(_) => (None, None),
+        };
+
+        let mut data = HashMap::new();
+        let mut types = HashMap::new();
+        let mut pointers = VecDeque::new();
+        let _ = symbols.enumerate_locals(lookup, |symbol, size| {
+            if size == 0 { return true; }
+
+            let value = value::parse_symbol(
+                child, &context, index, symbols, &symbol, string_length, fill, &mut pointers
+            );
+            data.insert(symbol.address, value);
+            types.insert(symbol.address, symbol.type_index);
+
+            true
+        });
+
+        if let Ok(module) = symbols.module_from_address(lookup) {
+            let base = context.frame_pointer();
+            let arrays = function.and_then(|function| arrays.get(&function));
+            let arrays = arrays.map(|arrays| &arrays[..]).unwrap_or(&[]);
+            value::trace_arrays(
+                child, symbols, module, string_length, fill, arrays, &types, &mut pointers,
+                &mut data
+            );
+            value::trace_pointers(
+                child, symbols, module, base, string_length, fill, &mut pointers, &mut data,
+                &mut targets
+            );
+        }
+
+        let pointers = targets.take();
+        stack.push(api::Frame {
+            function, function_name, address, source_path, line, data, pointers
+        });
+    }
+
+    stack
+}
+
+#[cfg(test)]
+mod tests {
+    use super::complete_utf8;
+
+    #[test]
+    fn complete_utf8_holds_back_partial_sequences() {
+        let text = "a\u{e9}\u{20ac}\u{1f600}".as_bytes();
+        assert_eq!(text.len(), 1 + 2 + 3 + 4);
+
+        // every split inside a sequence is held back to the sequence's start
+        let starts = [0, 1, 1, 3, 3, 3, 6, 6, 6, 6, 10];
+        for (end, &start) in starts.iter().enumerate() {
+            assert_eq!(complete_utf8(&text[..end]), start);
+        }
+    }
+
+    #[test]
+    fn complete_utf8_passes_invalid_bytes() {
+        assert_eq!(complete_utf8(b"ab\xff"), 3);
+        assert_eq!(complete_utf8(b"ab\x80\x80\x80\x80"), 6);
+        assert_eq!(complete_utf8(b"\xe2\x82a"), 3);
+        assert_eq!(complete_utf8(b""), 0);
+    }
+}
@@ -0,0 +1,540 @@
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+
+use timeline::Timeline;
+use api;
+
+/// How far apart two line sequences may be before they are no longer aligned
+///
+/// Alignment takes memory quadratic in the number of lines that differ, so past this many only
+/// the lines the executions start and end with in common are aligned.
+const MAX_DISTANCE: isize = 2048;
+
+/// How many pointers to follow when comparing the values they point to
+const MAX_DEPTH: usize = 8;
+
+/// Compare two executions of a function, given the addresses of its parameters and locals
+///
+/// The first `parameters` of `variables` are its parameters.
+pub fn diff(
+    left: &Timeline, right: &Timeline, variables: &[usize], parameters: usize
+) -> api::ExecutionDiff {
+    let left_steps = left.steps();
+    let right_steps = right.steps();
+
+    let left_lines: Vec<_> = left_steps.iter().map(|step| step.line).collect();
+    let right_lines: Vec<_> = right_steps.iter().map(|step| step.line).collect();
+    let pairs = align(&left_lines, &right_lines);
+
+    let mut divergences = vec![];
+    let mut differences = vec![];
+    let (mut next_left, mut next_right) = (0, 0);
+    let end = (left_steps.len(), right_steps.len());
+    for &(i, j) in pairs.iter().chain(Some(&end)) {
+        if i > next_left || j > next_right {
+            divergences.push(api::Divergence {
+                left: left_steps[next_left..i].iter().map(copy_step).collect(),
+                right: right_steps[next_right..j].iter().map(copy_step).collect(),
+            });
+        }
+        next_left = i + 1;
+        next_right = j + 1;
+
+        if i == left_steps.len() {
+            break;
+        }
+
+        let (left_index, right_index) = (left_steps[i].index, right_steps[j].index);
+        let left_pointee = |address: &str| pointee(left, address, left_index);
+        let right_pointee = |address: &str| pointee(right, address, right_index);
+
+        let mut changed = HashMap::new();
+        for &address in variables {
+            let (a, b) = (left.value(address, left_index), right.value(address, right_index));
+            if !same_option(a, b, &left_pointee, &right_pointee) {
+                let difference = api::ValueDifference { left: a.cloned(), right: b.cloned() };
+                changed.insert(address, difference);
+            }
+        }
+
+        if !changed.is_empty() {
+            differences.push(api::StepDifference {
+                left: left_index,
+                right: right_index,
+                line: left_steps[i].line,
+                variables: changed,
+            });
+        }
+    }
+
+    let arguments = &variables[..cmp::min(parameters, variables.len())];
+    let (return_value, return_data) = diff_returns(left, right, arguments);
+    api::ExecutionDiff { divergences, differences, return_value, return_data }
+}
+
+/// Compare the return values, and the values that the return values and the arguments as they
+/// were on return point to
+fn diff_returns(
+    left: &Timeline, right: &Timeline, arguments: &[usize]
+) -> (Option<api::ValueDifference>, HashMap<usize, api::ValueDifference>) {
+    let empty = HashMap::new();
+    let (left_value, left_data) = left.returned()
+        .map(|&(ref value, ref data)| (Some(value), data))
+        .unwrap_or((None, &empty));
+    let (right_value, right_data) = right.returned()
+        .map(|&(ref value, ref data)| (Some(value), data))
+        .unwrap_or((None, &empty));
+
+    let left_pointee = |address: &str| {
+        return_pointee(left_data, left.return_pointers(), address)
+    };
+    let right_pointee = |address: &str| {
+        return_pointee(right_data, right.return_pointers(), address)
+    };
+
+    let value = if same_option(left_value, right_value, &left_pointee, &right_pointee) {
+        None
+    } else {
+        Some(api::ValueDifference { left: left_value.cloned(), right: right_value.cloned() })
+    };
+
+    // the pointed-to values live at different addresses in each execution, so pair them up by
+    // following the same pointers on both sides
+    let mut pairs = vec![];
+    let mut seen = HashSet::new();
+    if let (Some(a), Some(b)) = (left_value, right_value) {
+        pair_pointees(a, b, &left_pointee, &right_pointee, 0, &mut pairs, &mut seen);
+    }
+    let (left_last, right_last) = (last_step(left), last_step(right));
+    for &address in arguments {
+        let a = left_last.and_then(|index| left.value(address, index));
+        let b = right_last.and_then(|index| right.value(address, index));
+        if let (Some(a), Some(b)) = (a, b) {
+            pair_pointees(a, b, &left_pointee, &right_pointee, 0, &mut pairs, &mut seen);
+        }
+    }
+
+    let mut data = HashMap::new();
+    for (a, b) in pairs {
+        let (x, y) = (a.and_then(|a| left_data.get(&a)), b.and_then(|b| right_data.get(&b)));
+        if let (Some(x), Some(y)) = (x, y) {
+            if same(x, y, &left_pointee, &right_pointee, 0) {
+                continue;
+            }
+        }
+
+        let address = a.or(b).unwrap();
+        data.insert(address, api::ValueDifference { left: x.cloned(), right: y.cloned() });
+    }
+
+    (value, data)
+}
+
+/// The index of an execution's last line trace
+fn last_step(timeline: &Timeline) -> Option<i32> {
+    timeline.steps().last().map(|step| step.index)
+}
+
+/// Collect the addresses of the values two values point to at the same place, walking both in
+/// step
+///
+/// A pointer whose value only one side has is paired with `None`. Each pair is visited once, so
+/// cycles end.
+fn pair_pointees<'a, L, R>(
+    a: &'a api::Value, b: &'a api::Value, left: &L, right: &R, depth: usize,
+    pairs: &mut Vec<(Option<usize>, Option<usize>)>,
+    seen: &mut HashSet<(Option<usize>, Option<usize>)>
+) where
+    L: Fn(&str) -> Option<&'a api::Value>,
+    R: Fn(&str) -> Option<&'a api::Value>
+{
+    let (x, y) = match (a, b) {
+        (&api::Value::Array(ref a), &api::Value::Array(ref b)) => {
+            for (a, b) in a.iter().zip(b) {
+                pair_pointees(a, b, left, right, depth, pairs, seen);
+            }
+            return;
+        }
+        (&api::Value::Struct(ref a), &api::Value::Struct(ref b)) => {
+            for (field, a) in a {
+                if let Some(b) = b.get(field) {
+                    pair_pointees(a, b, left, right, depth, pairs, seen);
+                }
+            }
+            return;
+        }
+        (&api::Value::String(ref x), &api::Value::String(ref y)) => (Some(x), Some(y)),
+        (&api::Value::String(ref x), _) => (Some(x), None),
+        (_, &api::Value::String(ref y)) => (None, Some(y)),
+        _ => return,
+    };
+    if depth >= MAX_DEPTH {
+        return;
+    }
+
+    let a = x.and_then(|x| left(x).map(|a| (x.parse::<usize>().unwrap(), a)));
+    let b = y.and_then(|y| right(y).map(|b| (y.parse::<usize>().unwrap(), b)));
+    let pair = (a.map(|(address, _)| address), b.map(|(address, _)| address));
+    if pair == (None, None) || !seen.insert(pair) {
+        return;
+    }
+    pairs.push(pair);
+
+    if let (Some((_, a)), Some((_, b))) = (a, b) {
+        pair_pointees(a, b, left, right, depth + 1, pairs, seen);
+    }
+}
+
+fn copy_step(step: &api::Step) -> api::Step {
+    api::Step { index: step.index, line: step.line }
+}
+
+/// The value a pointer points to as of a step, given its decimal address
+///
+/// Only values traced as pointers are followed, as the addresses of pointed-to values share their
+/// keys with variables' frame offsets.
+fn pointee<'a>(timeline: &'a Timeline, address: &str, index: i32) -> Option<&'a api::Value> {
+    let address = match address.parse() {
+        Ok(address) => address,
+        Err(_) => return None,
+    };
+
+    match timeline.pointer(address, index) {
+        Some(&api::PointerTarget::Null) | None => None,
+        Some(_) => timeline.value(address, index),
+    }
+}
+
+/// The value a pointer in a function's return value points to, given its decimal address
+fn return_pointee<'a>(
+    data: &'a HashMap<usize, api::Value>, pointers: &HashMap<usize, api::PointerTarget>,
+    address: &str
+) -> Option<&'a api::Value> {
+    let address = match address.parse() {
+        Ok(address) => address,
+        Err(_) => return None,
+    };
+
+    match pointers.get(&address) {
+        Some(&api::PointerTarget::Null) | None => None,
+        Some(_) => data.get(&address),
+    }
+}
+
+fn same_option<'a, L, R>(
+    a: Option<&'a api::Value>, b: Option<&'a api::Value>, left: &L, right: &R
+) -> bool where
+    L: Fn(&str) -> Option<&'a api::Value>,
+    R: Fn(&str) -> Option<&'a api::Value>
+{
+    match (a, b) {
+        (Some(a), Some(b)) => same(a, b, left, right, 0),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Compare two values, looking up the values pointed to by each side's pointers
+///
+/// Pointers in separate executions rarely hold the same address even when what they point to is
+/// the same, so pointers are equal when the values they point to are. Pointers are written as
+/// decimal strings, and those that point to no traced value are compared directly, as are all
+/// other scalars.
+fn same<'a, L, R>(
+    a: &'a api::Value, b: &'a api::Value, left: &L, right: &R, depth: usize
+) -> bool where
+    L: Fn(&str) -> Option<&'a api::Value>,
+    R: Fn(&str) -> Option<&'a api::Value>
+{
+    match (a, b) {
+        (&api::Value::String(ref x), &api::Value::String(ref y)) if depth < MAX_DEPTH => {
+            match (left(x), right(y)) {
+                (Some(a), Some(b)) => same(a, b, left, right, depth + 1),
+                _ => x == y,
+            }
+        }
+        (&api::Value::Array(ref a), &api::Value::Array(ref b)) => {
+            a.len() == b.len() &&
+                a.iter().zip(b).all(|(a, b)| same(a, b, left, right, depth))
+        }
+        (&api::Value::Struct(ref a), &api::Value::Struct(ref b)) => {
+            a.len() == b.len() &&
+                a.iter().all(|(field, a)| {
+                    b.get(field).map(|b| same(a, b, left, right, depth)).unwrap_or(false)
+                })
+        }
+        _ => a == b,
+    }
+}
+
+/// Pair up the positions of the lines two sequences have in common, in order
+fn align(left: &[u32], right: &[u32]) -> Vec<(usize, usize)> {
+    // executions usually start and end the same way, which needs no search
+    let prefix = left.iter().zip(right).take_while(|&(a, b)| a == b).count();
+    let suffix = left[prefix..].iter().rev().zip(right[prefix..].iter().rev())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let (left_end, right_end) = (left.len() - suffix, right.len() - suffix);
+
+    let mut pairs: Vec<_> = (0..prefix).map(|i| (i, i)).collect();
+    if let Some(middle) = shortest_edit(&left[prefix..left_end], &right[prefix..right_end]) {
+        pairs.extend(middle.into_iter().map(|(i, j)| (prefix + i, prefix + j)));
+    }
+    pairs.extend((0..suffix).map(|i| (left_end + i, right_end + i)));
+    pairs
+}
+
+/// Find the longest common subsequence with Myers' diff algorithm, or `None` if the sequences
+/// differ by more than `MAX_DISTANCE`
+fn shortest_edit(left: &[u32], right: &[u32]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (left.len() as isize, right.len() as isize);
+    let max = cmp::min(n + m, MAX_DISTANCE);
+
+    // the furthest position reached along each diagonal `x - y`, and its history for each number
+    // of edits, kept just wide enough to backtrack through
+    let offset = max + 1;
+    let mut v = vec![0; (2 * max + 3) as usize];
+    let mut history = vec![];
+
+    for d in 0..max + 1 {
+        history.push(v[(offset - d - 1) as usize..(offset + d + 2) as usize].to_vec());
+
+        let mut k = -d;
+        while k <= d {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && left[x as usize] == right[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+
+            if x >= n && y >= m {
+                return Some(backtrack(&history, n, m));
+            }
+            k += 2;
+        }
+    }
+
+    None
+}
+
+fn backtrack(history: &[Vec<isize>], n: isize, m: isize) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in history.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d + 1) as usize];
+
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            pairs.push((x as usize, y as usize));
+        }
+
+        x = prev_x;
+        y = prev_y;
+    }
+
+    pairs.reverse();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp;
+
+    use timeline::Timeline;
+    use api;
+    use super::*;
+
+    fn pointer(address: usize) -> api::Value {
+        api::Value::String(address.to_string())
+    }
+
+    /// Build a timeline from the lines of a function, each with the values it changed and the
+    /// pointers it traced
+    fn timeline(lines: Vec<(u32, Vec<(usize, api::Value)>, Vec<usize>)>) -> Timeline {
+        let mut timeline = Timeline::new();
+        let call = api::TraceData::Call { function: 0 };
+        timeline.push(&api::Trace { index: 0, line: 0, data: call }, false);
+
+        for (index, (line, state, pointers)) in lines.into_iter().enumerate() {
+            let data = api::TraceData::Line {
+                state: state.into_iter().collect(),
+                pointers: pointers.into_iter().map(|address| (address, api::PointerTarget::Heap))
+                    .collect(),
+                loops: vec![],
+            };
+            timeline.push(&api::Trace { index: index as i32 + 1, line, data }, false);
+        }
+
+        timeline
+    }
+
+    /// End a timeline with a return, given the values the return value and arguments point to
+    fn returned(
+        mut timeline: Timeline, value: api::Value, data: Vec<(usize, api::Value)>
+    ) -> Timeline {
+        let index = timeline.steps().len() as i32 + 1;
+        let pointers = data.iter().map(|&(address, _)| (address, api::PointerTarget::Heap))
+            .collect();
+        let data = api::TraceData::Return { value, data: data.into_iter().collect(), pointers };
+        timeline.push(&api::Trace { index, line: 0, data }, false);
+        timeline
+    }
+
+    /// The length of the longest common subsequence, by dynamic programming
+    fn lcs_length(left: &[u32], right: &[u32]) -> usize {
+        let mut lengths = vec![vec![0; right.len() + 1]; left.len() + 1];
+        for i in 0..left.len() {
+            for j in 0..right.len() {
+                lengths[i + 1][j + 1] = if left[i] == right[j] {
+                    lengths[i][j] + 1
+                } else {
+                    cmp::max(lengths[i][j + 1], lengths[i + 1][j])
+                };
+            }
+        }
+        lengths[left.len()][right.len()]
+    }
+
+    #[test]
+    fn align_identical() {
+        let lines = [1, 2, 3, 2, 3, 4];
+        let pairs = align(&lines, &lines);
+        assert_eq!(pairs, (0..6).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn align_extra_iteration() {
+        let left = [1, 2, 3, 2, 3, 4];
+        let right = [1, 2, 3, 4];
+        let pairs = align(&left, &right);
+        assert_eq!(pairs.len(), 4);
+        for &(i, j) in &pairs {
+            assert_eq!(left[i], right[j]);
+        }
+        assert_eq!(pairs.first(), Some(&(0, 0)));
+        assert_eq!(pairs.last(), Some(&(5, 3)));
+    }
+
+    #[test]
+    fn align_finds_longest_common_subsequence() {
+        let mut seed = 12345u64;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as u32
+        };
+
+        for _ in 0..200 {
+            let (n, m, alphabet) = (next() % 40, next() % 40, next() % 4 + 1);
+            let left: Vec<_> = (0..n).map(|_| next() % alphabet).collect();
+            let right: Vec<_> = (0..m).map(|_| next() % alphabet).collect();
+
+            let pairs = align(&left, &right);
+            assert_eq!(pairs.len(), lcs_length(&left, &right));
+            for (k, &(i, j)) in pairs.iter().enumerate() {
+                assert_eq!(left[i], right[j]);
+                if k > 0 {
+                    assert!(i > pairs[k - 1].0 && j > pairs[k - 1].1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pointers_compare_by_pointee() {
+        let left = timeline(vec![
+            (1, vec![(8, pointer(1000)), (1000, api::Value::Integer(5))], vec![1000]),
+        ]);
+        let right = timeline(vec![
+            (1, vec![(8, pointer(2000)), (2000, api::Value::Integer(5))], vec![2000]),
+        ]);
+        let result = diff(&left, &right, &[8], 0);
+        assert!(result.differences.is_empty());
+
+        let right = timeline(vec![
+            (1, vec![(8, pointer(2000)), (2000, api::Value::Integer(6))], vec![2000]),
+        ]);
+        let result = diff(&left, &right, &[8], 0);
+        assert_eq!(result.differences.len(), 1);
+        assert!(result.differences[0].variables.contains_key(&8));
+    }
+
+    #[test]
+    fn scalars_compare_directly() {
+        // an integer equal to another variable's key is not a pointer to it
+        let left = timeline(vec![
+            (1, vec![(8, api::Value::Integer(16)), (16, api::Value::Integer(1))], vec![]),
+        ]);
+        let right = timeline(vec![
+            (1, vec![(8, api::Value::Integer(16)), (16, api::Value::Integer(2))], vec![]),
+        ]);
+        let result = diff(&left, &right, &[8, 16], 0);
+        assert_eq!(result.differences.len(), 1);
+        let variables: Vec<_> = result.differences[0].variables.keys().cloned().collect();
+        assert_eq!(variables, vec![16]);
+
+        // nor is a decimal string that wasn't traced as a pointer
+        let left = timeline(vec![
+            (1, vec![(8, pointer(16)), (16, api::Value::Integer(1))], vec![]),
+        ]);
+        let right = timeline(vec![
+            (1, vec![(8, pointer(24)), (24, api::Value::Integer(1))], vec![]),
+        ]);
+        let result = diff(&left, &right, &[8], 0);
+        assert_eq!(result.differences.len(), 1);
+    }
+
+    #[test]
+    fn return_data_compares_by_pointee() {
+        let left = returned(
+            timeline(vec![(1, vec![(8, pointer(3000))], vec![])]),
+            pointer(1000), vec![(1000, api::Value::Integer(5)), (3000, api::Value::Integer(1))]
+        );
+        let right = returned(
+            timeline(vec![(1, vec![(8, pointer(4000))], vec![])]),
+            pointer(2000), vec![(2000, api::Value::Integer(5)), (4000, api::Value::Integer(1))]
+        );
+        let result = diff(&left, &right, &[8], 1);
+        assert!(result.return_value.is_none());
+        assert!(result.return_data.is_empty());
+
+        // the out-parameter's value differs, and is reported once under the left address
+        let right = returned(
+            timeline(vec![(1, vec![(8, pointer(4000))], vec![])]),
+            pointer(2000), vec![(2000, api::Value::Integer(5)), (4000, api::Value::Integer(2))]
+        );
+        let result = diff(&left, &right, &[8], 1);
+        assert!(result.return_value.is_none());
+        let addresses: Vec<_> = result.return_data.keys().cloned().collect();
+        assert_eq!(addresses, vec![3000]);
+        let difference = &result.return_data[&3000];
+        assert!(difference.left == Some(api::Value::Integer(1)));
+        assert!(difference.right == Some(api::Value::Integer(2)));
+    }
+
+    #[test]
+    fn divergent_lines() {
+        let left = timeline(vec![
+            (1, vec![], vec![]), (2, vec![], vec![]), (3, vec![], vec![]), (4, vec![], vec![]),
+        ]);
+        let right = timeline(vec![(1, vec![], vec![]), (4, vec![], vec![])]);
+        let result = diff(&left, &right, &[], 0);
+        assert_eq!(result.divergences.len(), 1);
+        let lines: Vec<_> = result.divergences[0].left.iter().map(|step| step.line).collect();
+        assert_eq!(lines, vec![2, 3]);
+        assert!(result.divergences[0].right.is_empty());
+    }
+}
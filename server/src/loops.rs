@@ -0,0 +1,256 @@
+use api;
+
+/// Finds the loops in a function's trace, from the lines it jumps back to, as it is streamed
+///
+/// A jump from one line back to an earlier one is taken as a loop's back edge, whose body runs
+/// from the earlier line to the furthest line jumped back from. Running a line outside the body
+/// leaves the loop, and reaching its first line again from outside enters it anew.
+///
+/// A loop isn't known until its first back edge, so the lines of its first iteration are streamed
+/// without it. Its iterations are still recorded from the start, by looking back over the lines
+/// already run.
+pub struct LoopTracker {
+    loops: Vec<Loop>,
+    /// The loops being run, outermost first
+    active: Vec<Run>,
+    /// Every line traced so far, with its trace index
+    history: Vec<(i32, u32)>,
+}
+
+/// A loop found in a trace, and all of its iterations in order
+#[derive(Serialize, Deserialize)]
+pub struct Loop {
+    /// The line jumped back to
+    pub header: u32,
+    /// The furthest line jumped back from
+    pub end: u32,
+    /// The loop this one is nested in
+    pub parent: Option<usize>,
+    pub iterations: Vec<Iteration>,
+}
+
+/// The trace indices of the first and last lines of an iteration
+#[derive(Serialize, Deserialize)]
+pub struct Iteration {
+    /// Counted from 1 each time the loop is entered
+    pub iteration: usize,
+    pub start: i32,
+    pub end: i32,
+}
+
+struct Run {
+    id: usize,
+    iteration: usize,
+    start: i32,
+}
+
+impl LoopTracker {
+    pub fn new() -> LoopTracker {
+        LoopTracker { loops: vec![], active: vec![], history: vec![] }
+    }
+
+    /// Follow the next line of the function, returning the loops it is in, outermost first
+    pub fn step(&mut self, index: i32, line: u32) -> Vec<api::LoopIteration> {
+        let previous = self.history.last().cloned();
+        let last_index = previous.map(|(index, _)| index).unwrap_or(index);
+
+        while self.active.last().map(|run| !self.loops[run.id].contains(line)).unwrap_or(false) {
+            let run = self.active.pop().unwrap();
+            self.close(run, last_index);
+        }
+
+        match previous {
+            Some((_, from)) if line < from => self.jump_back(index, line, from),
+            _ => self.enter(index, line),
+        }
+
+        self.history.push((index, line));
+        self.active.iter()
+            .map(|run| api::LoopIteration { loop_id: run.id, iteration: run.iteration })
+            .collect()
+    }
+
+    /// End every loop still being run, giving all the loops found
+    pub fn finish(mut self) -> Vec<Loop> {
+        let last_index = self.history.last().map(|&(index, _)| index).unwrap_or(0);
+        while let Some(run) = self.active.pop() {
+            self.close(run, last_index);
+        }
+
+        self.loops
+    }
+
+    /// Start the next iteration of the loop jumped back into, finding the loop if it is new
+    fn jump_back(&mut self, index: i32, header: u32, from: u32) {
+        let last_index = self.history.last().map(|&(index, _)| index).unwrap();
+
+        let current = self.active.last()
+            .map(|run| self.loops[run.id].header == header)
+            .unwrap_or(false);
+        if !current {
+            self.resume(header, from);
+        }
+
+        let Run { id, iteration, start } = self.active.pop().unwrap();
+        if from > self.loops[id].end {
+            self.loops[id].end = from;
+        }
+
+        self.loops[id].iterations.push(Iteration { iteration, start, end: last_index });
+        self.active.push(Run { id, iteration: iteration + 1, start: index });
+    }
+
+    /// Look back over the lines just run for the loop being jumped back into, and continue it
+    fn resume(&mut self, header: u32, from: u32) {
+        let parent = self.active.last().map(|run| run.id);
+
+        // the lines just run since leaving the code before the loop, which fall in its body or
+        // in loops nested in it
+        let window = self.history.iter().rev()
+            .take_while(|&&(_, line)| header <= line)
+            .count();
+        let window = &self.history[self.history.len() - window..];
+        let window_start = window.first().map(|&(index, _)| index).unwrap();
+
+        let known = self.loops.iter().position(|lp| lp.header == header && lp.parent == parent);
+        let id = match known {
+            // left by running a line past the end of the body as known so far
+            Some(id) if self.loops[id].iterations.last()
+                .map(|iteration| iteration.end >= window_start)
+                .unwrap_or(false) =>
+            {
+                let Iteration { iteration, start, .. } = self.loops[id].iterations.pop().unwrap();
+                self.active.push(Run { id, iteration, start });
+                return;
+            }
+
+            Some(id) => id,
+            None => {
+                self.loops.push(Loop { header, end: from, parent, iterations: vec![] });
+                self.loops.len() - 1
+            }
+        };
+
+        // loops already found in the body are nested in this one
+        for (other, lp) in self.loops.iter_mut().enumerate() {
+            if other != id && lp.parent == parent && header < lp.header && lp.header <= from {
+                lp.parent = Some(id);
+            }
+        }
+
+        let start = window.iter()
+            .find(|&&(_, line)| line == header)
+            .map(|&(index, _)| index)
+            .unwrap_or(window_start);
+        self.active.push(Run { id, iteration: 1, start });
+    }
+
+    /// Enter a known loop by reaching its first line from outside it
+    fn enter(&mut self, index: i32, line: u32) {
+        let parent = self.active.last().map(|run| run.id);
+        if self.active.last().map(|run| self.loops[run.id].header == line).unwrap_or(false) {
+            return;
+        }
+
+        let known = self.loops.iter().position(|lp| lp.header == line && lp.parent == parent);
+        if let Some(id) = known {
+            self.active.push(Run { id, iteration: 1, start: index });
+        }
+    }
+
+    fn close(&mut self, run: Run, end: i32) {
+        let iteration = Iteration { iteration: run.iteration, start: run.start, end };
+        self.loops[run.id].iterations.push(iteration);
+    }
+}
+
+impl Loop {
+    fn contains(&self, line: u32) -> bool {
+        self.header <= line && line <= self.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Track a function that runs `lines` in order, giving the loop and iteration of each line
+    /// that is in a loop, and the loops found
+    fn track(lines: &[u32]) -> (Vec<Vec<(usize, usize)>>, Vec<Loop>) {
+        let mut tracker = LoopTracker::new();
+        let active = lines.iter().enumerate()
+            .map(|(index, &line)| {
+                tracker.step(index as i32 + 1, line).iter()
+                    .map(|lp| (lp.loop_id, lp.iteration))
+                    .collect()
+            })
+            .collect();
+
+        (active, tracker.finish())
+    }
+
+    fn iterations(lp: &Loop) -> Vec<(usize, i32, i32)> {
+        lp.iterations.iter()
+            .map(|iteration| (iteration.iteration, iteration.start, iteration.end))
+            .collect()
+    }
+
+    #[test]
+    fn simple_loop() {
+        let (active, loops) = track(&[1, 2, 3, 2, 3, 2, 5]);
+
+        assert_eq!(active, vec![
+            vec![], vec![], vec![], vec![(0, 2)], vec![(0, 2)], vec![(0, 3)], vec![],
+        ]);
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].header, loops[0].end, loops[0].parent), (2, 3, None));
+        assert_eq!(iterations(&loops[0]), vec![(1, 2, 3), (2, 4, 5), (3, 6, 6)]);
+    }
+
+    #[test]
+    fn nested_loops() {
+        let (active, loops) = track(&[1, 2, 3, 4, 3, 4, 3, 2, 3, 4, 3, 2, 5]);
+
+        assert_eq!(loops.len(), 2);
+        let (outer, inner) = if loops[0].header == 2 { (0, 1) } else { (1, 0) };
+        assert_eq!((loops[outer].header, loops[outer].end, loops[outer].parent), (2, 3, None));
+        assert_eq!(
+            (loops[inner].header, loops[inner].end, loops[inner].parent), (3, 4, Some(outer))
+        );
+
+        assert_eq!(iterations(&loops[outer]), vec![(1, 2, 7), (2, 8, 11), (3, 12, 12)]);
+        let expected = vec![(1, 3, 4), (2, 5, 6), (3, 7, 7), (1, 9, 10), (2, 11, 11)];
+        assert_eq!(iterations(&loops[inner]), expected);
+
+        assert_eq!(active[7], vec![(outer, 2)]);
+        assert_eq!(active[8], vec![(outer, 2), (inner, 1)]);
+        assert_eq!(active[10], vec![(outer, 2), (inner, 2)]);
+        assert_eq!(active[12], vec![]);
+    }
+
+    #[test]
+    fn body_grows_after_first_iteration() {
+        let (active, loops) = track(&[1, 2, 3, 2, 3, 4, 2, 5]);
+
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].header, loops[0].end), (2, 4));
+        assert_eq!(iterations(&loops[0]), vec![(1, 2, 3), (2, 4, 6), (3, 7, 7)]);
+        assert_eq!(active[6], vec![(0, 3)]);
+        assert_eq!(active[7], vec![]);
+    }
+
+    #[test]
+    fn reentered_loop_counts_from_one() {
+        // the loop at line 3 runs twice, within two iterations of the loop at line 2
+        let (active, loops) = track(&[1, 2, 3, 4, 3, 5, 2, 3, 4, 3, 4, 3, 5, 6]);
+
+        assert_eq!(loops.len(), 2);
+        let inner = loops.iter().position(|lp| lp.header == 3).unwrap();
+        let outer = 1 - inner;
+        assert_eq!(iterations(&loops[outer]), vec![(1, 2, 6), (2, 7, 13)]);
+        let expected = vec![(1, 3, 4), (2, 5, 5), (1, 8, 9), (2, 10, 11), (3, 12, 12)];
+        assert_eq!(iterations(&loops[inner]), expected);
+        assert_eq!(active[7], vec![(outer, 2), (inner, 1)]);
+        assert_eq!(active[13], vec![]);
+    }
+}
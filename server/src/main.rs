@@ -18,20 +18,23 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate toml;
+extern crate xml;
 
 extern crate debug;
-extern crate winapi;
-extern crate kernel32;
+#[cfg(windows)] extern crate winapi;
+#[cfg(windows)] extern crate kernel32;
 
 #[macro_use]
 extern crate lazy_static;
 
-use std::{io, fs};
+use std::{io, fs, iter};
 use std::sync::{Mutex, Arc};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::io::{Write};
+use std::io::{Read, Write};
 use std::path::Path;
 use std::ffi::OsStr;
+use std::time::UNIX_EPOCH;
 use std::collections::HashMap;
 
 use hyper::status::StatusCode;
@@ -40,16 +43,35 @@ use reroute::{RouterBuilder, Captures};
 use mime_guess::guess_mime_type;
 
 use child::{ServerMessage, DebugMessage, DebugTrace};
+use archive::{Archive, Recording};
 
+mod archive;
 mod child;
+mod diff;
+mod loops;
 mod trace;
+mod timeline;
 mod value;
 mod api;
 
-/// The HTTP server is multithreaded, and thus shares access to the debug thread via an
+/// The HTTP server is multithreaded, and thus shares access to each debug thread via an
 /// `Arc<Mutex<T>>`
+///
+/// The thread is taken out when its session ends, so requests that looked up the session just
+/// before then find `None`.
 type ChildThread = Arc<Mutex<Option<child::Thread>>>;
-type ChildCancel = Arc<Mutex<Option<Cancel>>>;
+
+/// Live debug sessions, keyed by the `debugId` allocated in `child::Thread::spawn`
+///
+/// This lock is only held long enough to look up a session, never while locking its thread.
+type Sessions = Arc<Mutex<HashMap<usize, Session>>>;
+
+/// A debug session's thread, cancellation channel, and the process it was attached to
+struct Session {
+    child: ChildThread,
+    cancel: Arc<Mutex<Cancel>>,
+    process: api::Process,
+}
 
 /// The `child::Thread` lock is held during a trace, so this side channel is used to signal for
 /// cancellation.
@@ -61,9 +83,12 @@ struct Cancel {
 }
 
 fn main() {
-    // current child thread (only one at a time)
-    let child_thread = Arc::new(Mutex::new(None));
-    let child_cancel = Arc::new(Mutex::new(None));
+    // current debug sessions
+    let debug_sessions: Sessions = Arc::new(Mutex::new(HashMap::new()));
+
+    // finished executions, which outlive their sessions and the server
+    let execution_archive = Arc::new(Archive::open());
+    child::start_sessions_at(execution_archive.next_session());
 
     let mut router = RouterBuilder::new();
 
@@ -87,33 +112,41 @@ fn main() {
 
     // attaching
 
-    let child = child_thread.clone();
-    let cancel = child_cancel.clone();
+    let sessions = debug_sessions.clone();
     router.post(r"/api/v1/debug/attach/pid/([0-9]*)", move |req, res, caps| {
-        match debug_attach_pid(caps, child.clone(), cancel.clone()) {
+        match debug_attach_pid(caps, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
-    let cancel = child_cancel.clone();
-    router.post(r"/api/v1/debug/attach/bin/(.*)", move |req, res, caps| {
-        match debug_attach_bin(caps, child.clone(), cancel.clone()) {
+    let sessions = debug_sessions.clone();
+    router.post(r"/api/v1/debug/attach/bin/(.*)", move |mut req, res, caps| {
+        let body = match read_launch(&mut req) {
+            Ok(body) => body,
+            Err(e) => {
+                send_error(req, res, e).unwrap();
+                return
+            }
+        };
+
+        match debug_attach_bin(caps, body, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
     router.get(r"/api/v1/debug", move |req, res, caps| {
-        debug(req, res, caps, child.clone());
+        match debug(caps, &sessions) {
+            Ok(body) => send(req, res, &body),
+            Err(e) => send_error(req, res, e),
+        }.unwrap();
     });
 
-    let child = child_thread.clone();
-    let cancel = child_cancel.clone();
+    let sessions = debug_sessions.clone();
     router.post(r"/api/v1/debug/([0-9]*)/kill", move |req, res, caps| {
-        match debug_kill(caps, child.clone(), cancel.clone()) {
+        match debug_kill(caps, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
@@ -121,17 +154,18 @@ fn main() {
 
     // functions
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
     router.get(r"/api/v1/debug/([0-9]*)/functions", move |req, res, caps| {
-        match debug_functions(caps, child.clone()) {
+        match debug_functions(caps, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
+    let archive = execution_archive.clone();
     router.get(r"/api/v1/debug/([0-9]*)/functions/(.*)", move |req, res, caps| {
-        match debug_function(caps, child.clone()) {
+        match debug_function(caps, &sessions, &archive) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
@@ -139,9 +173,10 @@ fn main() {
 
     // types
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
+    let archive = execution_archive.clone();
     router.get(r"/api/v1/debug/([0-9]*)/types\?ids=([0-9]+(?:,[0-9]+)*)", move |req, res, caps| {
-        match debug_types(caps, child.clone()) {
+        match debug_types(caps, &sessions, &archive) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
@@ -149,35 +184,43 @@ fn main() {
 
     // breakpoints
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
     router.get(r"/api/v1/debug/([0-9]*)/breakpoints", move |req, res, caps| {
-        match debug_breakpoints(caps, child.clone()) {
+        match debug_breakpoints(caps, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
     router.put(r"/api/v1/debug/([0-9]*)/breakpoints/([0-9]*)", move |req, res, caps| {
-        match debug_breakpoint_put(caps, child.clone()) {
+        match debug_breakpoint_put(caps, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
     router.delete(r"/api/v1/debug/([0-9]*)/breakpoints/([0-9]*)", move |req, res, caps| {
-        match debug_breakpoint_delete(caps, child.clone()) {
+        match debug_breakpoint_delete(caps, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    // executions
+    // array views
+
+    let sessions = debug_sessions.clone();
+    router.get(r"/api/v1/debug/([0-9]*)/arrays/([0-9]*)", move |req, res, caps| {
+        match debug_arrays(caps, &sessions) {
+            Ok(body) => send(req, res, &body),
+            Err(e) => send_error(req, res, e),
+        }.unwrap();
+    });
 
-    let child = child_thread.clone();
-    router.post(r"/api/v1/debug/([0-9]*)/execute", move |mut req, res, caps| {
-        let body: api::Launch = match serde_json::from_reader(&mut req) {
+    let sessions = debug_sessions.clone();
+    router.put(r"/api/v1/debug/([0-9]*)/arrays/([0-9]*)", move |mut req, res, caps| {
+        let body: Vec<api::ArrayView> = match serde_json::from_reader(&mut req) {
             Ok(body) => body,
             Err(e) => {
                 send_error(req, res, io::Error::new(io::ErrorKind::InvalidInput, e)).unwrap();
@@ -185,13 +228,23 @@ fn main() {
             }
         };
 
-        match debug_execute(caps, body, child.clone()) {
+        match debug_arrays_put(caps, body, &sessions) {
+            Ok(body) => send(req, res, &body),
+            Err(e) => send_error(req, res, e),
+        }.unwrap();
+    });
+
+    // executions
+
+    let sessions = debug_sessions.clone();
+    router.post(r"/api/v1/debug/([0-9]*)/execute", move |req, res, caps| {
+        match debug_execute(caps, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
     router.post(r"/api/v1/debug/([0-9]*)/functions/([0-9]*)/execute", move |mut req, res, caps| {
         let body: api::Call = match serde_json::from_reader(&mut req) {
             Ok(body) => body,
@@ -201,42 +254,55 @@ fn main() {
             }
         };
 
-        match debug_function_execute(caps, body, child.clone()) {
+        match debug_function_execute(caps, body, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
+    let archive = execution_archive.clone();
     router.get(r"/api/v1/debug/([0-9]*)/executions", move |req, res, caps| {
-        match debug_executions(caps, child.clone()) {
+        match debug_executions(caps, &sessions, &archive) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
+    let sessions = debug_sessions.clone();
+    let archive = execution_archive.clone();
     router.get(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)", move |req, res, caps| {
-        match debug_execution(caps, child.clone()) {
+        match debug_execution(caps, &sessions, &archive) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
     });
 
-    let child = child_thread.clone();
-    let cancel = child_cancel.clone();
-    router.get(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)/trace", move |mut req, mut res, caps| {
-        let mut child_thread = child.lock().unwrap();
-        if child_thread.is_none() {
-            let e = io::Error::from(io::ErrorKind::NotConnected);
-            return send_error(req, res, e).unwrap();
+    let sessions = debug_sessions.clone();
+    let archive = execution_archive.clone();
+    router.get(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)/trace(?:\?(?:stringLength=([0-9]+))?&?(?:fill=(cc|cd|fe|none))?)?", move |mut req, mut res, caps| {
+        // finished executions are replayed from the archive
+        if let Ok(trace) = archived_trace(caps.clone(), &archive) {
+            return send(req, res, &trace).unwrap();
         }
 
-        let _ = match debug_execution_trace(caps, child_thread.as_ref().unwrap()) {
-            Ok(execution) => execution,
+        let trace = debug_execution_trace(caps.clone(), &sessions);
+        let (child, execution, string_length, fill) = match trace {
+            Ok(trace) => trace,
             Err(e) => return send_error(req, res, e).unwrap(),
         };
 
+        let mut child_thread = child.lock().unwrap();
+        let mut recording = match trace_execution(&child_thread, execution) {
+            Ok(recording) => recording,
+
+            // the execution may have finished while waiting for the lock
+            Err(e) => return match archived_trace(caps, &archive) {
+                Ok(trace) => send(req, res, &trace),
+                Err(_) => send_error(req, res, e),
+            }.unwrap(),
+        };
+
         io::copy(&mut req, &mut io::sink()).unwrap();
 
         {
@@ -248,13 +314,15 @@ fn main() {
         }
 
         let mut res = res.start().unwrap();
-        let terminated = match trace_stream(&mut res, child_thread.as_mut().unwrap()) {
+        let terminated = match trace_stream(
+            &mut res, child_thread.as_mut().unwrap(), &mut recording, string_length, fill
+        ) {
             Ok(terminated) => terminated,
             Err(e) => {
                 let error = api::Error { message: format!("{:?}", e) };
                 let data = api::TraceData::Error { error: error };
                 let message = api::Trace { index: 0, line: 0, data: data };
-                serde_json::to_writer(&mut res, &message).unwrap();
+                res.write_all(recording.push(&message)).unwrap();
 
                 res.write_all(b"\n]").unwrap();
                 false
@@ -262,21 +330,62 @@ fn main() {
         };
         res.end().unwrap();
 
-        if terminated {
-            let mut child_cancel = cancel.lock().unwrap();
+        // archiving is best-effort; the trace has already been streamed either way
+        let _ = archive.save(child_thread.as_ref().unwrap(), recording);
 
+        if terminated {
             if let Some(child) = child_thread.take() {
+                sessions.lock().unwrap().remove(&child.session);
+
                 child.tx.send(ServerMessage::Quit).unwrap();
                 child.thread.join().unwrap();
-
-                child_cancel.take().unwrap();
             }
         }
     });
 
-    let cancel = child_cancel.clone();
+    let archive = execution_archive.clone();
+    router.get(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)/state/([0-9]*)", move |req, res, caps| {
+        match debug_execution_state(caps, &archive) {
+            Ok(body) => send(req, res, &body),
+            Err(e) => send_error(req, res, e),
+        }.unwrap();
+    });
+
+    let archive = execution_archive.clone();
+    router.get(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)/changes/([0-9]*)", move |req, res, caps| {
+        match debug_execution_changes(caps, &archive) {
+            Ok(body) => send(req, res, &body),
+            Err(e) => send_error(req, res, e),
+        }.unwrap();
+    });
+
+    let archive = execution_archive.clone();
+    router.get(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)/changes/([0-9]*)/before/([0-9]*)", move |req, res, caps| {
+        match debug_execution_last_change(caps, &archive) {
+            Ok(body) => send(req, res, &body),
+            Err(e) => send_error(req, res, e),
+        }.unwrap();
+    });
+
+    let archive = execution_archive.clone();
+    router.get(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)/loops", move |req, res, caps| {
+        match debug_execution_loops(caps, &archive) {
+            Ok(body) => send(req, res, &body),
+            Err(e) => send_error(req, res, e),
+        }.unwrap();
+    });
+
+    let archive = execution_archive.clone();
+    router.get(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)/diff/([0-9]*)", move |req, res, caps| {
+        match debug_execution_diff(caps, &archive) {
+            Ok(body) => send(req, res, &body),
+            Err(e) => send_error(req, res, e),
+        }.unwrap();
+    });
+
+    let sessions = debug_sessions.clone();
     router.post(r"/api/v1/debug/([0-9]*)/executions/([0-9]*)/stop", move |req, res, caps| {
-        match debug_execution_stop(caps, cancel.clone()) {
+        match debug_execution_stop(caps, &sessions) {
             Ok(body) => send(req, res, &body),
             Err(e) => send_error(req, res, e),
         }.unwrap();
@@ -327,6 +436,17 @@ fn send(mut req: Request, mut res: Response, body: &[u8]) -> io::Result<()> {
     res.send(body)
 }
 
+/// Parse launch options from a request body, which may be empty to use the defaults
+fn read_launch(req: &mut Request) -> io::Result<api::Launch> {
+    let mut body = vec![];
+    req.read_to_end(&mut body)?;
+    if body.is_empty() {
+        return Ok(api::Launch::default());
+    }
+
+    serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
 /// Send JSON error messages to the client
 fn send_error(req: Request, mut res: Response, error: io::Error) -> io::Result<()> {
     *res.status_mut() = status_from_error(error.kind());
@@ -350,15 +470,29 @@ fn status_from_error(error: io::ErrorKind) -> StatusCode {
 /// Gets the list of processes running on the host machine
 fn processes(_: Captures) -> io::Result<Vec<u8>> {
     let procs: Vec<_> = debug::Process::running()?
-        .map(|debug::Process { id, name }| api::Process {
-            id: id,
-            name: name.to_string_lossy().into()
-        })
+        .map(api_process)
         .collect();
 
     Ok(serde_json::to_vec(&procs).unwrap())
 }
 
+fn api_process(process: debug::Process) -> api::Process {
+    let start_time = process.start_time
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_secs() * 1000 + time.subsec_nanos() as u64 / 1_000_000);
+
+    api::Process {
+        id: process.id,
+        name: process.name.to_string_lossy().into(),
+        parent_id: process.parent_id,
+        command_line: process.command_line.map(|command| command.to_string_lossy().into()),
+        path: process.path.map(|path| path.to_string_lossy().into()),
+        owner: process.owner.map(|owner| owner.to_string_lossy().into()),
+        start_time: start_time,
+        attachable: process.attachable,
+    }
+}
+
 /// GET /filesystem/:path*
 /// Gets the file(s) within the given path
 fn filesystem(caps: Captures) -> io::Result<Vec<u8>> {
@@ -367,17 +501,7 @@ fn filesystem(caps: Captures) -> io::Result<Vec<u8>> {
     let path = path.decode_utf8_lossy().into_owned();
 
     if path.is_empty() {
-        let drives = unsafe { kernel32::GetLogicalDrives() };
-
-        let mut contents = vec![];
-        for drive in (0..26).filter(|i| (drives & (1u32 << i)) != 0).map(|i| b'A' + i) {
-            let path = format!("{}:/", drive as char);
-            let name = format!("{}:", drive as char);
-            let data = api::FileData::Directory { contents: None };
-
-            contents.push(api::File { name, path, data });
-        }
-        let data = api::FileData::Directory { contents: Some(contents) };
+        let data = api::FileData::Directory { contents: Some(filesystem_roots()) };
 
         let message = api::File {
             name: "".into(),
@@ -462,129 +586,162 @@ fn file(mut req: Request, mut res: Response, caps: Captures) {
     }
 }
 
+/// List the roots of the host's filesystem
+#[cfg(windows)]
+fn filesystem_roots() -> Vec<api::File> {
+    let drives = unsafe { kernel32::GetLogicalDrives() };
+
+    let mut contents = vec![];
+    for drive in (0..26).filter(|i| (drives & (1u32 << i)) != 0).map(|i| b'A' + i) {
+        let path = format!("{}:/", drive as char);
+        let name = format!("{}:", drive as char);
+        let data = api::FileData::Directory { contents: None };
+
+        contents.push(api::File { name, path, data });
+    }
+    contents
+}
+
+/// List the roots of the host's filesystem
+#[cfg(unix)]
+fn filesystem_roots() -> Vec<api::File> {
+    let data = api::FileData::Directory { contents: None };
+    vec![api::File { name: "/".into(), path: "/".into(), data }]
+}
+
 /// POST /debug/attach/pid/:pid
 /// attach to a running process
-fn debug_attach_pid(caps: Captures, child: ChildThread, cancel: ChildCancel) -> io::Result<Vec<u8>> {
+fn debug_attach_pid(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let pid = caps[1].parse::<u32>()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
-
-    let mut child_thread = child.lock().unwrap();
-    let mut child_cancel = cancel.lock().unwrap();
-    if let Some(child) = child_thread.take() {
-        child.tx.send(ServerMessage::Quit).unwrap();
-        child.thread.join().unwrap();
-
-        child_cancel.take().unwrap();
-    }
+    let process = api_process(debug::Process::get(pid)?);
 
     let (child, flag) = child::Thread::attach(pid);
     let debug_id = child.session;
-    match child.rx.recv().unwrap() {
-        DebugMessage::Attached(cancel) => {
-            *child_thread = Some(child);
-            *child_cancel = Some(Cancel { cancel, flag });
-        }
-
+    let cancel = match child.rx.recv().unwrap() {
+        DebugMessage::Attached(cancel) => Cancel { cancel, flag },
         DebugMessage::Error(e) => return Err(e),
         _ => unreachable!(),
-    }
+    };
 
-    // TODO: get process name
     let message = api::DebugInfo {
         id: debug_id,
-        attached_process: api::Process {
-            id: pid,
-            name: String::new(),
-        },
+        attached_process: process.clone(),
+    };
+
+    let session = Session {
+        child: Arc::new(Mutex::new(Some(child))),
+        cancel: Arc::new(Mutex::new(cancel)),
+        process: process,
     };
+    sessions.lock().unwrap().insert(debug_id, session);
+
     Ok(serde_json::to_vec(&message).unwrap())
 }
 
 /// POST /debug/attach/bin/:path
 /// attach to a binary
-fn debug_attach_bin(caps: Captures, child: ChildThread, cancel: ChildCancel) -> io::Result<Vec<u8>> {
+fn debug_attach_bin(
+    caps: Captures, launch: api::Launch, sessions: &Sessions
+) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let path = url::percent_encoding::percent_decode(caps[1].as_bytes());
     let path = path.decode_utf8_lossy().into_owned();
     let path = Path::new(&path);
 
-    let mut child_thread = child.lock().unwrap();
-    let mut child_cancel = cancel.lock().unwrap();
-    if let Some(child) = child_thread.take() {
-        child.tx.send(ServerMessage::Quit).unwrap();
-        child.thread.join().unwrap();
-
-        child_cancel.take().unwrap();
-    }
-
-    let (child, flag) = child::Thread::launch(path.into());
+    let command_line = iter::once(path.to_string_lossy().into_owned())
+        .chain(launch.args.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (child, flag) = child::Thread::launch(path.into(), launch);
     let debug_id = child.session;
-    match child.rx.recv().unwrap() {
-        DebugMessage::Attached(cancel) => {
-            *child_thread = Some(child);
-            *child_cancel = Some(Cancel { cancel, flag });
-        }
-
+    let cancel = match child.rx.recv().unwrap() {
+        DebugMessage::Attached(cancel) => Cancel { cancel, flag },
         DebugMessage::Error(e) => return Err(e),
         _ => unreachable!(),
-    }
+    };
 
     let name = path.file_name().unwrap_or(OsStr::new(""));
+    let process = api::Process {
+        id: 0,
+        name: name.to_string_lossy().into(),
+        parent_id: 0,
+        command_line: Some(command_line),
+        path: Some(path.to_string_lossy().into()),
+        owner: None,
+        start_time: None,
+        attachable: false,
+    };
+
     let message = api::DebugInfo {
         id: debug_id,
-        attached_process: api::Process {
-            id: 0,
-            name: name.to_string_lossy().into(),
-        },
+        attached_process: process.clone(),
     };
+
+    let session = Session {
+        child: Arc::new(Mutex::new(Some(child))),
+        cancel: Arc::new(Mutex::new(cancel)),
+        process: process,
+    };
+    sessions.lock().unwrap().insert(debug_id, session);
+
     Ok(serde_json::to_vec(&message).unwrap())
 }
 
+/// Look up the debug thread of a live session
+fn session_child(sessions: &Sessions, debug_id: usize) -> io::Result<ChildThread> {
+    let sessions = sessions.lock().unwrap();
+    sessions.get(&debug_id)
+        .map(|session| session.child.clone())
+        .ok_or(io::Error::new(io::ErrorKind::NotConnected, "no such session"))
+}
+
 /// GET /debug
-/// Returns information about the current debug session
-fn debug(req: Request, mut res: Response, _: Captures, _child: ChildThread) {
-    *res.status_mut() = StatusCode::NotImplemented;
-    send(req, res, b"").unwrap();
+/// Returns information about the live debug sessions
+fn debug(_: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
+    let sessions = sessions.lock().unwrap();
+
+    let mut message: Vec<_> = sessions.iter()
+        .map(|(&id, session)| api::DebugInfo {
+            id: id,
+            attached_process: session.process.clone(),
+        })
+        .collect();
+    message.sort_by_key(|info| info.id);
+    Ok(serde_json::to_vec(&message).unwrap())
 }
 
 /// POST /debug/:id/kill
 /// Kills the running proceses and detaches the debugger.  Invalidates this `debugId`
-fn debug_kill(caps: Captures, child: ChildThread, cancel: ChildCancel) -> io::Result<Vec<u8>> {
+fn debug_kill(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
 
-    let mut child_thread = child.lock().unwrap();
-    let child = child_thread.take()
-        .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        *child_thread = Some(child);
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
+    let session = sessions.lock().unwrap().remove(&debug_id)
+        .ok_or(io::Error::new(io::ErrorKind::NotConnected, "no such session"))?;
 
-    child.tx.send(ServerMessage::Quit).unwrap();
-    child.thread.join().unwrap();
-
-    let mut child_cancel = cancel.lock().unwrap();
-    child_cancel.take().unwrap();
+    let mut child_thread = session.child.lock().unwrap();
+    if let Some(child) = child_thread.take() {
+        child.tx.send(ServerMessage::Quit).unwrap();
+        child.thread.join().unwrap();
+    }
 
     Ok(vec![])
 }
 
 /// GET /debug/:id/functions
 /// return a list of debuggable functions in the attached process
-fn debug_functions(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_functions(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
 
+    let child = session_child(sessions, debug_id)?;
     let child = child.lock().unwrap();
     let child = child.as_ref()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
     child.tx.send(ServerMessage::ListFunctions).unwrap();
     let message = match child.rx.recv().unwrap() {
@@ -597,19 +754,21 @@ fn debug_functions(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
 
 /// GET /debug/:id/functions/:function
 /// Returns information about the function, including source file path and input parameter types
-fn debug_function(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_function(caps: Captures, sessions: &Sessions, archive: &Archive) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
     let address = caps[2].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
+    // ended sessions can still describe the functions their archived executions refer to
+    let child = match session_child(sessions, debug_id) {
+        Ok(child) => child,
+        Err(_) => return Ok(serde_json::to_vec(&archive.function(debug_id, address)?).unwrap()),
+    };
     let child = child.lock().unwrap();
     let child = child.as_ref()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
     child.tx.send(ServerMessage::DescribeFunction { address }).unwrap();
     let message = match child.rx.recv().unwrap() {
@@ -622,19 +781,21 @@ fn debug_function(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
 
 /// GET /debug/:id/types?ids=:id,:id,:id,...
 /// List type definitions
-fn debug_types(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_types(caps: Captures, sessions: &Sessions, archive: &Archive) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
     let types: Result<Vec<u32>, _> = caps[2].split(',').map(str::parse).collect();
     let types = types.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
+    // ended sessions can still describe the types their archived executions refer to
+    let child = match session_child(sessions, debug_id) {
+        Ok(child) => child,
+        Err(_) => return Ok(serde_json::to_vec(&archive.types(debug_id, &types)?).unwrap()),
+    };
     let child = child.lock().unwrap();
     let child = child.as_ref()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
     child.tx.send(ServerMessage::ListTypes { types }).unwrap();
     let message = match child.rx.recv().unwrap() {
@@ -647,17 +808,15 @@ fn debug_types(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
 
 /// GET /debug/:id/breakpoints
 /// List breakpoints
-fn debug_breakpoints(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_breakpoints(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
 
+    let child = session_child(sessions, debug_id)?;
     let child = child.lock().unwrap();
     let child = child.as_ref()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
     child.tx.send(ServerMessage::ListBreakpoints).unwrap();
     let breakpoints = match child.rx.recv().unwrap() {
@@ -674,19 +833,17 @@ fn debug_breakpoints(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>>
 
 /// PUT /debug/:id/breakpoints/:function
 /// Sets a breakpoint on this function
-fn debug_breakpoint_put(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_breakpoint_put(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
     let address = caps[2].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
+    let child = session_child(sessions, debug_id)?;
     let child = child.lock().unwrap();
     let child = child.as_ref()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
     child.tx.send(ServerMessage::SetBreakpoint { address }).unwrap();
     match child.rx.recv().unwrap() {
@@ -701,19 +858,17 @@ fn debug_breakpoint_put(caps: Captures, child: ChildThread) -> io::Result<Vec<u8
 
 /// DELETE /debug/:id/breakpoints/:function
 /// Removes breakpoint on this function
-fn debug_breakpoint_delete(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_breakpoint_delete(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
     let address = caps[2].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
+    let child = session_child(sessions, debug_id)?;
     let child = child.lock().unwrap();
     let child = child.as_ref()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
     child.tx.send(ServerMessage::ClearBreakpoint { address }).unwrap();
     match child.rx.recv().unwrap() {
@@ -725,19 +880,65 @@ fn debug_breakpoint_delete(caps: Captures, child: ChildThread) -> io::Result<Vec
     Ok(vec![])
 }
 
+/// GET /debug/:id/arrays/:function
+/// List the array views of this function's variables
+fn debug_arrays(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
+    let caps = caps.unwrap();
+    let debug_id = caps[1].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+    let address = caps[2].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let child = session_child(sessions, debug_id)?;
+    let child = child.lock().unwrap();
+    let child = child.as_ref()
+        .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
+
+    child.tx.send(ServerMessage::ListArrays { address }).unwrap();
+    let message = match child.rx.recv().unwrap() {
+        DebugMessage::Arrays(arrays) => arrays,
+        DebugMessage::Error(e) => return Err(e),
+        _ => unreachable!(),
+    };
+    Ok(serde_json::to_vec(&message).unwrap())
+}
+
+/// PUT /debug/:id/arrays/:function
+/// Replaces the array views of this function's variables
+fn debug_arrays_put(
+    caps: Captures, arrays: Vec<api::ArrayView>, sessions: &Sessions
+) -> io::Result<Vec<u8>> {
+    let caps = caps.unwrap();
+    let debug_id = caps[1].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+    let address = caps[2].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let child = session_child(sessions, debug_id)?;
+    let child = child.lock().unwrap();
+    let child = child.as_ref()
+        .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
+
+    child.tx.send(ServerMessage::SetArrays { address, arrays }).unwrap();
+    let message = match child.rx.recv().unwrap() {
+        DebugMessage::Arrays(arrays) => arrays,
+        DebugMessage::Error(e) => return Err(e),
+        _ => unreachable!(),
+    };
+    Ok(serde_json::to_vec(&message).unwrap())
+}
+
 /// POST /debug/:id/execute
 /// Launches the process if it is not running or continues execution until the next breakpoint
-fn debug_execute(caps: Captures, _body: api::Launch, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_execute(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
 
+    let child = session_child(sessions, debug_id)?;
     let mut child = child.lock().unwrap();
     let child = child.as_mut()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
     child.tx.send(ServerMessage::Continue).unwrap();
     let id = match child.rx.recv().unwrap() {
@@ -753,22 +954,20 @@ fn debug_execute(caps: Captures, _body: api::Launch, child: ChildThread) -> io::
 
 /// POST /debug/:id/functions/:function/execute
 /// Calls the function
-fn debug_function_execute(caps: Captures, body: api::Call, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_function_execute(caps: Captures, body: api::Call, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
     let address = caps[2].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
-    let arguments = body.arguments;
+    let api::Call { arguments, callees } = body;
 
+    let child = session_child(sessions, debug_id)?;
     let mut child = child.lock().unwrap();
     let child = child.as_mut()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
-    child.tx.send(ServerMessage::CallFunction { address, arguments }).unwrap();
+    child.tx.send(ServerMessage::CallFunction { address, arguments, callees }).unwrap();
     let id = match child.rx.recv().unwrap() {
         DebugMessage::Executing => child.next_id(),
         DebugMessage::Error(e) => return Err(e),
@@ -781,44 +980,48 @@ fn debug_function_execute(caps: Captures, body: api::Call, child: ChildThread) -
     Ok(serde_json::to_vec(&message).unwrap())
 }
 
-/// POST /debug/:id/executions
-/// Get a list of active executions.  There is only ever one at a time.
-fn debug_executions(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
+/// GET /debug/:id/executions
+/// Get a list of the session's archived executions, followed by its active one if there is one.
+/// Ended sessions still list their archived executions.
+fn debug_executions(caps: Captures, sessions: &Sessions, archive: &Archive) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
 
+    let child = match session_child(sessions, debug_id) {
+        Ok(child) => child,
+        Err(_) => return Ok(serde_json::to_vec(&archive.executions(debug_id)?).unwrap()),
+    };
     let mut child = child.lock().unwrap();
     let child = child.as_mut()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
-    let message: Vec<_> = child.execution.iter()
+    let mut message = archive.executions(debug_id).unwrap_or(vec![]);
+    message.extend(child.execution.iter()
         .map(|&(id, ref execution)| {
             let data = api::ExecutionData::from(execution);
             api::Execution { id: id, data: data }
-        })
-        .collect();
+        }));
     Ok(serde_json::to_vec(&message).unwrap())
 }
 
 /// GET /debug/:id/executions/:execution
 /// Get information about an execution status
-fn debug_execution(caps: Captures, child: ChildThread) -> io::Result<Vec<u8>> {
+fn debug_execution(caps: Captures, sessions: &Sessions, archive: &Archive) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
     let execution_id = caps[2].parse::<i32>()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
+    if let Ok(execution) = archive.execution(debug_id, execution_id) {
+        return Ok(serde_json::to_vec(&execution).unwrap());
+    }
+
+    let child = session_child(sessions, debug_id)?;
     let mut child = child.lock().unwrap();
     let child = child.as_mut()
         .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
 
     let (id, execution) = match child.execution {
         Some((id, ref execution)) if id == execution_id => (id, execution),
@@ -840,43 +1043,82 @@ impl<'a> From<&'a child::Execution> for api::ExecutionData {
     }
 }
 
-/// GET /debug/:id/executions/:execution/trace
+/// GET /debug/:id/executions/:execution/trace?stringLength=:length&fill=:pattern
 /// Get trace data for the execution
-fn debug_execution_trace(caps: Captures, child: &child::Thread) -> io::Result<i32> {
+fn debug_execution_trace(
+    caps: Captures, sessions: &Sessions
+) -> io::Result<(ChildThread, i32, usize, Option<u8>)> {
     let caps = caps.unwrap();
     let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
     let execution = caps[2].parse::<i32>()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let string_length = match caps.get(3).map(|length| &length[..]) {
+        None | Some("") => value::DEFAULT_STRING_LENGTH,
+        Some(length) => length.parse::<usize>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+    };
+    let fill = match caps.get(4).map(|fill| &fill[..]) {
+        None | Some("") => value::DEFAULT_FILL,
+        Some("none") => None,
+        Some(fill) => Some(u8::from_str_radix(fill, 16)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?),
+    };
 
-    if debug_id != child.session {
-        return Err(io::Error::new(io::ErrorKind::NotConnected, "no such session"));
-    }
+    let child = session_child(sessions, debug_id)?;
+    Ok((child, execution, string_length, fill))
+}
+
+/// Check that the execution to be traced is still the session's active one, with its thread locked,
+/// and start recording it for the archive
+fn trace_execution(child: &Option<child::Thread>, execution: i32) -> io::Result<Recording> {
+    let child = child.as_ref()
+        .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
 
     match child.execution {
-        Some((id, _)) if id == execution => Ok(id),
+        Some((id, ref active)) if id == execution => {
+            let data = api::ExecutionData::from(active);
+            Ok(Recording::new(api::Execution { id: id, data: data }))
+        }
         _ => Err(io::Error::new(io::ErrorKind::NotFound, "no such execution")),
     }
 }
 
+/// Replay the traces of an archived execution, which ignores the `stringLength` and `fill` options
+fn archived_trace(caps: Captures, archive: &Archive) -> io::Result<Vec<u8>> {
+    let caps = caps.unwrap();
+    let debug_id = caps[1].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+    let execution = caps[2].parse::<i32>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    archive.trace(debug_id, execution)
+}
+
 /// Stream function or process trace data to the client as it's generated
-fn trace_stream(res: &mut Response<Streaming>, child: &mut child::Thread) -> io::Result<bool> {
-    child.tx.send(ServerMessage::Trace).unwrap();
+fn trace_stream(
+    res: &mut Response<Streaming>, child: &mut child::Thread, recording: &mut Recording,
+    string_length: usize, fill: Option<u8>
+) -> io::Result<bool> {
+    child.tx.send(ServerMessage::Trace { string_length, fill }).unwrap();
 
     res.write_all(b"[\n")?;
 
     let mut next_index = 0;
-    let mut prev_locals = HashMap::new();
+
+    // lines only report the locals that changed since the last line of the same call
+    let mut prev_locals = vec![HashMap::new()];
 
     let mut terminated = false;
     let mut stack: usize = 0;
     let mut done = false;
     while !done {
         let message = match child.rx.recv().unwrap() {
-            DebugMessage::Trace(DebugTrace::Line(line, locals)) => {
+            DebugMessage::Trace(DebugTrace::Line(line, locals, pointers)) => {
                 let index = next_index;
                 next_index += 1;
 
+                let prev_locals = prev_locals.last_mut().unwrap();
                 let mut state = HashMap::new();
                 for (name, value) in locals.iter() {
                     let prev_value = prev_locals.get(name);
@@ -886,7 +1128,10 @@ fn trace_stream(res: &mut Response<Streaming>, child: &mut child::Thread) -> io:
                 }
                 prev_locals.extend(locals.into_iter());
 
-                let data = api::TraceData::Line { state };
+                // lines of traced callees don't take part in the function's loops
+                let loops = if stack == 1 { recording.loops(index, line) } else { vec![] };
+
+                let data = api::TraceData::Line { state, pointers, loops };
                 api::Trace { index, line, data }
             }
 
@@ -895,22 +1140,26 @@ fn trace_stream(res: &mut Response<Streaming>, child: &mut child::Thread) -> io:
                 next_index += 1;
 
                 stack += 1;
+                prev_locals.push(HashMap::new());
 
                 let data = api::TraceData::Call { function };
                 api::Trace { index, line, data }
             }
 
-            DebugMessage::Trace(DebugTrace::Return(line, value, data)) => {
+            DebugMessage::Trace(DebugTrace::Return(line, value, data, pointers)) => {
                 let index = next_index;
                 next_index += 1;
 
                 stack -= 1;
+                if prev_locals.len() > 1 {
+                    prev_locals.pop();
+                }
                 if stack == 0 {
                     done = true;
                     child.execution = None;
                 }
 
-                let data = api::TraceData::Return { value, data };
+                let data = api::TraceData::Return { value, data, pointers };
                 api::Trace { index, line, data }
             }
 
@@ -941,15 +1190,23 @@ fn trace_stream(res: &mut Response<Streaming>, child: &mut child::Thread) -> io:
                 api::Trace { index: next_index, line: 0, data }
             }
 
-            DebugMessage::Trace(DebugTrace::Crash(stack)) => {
+            DebugMessage::Trace(DebugTrace::Crash(exception, stack)) => {
                 terminated = true;
                 done = true;
                 child.execution = None;
 
-                let data = api::TraceData::Crash { stack };
+                let data = api::TraceData::Crash { exception, stack };
                 api::Trace { index: next_index, line: 0, data }
             }
 
+            DebugMessage::Trace(DebugTrace::Output(line, stream, text)) => {
+                let index = next_index;
+                next_index += 1;
+
+                let data = api::TraceData::Output { stream, text };
+                api::Trace { index, line, data }
+            }
+
             DebugMessage::Error(e) => {
                 child.execution = None;
                 return Err(e);
@@ -957,7 +1214,7 @@ fn trace_stream(res: &mut Response<Streaming>, child: &mut child::Thread) -> io:
             _ => unreachable!(),
         };
 
-        serde_json::to_writer(res, &message).unwrap();
+        res.write_all(recording.push(&message))?;
         if !done { res.write_all(b",\n")?; }
         res.flush()?;
     }
@@ -966,18 +1223,127 @@ fn trace_stream(res: &mut Response<Streaming>, child: &mut child::Thread) -> io:
     Ok(terminated)
 }
 
+/// Look up the timeline of an archived execution from the first two captures
+fn archived_timeline(caps: &[String], archive: &Archive) -> io::Result<timeline::Timeline> {
+    let debug_id = caps[1].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+    let execution = caps[2].parse::<i32>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    archive.timeline(debug_id, execution)
+}
+
+/// GET /debug/:id/executions/:execution/state/:index
+/// Get the full state of an archived execution as of a step of its trace
+fn debug_execution_state(caps: Captures, archive: &Archive) -> io::Result<Vec<u8>> {
+    let caps = caps.unwrap();
+    let index = caps[3].parse::<i32>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let timeline = archived_timeline(&caps, archive)?;
+    let message = timeline.state(index)
+        .ok_or(io::Error::new(io::ErrorKind::NotFound, "no such trace index"))?;
+    Ok(serde_json::to_vec(&message).unwrap())
+}
+
+/// GET /debug/:id/executions/:execution/changes/:address
+/// List the steps of an archived execution at which a variable or pointed-to value changed
+fn debug_execution_changes(caps: Captures, archive: &Archive) -> io::Result<Vec<u8>> {
+    let caps = caps.unwrap();
+    let address = caps[3].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let timeline = archived_timeline(&caps, archive)?;
+    Ok(serde_json::to_vec(timeline.changes(address)).unwrap())
+}
+
+/// GET /debug/:id/executions/:execution/changes/:address/before/:index
+/// Get the last step before another at which a variable or pointed-to value changed
+fn debug_execution_last_change(caps: Captures, archive: &Archive) -> io::Result<Vec<u8>> {
+    let caps = caps.unwrap();
+    let address = caps[3].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let index = caps[4].parse::<i32>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let timeline = archived_timeline(&caps, archive)?;
+    let message = timeline.last_change(address, index)
+        .ok_or(io::Error::new(io::ErrorKind::NotFound, "no change before that index"))?;
+    Ok(serde_json::to_vec(message).unwrap())
+}
+
+/// GET /debug/:id/executions/:execution/loops
+/// List the loops found in an archived function execution, with values for each iteration
+fn debug_execution_loops(caps: Captures, archive: &Archive) -> io::Result<Vec<u8>> {
+    let caps = caps.unwrap();
+    let debug_id = caps[1].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+    let execution = caps[2].parse::<i32>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    // process executions don't trace lines, so they have no loops
+    let variables = match archive.execution(debug_id, execution)?.data {
+        api::ExecutionData::Function { function } => {
+            let function = archive.function(debug_id, function)?;
+            function.parameters.iter().chain(&function.locals)
+                .map(|variable| variable.address)
+                .collect()
+        }
+        api::ExecutionData::Process => vec![],
+    };
+
+    let timeline = archive.timeline(debug_id, execution)?;
+    let message = timeline.loops(&variables);
+    Ok(serde_json::to_vec(&message).unwrap())
+}
+
+/// GET /debug/:id/executions/:execution/diff/:other
+/// Compare two archived executions of the same function
+fn debug_execution_diff(caps: Captures, archive: &Archive) -> io::Result<Vec<u8>> {
+    let caps = caps.unwrap();
+    let debug_id = caps[1].parse::<usize>()
+        .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+    let left = caps[2].parse::<i32>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let right = caps[3].parse::<i32>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let left_data = archive.execution(debug_id, left)?.data;
+    let right_data = archive.execution(debug_id, right)?.data;
+    let address = match (left_data, right_data) {
+        (api::ExecutionData::Function { function: left },
+         api::ExecutionData::Function { function: right }) if left == right => left,
+        _ => {
+            let message = "executions are not of the same function";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+    };
+
+    let function = archive.function(debug_id, address)?;
+    let variables: Vec<_> = function.parameters.iter().chain(&function.locals)
+        .map(|variable| variable.address)
+        .collect();
+    let parameters = function.parameters.len();
+
+    let left = archive.timeline(debug_id, left)?;
+    let right = archive.timeline(debug_id, right)?;
+    let message = diff::diff(&left, &right, &variables, parameters);
+    Ok(serde_json::to_vec(&message).unwrap())
+}
+
 /// POST /debug/:id/executions/:execution/stop
 /// Halts a running execution
-fn debug_execution_stop(caps: Captures, cancel: ChildCancel) -> io::Result<Vec<u8>> {
+fn debug_execution_stop(caps: Captures, sessions: &Sessions) -> io::Result<Vec<u8>> {
     let caps = caps.unwrap();
-    let _debug_id = caps[1].parse::<usize>()
+    let debug_id = caps[1].parse::<usize>()
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
     let _execution_id = caps[2].parse::<i32>()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
-    let mut cancel = cancel.lock().unwrap();
-    let cancel = cancel.as_mut()
-        .ok_or(io::Error::from(io::ErrorKind::NotConnected))?;
+    let cancel = sessions.lock().unwrap().get(&debug_id)
+        .map(|session| session.cancel.clone())
+        .ok_or(io::Error::new(io::ErrorKind::NotConnected, "no such session"))?;
+    let cancel = cancel.lock().unwrap();
 
     cancel.flag.store(true, Ordering::Relaxed);
     cancel.cancel.trigger_breakpoint()?;
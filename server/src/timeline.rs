@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use loops::Loop;
+use api;
+
+/// An index over a recorded trace, for looking up its state at any step without replaying it
+///
+/// Line traces only carry the values that changed since the line before, so the timeline keeps
+/// every value each address took along with the step it changed at. The state at a step is the
+/// last change of each address up to it.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Timeline {
+    /// The source line of each step, by trace index
+    lines: Vec<u32>,
+    /// The values each address took, in the order they changed
+    changes: HashMap<usize, Vec<api::Change>>,
+    /// The pointer targets of each line trace, kept only where they differ from the line before
+    pointers: Vec<(i32, HashMap<usize, api::PointerTarget>)>,
+    /// The indices of the line traces, which follow the traced function's control flow
+    steps: Vec<i32>,
+    /// The value returned by the traced function and the values it points to
+    returned: Option<(api::Value, HashMap<usize, api::Value>)>,
+    /// The pointer targets of the returned values
+    #[serde(default)]
+    return_pointers: HashMap<usize, api::PointerTarget>,
+    /// The loops found in the traced function, once the trace has ended
+    loops: Vec<Loop>,
+}
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline::default()
+    }
+
+    /// Add the next trace, in the order they were streamed
+    ///
+    /// Traces from within the calls the function makes are `nested`. Their lines are left out of
+    /// its steps and changes, whose addresses are only meaningful in the function's own frame.
+    pub fn push(&mut self, trace: &api::Trace, nested: bool) {
+        // the trace that ends an execution shares its index with the step after the last, and an
+        // error may be reported at index 0
+        if trace.index as usize == self.lines.len() {
+            self.lines.push(trace.line);
+        }
+
+        if nested {
+            return;
+        }
+
+        if let api::TraceData::Return { ref value, ref data, ref pointers } = trace.data {
+            self.returned = Some((value.clone(), data.clone()));
+            self.return_pointers = pointers.clone();
+        }
+
+        if let api::TraceData::Line { ref state, ref pointers, .. } = trace.data {
+            self.steps.push(trace.index);
+            for (&address, value) in state {
+                let change = api::Change {
+                    index: trace.index,
+                    line: trace.line,
+                    value: value.clone(),
+                };
+                self.changes.entry(address).or_insert(vec![]).push(change);
+            }
+
+            let changed = self.pointers.last()
+                .map(|&(_, ref last)| last != pointers)
+                .unwrap_or(true);
+            if changed {
+                self.pointers.push((trace.index, pointers.clone()));
+            }
+        }
+    }
+
+    /// Add the loops found over the whole trace
+    pub fn set_loops(&mut self, loops: Vec<Loop>) {
+        self.loops = loops;
+    }
+
+    /// Summarize the loops found, with the values of the given parameters and locals at the end of
+    /// each iteration
+    pub fn loops(&self, variables: &[usize]) -> Vec<api::Loop> {
+        self.loops.iter().enumerate()
+            .map(|(id, lp)| {
+                let iterations = lp.iterations.iter()
+                    .map(|iteration| {
+                        let values = variables.iter()
+                            .filter_map(|&address| {
+                                let value = self.value(address, iteration.end);
+                                value.map(|value| (address, value.clone()))
+                            })
+                            .collect();
+
+                        let (start, end) = (iteration.start, iteration.end);
+                        api::Iteration { iteration: iteration.iteration, start, end, values }
+                    })
+                    .collect();
+
+                api::Loop { id, line: lp.header, line_end: lp.end, parent: lp.parent, iterations }
+            })
+            .collect()
+    }
+
+    /// The full state as of a step, including the changes made by its own line
+    pub fn state(&self, index: i32) -> Option<api::TraceState> {
+        if index < 0 || index as usize >= self.lines.len() {
+            return None;
+        }
+
+        let state = self.changes.iter()
+            .filter_map(|(&address, changes)| {
+                last_before(changes, index + 1).map(|change| (address, change.value.clone()))
+            })
+            .collect();
+
+        let pointers = self.pointers_at(index).cloned().unwrap_or(HashMap::new());
+
+        Some(api::TraceState { index, line: self.lines[index as usize], state, pointers })
+    }
+
+    /// What the pointer to an address pointed into as of a step, if a pointer to it was traced
+    pub fn pointer(&self, address: usize, index: i32) -> Option<&api::PointerTarget> {
+        self.pointers_at(index).and_then(|pointers| pointers.get(&address))
+    }
+
+    /// The value at an address as of a step
+    pub fn value(&self, address: usize, index: i32) -> Option<&api::Value> {
+        self.changes.get(&address)
+            .and_then(|changes| last_before(changes, index + 1))
+            .map(|change| &change.value)
+    }
+
+    /// The index and line of each line trace, in order
+    pub fn steps(&self) -> Vec<api::Step> {
+        self.steps.iter()
+            .map(|&index| api::Step { index, line: self.lines[index as usize] })
+            .collect()
+    }
+
+    /// The return value and the values it points to, if the function returned
+    pub fn returned(&self) -> Option<&(api::Value, HashMap<usize, api::Value>)> {
+        self.returned.as_ref()
+    }
+
+    /// What each pointer in the return value and the values it points to pointed into
+    pub fn return_pointers(&self) -> &HashMap<usize, api::PointerTarget> {
+        &self.return_pointers
+    }
+
+    /// Every step at which the value at an address changed
+    pub fn changes(&self, address: usize) -> &[api::Change] {
+        self.changes.get(&address).map(|changes| &changes[..]).unwrap_or(&[])
+    }
+
+    /// The last step before `index` at which the value at an address changed
+    pub fn last_change(&self, address: usize, index: i32) -> Option<&api::Change> {
+        self.changes.get(&address).and_then(|changes| last_before(changes, index))
+    }
+
+    /// The pointer targets of the last line trace up to a step
+    fn pointers_at(&self, index: i32) -> Option<&HashMap<usize, api::PointerTarget>> {
+        match self.pointers.binary_search_by_key(&index, |&(step, _)| step) {
+            Ok(position) => Some(&self.pointers[position].1),
+            Err(0) => None,
+            Err(position) => Some(&self.pointers[position - 1].1),
+        }
+    }
+}
+
+fn last_before(changes: &[api::Change], index: i32) -> Option<&api::Change> {
+    let end = match changes.binary_search_by_key(&index, |change| change.index) {
+        Ok(end) | Err(end) => end,
+    };
+
+    if end > 0 { Some(&changes[end - 1]) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use api;
+    use super::*;
+
+    fn line(index: i32, line: u32, state: Vec<(usize, i64)>) -> api::Trace {
+        let state = state.into_iter().map(|(address, value)| (address, api::Value::Integer(value)));
+        let data = api::TraceData::Line {
+            state: state.collect(), pointers: HashMap::new(), loops: vec![]
+        };
+        api::Trace { index, line, data }
+    }
+
+    fn call(index: i32, line: u32) -> api::Trace {
+        api::Trace { index, line, data: api::TraceData::Call { function: 0 } }
+    }
+
+    fn ret(index: i32, line: u32, value: i64) -> api::Trace {
+        let value = api::Value::Integer(value);
+        let data = api::TraceData::Return { value, data: HashMap::new(), pointers: HashMap::new() };
+        api::Trace { index, line, data }
+    }
+
+    /// A function that sets a local at 100 on line 10, calls a function whose own local happens
+    /// to live at 100 too, then sets its local again on line 12
+    fn nested_timeline() -> Timeline {
+        let mut timeline = Timeline::new();
+        timeline.push(&call(0, 9), false);
+        timeline.push(&line(1, 10, vec![(100, 1)]), false);
+        timeline.push(&call(2, 11), true);
+        timeline.push(&line(3, 50, vec![(100, 99)]), true);
+        timeline.push(&line(4, 51, vec![(100, 98), (200, 7)]), true);
+        timeline.push(&ret(5, 52, 98), true);
+        timeline.push(&line(6, 12, vec![(100, 2)]), false);
+        timeline.push(&ret(7, 13, 2), false);
+        timeline
+    }
+
+    #[test]
+    fn nested_calls_leave_state_alone() {
+        let timeline = nested_timeline();
+
+        for index in 1..6 {
+            let state = timeline.state(index).unwrap();
+            assert!(state.state.len() == 1);
+            assert!(state.state[&100] == api::Value::Integer(1));
+        }
+        let state = timeline.state(6).unwrap();
+        assert!(state.state.len() == 1);
+        assert!(state.state[&100] == api::Value::Integer(2));
+
+        // nested steps still have their own lines
+        assert_eq!(timeline.state(4).unwrap().line, 51);
+    }
+
+    #[test]
+    fn nested_calls_leave_changes_alone() {
+        let timeline = nested_timeline();
+
+        assert_eq!(timeline.last_change(100, 6).unwrap().index, 1);
+        assert_eq!(timeline.last_change(100, 7).unwrap().index, 6);
+        assert!(timeline.last_change(200, 7).is_none());
+        let lines: Vec<_> = timeline.changes(100).iter().map(|change| change.line).collect();
+        assert_eq!(lines, vec![10, 12]);
+
+        let steps: Vec<_> = timeline.steps().iter().map(|step| step.index).collect();
+        assert_eq!(steps, vec![1, 6]);
+    }
+
+    #[test]
+    fn nested_returns_are_not_the_return_value() {
+        let timeline = nested_timeline();
+        let &(ref value, _) = timeline.returned().unwrap();
+        assert!(*value == api::Value::Integer(2));
+    }
+}
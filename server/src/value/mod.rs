@@ -0,0 +1,695 @@
+use std::{io, cmp, char, f64};
+use std::collections::{HashMap, VecDeque};
+use debug::{self, SymbolHandler, IntoValue};
+use api;
+
+pub use self::pointer::PointerTargets;
+
+mod stl;
+mod pointer;
+pub mod visualizer;
+
+/// The number of code units read from character arrays and pointers, unless the client asks for
+/// a different limit
+pub const DEFAULT_STRING_LENGTH: usize = 1024;
+
+/// The byte that marks memory as uninitialized, unless the client asks for a different pattern
+///
+/// MSVC debug builds fill stack frames with `0xcc`.
+#[cfg(windows)]
+pub const DEFAULT_FILL: Option<u8> = Some(0xcc);
+
+/// The byte that marks memory as uninitialized, unless the client asks for a different pattern
+///
+/// GCC and Clang leave stack frames as they were, so nothing is assumed uninitialized.
+#[cfg(not(windows))]
+pub const DEFAULT_FILL: Option<u8> = None;
+
+/// The number of elements read through an array view, or from a container or visualized struct,
+/// before the rest are left out
+pub const MAX_ELEMENTS: usize = 1024;
+
+/// Whether every byte of a value matches the fill pattern, if there is one
+pub fn is_uninitialized(value: &debug::Value, fill: Option<u8>) -> bool {
+    match fill {
+        Some(fill) => value.data.iter().all(|&byte| byte == fill),
+        None => false,
+    }
+}
+
+/// Read a function local or argument into an `api::Value`, or the reason it has no value
+///
+/// `frame` is the context's index in the stack, innermost first.
+pub fn parse_symbol(
+    child: &debug::Child, context: &debug::Context, frame: usize, symbols: &SymbolHandler,
+    symbol: &debug::Symbol, string_length: usize, fill: Option<u8>,
+    pointers: &mut VecDeque<(usize, u32)>
+) -> api::Value {
+    match debug::Value::read_symbol(child, context, frame, symbols, symbol) {
+        Ok(Some(ref value)) if is_uninitialized(value, fill) => {
+            api::Value::Unavailable(api::Unavailable::Uninitialized)
+        }
+        Ok(Some(value)) => parse(&value, child, symbols, string_length, pointers),
+        Ok(None) => api::Value::Unavailable(api::Unavailable::OptimizedOut),
+        Err(_) => api::Value::Unavailable(api::Unavailable::Unreadable { address: None }),
+    }
+}
+
+/// Convert a byte buffer and its type into an `api::Value` and a list of pointers it contains
+///
+/// Character arrays become text of at most `string_length` code units.
+///
+/// The inverse of `api::Value::into_value`.
+pub fn parse(
+    value: &debug::Value, child: &debug::Child, symbols: &SymbolHandler, string_length: usize,
+    pointers: &mut VecDeque<(usize, u32)>
+) -> api::Value {
+    let debug::Value { ref data, ref data_type, module } = *value;
+    parse_bytes(data, data_type, child, symbols, module, string_length, pointers)
+}
+
+fn parse_bytes(
+    data: &[u8], data_type: &debug::Type, child: &debug::Child, symbols: &SymbolHandler,
+    module: usize, string_length: usize, pointers: &mut VecDeque<(usize, u32)>
+) -> api::Value {
+    let value = data.as_ptr();
+
+    use debug::Type::*;
+    match *data_type {
+        Base { base, size } => {
+            use debug::Primitive::*;
+            let data = &data[..size];
+            match base {
+                Void => api::Value::Null,
+                Opaque => api::Value::Unavailable(api::Unavailable::Unsupported),
+                Bool => api::Value::Boolean(unsafe { *(value as *const bool) }),
+                // JSON numbers are doubles, so anything that may not fit in 53 bits is a string
+                Int { signed } if size >= 8 => {
+                    api::Value::String(debug::format_int(data, signed))
+                }
+                Char { signed } | Int { signed } => {
+                    api::Value::Integer(debug::read_integer(data, signed))
+                }
+                Float => float_value(debug::read_float(data, false)),
+                Extended => extended_value(data),
+                Complex { extended } => {
+                    let (real, imaginary) = data.split_at(size / 2);
+                    let part = |data: &[u8]| if extended {
+                        extended_value(data)
+                    } else {
+                        float_value(debug::read_float(data, false))
+                    };
+                    api::Value::Array(vec![part(real), part(imaginary)])
+                }
+            }
+        }
+
+        Pointer { type_index } => {
+            let address = unsafe { *(value as *const usize) };
+            pointers.push_back((address, type_index));
+
+            api::Value::String(address.to_string())
+        }
+
+        Array { type_index, count } | Vector { type_index, count } => {
+            let element_type = match symbols.type_from_index(module, type_index) {
+                Ok(element_type) => element_type,
+                Err(_) => return api::Value::Unavailable(api::Unavailable::Unsupported),
+            };
+            let size = element_type.size(symbols, module);
+
+            // arrays of characters are text, but vectors of them are still integers
+            if let (&Array { .. }, &Base { base: debug::Primitive::Char { .. }, .. }) =
+                (data_type, &element_type)
+            {
+                return parse_text(&data[..count * size], size, string_length);
+            }
+
+            let mut values = vec![];
+            for offset in (0..count).map(|i| i * size) {
+                let data = &data[offset..offset+size];
+
+                let value = parse_bytes(
+                    data, &element_type, child, symbols, module, string_length, pointers
+                );
+                values.push(value);
+            }
+
+            api::Value::Array(values)
+        }
+
+        Struct { ref fields, .. } | Union { ref fields, .. } => {
+            let mut values = HashMap::new();
+            for (index, field) in fields.iter().enumerate() {
+                let field_type = match symbols.type_from_index(module, field.type_index) {
+                    Ok(field_type) => field_type,
+                    Err(_) => {
+                        let value = api::Value::Unavailable(api::Unavailable::Unsupported);
+                        values.insert(index as u32, value);
+                        continue;
+                    }
+                };
+
+                let size = field_type.size(symbols, module);
+                let data = field.read(data, &field_type, size);
+
+                let value = parse_bytes(
+                    &data, &field_type, child, symbols, module, string_length, pointers
+                );
+                values.insert(index as u32, value);
+            }
+
+            // user-defined visualizers take precedence over the built-in ones
+            let raw = api::Value::Struct(values);
+            match *data_type {
+                Struct { ref name, .. } => match symbols.visualizer(name) {
+                    Some(visualizer) => visualizer::visualize(
+                        &visualizer, data, data_type, child, symbols, module, string_length,
+                        pointers, raw
+                    ),
+                    None => stl::visualize(
+                        data, data_type, child, symbols, module, string_length, pointers, raw
+                    ),
+                },
+                _ => raw,
+            }
+        }
+
+        Enum { base, size, ref enumerators, .. } => {
+            let data_type = Base { base, size };
+            let value = match base {
+                debug::Primitive::Int { signed } => debug::read_integer(&data[..size], signed),
+                _ => {
+                    return parse_bytes(
+                        data, &data_type, child, symbols, module, string_length, pointers
+                    );
+                }
+            };
+
+            match debug::Enumerator::describe(enumerators, value) {
+                Some(flags) => {
+                    let names: Vec<_> = flags.iter()
+                        .map(|enumerator| enumerator.name.to_string_lossy())
+                        .collect();
+                    api::Value::String(names.join(" | "))
+                }
+
+                None => parse_bytes(
+                    data, &data_type, child, symbols, module, string_length, pointers
+                ),
+            }
+        }
+
+        _ => api::Value::Null,
+    }
+}
+
+/// Convert a float to a JSON number, or to a string for values JSON has no numbers for
+fn float_value(value: f64) -> api::Value {
+    if value.is_nan() {
+        api::Value::String("NaN".to_string())
+    } else if value.is_infinite() && value > 0.0 {
+        api::Value::String("Infinity".to_string())
+    } else if value.is_infinite() {
+        api::Value::String("-Infinity".to_string())
+    } else {
+        api::Value::Number(value)
+    }
+}
+
+/// Convert an x87 80-bit float to a JSON number if one holds it exactly, or else to its exact
+/// hexadecimal form like `0x1.0000000000000002p+0`
+fn extended_value(data: &[u8]) -> api::Value {
+    let value = debug::read_float(data, true);
+    let mut rounded = [0u8; 10];
+    debug::write_float(value, true, &mut rounded);
+    if !value.is_finite() || rounded[..] == data[..10] {
+        float_value(value)
+    } else {
+        api::Value::String(debug::format_extended(data))
+    }
+}
+
+/// Decode code units of 1, 2 or 4 bytes as UTF-8, UTF-16 or UTF-32 text, up to the first NUL
+///
+/// Text longer than `max_length` code units is truncated, and text that doesn't decode keeps its
+/// raw bytes alongside a lossy decoding.
+fn parse_text(data: &[u8], size: usize, max_length: usize) -> api::Value {
+    let length = data.chunks(size)
+        .position(|unit| unit.iter().all(|&byte| byte == 0))
+        .unwrap_or(data.len() / size);
+    let truncated = length > max_length;
+    let data = &data[..cmp::min(length, max_length) * size];
+
+    let units = data.chunks(size).map(|unit| {
+        unit.iter().rev().fold(0, |value, &byte| value << 8 | byte as u32)
+    });
+    let (text, valid) = match size {
+        1 => match String::from_utf8(data.to_vec()) {
+            Ok(text) => (text, true),
+            Err(_) => (String::from_utf8_lossy(data).into_owned(), false),
+        },
+        2 => {
+            let units: Vec<_> = units.map(|unit| unit as u16).collect();
+            match String::from_utf16(&units) {
+                Ok(text) => (text, true),
+                Err(_) => (String::from_utf16_lossy(&units), false),
+            }
+        }
+        _ => {
+            let chars: Vec<_> = units.map(char::from_u32).collect();
+            let valid = chars.iter().all(Option::is_some);
+            (chars.into_iter().map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect(), valid)
+        }
+    };
+
+    let bytes = if valid { None } else { Some(data.to_vec()) };
+    api::Value::Text(api::Text { text, bytes, truncated })
+}
+
+/// Drain the pointer worklist built by `parse`/`parse_bytes` to build a graph of `api::Value`s
+///
+/// Character pointers are read as NUL-terminated strings of at most `string_length` code units.
+/// Pointed-to values that can't be read or match `fill` are recorded as unavailable. Every
+/// pointer, including null and those into the stack, is classified into `targets`.
+pub fn trace_pointers(
+    child: &debug::Child, symbols: &debug::SymbolHandler, module: usize, base: usize,
+    string_length: usize, fill: Option<u8>, pointers: &mut VecDeque<(usize, u32)>,
+    values: &mut HashMap<usize, api::Value>, targets: &mut PointerTargets
+) {
+    while let Some((address, type_index)) = pointers.pop_front() {
+        targets.classify(address, child, symbols);
+
+        let offset = address.checked_sub(base);
+        if
+            address == 0 ||
+            values.contains_key(&address) ||
+            offset.map(|offset| values.contains_key(&offset)).unwrap_or(false)
+        {
+            continue;
+        }
+
+        // read one code unit past the limit, to tell whether the string was truncated
+        let value = match symbols.type_from_index(module, type_index) {
+            Ok(debug::Type::Base { base: debug::Primitive::Char { .. }, .. }) => {
+                debug::Value::read_string(
+                    child, symbols, address, module, type_index, string_length + 1
+                )
+            }
+            _ => debug::Value::read_pointer(child, symbols, address, module, type_index),
+        };
+        let value = match value {
+            Ok(ref value) if is_uninitialized(value, fill) => {
+                api::Value::Unavailable(api::Unavailable::Uninitialized)
+            }
+            Ok(value) => parse(&value, child, symbols, string_length, pointers),
+            Err(_) => {
+                let address = Some(address.to_string());
+                api::Value::Unavailable(api::Unavailable::Unreadable { address })
+            }
+        };
+        values.insert(address, value);
+    }
+}
+
+/// Read the buffers of pointers with array views, in place of the single values they point to
+///
+/// `types` holds the type of each variable in `values`. Views whose variables aren't in `values`,
+/// or whose lengths aren't integers, are skipped, leaving their pointers to `trace_pointers`.
+/// At most `MAX_ELEMENTS` elements are read.
+pub fn trace_arrays(
+    child: &debug::Child, symbols: &debug::SymbolHandler, module: usize, string_length: usize,
+    fill: Option<u8>, views: &[api::ArrayView], types: &HashMap<usize, u32>,
+    pointers: &mut VecDeque<(usize, u32)>, values: &mut HashMap<usize, api::Value>
+) {
+    for view in views {
+        let type_index = match path_type(symbols, module, types, &view.pointer) {
+            Ok(debug::Type::Pointer { type_index }) => type_index,
+            _ => continue,
+        };
+        let address = match path_value(values, &view.pointer) {
+            Some(&api::Value::String(ref address)) => address.parse().unwrap_or(0),
+            _ => continue,
+        };
+        let length = match view.length {
+            api::ArrayLength::Constant(length) => length,
+            api::ArrayLength::Variable(ref path) => match path_value(values, path) {
+                Some(&api::Value::Integer(length)) if length >= 0 => length as usize,
+                Some(&api::Value::String(ref length)) => match length.parse() {
+                    Ok(length) => length,
+                    Err(_) => continue,
+                },
+                _ => continue,
+            },
+        };
+        if address == 0 || values.contains_key(&address) {
+            continue;
+        }
+
+        let data_type = debug::Type::Array {
+            type_index,
+            count: cmp::min(length, MAX_ELEMENTS),
+        };
+        let mut data = vec![0; data_type.size(symbols, module)];
+        let value = match child.read_memory(address, &mut data) {
+            Ok(_) => {
+                let value = debug::Value { data, data_type, module };
+                if !value.data.is_empty() && is_uninitialized(&value, fill) {
+                    api::Value::Unavailable(api::Unavailable::Uninitialized)
+                } else {
+                    parse(&value, child, symbols, string_length, pointers)
+                }
+            }
+            Err(_) => {
+                let address = Some(address.to_string());
+                api::Value::Unavailable(api::Unavailable::Unreadable { address })
+            }
+        };
+        values.insert(address, value);
+    }
+}
+
+/// Check that an array view names a pointer, and an integer for its length
+///
+/// `types` holds the type of each of the function's variables.
+pub fn check_array_view(
+    symbols: &debug::SymbolHandler, module: usize, types: &HashMap<usize, u32>,
+    view: &api::ArrayView
+) -> io::Result<()> {
+    match path_type(symbols, module, types, &view.pointer)? {
+        debug::Type::Pointer { .. } => (),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "array view of a non-pointer")),
+    }
+
+    if let api::ArrayLength::Variable(ref path) = view.length {
+        match path_type(symbols, module, types, path)? {
+            debug::Type::Base { base: debug::Primitive::Int { .. }, .. } |
+            debug::Type::Base { base: debug::Primitive::Char { .. }, .. } => (),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "non-integer length")),
+        }
+    }
+
+    Ok(())
+}
+
+/// The type of a variable or one of its nested fields
+fn path_type(
+    symbols: &debug::SymbolHandler, module: usize, types: &HashMap<usize, u32>,
+    path: &api::VariablePath
+) -> io::Result<debug::Type> {
+    let not_found = || io::Error::new(io::ErrorKind::NotFound, "no such variable or field");
+
+    let type_index = types.get(&path.variable).ok_or_else(not_found)?;
+    let mut data_type = symbols.type_from_index(module, *type_index)?;
+    for &index in &path.fields {
+        let type_index = match data_type {
+            debug::Type::Struct { ref fields, .. } | debug::Type::Union { ref fields, .. } => {
+                fields.get(index as usize).ok_or_else(not_found)?.type_index
+            }
+            _ => return Err(not_found()),
+        };
+        data_type = symbols.type_from_index(module, type_index)?;
+    }
+
+    Ok(data_type)
+}
+
+/// The value of a variable or one of its nested fields
+///
+/// Visualized structs are searched through their raw fields.
+fn path_value<'a>(
+    values: &'a HashMap<usize, api::Value>, path: &api::VariablePath
+) -> Option<&'a api::Value> {
+    let mut value = values.get(&path.variable);
+    for index in &path.fields {
+        value = match value {
+            Some(&api::Value::Struct(ref fields)) => fields.get(index),
+            Some(&api::Value::Visualized(ref visualized)) => match *visualized.raw {
+                api::Value::Struct(ref fields) => fields.get(index),
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+
+    value
+}
+
+impl debug::IntoValue for api::Value {
+    /// Convert an `api::Value` into a byte buffer, its type, and a list of pointers that need to
+    /// be fixed up.
+    ///
+    /// The inverse of `parse` and `trace_pointers`
+    fn into_value(
+        self, data_type: debug::Type, module: usize, symbols: &debug::SymbolHandler,
+        value_offset: usize, offsets: &mut HashMap<usize, usize>,
+        pointers: &mut VecDeque<(usize, u32)>
+    ) -> io::Result<debug::Value> {
+        let mut data = vec![0; data_type.size(symbols, module)];
+
+        use debug::Type::*;
+        use debug::Primitive::*;
+        match (&data_type, self) {
+            (&Base { base: Void, .. }, api::Value::Null) => {}
+
+            (&Base { base: Bool, .. }, api::Value::Boolean(value)) => {
+                unsafe { *(data.as_mut_ptr() as *mut bool) = value };
+            }
+
+            (&Base { base: Char { signed }, size }, api::Value::Integer(value)) |
+            (&Base { base: Int { signed }, size }, api::Value::Integer(value)) => {
+                if signed {
+                    match size {
+                        1 => unsafe { *(data.as_mut_ptr() as *mut i8) = value as i8 },
+                        2 => unsafe { *(data.as_mut_ptr() as *mut i16) = value as i16 },
+                        4 => unsafe { *(data.as_mut_ptr() as *mut i32) = value as i32 },
+                        8 => unsafe { *(data.as_mut_ptr() as *mut i64) = value as i64 },
+                        _ => debug::parse_int(&value.to_string(), signed, &mut data)?,
+                    }
+                } else {
+                    match size {
+                        1 => unsafe { *(data.as_mut_ptr() as *mut u8) = value as u8 },
+                        2 => unsafe { *(data.as_mut_ptr() as *mut u16) = value as u16 },
+                        4 => unsafe { *(data.as_mut_ptr() as *mut u32) = value as u32 },
+                        8 => unsafe { *(data.as_mut_ptr() as *mut u64) = value as u64 },
+                        _ => debug::parse_int(&(value as u64).to_string(), signed, &mut data)?,
+                    }
+                }
+            }
+
+            (&Base { base: Int { signed }, .. }, api::Value::String(ref value)) => {
+                debug::parse_int(value, signed, &mut data)?;
+            }
+
+            // characters may also be given as strings of a single code point
+            (&Base { base: Char { signed }, .. }, api::Value::String(ref value)) => {
+                let mut chars = value.chars();
+                let code = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c as u32,
+                    _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+                };
+                debug::parse_int(&code.to_string(), signed, &mut data)?;
+            }
+
+            (&Base { base: Float, .. }, value) => {
+                write_float_from(&value, false, &mut data)?;
+            }
+
+            (&Base { base: Extended, .. }, value) => {
+                write_float_from(&value, true, &mut data)?;
+            }
+
+            (&Base { base: Complex { extended }, size }, api::Value::Array(ref values))
+            if values.len() == 2 => {
+                let (real, imaginary) = data.split_at_mut(size / 2);
+                for (part, value) in Iterator::zip([real, imaginary].iter_mut(), values) {
+                    write_float_from(value, extended, part)?;
+                }
+            }
+
+            (&Pointer { type_index }, api::Value::Integer(value)) => {
+                offsets.insert(value_offset, value as usize);
+                pointers.push_back((value as usize, type_index));
+            }
+
+            (&Pointer { type_index }, api::Value::String(ref value)) => {
+                let value: usize = value.parse()
+                    .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+                offsets.insert(value_offset, value);
+                pointers.push_back((value, type_index));
+            }
+
+            (&Pointer { .. }, api::Value::Null) => {}
+
+            // character arrays can be written as text, padded out with NULs
+            (&Array { type_index, .. }, api::Value::String(text)) => {
+                let text = api::Text { text, bytes: None, truncated: false };
+                write_text(&mut data, type_index, text, module, symbols)?;
+            }
+
+            (&Array { type_index, .. }, api::Value::Text(text)) => {
+                write_text(&mut data, type_index, text, module, symbols)?;
+            }
+
+            (&Array { type_index, count }, api::Value::Array(values)) |
+            (&Vector { type_index, count }, api::Value::Array(values)) => {
+                if count != values.len() {
+                    return Err(io::Error::from(io::ErrorKind::InvalidInput));
+                }
+
+                let element_type = symbols.type_from_index(module, type_index)?;
+                let size = element_type.size(symbols, module);
+
+                for (offset, value) in values.into_iter().enumerate().map(|(i, v)| (i * size, v)) {
+                    let data_type = element_type.clone();
+                    let data = &mut data[offset..offset + size];
+
+                    let offset = value_offset + offset;
+                    let value = value
+                        .into_value(data_type, module, symbols, offset, offsets, pointers)?;
+                    data.copy_from_slice(&value.data);
+                }
+            }
+
+            // containers are written through their raw struct
+            (&Struct { .. }, api::Value::Visualized(visualized)) => {
+                let raw_type = data_type.clone();
+                let value = visualized.raw
+                    .into_value(raw_type, module, symbols, value_offset, offsets, pointers)?;
+                data.copy_from_slice(&value.data);
+            }
+
+            (&Struct { ref fields, .. }, api::Value::Struct(ref mut values)) => {
+                for (index, field) in fields.iter().enumerate() {
+                    let value = match values.remove(&(index as u32)) {
+                        Some(value) => value,
+                        _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+                    };
+
+                    let value = field_value(
+                        field, value, module, symbols, value_offset, offsets, pointers
+                    )?;
+                    field.write(&mut data, &value.data);
+                }
+            }
+
+            // unions are written through exactly one of their fields
+            (&Union { ref fields, .. }, api::Value::Struct(values)) => {
+                if values.len() != 1 {
+                    return Err(io::Error::from(io::ErrorKind::InvalidInput));
+                }
+
+                let (index, value) = values.into_iter().next().unwrap();
+                let field = fields.get(index as usize)
+                    .ok_or(io::Error::from(io::ErrorKind::InvalidInput))?;
+
+                let value = field_value(
+                    field, value, module, symbols, value_offset, offsets, pointers
+                )?;
+                field.write(&mut data, &value.data);
+            }
+
+            (&Enum { base, size, ref enumerators, .. }, value) => {
+                // values with no enumerators may come back as decimal strings
+                let value = match value {
+                    api::Value::String(ref names)
+                    if !names.starts_with(|c: char| c == '-' || c.is_digit(10)) => {
+                        api::Value::Integer(enum_value(enumerators, names)?)
+                    }
+                    value @ api::Value::Integer(_) | value @ api::Value::String(_) => value,
+                    _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+                };
+
+                let data_type = Base { base, size };
+                let value = value
+                    .into_value(data_type, module, symbols, value_offset, offsets, pointers)?;
+                data.copy_from_slice(&value.data);
+            }
+
+            _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        }
+
+        Ok(debug::Value { data, data_type, module })
+    }
+}
+
+/// Convert the value of a struct or union field, leaving it to the caller to write into place
+fn field_value(
+    field: &debug::Field, value: api::Value, module: usize, symbols: &debug::SymbolHandler,
+    value_offset: usize, offsets: &mut HashMap<usize, usize>,
+    pointers: &mut VecDeque<(usize, u32)>
+) -> io::Result<debug::Value> {
+    let data_type = symbols.type_from_index(module, field.type_index)?;
+    let offset = value_offset + field.offset as usize;
+    value.into_value(data_type, module, symbols, offset, offsets, pointers)
+}
+
+/// Write a float from anything `float_from` accepts, or for x87 80-bit floats, also from the exact
+/// hexadecimal form `extended_value` gives
+fn write_float_from(value: &api::Value, extended: bool, data: &mut [u8]) -> io::Result<()> {
+    match *value {
+        api::Value::String(ref text) if extended && text.contains(|c| c == 'x' || c == 'X') =>
+            debug::parse_extended(text, data),
+        ref value => {
+            debug::write_float(float_from(value)?, extended, data);
+            Ok(())
+        }
+    }
+}
+
+/// Read a float from a JSON number, or from the strings `float_value` gives values JSON has no
+/// numbers for
+fn float_from(value: &api::Value) -> io::Result<f64> {
+    match *value {
+        api::Value::Number(value) => Ok(value),
+        api::Value::Integer(value) => Ok(value as f64),
+        api::Value::String(ref value) if value == "NaN" => Ok(f64::NAN),
+        api::Value::String(ref value) if value == "Infinity" => Ok(f64::INFINITY),
+        api::Value::String(ref value) if value == "-Infinity" => Ok(f64::NEG_INFINITY),
+        _ => Err(io::Error::from(io::ErrorKind::InvalidInput)),
+    }
+}
+
+/// Look up an enumerator by name, or OR together several names separated by `|`
+fn enum_value(enumerators: &[debug::Enumerator], names: &str) -> io::Result<i64> {
+    let mut value = 0;
+    for name in names.split('|').map(str::trim) {
+        let enumerator = enumerators.iter()
+            .find(|enumerator| enumerator.name == *name)
+            .ok_or(io::Error::new(io::ErrorKind::InvalidInput, "no such enumerator"))?;
+        value |= enumerator.value;
+    }
+
+    Ok(value)
+}
+
+/// Encode text into a character array, preferring its raw bytes if it has them
+fn write_text(
+    data: &mut [u8], type_index: u32, text: api::Text, module: usize,
+    symbols: &debug::SymbolHandler
+) -> io::Result<()> {
+    let size = match symbols.type_from_index(module, type_index)? {
+        debug::Type::Base { base: debug::Primitive::Char { .. }, size } => size,
+        _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+    };
+
+    let bytes = match text.bytes {
+        Some(bytes) => bytes,
+        None => {
+            let units: Vec<u32> = match size {
+                1 => text.text.bytes().map(|unit| unit as u32).collect(),
+                2 => text.text.encode_utf16().map(|unit| unit as u32).collect(),
+                _ => text.text.chars().map(|unit| unit as u32).collect(),
+            };
+            units.iter()
+                .flat_map(|&unit| (0..size).map(move |i| (unit >> (8 * i)) as u8))
+                .collect()
+        }
+    };
+
+    if bytes.len() > data.len() || bytes.len() % size != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "text does not fit the array"));
+    }
+    data[..bytes.len()].copy_from_slice(&bytes);
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::{cmp, mem};
+use std::collections::HashMap;
+
+use debug::{self, SymbolHandler};
+use api;
+
+/// Classifies the addresses held by pointers, by what they point into
+///
+/// A thread's stack frames are found up front, but each frame's locals are only located once a
+/// pointer into that frame turns up. Addresses are classified once and then remembered, so one
+/// `PointerTargets` can be shared by everything traced at the same stop.
+pub struct PointerTargets {
+    frames: Vec<Frame>,
+    known: HashMap<usize, api::PointerTarget>,
+    found: HashMap<usize, api::PointerTarget>,
+}
+
+/// The bytes below the stack pointer a System V leaf function may use without moving it
+#[cfg(not(windows))]
+const RED_ZONE: usize = 128;
+#[cfg(windows)]
+const RED_ZONE: usize = 0;
+
+struct Frame {
+    /// The frame's memory, from its stack pointer (or the innermost frame's red zone) up to its
+    /// caller's
+    start: usize,
+    end: usize,
+    /// An address within the frame's function and line
+    lookup: usize,
+    context: debug::Context,
+    /// Names, addresses and sizes of the locals that live in memory
+    locals: Option<Vec<(String, usize, usize)>>,
+}
+
+impl PointerTargets {
+    /// Classify pointers against a thread's stack, given innermost frame first
+    pub fn new<I: IntoIterator<Item = debug::StackFrame>>(frames: I) -> PointerTargets {
+        let frames: Vec<_> = frames.into_iter().collect();
+
+        let mut stack = vec![];
+        for (index, frame) in frames.iter().enumerate() {
+            let start = match index {
+                0 => frame.stack_pointer().saturating_sub(RED_ZONE),
+                _ => frame.stack_pointer(),
+            };
+
+            // the outermost frame has no caller, so it ends after its saved frame pointer and
+            // return address
+            let end = match frames.get(index + 1) {
+                Some(caller) => caller.stack_pointer(),
+                None if frame.frame_pointer() >= frame.stack_pointer() => {
+                    frame.frame_pointer() + 2 * mem::size_of::<usize>()
+                }
+                None => start,
+            };
+
+            // return addresses point after the call instruction, which may be in the next line
+            let address = frame.instruction_pointer();
+            let lookup = if index == 0 { address } else { address.saturating_sub(1) };
+
+            stack.push(Frame { start, end, lookup, context: frame.context(), locals: None });
+        }
+
+        PointerTargets { frames: stack, known: HashMap::new(), found: HashMap::new() }
+    }
+
+    /// Classify a pointer's address, recording it for the next call to `take`
+    pub fn classify(&mut self, address: usize, child: &debug::Child, symbols: &SymbolHandler) {
+        if !self.known.contains_key(&address) {
+            let target = self.find(address, child, symbols);
+            self.known.insert(address, target);
+        }
+
+        let target = self.known[&address].clone();
+        self.found.insert(address, target);
+    }
+
+    /// The addresses classified since the last call to `take`
+    pub fn take(&mut self) -> HashMap<usize, api::PointerTarget> {
+        mem::replace(&mut self.found, HashMap::new())
+    }
+
+    fn find(
+        &mut self, address: usize, child: &debug::Child, symbols: &SymbolHandler
+    ) -> api::PointerTarget {
+        if address == 0 {
+            return api::PointerTarget::Null;
+        }
+
+        let frame = self.frames.iter_mut()
+            .position(|frame| frame.start <= address && address < frame.end);
+        if let Some(index) = frame {
+            return stack_target(&mut self.frames[index], index, address, child, symbols);
+        }
+
+        if let Ok((symbol, offset)) = symbols.symbol_from_address(address) {
+            if symbol.size == 0 || offset < symbol.size {
+                let name = symbol.name.to_string_lossy().into_owned();
+                let data_type = symbols.module_from_address(address)
+                    .and_then(|module| symbols.type_from_index(module, symbol.type_index));
+
+                // code without debug info has symbols but no types
+                return match data_type {
+                    Ok(debug::Type::Function { .. }) | Err(_) => {
+                        let function = if offset == 0 { Some(symbol.address) } else { None };
+                        api::PointerTarget::Function { function, name, offset }
+                    }
+                    Ok(_) => api::PointerTarget::Global { name: Some(name), offset },
+                };
+            }
+        }
+
+        if let Ok(module) = symbols.module_from_address(address) {
+            return api::PointerTarget::Global { name: None, offset: address - module };
+        }
+
+        let mut byte = [0];
+        match child.read_memory(address, &mut byte) {
+            Ok(_) => api::PointerTarget::Heap,
+            Err(_) => api::PointerTarget::Unmapped,
+        }
+    }
+}
+
+fn stack_target(
+    frame: &mut Frame, index: usize, address: usize, child: &debug::Child, symbols: &SymbolHandler
+) -> api::PointerTarget {
+    let (function, function_name) = match symbols.symbol_from_address(frame.lookup) {
+        Ok((symbol, _)) => {
+            (Some(symbol.address), Some(symbol.name.to_string_lossy().into_owned()))
+        }
+        Err(_) => (None, None),
+    };
+
+    if frame.locals.is_none() {
+        let mut locals = vec![];
+        let _ = symbols.enumerate_locals(frame.lookup, |symbol, size| {
+            let location = symbols.locate_symbol(child, &frame.context, index, &symbol);
+            if let Ok(Some(debug::Location::Address(address))) = location {
+                locals.push((symbol.name.to_string_lossy().into_owned(), address, size));
+            }
+            true
+        });
+        frame.locals = Some(locals);
+    }
+
+    let locals = frame.locals.as_ref().unwrap();
+    let local = locals.iter()
+        .find(|&&(_, start, size)| start <= address && address < start + cmp::max(size, 1));
+    let (variable, offset) = match local {
+        Some(&(ref name, start, _)) => (Some(name.clone()), address - start),
+        None => (None, address - frame.start),
+    };
+
+    api::PointerTarget::Stack { frame: index, function, function_name, variable, offset }
+}
@@ -0,0 +1,658 @@
+use std::{io, mem, cmp};
+use std::collections::VecDeque;
+
+use debug::{self, SymbolHandler};
+use api;
+
+use super::{parse_bytes, parse_text, MAX_ELEMENTS};
+
+/// Show a standard library container or smart pointer by its logical contents
+///
+/// Types are recognized by their template's name. The members a visualizer needs are then found
+/// by name among the type's fields, their fields, and its base classes, so one visualizer covers
+/// the layouts of the MSVC STL, libstdc++ and libc++ across their versions. Anything that doesn't
+/// match a known layout, or whose memory can't be read, is left as its `raw` struct.
+pub fn visualize(
+    data: &[u8], data_type: &debug::Type, child: &debug::Child, symbols: &SymbolHandler,
+    module: usize, string_length: usize, pointers: &mut VecDeque<(usize, u32)>, raw: api::Value
+) -> api::Value {
+    let name = match *data_type {
+        debug::Type::Struct { ref name, .. } => template_name(&name.to_string_lossy()),
+        _ => None,
+    };
+    let name = match name {
+        Some(name) => name,
+        None => return raw,
+    };
+
+    let mut reader = Reader { child, symbols, module, string_length, pointers };
+    let contents = match &name[..] {
+        "vector" => vector(&mut reader, data, data_type),
+        "basic_string" => string(&mut reader, data, data_type),
+        "list" => list(&mut reader, data, data_type),
+        "map" | "multimap" => tree(&mut reader, data, data_type, Visualizer::Map),
+        "set" | "multiset" => tree(&mut reader, data, data_type, Visualizer::Set),
+        "unordered_map" | "unordered_multimap" => {
+            hash_table(&mut reader, data, data_type, Visualizer::UnorderedMap)
+        }
+        "unordered_set" | "unordered_multiset" => {
+            hash_table(&mut reader, data, data_type, Visualizer::UnorderedSet)
+        }
+        "unique_ptr" => smart_pointer(&mut reader, data, data_type, Visualizer::UniquePtr),
+        "shared_ptr" => smart_pointer(&mut reader, data, data_type, Visualizer::SharedPtr),
+        _ => return raw,
+    };
+
+    match contents {
+        Ok(Contents { visualizer, contents, type_index, key_type_index, truncated }) => {
+            api::Value::Visualized(api::Visualized {
+                visualizer: visualizer.name().to_string(),
+                type_index: Some(type_index),
+                key_type_index,
+                display: None,
+                items: vec![],
+                contents: Box::new(contents),
+                truncated,
+                raw: Box::new(raw),
+            })
+        }
+        Err(_) => raw,
+    }
+}
+
+/// The name of a standard library class template, without its namespaces or arguments
+///
+/// PDBs qualify names, while DWARF leaves them to the enclosing namespaces, so both forms are
+/// accepted. Names in other namespaces are rejected.
+fn template_name(name: &str) -> Option<String> {
+    let mut name = match name.find('<') {
+        Some(end) => &name[..end],
+        None => return None,
+    };
+
+    for namespace in &["std::", "__1::", "__cxx11::", "__debug::"] {
+        if name.starts_with(namespace) {
+            name = &name[namespace.len()..];
+        }
+    }
+
+    if name.contains("::") {
+        return None;
+    }
+
+    Some(name.to_string())
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Visualizer {
+    Vector,
+    String,
+    List,
+    Map,
+    Set,
+    UnorderedMap,
+    UnorderedSet,
+    UniquePtr,
+    SharedPtr,
+}
+
+impl Visualizer {
+    fn name(self) -> &'static str {
+        match self {
+            Visualizer::Vector => "vector",
+            Visualizer::String => "string",
+            Visualizer::List => "list",
+            Visualizer::Map => "map",
+            Visualizer::Set => "set",
+            Visualizer::UnorderedMap => "unorderedMap",
+            Visualizer::UnorderedSet => "unorderedSet",
+            Visualizer::UniquePtr => "uniquePtr",
+            Visualizer::SharedPtr => "sharedPtr",
+        }
+    }
+
+    /// Whether elements are key-value pairs, to be shown as `[key, value]` entries
+    fn is_map(self) -> bool {
+        self == Visualizer::Map || self == Visualizer::UnorderedMap
+    }
+}
+
+/// The logical contents of a container, as found by a visualizer
+struct Contents {
+    visualizer: Visualizer,
+    contents: api::Value,
+    /// The element type, the mapped type of maps, or the pointed-to type
+    type_index: u32,
+    key_type_index: Option<u32>,
+    truncated: bool,
+}
+
+/// Everything `parse_bytes` needs to read elements out of the target's memory
+struct Reader<'a> {
+    child: &'a debug::Child,
+    symbols: &'a SymbolHandler,
+    module: usize,
+    string_length: usize,
+    pointers: &'a mut VecDeque<(usize, u32)>,
+}
+
+/// A member found somewhere inside a struct
+struct Member {
+    data: Vec<u8>,
+    type_index: u32,
+    data_type: debug::Type,
+}
+
+fn unrecognized() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "unrecognized container layout")
+}
+
+impl<'a> Reader<'a> {
+    /// Search a struct's fields, their fields, and its base classes, breadth-first, for a member
+    fn find(&self, data: &[u8], data_type: &debug::Type, name: &str) -> io::Result<Member> {
+        self.find_by(data, data_type, |field, _| field.name == *name)
+    }
+
+    /// Search a struct breadth-first for the first member that satisfies `predicate`
+    fn find_by<F>(
+        &self, data: &[u8], data_type: &debug::Type, mut predicate: F
+    ) -> io::Result<Member>
+        where F: FnMut(&debug::Field, &debug::Type) -> bool
+    {
+        let mut queue = VecDeque::new();
+        queue.push_back((data.to_vec(), data_type.clone()));
+        while let Some((data, data_type)) = queue.pop_front() {
+            let fields = match data_type {
+                debug::Type::Struct { fields, .. } | debug::Type::Union { fields, .. } => fields,
+                _ => continue,
+            };
+
+            for field in &fields {
+                let field_type = self.symbols.type_from_index(self.module, field.type_index)?;
+                let size = field_type.size(self.symbols, self.module);
+                if field.offset as usize + size > data.len() {
+                    continue;
+                }
+
+                let field_data = field.read(&data, &field_type, size);
+                if predicate(field, &field_type) {
+                    let type_index = field.type_index;
+                    return Ok(Member { data: field_data, type_index, data_type: field_type });
+                }
+
+                queue.push_back((field_data, field_type));
+            }
+        }
+
+        Err(unrecognized())
+    }
+
+    /// Find the first of several alternative member names
+    fn find_any(&self, data: &[u8], data_type: &debug::Type, names: &[&str]) -> io::Result<Member> {
+        names.iter()
+            .filter_map(|name| self.find(data, data_type, name).ok())
+            .next()
+            .ok_or(unrecognized())
+    }
+
+    /// The offset of a field in a struct, including fields of its base classes
+    fn field_offset(&self, data_type: &debug::Type, name: &str) -> io::Result<usize> {
+        let fields = match *data_type {
+            debug::Type::Struct { ref fields, .. } => fields,
+            _ => return Err(unrecognized()),
+        };
+
+        if let Some(field) = fields.iter().find(|field| field.name == *name) {
+            return Ok(field.offset as usize);
+        }
+
+        fields.iter()
+            .filter(|field| field.base_class)
+            .filter_map(|field| {
+                self.type_from_index(field.type_index)
+                    .and_then(|base_type| self.field_offset(&base_type, name))
+                    .map(|offset| field.offset as usize + offset)
+                    .ok()
+            })
+            .next()
+            .ok_or(unrecognized())
+    }
+
+    fn type_from_index(&self, type_index: u32) -> io::Result<debug::Type> {
+        self.symbols.type_from_index(self.module, type_index)
+    }
+
+    fn size_of(&self, type_index: u32) -> io::Result<usize> {
+        Ok(self.type_from_index(type_index)?.size(self.symbols, self.module))
+    }
+
+    fn read(&self, address: usize, size: usize) -> io::Result<Vec<u8>> {
+        let mut data = vec![0; size];
+        self.child.read_memory(address, &mut data)?;
+        Ok(data)
+    }
+
+    /// Read the pointer stored at `address`
+    fn read_address(&self, address: usize) -> io::Result<usize> {
+        Ok(read_address(&self.read(address, mem::size_of::<usize>())?))
+    }
+
+    fn parse(&mut self, data: &[u8], data_type: &debug::Type) -> api::Value {
+        parse_bytes(
+            data, data_type, self.child, self.symbols, self.module, self.string_length,
+            self.pointers
+        )
+    }
+
+    /// Read and parse elements, or their `[key, value]` entries for maps
+    ///
+    /// Elements that can't be read are reported as unreadable.
+    fn elements(
+        &mut self, addresses: &[usize], type_index: u32, visualizer: Visualizer
+    ) -> io::Result<(api::Value, u32, Option<u32>)> {
+        let element_type = self.type_from_index(type_index)?;
+        let size = element_type.size(self.symbols, self.module);
+
+        // the key and mapped types are found from the layout of an empty pair
+        let pair = if visualizer.is_map() {
+            let empty = vec![0; size];
+            let key = self.find(&empty, &element_type, "first")?;
+            let value = self.find(&empty, &element_type, "second")?;
+            Some((key.type_index, value.type_index))
+        } else {
+            None
+        };
+
+        let mut values = vec![];
+        for &address in addresses {
+            let data = match self.read(address, size) {
+                Ok(data) => data,
+                Err(_) => {
+                    let address = Some(address.to_string());
+                    values.push(api::Value::Unavailable(api::Unavailable::Unreadable { address }));
+                    continue;
+                }
+            };
+
+            let value = if pair.is_some() {
+                let key = self.find(&data, &element_type, "first")?;
+                let value = self.find(&data, &element_type, "second")?;
+                api::Value::Array(vec![
+                    self.parse(&key.data, &key.data_type),
+                    self.parse(&value.data, &value.data_type),
+                ])
+            } else {
+                self.parse(&data, &element_type)
+            };
+            values.push(value);
+        }
+
+        let value = api::Value::Array(values);
+        match pair {
+            Some((key, value_type)) => Ok((value, value_type, Some(key))),
+            None => Ok((value, type_index, None)),
+        }
+    }
+
+    /// Build the contents of a container from the addresses of its elements
+    fn contents(
+        &mut self, addresses: &[usize], type_index: u32, visualizer: Visualizer,
+        truncated: bool
+    ) -> io::Result<Contents> {
+        let (contents, type_index, key_type_index) =
+            self.elements(addresses, type_index, visualizer)?;
+        Ok(Contents { visualizer, contents, type_index, key_type_index, truncated })
+    }
+}
+
+impl Member {
+    /// The address held by a pointer member, and the type it points to
+    fn pointer(&self) -> io::Result<(usize, u32)> {
+        match self.data_type {
+            debug::Type::Pointer { type_index } => Ok((read_address(&self.data), type_index)),
+            _ => Err(unrecognized()),
+        }
+    }
+
+    /// The value of an unsigned integer member, such as a size or a bitfield flag
+    fn integer(&self) -> io::Result<usize> {
+        match self.data_type {
+            debug::Type::Base { base: debug::Primitive::Int { .. }, size } |
+            debug::Type::Base { base: debug::Primitive::Char { .. }, size } |
+            debug::Type::Base { base: debug::Primitive::Bool, size } if size <= 8 => {
+                Ok(debug::read_integer(&self.data[..size], false) as usize)
+            }
+            _ => Err(unrecognized()),
+        }
+    }
+}
+
+fn read_address(data: &[u8]) -> usize {
+    debug::read_integer(&data[..mem::size_of::<usize>()], false) as usize
+}
+
+/// The type of a class template's argument, for layouts that don't otherwise mention it
+fn type_param(data_type: &debug::Type, index: usize) -> io::Result<u32> {
+    match *data_type {
+        debug::Type::Struct { ref type_params, .. } if index < type_params.len() => {
+            Ok(type_params[index])
+        }
+        _ => Err(unrecognized()),
+    }
+}
+
+/// `std::vector`: a pointer to the first element and one past the last
+fn vector(r: &mut Reader, data: &[u8], data_type: &debug::Type) -> io::Result<Contents> {
+    let layouts = [("_Myfirst", "_Mylast"), ("_M_start", "_M_finish"), ("__begin_", "__end_")];
+    let (begin, end) = layouts.iter()
+        .filter_map(|&(begin, end)| {
+            match (r.find(data, data_type, begin), r.find(data, data_type, end)) {
+                (Ok(begin), Ok(end)) => Some((begin, end)),
+                _ => None,
+            }
+        })
+        .next()
+        .ok_or(unrecognized())?;
+
+    // `std::vector<bool>` packs its bits behind iterator structs, so isn't recognized here
+    let (begin, type_index) = begin.pointer()?;
+    let (end, _) = end.pointer()?;
+    let size = r.size_of(type_index)?;
+    if size == 0 || end < begin || (end - begin) % size != 0 {
+        return Err(unrecognized());
+    }
+
+    let count = (end - begin) / size;
+    let length = cmp::min(count, MAX_ELEMENTS);
+    let addresses: Vec<_> = (0..length).map(|i| begin + i * size).collect();
+    r.contents(&addresses, type_index, Visualizer::Vector, count > length)
+}
+
+/// `std::basic_string`: a length, and either an inline buffer or a pointer to the heap
+fn string(r: &mut Reader, data: &[u8], data_type: &debug::Type) -> io::Result<Contents> {
+    let (address, type_index, length) = if let Ok(buffer) = r.find(data, data_type, "_Bx") {
+        // MSVC keeps short strings in `_Buf`, and longer ones behind `_Ptr`
+        let length = r.find(data, data_type, "_Mysize")?.integer()?;
+        let capacity = r.find(data, data_type, "_Myres")?.integer()?;
+        let inline = r.find(&buffer.data, &buffer.data_type, "_Buf")?;
+        match inline.data_type {
+            debug::Type::Array { type_index, count } if capacity < count => {
+                return text(r, &inline.data, type_index, length);
+            }
+            _ => {}
+        }
+
+        let (address, type_index) = r.find(&buffer.data, &buffer.data_type, "_Ptr")?.pointer()?;
+        (address, type_index, Some(length))
+    } else if let Ok(pointer) = r.find(data, data_type, "_M_p") {
+        // libstdc++ always points at the characters, even when they're stored inline; its old
+        // copy-on-write strings keep their length out of reach, so are read up to a NUL
+        let (address, type_index) = pointer.pointer()?;
+        let length = r.find(data, data_type, "_M_string_length").and_then(|m| m.integer()).ok();
+        (address, type_index, length)
+    } else {
+        // libc++ overlays a short and a long representation, distinguished by a flag bit
+        let short = r.find(data, data_type, "__s")?;
+        let long = r.find(data, data_type, "__l")?;
+        let is_long = match r.find(&short.data, &short.data_type, "__is_long_") {
+            Ok(flag) => flag.integer()? != 0,
+            Err(_) => short.data[0] & 1 != 0,
+        };
+
+        if !is_long {
+            let length = r.find(&short.data, &short.data_type, "__size_")?.integer()?;
+            let length = match r.find(&short.data, &short.data_type, "__is_long_") {
+                Ok(_) => length,
+                Err(_) => length >> 1,
+            };
+            let inline = r.find(&short.data, &short.data_type, "__data_")?;
+            return match inline.data_type {
+                debug::Type::Array { type_index, .. } => text(r, &inline.data, type_index, length),
+                _ => Err(unrecognized()),
+            };
+        }
+
+        let (address, type_index) = r.find(&long.data, &long.data_type, "__data_")?.pointer()?;
+        let length = r.find(&long.data, &long.data_type, "__size_")?.integer()?;
+        (address, type_index, Some(length))
+    };
+
+    let size = r.size_of(type_index)?;
+    let data = match length {
+        // read one code unit past the limit, to tell whether the string was truncated
+        Some(length) => r.read(address, cmp::min(length, r.string_length + 1) * size)?,
+        None => {
+            let value = debug::Value::read_string(
+                r.child, r.symbols, address, r.module, type_index, r.string_length + 1
+            )?;
+            value.data
+        }
+    };
+    let length = data.len() / cmp::max(size, 1);
+    text(r, &data, type_index, length)
+}
+
+/// Decode `length` characters of a string's contents
+fn text(r: &mut Reader, data: &[u8], type_index: u32, length: usize) -> io::Result<Contents> {
+    let size = match r.type_from_index(type_index)? {
+        debug::Type::Base { base: debug::Primitive::Char { .. }, size } => size,
+        _ => return Err(unrecognized()),
+    };
+    if length * size > data.len() {
+        return Err(unrecognized());
+    }
+
+    let contents = parse_text(&data[..length * size], size, r.string_length);
+    Ok(Contents {
+        visualizer: Visualizer::String,
+        contents,
+        type_index,
+        key_type_index: None,
+        truncated: false,
+    })
+}
+
+/// `std::list`: a circular doubly-linked list through a sentinel node
+fn list(r: &mut Reader, data: &[u8], data_type: &debug::Type) -> io::Result<Contents> {
+    let (addresses, type_index, truncated) = list_elements(r, data, data_type)?;
+    r.contents(&addresses, type_index, Visualizer::List, truncated)
+}
+
+/// Find the addresses and type of a list's elements, following at most `MAX_ELEMENTS` links
+fn list_elements(
+    r: &mut Reader, data: &[u8], data_type: &debug::Type
+) -> io::Result<(Vec<usize>, u32, bool)> {
+    let (first, count, next_offset, value_offset, type_index) = if let Ok(head) =
+        r.find(data, data_type, "_Myhead")
+    {
+        // MSVC allocates its sentinel as a full node, so the node type names the element type
+        let (head, node_index) = head.pointer()?;
+        let node_type = r.type_from_index(node_index)?;
+        let node = r.read(head, node_type.size(r.symbols, r.module))?;
+        let value = r.find(&node, &node_type, "_Myval")?;
+        let (first, _) = r.find(&node, &node_type, "_Next")?.pointer()?;
+        let count = r.find(data, data_type, "_Mysize")?.integer()?;
+
+        let next_offset = r.field_offset(&node_type, "_Next")?;
+        let value_offset = r.field_offset(&node_type, "_Myval")?;
+        (first, count, next_offset, value_offset, value.type_index)
+    } else if let Ok(node) = r.find(data, data_type, "_M_node") {
+        // libstdc++ embeds its sentinel, so the element type comes from the template argument
+        let (first, base_index) = r.find(&node.data, &node.data_type, "_M_next")?.pointer()?;
+        let count = r.find(&node.data, &node.data_type, "_M_size")?.integer()?;
+
+        let base_type = r.type_from_index(base_index)?;
+        let next_offset = r.field_offset(&base_type, "_M_next")?;
+        let value_offset = base_type.size(r.symbols, r.module);
+        (first, count, next_offset, value_offset, type_param(data_type, 0)?)
+    } else {
+        // libc++ embeds its sentinel too, and keeps the size alongside the allocator
+        let end = r.find(data, data_type, "__end_")?;
+        let (first, base_index) = r.find(&end.data, &end.data_type, "__next_")?.pointer()?;
+        let size = r.find(data, data_type, "__size_alloc_")?;
+        let count = r.find_by(&size.data, &size.data_type, |_, field_type| is_integer(field_type))?
+            .integer()?;
+
+        let base_type = r.type_from_index(base_index)?;
+        let next_offset = r.field_offset(&base_type, "__next_")?;
+        let value_offset = base_type.size(r.symbols, r.module);
+        (first, count, next_offset, value_offset, type_param(data_type, 0)?)
+    };
+
+    let length = cmp::min(count, MAX_ELEMENTS);
+    let mut addresses = vec![];
+    let mut node = first;
+    while addresses.len() < length {
+        addresses.push(node + value_offset);
+        node = r.read_address(node + next_offset)?;
+    }
+
+    Ok((addresses, type_index, count > length))
+}
+
+fn is_integer(data_type: &debug::Type) -> bool {
+    match *data_type {
+        debug::Type::Base { base: debug::Primitive::Int { .. }, .. } => true,
+        _ => false,
+    }
+}
+
+/// `std::map` and `std::set`: a red-black tree, walked in order
+fn tree(
+    r: &mut Reader, data: &[u8], data_type: &debug::Type, visualizer: Visualizer
+) -> io::Result<Contents> {
+    let (root, nil, count, node_type, value_offset, type_index) = if let Ok(head) =
+        r.find(data, data_type, "_Myhead")
+    {
+        // MSVC points leaves at its allocated sentinel, whose parent is the root
+        let (head, node_index) = head.pointer()?;
+        let node_type = r.type_from_index(node_index)?;
+        let node = r.read(head, node_type.size(r.symbols, r.module))?;
+        let value = r.find(&node, &node_type, "_Myval")?;
+        let (root, _) = r.find(&node, &node_type, "_Parent")?.pointer()?;
+        let count = r.find(data, data_type, "_Mysize")?.integer()?;
+
+        let value_offset = r.field_offset(&node_type, "_Myval")?;
+        (root, head, count, node_type, value_offset, value.type_index)
+    } else if let Ok(header) = r.find(data, data_type, "_M_header") {
+        // libstdc++ embeds its header, and the value type is the tree's second argument
+        let (root, _) = r.find(&header.data, &header.data_type, "_M_parent")?.pointer()?;
+        let count = r.find(data, data_type, "_M_node_count")?.integer()?;
+        let tree = r.find(data, data_type, "_M_t")?;
+
+        let value_offset = header.data.len();
+        (root, 0, count, header.data_type, value_offset, type_param(&tree.data_type, 1)?)
+    } else {
+        // libc++ keeps the root as the left child of an embedded end node
+        let tree = r.find(data, data_type, "__tree_")?;
+        let end = r.find(&tree.data, &tree.data_type, "__pair1_")?;
+        let (root, base_index) = r.find(&end.data, &end.data_type, "__left_")?.pointer()?;
+        let size = r.find(&tree.data, &tree.data_type, "__pair3_")?;
+        let count = r.find_by(&size.data, &size.data_type, |_, field_type| is_integer(field_type))?
+            .integer()?;
+
+        let base_type = r.type_from_index(base_index)?;
+        let value_offset = base_type.size(r.symbols, r.module);
+        (root, 0, count, base_type, value_offset, type_param(&tree.data_type, 0)?)
+    };
+
+    let links = ["_Left", "_Right", "_M_left", "_M_right", "__left_", "__right_"];
+    let mut offsets = links.iter().filter_map(|link| r.field_offset(&node_type, link).ok());
+    let (left, right) = match (offsets.next(), offsets.next()) {
+        (Some(left), Some(right)) => (left, right),
+        _ => return Err(unrecognized()),
+    };
+
+    // an in-order walk, which gives up on a tree deeper than its size allows
+    let length = cmp::min(count, MAX_ELEMENTS);
+    let depth = 2 * (mem::size_of::<usize>() * 8);
+    let mut addresses = vec![];
+    let mut stack = vec![];
+    let mut node = root;
+    while addresses.len() < length {
+        if node != 0 && node != nil {
+            if stack.len() > depth {
+                return Err(unrecognized());
+            }
+
+            stack.push(node);
+            node = r.read_address(node + left)?;
+        } else if let Some(parent) = stack.pop() {
+            addresses.push(parent + value_offset);
+            node = r.read_address(parent + right)?;
+        } else {
+            break;
+        }
+    }
+
+    r.contents(&addresses, type_index, visualizer, count > length)
+}
+
+/// `std::unordered_map` and `std::unordered_set`: a hash table whose nodes form a single list
+fn hash_table(
+    r: &mut Reader, data: &[u8], data_type: &debug::Type, visualizer: Visualizer
+) -> io::Result<Contents> {
+    // MSVC keeps the elements in a `std::list`, with the buckets pointing into it
+    if let Ok(list) = r.find(data, data_type, "_List") {
+        let (addresses, type_index, truncated) = list_elements(r, &list.data, &list.data_type)?;
+        return r.contents(&addresses, type_index, visualizer, truncated);
+    }
+
+    let (first, count, value_offset, type_index) = if let Ok(table) =
+        r.find(data, data_type, "_M_h")
+    {
+        // libstdc++ nodes hold a link, then the value
+        let before_begin = r.find(&table.data, &table.data_type, "_M_before_begin")?;
+        let (first, base_index) =
+            r.find(&before_begin.data, &before_begin.data_type, "_M_nxt")?.pointer()?;
+        let count = r.find(&table.data, &table.data_type, "_M_element_count")?.integer()?;
+        (first, count, r.size_of(base_index)?, type_param(&table.data_type, 1)?)
+    } else {
+        // libc++ nodes hold a link and the hash, then the value
+        let table = r.find(data, data_type, "__table_")?;
+        let before_begin = r.find(&table.data, &table.data_type, "__p1_")?;
+        let (first, _) =
+            r.find(&before_begin.data, &before_begin.data_type, "__next_")?.pointer()?;
+        let size = r.find(&table.data, &table.data_type, "__p2_")?;
+        let count = r.find_by(&size.data, &size.data_type, |_, field_type| is_integer(field_type))?
+            .integer()?;
+        (first, count, 2 * mem::size_of::<usize>(), type_param(&table.data_type, 0)?)
+    };
+
+    // the link to the next node is each node's first field
+    let length = cmp::min(count, MAX_ELEMENTS);
+    let mut addresses = vec![];
+    let mut node = first;
+    while addresses.len() < length && node != 0 {
+        addresses.push(node + value_offset);
+        node = r.read_address(node)?;
+    }
+
+    r.contents(&addresses, type_index, visualizer, count > length)
+}
+
+/// `std::unique_ptr` and `std::shared_ptr`: the pointer they own, traced like any other
+fn smart_pointer(
+    r: &mut Reader, data: &[u8], data_type: &debug::Type, visualizer: Visualizer
+) -> io::Result<Contents> {
+    // a `unique_ptr`'s only pointer is the one it owns, while a `shared_ptr` also points to its
+    // control block
+    let pointer = match visualizer {
+        Visualizer::SharedPtr => r.find_any(data, data_type, &["_Ptr", "_M_ptr", "__ptr_"])?,
+        _ => r.find_by(data, data_type, |_, field_type| match *field_type {
+            debug::Type::Pointer { .. } => true,
+            _ => false,
+        })?,
+    };
+
+    let (address, type_index) = pointer.pointer()?;
+    r.pointers.push_back((address, type_index));
+
+    Ok(Contents {
+        visualizer,
+        contents: api::Value::String(address.to_string()),
+        type_index,
+        key_type_index: None,
+        truncated: false,
+    })
+}
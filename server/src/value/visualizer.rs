@@ -0,0 +1,310 @@
+use std::{io, env, fs, mem};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, VecDeque};
+
+use debug::{self, SymbolHandler};
+use serde_json;
+use toml;
+use xml::reader::{EventReader, XmlEvent};
+use api;
+
+use super::{parse, MAX_ELEMENTS};
+
+/// Show a struct through its user-defined visualizer
+///
+/// Anything the visualizer can't find, or whose memory can't be read, is left out. If that
+/// leaves nothing to show, the value stays as its `raw` struct.
+pub fn visualize(
+    visualizer: &debug::Visualizer, data: &[u8], data_type: &debug::Type, child: &debug::Child,
+    symbols: &SymbolHandler, module: usize, string_length: usize,
+    pointers: &mut VecDeque<(usize, u32)>, raw: api::Value
+) -> api::Value {
+    let display = visualizer.display(data, data_type, Some(child), symbols, module);
+    let expanded = visualizer.expand(data, data_type, child, symbols, module, MAX_ELEMENTS);
+    if display.is_none() && expanded.is_empty() {
+        return raw;
+    }
+
+    let mut items = vec![];
+    let mut contents = vec![];
+    let mut type_index = None;
+    let mut truncated = false;
+    for expanded in expanded {
+        match expanded {
+            debug::Expanded::Item { name, value, type_index } => {
+                let value = parse(&value, child, symbols, string_length, pointers);
+                items.push(api::Item { name, type_index, value });
+            }
+
+            debug::Expanded::Elements { elements, type_index: element_type, truncated: cut } => {
+                for element in elements {
+                    let value = match element {
+                        Ok(value) => parse(&value, child, symbols, string_length, pointers),
+                        Err(address) => {
+                            let address = Some(address.to_string());
+                            api::Value::Unavailable(api::Unavailable::Unreadable { address })
+                        }
+                    };
+                    contents.push(value);
+                }
+
+                type_index = type_index.or(element_type);
+                truncated |= cut;
+            }
+        }
+    }
+
+    api::Value::Visualized(api::Visualized {
+        visualizer: "custom".to_string(),
+        type_index,
+        key_type_index: None,
+        display,
+        items,
+        contents: Box::new(api::Value::Array(contents)),
+        truncated,
+        raw: Box::new(raw),
+    })
+}
+
+/// Load the visualizer files from the directory named by `SPICE_VISUALIZERS`, or from
+/// `visualizers` in the working directory
+///
+/// Files are read in order of their names, and each may be natvis XML (`.natvis`), TOML (`.toml`)
+/// or JSON (`.json`). Files that can't be read or parsed are skipped, so a broken file never
+/// prevents a debug session from starting.
+pub fn load() -> Vec<debug::Visualizer> {
+    let directory = env::var_os("SPICE_VISUALIZERS")
+        .map(PathBuf::from)
+        .unwrap_or(PathBuf::from("visualizers"));
+
+    let mut paths: Vec<_> = match fs::read_dir(&directory) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+        Err(_) => return vec![],
+    };
+    paths.sort();
+
+    paths.iter()
+        .filter_map(|path| load_file(path).ok())
+        .flat_map(|visualizers| visualizers)
+        .collect()
+}
+
+fn load_file(path: &Path) -> io::Result<Vec<debug::Visualizer>> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let extension = extension.map(|extension| extension.to_lowercase());
+
+    let mut text = String::new();
+    match extension.as_ref().map(|extension| &extension[..]) {
+        Some("natvis") => parse_natvis(fs::File::open(path)?),
+        Some("toml") => {
+            fs::File::open(path)?.read_to_string(&mut text)?;
+            let file: Rules = toml::from_str(&text).map_err(invalid)?;
+            Ok(file.into_visualizers())
+        }
+        Some("json") => {
+            let file: Rules = serde_json::from_reader(fs::File::open(path)?).map_err(invalid)?;
+            Ok(file.into_visualizers())
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+fn invalid<E: ToString>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+/// Read the `Type` elements of a natvis file
+///
+/// Only the elements with equivalents in `debug::Visualizer` are used, and any `AlternativeType`s
+/// share their type's visualizer. Conditions can't be evaluated, so a type's first unconditional
+/// `DisplayString` is used, or failing that its first conditional one.
+fn parse_natvis<R: Read>(source: R) -> io::Result<Vec<debug::Visualizer>> {
+    let mut visualizers = vec![];
+
+    let mut elements: Vec<String> = vec![];
+    let mut names = vec![];
+    let mut visualizer = None;
+    let mut item_name = None;
+    let mut conditional = false;
+    let mut has_display = false;
+    let mut expressions = HashMap::new();
+    let mut text = String::new();
+    for event in EventReader::new(source) {
+        match event.map_err(invalid)? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attribute = |key: &str| {
+                    attributes.iter()
+                        .find(|attribute| attribute.name.local_name == key)
+                        .map(|attribute| attribute.value.clone())
+                };
+
+                let parent = elements.last().cloned();
+                match (parent.as_ref().map(|parent| &parent[..]), &name.local_name[..]) {
+                    (_, "Type") => {
+                        names = attribute("Name").into_iter().collect();
+                        has_display = false;
+                        visualizer = Some(debug::Visualizer {
+                            name: String::new(),
+                            display: None,
+                            items: vec![],
+                        });
+                    }
+                    (Some("Type"), "AlternativeType") => names.extend(attribute("Name")),
+                    (Some("Type"), "DisplayString") => {
+                        conditional = attribute("Condition").is_some();
+                    }
+                    (Some("Expand"), "Item") => item_name = attribute("Name"),
+                    (Some("Expand"), _) => expressions.clear(),
+                    _ => (),
+                }
+
+                elements.push(name.local_name);
+                text.clear();
+            }
+
+            XmlEvent::Characters(characters) | XmlEvent::CData(characters) => {
+                text.push_str(&characters);
+            }
+
+            XmlEvent::EndElement { .. } => {
+                let element = elements.pop().unwrap_or(String::new());
+                let text = mem::replace(&mut text, String::new()).trim().to_string();
+
+                // a finished `Type` is registered under each of its names
+                if element == "Type" {
+                    if let Some(visualizer) = visualizer.take() {
+                        for name in names.drain(..) {
+                            visualizers.push(debug::Visualizer { name, ..visualizer.clone() });
+                        }
+                    }
+                    continue;
+                }
+
+                let visualizer = match visualizer {
+                    Some(ref mut visualizer) => visualizer,
+                    None => continue,
+                };
+                let parent = elements.last().cloned();
+                match (parent.as_ref().map(|parent| &parent[..]), &element[..]) {
+                    (Some("Type"), "DisplayString") => {
+                        if visualizer.display.is_none() || !conditional && !has_display {
+                            visualizer.display = Some(text);
+                        }
+                        has_display |= !conditional;
+                    }
+                    (Some("Expand"), "Item") => {
+                        let name = item_name.take().unwrap_or(String::new());
+                        visualizer.items.push(debug::Items::Item { name, value: text });
+                    }
+                    (Some("Expand"), group) => {
+                        visualizer.items.extend(natvis_items(group, &mut expressions));
+                    }
+                    (Some("ArrayItems"), _) | (Some("LinkedListItems"), _) |
+                    (Some("TreeItems"), _) => {
+                        expressions.entry(element.clone()).or_insert(text);
+                    }
+                    _ => (),
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    Ok(visualizers)
+}
+
+/// Build the items of an `ArrayItems`, `LinkedListItems` or `TreeItems` element from the
+/// expressions of its children
+fn natvis_items(
+    element: &str, expressions: &mut HashMap<String, String>
+) -> Option<debug::Items> {
+    let size = expressions.remove("Size");
+    let head = expressions.remove("HeadPointer");
+    let value = expressions.remove("ValueNode");
+    match (element, size, head, value) {
+        ("ArrayItems", Some(size), _, _) => {
+            let pointer = expressions.remove("ValuePointer");
+            pointer.map(|pointer| debug::Items::Array { size, pointer })
+        }
+        ("LinkedListItems", size, Some(head), Some(value)) => {
+            let next = expressions.remove("NextPointer");
+            next.map(|next| debug::Items::LinkedList { size, head, next, value })
+        }
+        ("TreeItems", size, Some(head), Some(value)) => {
+            match (expressions.remove("LeftPointer"), expressions.remove("RightPointer")) {
+                (Some(left), Some(right)) => {
+                    Some(debug::Items::Tree { size, head, left, right, value })
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The TOML and JSON visualizer format, a list of types with the same parts as in natvis
+#[derive(Deserialize)]
+struct Rules {
+    #[serde(rename = "type", default)]
+    types: Vec<TypeRule>,
+}
+
+#[derive(Deserialize)]
+struct TypeRule {
+    name: String,
+    #[serde(default)]
+    display: Option<String>,
+    #[serde(default)]
+    items: Vec<ItemsRule>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind")]
+enum ItemsRule {
+    #[serde(rename = "item")]
+    Item { name: String, value: String },
+    #[serde(rename = "array")]
+    Array { size: String, pointer: String },
+    #[serde(rename = "list")]
+    LinkedList {
+        #[serde(default)]
+        size: Option<String>,
+        head: String,
+        next: String,
+        value: String,
+    },
+    #[serde(rename = "tree")]
+    Tree {
+        #[serde(default)]
+        size: Option<String>,
+        head: String,
+        left: String,
+        right: String,
+        value: String,
+    },
+}
+
+impl Rules {
+    fn into_visualizers(self) -> Vec<debug::Visualizer> {
+        self.types.into_iter()
+            .map(|TypeRule { name, display, items }| {
+                let items = items.into_iter()
+                    .map(|items| match items {
+                        ItemsRule::Item { name, value } => debug::Items::Item { name, value },
+                        ItemsRule::Array { size, pointer } => debug::Items::Array { size, pointer },
+                        ItemsRule::LinkedList { size, head, next, value } => {
+                            debug::Items::LinkedList { size, head, next, value }
+                        }
+                        ItemsRule::Tree { size, head, left, right, value } => {
+                            debug::Items::Tree { size, head, left, right, value }
+                        }
+                    })
+                    .collect();
+
+                debug::Visualizer { name, display, items }
+            })
+            .collect()
+    }
+}